@@ -139,6 +139,39 @@ pub struct WorkerApiConfig {
     pub scheduler: SchedulerRefName,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct OperationsConfig {
+    /// The scheduler name referenced in the `schedulers` map in the main config.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub scheduler: SchedulerRefName,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SchedulerEventsConfig {
+    /// The scheduler name referenced in the `schedulers` map in the main config.
+    /// The scheduler must be a scheduler that matches actions to workers, eg:
+    /// a `simple` scheduler.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub scheduler: SchedulerRefName,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorkerPoolDemandConfig {
+    /// The scheduler name referenced in the `schedulers` map in the main config.
+    /// The scheduler must be a scheduler that matches actions to workers, eg:
+    /// a `simple` scheduler.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub scheduler: SchedulerRefName,
+
+    /// Path the demand is served on as JSON over HTTP. If path is
+    /// "/worker_pool_demand", and your domain is "example.com", you can reach
+    /// the endpoint with: <http://example.com/worker_pool_demand>.
+    ///
+    /// Default: "/worker_pool_demand"
+    #[serde(default)]
+    pub path: String,
+}
+
 #[derive(Deserialize, Debug, Default)]
 pub struct PrometheusConfig {
     /// Path to register prometheus metrics. If path is "/metrics", and your
@@ -172,6 +205,12 @@ pub struct ServicesConfig {
     /// place holder.
     pub execution: Option<HashMap<InstanceName, ExecutionConfig>>,
 
+    /// The google.longrunning.Operations service, which lets clients look up
+    /// the state of operations started through the execution service by name.
+    /// Only GetOperation is supported. ListOperations, DeleteOperation,
+    /// CancelOperation and WaitOperation return Unimplemented.
+    pub operations: Option<HashMap<InstanceName, OperationsConfig>>,
+
     /// This is the service used to stream data to and from the CAS.
     /// Bazel's protocol strongly encourages users to use this streaming
     /// interface to interact with the CAS when the data is large.
@@ -186,6 +225,18 @@ pub struct ServicesConfig {
     /// that makes the remote execution/cache requests.
     pub worker_api: Option<WorkerApiConfig>,
 
+    /// Streams the events of a scheduler to subscribers, eg: to build
+    /// dashboards. Events include actions being queued, matched to a worker,
+    /// retried and completed, and workers being evicted.
+    pub scheduler_events: Option<SchedulerEventsConfig>,
+
+    /// Reports how much work a scheduler has per distinct set of platform
+    /// properties requested by actions: the number of queued and executing
+    /// actions, the number of idle workers that could run them and how many
+    /// workers should be added. Served over gRPC and as JSON over HTTP, eg:
+    /// to drive an autoscaler of a pool of workers.
+    pub worker_pool_demand: Option<WorkerPoolDemandConfig>,
+
     /// Prometheus metrics configuration. Metrics are gathered as a singleton
     /// but may be served on multiple endpoints.
     pub prometheus: Option<PrometheusConfig>,
@@ -331,6 +382,8 @@ pub enum EnvironmentSource {
     ///   // If set the command will be considered a failure.
     ///   // May be one of the following static strings:
     ///   // "timeout": Will Consider this task to be a timeout.
+    ///   // "retry": Will consider this task to have failed in a flaky way,
+    ///   //   see: `LocalWorkerConfig::flaky_failures`.
     ///   "failure": "timeout",
     /// }
     ///
@@ -435,6 +488,29 @@ pub struct LocalWorkerConfig {
     #[serde(default)]
     pub timeout_handled_externally: bool,
 
+    /// When an action times out or is killed, all of its processes are sent
+    /// SIGTERM. Processes that did not exit after this grace period are sent
+    /// SIGKILL. Value in seconds.
+    ///
+    /// Default: 5 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub termination_grace_period: usize,
+
+    /// The stdout of an action is written to a file next to its work
+    /// directory and uploaded from there. Output beyond this many bytes is
+    /// discarded and a note that the output was truncated is appended.
+    /// Zero means there is no limit.
+    ///
+    /// Default: 0 (no limit)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_stdout_size: u64,
+
+    /// Same as `max_stdout_size`, but for the stderr of an action.
+    ///
+    /// Default: 0 (no limit)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_stderr_size: u64,
+
     /// The command to execute on every execution request. This will be parsed as
     /// a command + arguments (not shell).
     /// Example: "run.sh" and a job with command: "sleep 5" will result in a
@@ -485,6 +561,282 @@ pub struct LocalWorkerConfig {
     /// of the environment variable being the value of the property of the
     /// action being executed of that name or the fixed value.
     pub additional_environment: Option<HashMap<String, EnvironmentSource>>,
+
+    /// Which failed executions are reported to the scheduler as flaky, so the
+    /// scheduler can run the action again if `flaky_retry` is configured on
+    /// it. An execution is also reported as flaky if it sets `"failure": "retry"`
+    /// in its `EnvironmentSource::SideChannelFile`.
+    #[serde(default)]
+    pub flaky_failures: FlakyFailuresConfig,
+
+    /// If set, actions are executed in a Linux sandbox built from user, mount,
+    /// PID, IPC and (optionally) network namespaces. The sandbox only sees the
+    /// configured read-only host paths, the action's work directory, a private
+    /// `/tmp`, `/dev` and its own `/proc`.
+    /// Default: None (actions run directly on the host)
+    pub linux_sandbox: Option<LinuxSandboxConfig>,
+
+    /// If set, every action is executed in its own cgroup v2 below the
+    /// configured cgroup. The cgroup limits the resources of the action
+    /// according to its platform properties and is used to report the
+    /// resources the action used in the auxiliary metadata of its result.
+    /// Actions that are killed because they ran out of memory fail.
+    /// Processes that left the session of the action are only ended with
+    /// the action if it has a cgroup.
+    /// Default: None (actions are not limited or accounted)
+    pub cgroup: Option<CgroupConfig>,
+
+    /// If set, actions that support Bazel persistent workers are sent as
+    /// work requests to long-lived worker processes instead of starting a
+    /// new process for every action. An action is executed in a persistent
+    /// worker if it has the platform property `key_property` and the last
+    /// of its arguments is a flagfile (`@file` or `--flagfile=file`). Bazel
+    /// marks such actions when `--experimental_remote_mark_tool_inputs` is
+    /// used. Persistent workers do not use the `entrypoint_cmd` and can not
+    /// be combined with `linux_sandbox`, `cgroup` or `execution_user`.
+    /// Default: None (every action starts a new process)
+    pub persistent_workers: Option<PersistentWorkersConfig>,
+
+    /// If set, actions with the `image_property` platform property are
+    /// executed in a container of that image, eg: "docker://ubuntu:22.04",
+    /// by invoking an OCI runtime with a docker compatible command line
+    /// (docker, podman, nerdctl). The work directory of the action is bind
+    /// mounted into the container at the same path. Actions without the
+    /// property run directly on the host. Actions executed in a container
+    /// do not run in the `linux_sandbox` and do not use the `entrypoint_cmd`.
+    /// Default: None (the property is ignored)
+    pub container: Option<ContainerConfig>,
+
+    /// If set, the subdirectories of input roots are kept in a cache in the
+    /// `work_directory`, keyed by the digest of their `Directory`. A cached
+    /// subdirectory is materialized for an action with a single copy of its
+    /// tree, made according to `input_materialization`,
+    /// instead of fetching every `Directory` and file of it again. Cached
+    /// directories hardlink the files of the `FilesystemStore`, so they use
+    /// no additional disk space while the store holds their files. Using a
+    /// cached directory refreshes its files in the store and a cached
+    /// directory is dropped when it is used after the store evicted any of
+    /// its files.
+    /// Default: None (inputs are always materialized file by file)
+    pub directory_cache: Option<DirectoryCacheConfig>,
+
+    /// How the input files of actions are placed in their work directories.
+    /// Default: Hardlink
+    #[serde(default)]
+    pub input_materialization: InputMaterialization,
+
+    /// If set, actions are executed as this user instead of the user of the
+    /// worker, which has to be root for this. The directories of the work
+    /// directory and copied or reflinked input files are given to this
+    /// user, hardlinked input files stay owned by the worker, so actions
+    /// can not make them writable again. Actions executed in the
+    /// `linux_sandbox` or in a container run as this user as well,
+    /// persistent workers are still started as the user of the worker.
+    /// Default: None (actions are executed as the user of the worker)
+    pub execution_user: Option<ExecutionUserConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct ExecutionUserConfig {
+    /// User id actions are executed as.
+    #[serde(deserialize_with = "convert_numeric_with_shellexpand")]
+    pub uid: u32,
+
+    /// Group id actions are executed as. The supplementary groups of the
+    /// worker are dropped.
+    #[serde(deserialize_with = "convert_numeric_with_shellexpand")]
+    pub gid: u32,
+}
+
+#[derive(Copy, Clone, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum InputMaterialization {
+    /// Hardlink the files of the `FilesystemStore`, which is the fastest,
+    /// but shares the files with the store and all other actions. The store
+    /// keeps its files read-only and their mode and mtime are never changed
+    /// for an action, so executable files and files with `node_properties`
+    /// are reflinked or copied instead. Actions that make the inputs
+    /// writable again anyway are detected after the execution by the size,
+    /// mtime and permissions of the inputs and the modified files are
+    /// evicted from the store. Actions executed as the `execution_user` can
+    /// not make them writable.
+    #[default]
+    Hardlink,
+
+    /// Copy the files of the `FilesystemStore`, so actions can modify their
+    /// inputs.
+    Copy,
+
+    /// Clone the files of the `FilesystemStore` with a copy-on-write reflink
+    /// on filesystems that support it (eg: Btrfs, XFS), so actions can modify
+    /// their inputs without the cost of copying them. Files are copied where
+    /// reflinks are not supported.
+    Reflink,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct DirectoryCacheConfig {
+    /// Maximum number of directories kept in the cache. The least recently
+    /// used directories are evicted first.
+    /// Default: 10000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_entries: u64,
+
+    /// Maximum total size of the files of the cached directories. A cached
+    /// directory is charged for the files directly in it, the files of its
+    /// subdirectories are charged to the cached subdirectories, and files
+    /// in multiple cached directories are counted for each of them. The
+    /// cache is not part of the eviction policy of the `FilesystemStore`, a
+    /// cached directory only takes up disk space of its own for the files
+    /// the store evicted until it is used again, which this limit bounds.
+    /// Default: 10737418240 (10GiB)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_bytes: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ContainerConfig {
+    /// The command of the OCI runtime, split into arguments like a shell
+    /// would, eg: "sudo podman". It is executed in the environment of the
+    /// worker, the environment of the action is only set in the container.
+    /// Containers are named after the action and run as the user of the
+    /// worker. Containers of killed actions are removed with `rm --force`.
+    /// Images are pulled with its `pull` command, unless `image inspect`
+    /// finds them, and are only pulled once per worker process.
+    /// Default: "docker"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub runtime_cmd: String,
+
+    /// Name of the platform property with the image an action is executed
+    /// in. A "docker://" prefix of the image is removed. Actions whose image
+    /// is not a valid image reference fail. The image is passed to the
+    /// runtime after a `--`, so the runtime has to accept one.
+    /// Default: "container-image"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub image_property: String,
+
+    /// Name of the platform property with the memory limit of an action in
+    /// KiB. It is passed to the runtime as `--memory`.
+    /// Default: "memory_kb"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub memory_kb_property: String,
+
+    /// Name of the platform property with the number of CPUs an action may
+    /// use. It is passed to the runtime as `--cpus`.
+    /// Default: "cpu_count"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub cpu_count_property: String,
+
+    /// Additional arguments of the `run` command of the runtime, eg:
+    /// ["--network=none"]. They are added before the image.
+    /// Default: []
+    #[serde(default)]
+    pub run_arguments: Vec<String>,
+
+    /// Maximum number of actions that are executed at the same time in
+    /// containers of the same image. Actions wait until a container of
+    /// their image finishes.
+    /// Default: 0 (no limit)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_concurrent_actions_per_image: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct PersistentWorkersConfig {
+    /// Name of the platform property with the key of the persistent worker
+    /// an action is executed in. Actions are only sent to a worker that was
+    /// started with the same key, tool inputs, arguments and environment.
+    /// The scheduler has to be configured to ignore this property.
+    /// Default: "persistentWorkerKey"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub key_property: String,
+
+    /// Name of the platform property that selects the protocol spoken with
+    /// the worker, "proto" (length delimited protobuf) or "json".
+    /// Default: "persistentWorkerProtocol" (and "proto" if it is not set)
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub protocol_property: String,
+
+    /// Maximum number of idle workers that are kept running per key.
+    /// Workers that finish a request while this many workers of the same
+    /// key are idle are stopped.
+    /// Default: 1
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_idle_workers_per_key: usize,
+
+    /// Maximum number of idle workers that are kept running across all keys.
+    /// The workers that were idle the longest are stopped first.
+    /// Default: 16
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_idle_workers: usize,
+
+    /// Number of seconds after which an idle worker is stopped. Expired
+    /// workers are stopped when the next worker is taken or put back.
+    /// Default: 600 (10 minutes)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub idle_timeout: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct CgroupConfig {
+    /// Path of the cgroup v2 the cgroups of the actions are created in, eg:
+    /// "/sys/fs/cgroup/nativelink". It is created if it does not exist. The
+    /// worker must be allowed to write to it and the worker process must not
+    /// be in it. The cpu, memory and pids controllers are enabled for the
+    /// actions if they are available.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub path: String,
+
+    /// Name of the platform property with the memory limit of an action in
+    /// KiB, usually configured as `Minimum` on the worker.
+    /// Default: "memory_kb"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub memory_kb_property: String,
+
+    /// Name of the platform property with the number of CPUs an action may
+    /// use, usually configured as `Minimum` on the worker.
+    /// Default: "cpu_count"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub cpu_count_property: String,
+
+    /// Name of the platform property with the maximum number of processes
+    /// of an action.
+    /// Default: "max_pids"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub max_pids_property: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct LinuxSandboxConfig {
+    /// Host paths that are bind mounted read-only at the same location in the
+    /// sandbox, eg: the system directories and toolchains the actions need.
+    /// Paths that do not exist on the host are skipped. The work directory
+    /// must not be inside any of these paths.
+    /// Default: ["/bin", "/etc", "/lib", "/lib32", "/lib64", "/sbin", "/usr"]
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+
+    /// Name of the platform property that controls network access of an
+    /// action. Actions that set this property to "off" are executed in their
+    /// own network namespace that only has a loopback interface.
+    /// Default: "dockerNetwork"
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
+    pub network_property: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct FlakyFailuresConfig {
+    /// Exit codes of executions that should be considered flaky, eg: the exit
+    /// code of a test runner for an infrastructure failure.
+    /// Default: [] (exit codes are not considered)
+    #[serde(default)]
+    pub exit_codes: Vec<i32>,
+
+    /// Regexes matched against the stderr of failed executions. The execution
+    /// is considered flaky if any of them matches. Only the last 1MiB of
+    /// stderr is matched.
+    /// Default: [] (stderr is not considered)
+    #[serde(default)]
+    pub stderr_regexes: Vec<String>,
 }
 
 #[allow(non_camel_case_types)]
@@ -16,16 +16,20 @@ use std::collections::HashMap;
 
 use serde::Deserialize;
 
-use crate::serde_utils::{convert_numeric_with_shellexpand, convert_string_with_shellexpand};
+use crate::cas_server::SchedulerRefName;
+use crate::serde_utils::{
+    convert_numeric_with_shellexpand, convert_string_with_shellexpand, convert_vec_string_with_shellexpand,
+};
 use crate::stores::{Retry, StoreRefName};
 
 #[allow(non_camel_case_types)]
 #[derive(Deserialize, Debug)]
 pub enum SchedulerConfig {
-    simple(SimpleScheduler),
+    simple(Box<SimpleScheduler>),
     grpc(GrpcScheduler),
     cache_lookup(CacheLookupScheduler),
     property_modifier(PropertyModifierScheduler),
+    router(RouterScheduler),
 }
 
 /// When the scheduler matches tasks to workers that are capable of running
@@ -98,12 +102,32 @@ pub struct SimpleScheduler {
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub retain_completed_for_s: u64,
 
+    /// The store the final state of completed actions is persisted in, so
+    /// WaitExecution and GetOperation can answer after `retain_completed_for_s`,
+    /// after a restart or on another scheduler sharing the store. Entries are
+    /// keyed by the digest of the operation name, so this should be a dedicated
+    /// store that does not verify digests and evicts old entries.
+    /// Default: None (completed actions are only kept in memory)
+    #[serde(default)]
+    pub completed_actions_store: Option<StoreRefName>,
+
     /// Remove workers from pool once the worker has not responded in this
     /// amount of time in seconds.
     /// Default: 5 (seconds)
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub worker_timeout_s: u64,
 
+    /// Once a worker announces it is going away, it is no longer assigned new
+    /// actions but may finish the actions it is running. If the worker still
+    /// has running actions after this amount of time in seconds, it is removed
+    /// from the pool and its actions are rescheduled. Draining workers are not
+    /// removed for missing keep alives (see `worker_timeout_s`) before this
+    /// time, so the actions of a draining worker that died are only
+    /// rescheduled once it expired.
+    /// Default: 600 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub worker_drain_timeout_s: u64,
+
     /// If a job returns an internal error or times out this many times when
     /// attempting to run on a worker the scheduler will return the last error
     /// to the client. Jobs will be retried and this configuration is to help
@@ -117,6 +141,279 @@ pub struct SimpleScheduler {
     /// The strategy used to assign workers jobs.
     #[serde(default)]
     pub allocation_strategy: WorkerAllocationStrategy,
+
+    /// If set, actions that have been executing for much longer than previous
+    /// executions of the same command will speculatively be executed on a
+    /// second worker. Whichever execution completes first is used and the
+    /// other one is killed.
+    /// Default: None (hedging disabled)
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+
+    /// If set, workers that report too many internal errors in a short amount
+    /// of time are quarantined. A quarantined worker keeps running the actions
+    /// it already has, but is not assigned any new actions until it is
+    /// re-admitted after a backoff period.
+    /// Default: None (quarantine disabled)
+    #[serde(default)]
+    pub worker_quarantine: Option<WorkerQuarantineConfig>,
+
+    /// If set, actions whose execution the worker reports as flaky (see
+    /// `LocalWorkerConfig::flaky_failures`) are put back in the queue instead
+    /// of being completed. The executions that were retried are listed in the
+    /// `auxiliary_metadata` of the final result as `ExecutionAttempt`s.
+    /// Default: None (flaky executions complete the action)
+    #[serde(default)]
+    pub flaky_retry: Option<FlakyRetryConfig>,
+
+    /// If set, the priority of queued actions increases the longer they wait
+    /// to be executed, so low priority actions are not starved by a steady
+    /// stream of higher priority actions.
+    /// Default: None (aging disabled)
+    #[serde(default)]
+    pub priority_aging: Option<PriorityAgingConfig>,
+
+    /// If set, a queued action that no worker can currently run may preempt
+    /// a running action of a sufficiently lower priority. The preempted action
+    /// is killed and put back in the queue without counting as an attempt
+    /// towards `max_job_retries`.
+    /// Default: None (preemption disabled)
+    #[serde(default)]
+    pub preemption: Option<PreemptionConfig>,
+
+    /// If set, the execution duration of actions is recorded and queued
+    /// actions of the same priority are ordered by how long they are expected
+    /// to execute for.
+    /// Default: None (actions of the same priority run in the order they
+    /// were queued)
+    #[serde(default)]
+    pub execution_statistics: Option<ExecutionStatisticsConfig>,
+
+    /// If set, clients are periodically sent the position of their queued
+    /// action within the actions that have the same platform properties and
+    /// the time it is expected to be sent to a worker. Both are carried as a
+    /// `QueuePosition` in the `auxiliary_metadata` of the
+    /// `partial_execution_metadata` of `ExecuteOperationMetadata`. The start
+    /// time is only estimated if `execution_statistics` is configured.
+    /// Default: None (clients only see that the action is queued)
+    #[serde(default)]
+    pub queue_position: Option<QueuePositionConfig>,
+
+    /// Platform property keys (eg: `OSFamily` or `pool`) the scheduler metrics
+    /// are broken down by. For every distinct combination of the values of
+    /// these keys requested by actions, the number of queued and executing
+    /// actions, the time queued actions have been waiting, the number of times
+    /// actions could not be matched to a worker and the total time matched
+    /// actions waited in the queue are published with the values as labels.
+    /// Actions that do not request a key get an empty value for it. Metrics
+    /// are kept for at most 1000 combinations, actions with further
+    /// combinations are counted with every value set to `__other__`. Keys
+    /// are turned into label names by replacing characters other than
+    /// alphanumerics with `_` and prefixing keys starting with a digit with `_`.
+    /// Default: [] (metrics are not broken down by platform properties)
+    #[serde(default)]
+    pub metrics_platform_property_keys: Vec<String>,
+
+    /// Where to send scheduler events to, eg: actions being queued, matched to
+    /// a worker, retried and completed, and workers being evicted. Events can
+    /// also be followed live through the `scheduler_events` service.
+    /// Default: [] (events are not written anywhere)
+    #[serde(default)]
+    pub event_sinks: Vec<SchedulerEventSink>,
+}
+
+/// A destination scheduler events are written to.
+#[allow(non_camel_case_types)]
+#[derive(Deserialize, Debug, Clone)]
+pub enum SchedulerEventSink {
+    /// Append the events as newline delimited JSON to the file at this path.
+    /// The file is created if it does not exist.
+    ndjson_file(#[serde(deserialize_with = "convert_string_with_shellexpand")] String),
+}
+
+/// What the execution durations of actions are recorded by.
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Deserialize, Debug, Default)]
+pub enum ExecutionStatisticsKey {
+    /// Record durations per `Command` digest, so only actions running the
+    /// exact same command share an estimate.
+    #[default]
+    command_digest,
+    /// Record durations per action mnemonic (eg: `CppCompile`) from the
+    /// `RequestMetadata` sent by the client. Actions without a mnemonic are
+    /// recorded by their `Command` digest.
+    mnemonic,
+}
+
+/// How queued actions of the same priority are ordered by their expected
+/// execution duration. Actions without any recorded executions are run
+/// first.
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Deserialize, Debug, Default)]
+pub enum ExecutionOrdering {
+    /// Run the actions expected to finish soonest first.
+    #[default]
+    shortest_job_first,
+    /// Run the actions expected to take the longest first. Long actions are
+    /// the most likely to be on the critical path of a build, so starting
+    /// them early shortens the build as a whole.
+    critical_path_first,
+}
+
+/// Configuration of the recording of execution durations. The expected
+/// duration of an action is the moving average of the durations between
+/// `worker_start_timestamp` and `worker_completed_timestamp` of previous
+/// successful executions with the same key.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ExecutionStatisticsConfig {
+    /// The store the recorded durations are persisted in, so they survive
+    /// restarts of the scheduler. Entries are keyed by the command digest or
+    /// the digest of the mnemonic, so this should be a dedicated store that
+    /// does not verify digests. Only the moving average is persisted.
+    /// Default: None (durations are only kept in memory)
+    #[serde(default)]
+    pub store: Option<StoreRefName>,
+
+    /// What the durations are recorded by.
+    /// Default: command_digest
+    #[serde(default)]
+    pub key: ExecutionStatisticsKey,
+
+    /// How the expected durations are used to order queued actions.
+    /// Default: shortest_job_first
+    #[serde(default)]
+    pub ordering: ExecutionOrdering,
+
+    /// The number of keys the durations are kept in memory for.
+    /// Default: 100000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_entries: usize,
+}
+
+/// Configuration of priority aging. The effective priority of a queued action
+/// is its priority plus `priority_increment` for every multiple of
+/// `aging_interval_s` since the unix epoch that passed since it was first
+/// queued. Aging all queued actions at the same time keeps the queue in order.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct PriorityAgingConfig {
+    /// The amount of time in seconds an action has to wait in the queue for
+    /// its effective priority to be increased by `priority_increment`.
+    /// Default: 60 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub aging_interval_s: u64,
+
+    /// The amount the effective priority is increased by for every
+    /// `aging_interval_s` an action waits.
+    /// Default: 1
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub priority_increment: i32,
+
+    /// The maximum amount the effective priority of an action can be
+    /// increased by. Zero means there is no limit.
+    /// Default: 0 (no limit)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_priority_increase: i32,
+}
+
+/// Configuration of preemption of running actions by higher priority actions.
+/// Priorities are compared without aging, so aging alone never causes an
+/// action to be preempted.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct PreemptionConfig {
+    /// The minimum amount a queued action's priority must be higher than the
+    /// priority of a running action to preempt it.
+    /// Default: 1
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub min_priority_difference: i32,
+
+    /// Only running actions with a priority lower than or equal to this value
+    /// may be preempted.
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_preemptible_priority: i32,
+}
+
+/// Configuration of the queue positions sent to clients of queued actions.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct QueuePositionConfig {
+    /// How often the queue positions and expected start times of all queued
+    /// actions are recomputed and sent to clients, in seconds. Clients are
+    /// only sent an update if their position or expected start time changed.
+    /// Default: 10 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub update_interval_s: u64,
+}
+
+/// Configuration of the retries of executions that failed in a flaky way.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct FlakyRetryConfig {
+    /// The number of times an action is run again after a flaky execution.
+    /// The result of the last execution is used once this is reached. These
+    /// retries do not count towards `max_job_retries`.
+    /// Default: 3
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_retries: usize,
+}
+
+/// Configuration of the quarantine of workers that repeatedly fail actions
+/// with internal errors (eg: full disk or broken toolchain).
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct WorkerQuarantineConfig {
+    /// The number of internal errors within `error_window_s` that causes the
+    /// worker to be quarantined. Back pressure errors are not counted.
+    /// Default: 3
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_internal_errors: usize,
+
+    /// The amount of time in seconds internal errors are remembered for.
+    /// Default: 60 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub error_window_s: u64,
+
+    /// The amount of time in seconds a worker is quarantined for the first
+    /// time. Each following quarantine doubles this time until the worker
+    /// successfully completes an action again.
+    /// Default: 30 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub initial_backoff_s: u64,
+
+    /// The maximum amount of time in seconds a worker is quarantined for.
+    /// Default: 600 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_backoff_s: u64,
+}
+
+/// Configuration of speculative re-execution (hedging) of straggling actions.
+/// An action is hedged once it has been executing for longer than
+/// `duration_percentile` of the historical execution durations of actions
+/// with the same key multiplied by `duration_multiplier`. The durations are
+/// recorded by `execution_statistics.key`, or by command digest if
+/// `execution_statistics` is not set.
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct HedgingConfig {
+    /// The percentile (between 0 and 100) of historical execution durations
+    /// used as the base of the hedging threshold.
+    /// Default: 95
+    #[serde(default)]
+    pub duration_percentile: f32,
+
+    /// The multiplier applied to the percentile duration. Setting
+    /// `duration_percentile` to 50 and this to 3 will hedge actions that run
+    /// for three times longer than they usually do.
+    /// Default: 1.5
+    #[serde(default)]
+    pub duration_multiplier: f32,
+
+    /// The minimum number of historical executions with the same key before
+    /// any action with that key is considered for hedging.
+    /// Default: 5
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub min_samples: usize,
+
+    /// The number of most recent execution durations remembered per key.
+    /// Default: 100
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_samples: usize,
 }
 
 /// A scheduler that simply forwards requests to an upstream scheduler.  This
@@ -125,9 +422,18 @@ pub struct SimpleScheduler {
 /// build at the main scheduler directly though.
 #[derive(Deserialize, Debug, Default)]
 pub struct GrpcScheduler {
-    /// The upstream scheduler to forward requests to.
-    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    /// The upstream scheduler to forward requests to. Same as listing a
+    /// single endpoint in `endpoints`.
+    #[serde(default, deserialize_with = "convert_string_with_shellexpand")]
     pub endpoint: String,
+
+    /// The upstream schedulers to forward requests to. New actions are
+    /// balanced across the upstreams that are not failing with connection
+    /// errors. Looking up an existing action is always sent to the upstream
+    /// the action was sent to.
+    #[serde(default, deserialize_with = "convert_vec_string_with_shellexpand")]
+    pub endpoints: Vec<String>,
+
     /// Retry configuration to use when a network request fails.
     #[serde(default)]
     pub retry: Retry,
@@ -156,18 +462,76 @@ pub struct PlatformPropertyAddition {
     pub value: String,
 }
 
+/// A condition on a platform property of an action.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlatformPropertyCondition {
+    /// The name of the property the condition checks. The condition never
+    /// matches if the action does not have this property.
+    pub name: String,
+
+    /// If set, the property must be set to exactly this value.
+    /// Default: None (any value matches)
+    #[serde(default)]
+    pub value: Option<String>,
+
+    /// If set, the value of the property must match this regex. The regex is
+    /// not anchored, so use `^` and `$` to match the whole value.
+    /// Default: None (any value matches)
+    #[serde(default)]
+    pub regex: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlatformPropertyRewrite {
+    /// The name of the property whose value is matched against `regex`.
+    pub source: String,
+
+    /// The regex the value of `source` is matched against. If the action does
+    /// not have `source` or its value does not match, nothing is changed.
+    pub regex: String,
+
+    /// The name of the property to set.
+    pub name: String,
+
+    /// The value to set the property to. Capture groups of `regex` can be
+    /// referenced, eg: `$1` or `${name}`.
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ConditionalPropertyModification {
+    /// The condition that must match for the modifications to be performed.
+    pub condition: PlatformPropertyCondition,
+
+    /// The modifications to perform if the condition matches.
+    pub modifications: Vec<PropertyModification>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub enum PropertyModification {
     /// Add a property to the action properties.
     Add(PlatformPropertyAddition),
+    /// Add a property to the action properties if the action does not
+    /// already have it.
+    AddIfAbsent(PlatformPropertyAddition),
     /// Remove a named property from the action.
     Remove(String),
+    /// Set a property to a value derived from another property with a regex,
+    /// eg: set `OSFamily` to `linux` if `container-image` starts with
+    /// `docker://`.
+    Rewrite(PlatformPropertyRewrite),
+    /// Reject the action with an `InvalidArgument` error if the condition
+    /// matches. Used to forbid certain properties or values.
+    Reject(PlatformPropertyCondition),
+    /// Perform a list of modifications only if the condition matches.
+    Conditional(ConditionalPropertyModification),
 }
 
 #[derive(Deserialize, Debug)]
 pub struct PropertyModifierScheduler {
     /// A list of modifications to perform to incoming actions for the nested
-    /// scheduler.  These are performed in order and blindly, so removing a
+    /// scheduler.  These are performed in order, so a modification sees the
+    /// properties as changed by the modifications before it.  Removing a
     /// property that doesn't exist is fine and overwriting an existing property
     /// is also fine.  If adding properties that do not exist in the nested
     /// scheduler is not supported and will likely cause unexpected behaviour.
@@ -176,3 +540,43 @@ pub struct PropertyModifierScheduler {
     /// The nested scheduler to use after modifying the properties.
     pub scheduler: Box<SchedulerConfig>,
 }
+
+/// A scheduler that forwards each action to one of several other schedulers
+/// based on the action's instance name and platform properties. This makes it
+/// possible to, for example, run linux actions on a local pool of workers and
+/// forward macOS actions to a remote cluster.
+///
+/// The platform properties reported to clients are the union of the
+/// properties of all the schedulers actions can be forwarded to.
+#[derive(Deserialize, Debug)]
+pub struct RouterScheduler {
+    /// The routes to match actions against. Routes are evaluated in order and
+    /// the action is forwarded to the scheduler of the first matching route.
+    pub routes: Vec<RouterRoute>,
+
+    /// The scheduler to forward actions to that do not match any route,
+    /// referenced by its name in the `schedulers` map of the main config.
+    /// Default: None (actions that match no route are rejected)
+    #[serde(default)]
+    pub fallback_scheduler: Option<SchedulerRefName>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RouterRoute {
+    /// If set, only actions of this instance name match the route.
+    /// Default: None (matches any instance name)
+    #[serde(default)]
+    pub instance_name: Option<String>,
+
+    /// Platform properties an action must have to match the route. Every
+    /// property listed here must be set on the action to exactly this value.
+    /// Default: {} (matches any platform properties)
+    #[serde(default)]
+    pub platform_properties: HashMap<String, String>,
+
+    /// The scheduler to forward matching actions to, referenced by its name in
+    /// the `schedulers` map of the main config. This may be another router, as
+    /// long as routers do not forward actions to each other in a cycle.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub scheduler: SchedulerRefName,
+}
@@ -106,3 +106,14 @@ pub fn convert_string_with_shellexpand<'de, D: Deserializer<'de>>(deserializer:
     let value = String::deserialize(deserializer)?;
     Ok((*(shellexpand::env(&value).map_err(de::Error::custom)?)).to_string())
 }
+
+/// Same as `convert_string_with_shellexpand`, but for every string in a list.
+pub fn convert_vec_string_with_shellexpand<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let values = Vec::<String>::deserialize(deserializer)?;
+    values
+        .into_iter()
+        .map(|value| Ok((*(shellexpand::env(&value).map_err(de::Error::custom)?)).to_string()))
+        .collect()
+}
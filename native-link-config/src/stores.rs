@@ -195,7 +195,8 @@ pub struct FilesystemStore {
     /// the bulk of the data will be placed.
     /// On service bootup this folder will be scanned and all files will be
     /// added to the cache. In the event one of the files doesn't match the
-    /// criteria, the file will be deleted.
+    /// criteria, the file will be deleted. Files are made read-only once
+    /// their content was written.
     #[serde(deserialize_with = "convert_string_with_shellexpand")]
     pub content_path: String,
 
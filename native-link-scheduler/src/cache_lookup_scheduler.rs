@@ -155,6 +155,7 @@ impl ActionScheduler for CacheLookupScheduler {
         let mut current_state = Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::CacheCheck,
+            queue_position: None,
         });
         let (tx, rx) = watch::channel(current_state.clone());
         let tx = Arc::new(tx);
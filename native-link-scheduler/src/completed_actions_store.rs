@@ -0,0 +1,79 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use error::{Code, Error, ResultExt};
+use native_link_store::ac_utils::{get_and_decode_digest, name_to_digest};
+use native_link_util::action_messages::{ActionInfoHashKey, ActionState};
+use native_link_util::common::{log, DigestInfo};
+use native_link_util::store_trait::Store;
+use prost::Message;
+use proto::google::longrunning::Operation;
+
+/// Persists the final state of completed actions in a store as
+/// `google.longrunning.Operation`, so it can be looked up by operation name
+/// long after the action completed, after a restart or by another scheduler.
+pub struct CompletedActionsStore {
+    store: Arc<dyn Store>,
+}
+
+impl CompletedActionsStore {
+    pub fn new(store: Arc<dyn Store>) -> Self {
+        Self { store }
+    }
+
+    /// The digest the state of the action is persisted under in the store.
+    fn store_digest(unique_qualifier: &ActionInfoHashKey) -> Result<DigestInfo, Error> {
+        name_to_digest(&unique_qualifier.action_name())
+    }
+
+    /// Returns the persisted state of the completed action, or None if it was
+    /// never persisted or was evicted from the store.
+    pub async fn get(&self, unique_qualifier: &ActionInfoHashKey) -> Result<Option<ActionState>, Error> {
+        let store_digest = Self::store_digest(unique_qualifier).err_tip(|| "In CompletedActionsStore::get")?;
+        let result = get_and_decode_digest::<Operation>(Pin::new(self.store.as_ref()), &store_digest).await;
+        let operation = match result {
+            Ok(operation) => operation,
+            Err(err) if err.code == Code::NotFound => return Ok(None),
+            Err(err) => return Err(err).err_tip(|| "In CompletedActionsStore::get"),
+        };
+        ActionState::try_from(operation)
+            .err_tip(|| "Could not convert persisted operation in CompletedActionsStore::get")
+            .map(Some)
+    }
+
+    /// Persists the state of the completed action in the background.
+    pub fn persist(&self, action_state: ActionState) {
+        let store = self.store.clone();
+        let store_digest = Self::store_digest(&action_state.unique_qualifier);
+        let operation_name = action_state.unique_qualifier.action_name();
+        let operation = Operation::from(action_state);
+        tokio::spawn(async move {
+            let result = match store_digest {
+                Ok(store_digest) => {
+                    Pin::new(store.as_ref())
+                        .update_oneshot(store_digest, Bytes::from(operation.encode_to_vec()))
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                log::warn!("Could not persist completed operation '{operation_name}' : {err:?}");
+            }
+        });
+    }
+}
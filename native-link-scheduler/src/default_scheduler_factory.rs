@@ -12,11 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
-use error::{Error, ResultExt};
+use error::{make_input_err, Error, ResultExt};
 use native_link_config::schedulers::SchedulerConfig;
 use native_link_store::store_manager::StoreManager;
 use native_link_util::metrics_utils::Registry;
@@ -26,20 +26,26 @@ use crate::action_scheduler::ActionScheduler;
 use crate::cache_lookup_scheduler::CacheLookupScheduler;
 use crate::grpc_scheduler::GrpcScheduler;
 use crate::property_modifier_scheduler::PropertyModifierScheduler;
-use crate::simple_scheduler::SimpleScheduler;
+use crate::router_scheduler::RouterScheduler;
+use crate::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
 use crate::worker_scheduler::WorkerScheduler;
 
 pub type SchedulerFactoryResults = (Option<Arc<dyn ActionScheduler>>, Option<Arc<dyn WorkerScheduler>>);
 
+/// Creates the scheduler described by the config. `action_schedulers` holds the
+/// already created top level schedulers, which routers may forward actions to,
+/// see `scheduler_creation_order()`.
 pub fn scheduler_factory(
     scheduler_type_cfg: &SchedulerConfig,
     store_manager: &StoreManager,
+    action_schedulers: &HashMap<String, Arc<dyn ActionScheduler>>,
     scheduler_metrics: &mut Registry,
 ) -> Result<SchedulerFactoryResults, Error> {
     let mut visited_schedulers = HashSet::new();
     inner_scheduler_factory(
         scheduler_type_cfg,
         store_manager,
+        action_schedulers,
         Some(scheduler_metrics),
         &mut visited_schedulers,
     )
@@ -48,12 +54,38 @@ pub fn scheduler_factory(
 fn inner_scheduler_factory(
     scheduler_type_cfg: &SchedulerConfig,
     store_manager: &StoreManager,
+    action_schedulers: &HashMap<String, Arc<dyn ActionScheduler>>,
     maybe_scheduler_metrics: Option<&mut Registry>,
     visited_schedulers: &mut HashSet<usize>,
 ) -> Result<SchedulerFactoryResults, Error> {
     let scheduler: SchedulerFactoryResults = match scheduler_type_cfg {
         SchedulerConfig::simple(config) => {
-            let scheduler = Arc::new(SimpleScheduler::new(config));
+            let execution_statistics_store = config
+                .execution_statistics
+                .as_ref()
+                .and_then(|execution_statistics| execution_statistics.store.as_ref())
+                .map(|store| {
+                    store_manager
+                        .get_store(store)
+                        .err_tip(|| format!("'execution_statistics.store': '{store}' does not exist"))
+                })
+                .transpose()?;
+            let completed_actions_store = config
+                .completed_actions_store
+                .as_ref()
+                .map(|store| {
+                    store_manager
+                        .get_store(store)
+                        .err_tip(|| format!("'completed_actions_store': '{store}' does not exist"))
+                })
+                .transpose()?;
+            let scheduler = Arc::new(SimpleScheduler::new(
+                config,
+                SimpleSchedulerStores {
+                    execution_statistics: execution_statistics_store,
+                    completed_actions: completed_actions_store,
+                },
+            ));
             (Some(scheduler.clone()), Some(scheduler))
         }
         SchedulerConfig::grpc(config) => (Some(Arc::new(GrpcScheduler::new(config)?)), None),
@@ -64,9 +96,14 @@ fn inner_scheduler_factory(
             let ac_store = store_manager
                 .get_store(&config.ac_store)
                 .err_tip(|| format!("'ac_store': '{}' does not exist", config.ac_store))?;
-            let (action_scheduler, worker_scheduler) =
-                inner_scheduler_factory(&config.scheduler, store_manager, None, visited_schedulers)
-                    .err_tip(|| "In nested CacheLookupScheduler construction")?;
+            let (action_scheduler, worker_scheduler) = inner_scheduler_factory(
+                &config.scheduler,
+                store_manager,
+                action_schedulers,
+                None,
+                visited_schedulers,
+            )
+            .err_tip(|| "In nested CacheLookupScheduler construction")?;
             let cache_lookup_scheduler = Arc::new(CacheLookupScheduler::new(
                 cas_store,
                 ac_store,
@@ -75,15 +112,26 @@ fn inner_scheduler_factory(
             (Some(cache_lookup_scheduler), worker_scheduler)
         }
         SchedulerConfig::property_modifier(config) => {
-            let (action_scheduler, worker_scheduler) =
-                inner_scheduler_factory(&config.scheduler, store_manager, None, visited_schedulers)
-                    .err_tip(|| "In nested PropertyModifierScheduler construction")?;
+            let (action_scheduler, worker_scheduler) = inner_scheduler_factory(
+                &config.scheduler,
+                store_manager,
+                action_schedulers,
+                None,
+                visited_schedulers,
+            )
+            .err_tip(|| "In nested PropertyModifierScheduler construction")?;
             let property_modifier_scheduler = Arc::new(PropertyModifierScheduler::new(
                 config,
                 action_scheduler.err_tip(|| "Nested scheduler is not an action scheduler")?,
-            ));
+            )?);
             (Some(property_modifier_scheduler), worker_scheduler)
         }
+        SchedulerConfig::router(config) => (
+            Some(Arc::new(
+                RouterScheduler::new(config, action_schedulers).err_tip(|| "In RouterScheduler construction")?,
+            )),
+            None,
+        ),
     };
 
     if let Some(scheduler_metrics) = maybe_scheduler_metrics {
@@ -114,6 +162,83 @@ fn inner_scheduler_factory(
     Ok(scheduler)
 }
 
+/// Returns the names of the top level schedulers in the order they have to be
+/// created in, so the schedulers a router forwards actions to, including routers
+/// nested in other schedulers, are created before it. Errors if a router
+/// references a scheduler that does not exist or the references form a cycle.
+pub fn scheduler_creation_order(schedulers: &HashMap<String, SchedulerConfig>) -> Result<Vec<String>, Error> {
+    // Visit in a fixed order, so errors do not depend on the iteration order of the map.
+    let mut names: Vec<&String> = schedulers.keys().collect();
+    names.sort();
+    let mut created = HashSet::new();
+    let mut path = Vec::new();
+    let mut order = Vec::with_capacity(schedulers.len());
+    for name in names {
+        visit_scheduler(name, schedulers, &mut created, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+fn visit_scheduler<'a>(
+    name: &'a String,
+    schedulers: &'a HashMap<String, SchedulerConfig>,
+    created: &mut HashSet<&'a String>,
+    path: &mut Vec<&'a String>,
+    order: &mut Vec<String>,
+) -> Result<(), Error> {
+    if created.contains(name) {
+        return Ok(());
+    }
+    if let Some(cycle_start) = path.iter().position(|path_name| *path_name == name) {
+        let cycle: Vec<&str> = path[cycle_start..]
+            .iter()
+            .chain([&name])
+            .map(|name| name.as_str())
+            .collect();
+        return Err(make_input_err!(
+            "Schedulers forward actions to each other in a cycle: {}",
+            cycle.join(" -> ")
+        ));
+    }
+    let scheduler_cfg = &schedulers[name];
+    let mut referenced_schedulers = Vec::new();
+    collect_referenced_schedulers(scheduler_cfg, &mut referenced_schedulers);
+    path.push(name);
+    for referenced_name in referenced_schedulers {
+        let (referenced_name, _) = schedulers.get_key_value(referenced_name).ok_or_else(|| {
+            make_input_err!(
+                "Scheduler '{name}' forwards actions to scheduler '{referenced_name}', which does not exist"
+            )
+        })?;
+        visit_scheduler(referenced_name, schedulers, created, path, order)?;
+    }
+    path.pop();
+    created.insert(name);
+    order.push(name.clone());
+    Ok(())
+}
+
+/// Adds the names of the top level schedulers the routers in the config forward
+/// actions to.
+fn collect_referenced_schedulers<'a>(scheduler_cfg: &'a SchedulerConfig, referenced_schedulers: &mut Vec<&'a String>) {
+    match scheduler_cfg {
+        SchedulerConfig::simple(_) | SchedulerConfig::grpc(_) => {}
+        SchedulerConfig::cache_lookup(config) => {
+            collect_referenced_schedulers(&config.scheduler, referenced_schedulers)
+        }
+        SchedulerConfig::property_modifier(config) => {
+            collect_referenced_schedulers(&config.scheduler, referenced_schedulers);
+        }
+        SchedulerConfig::router(config) => referenced_schedulers.extend(
+            config
+                .routes
+                .iter()
+                .map(|route| &route.scheduler)
+                .chain(config.fallback_scheduler.iter()),
+        ),
+    }
+}
+
 fn start_cleanup_timer(action_scheduler: &Arc<dyn ActionScheduler>) {
     let weak_scheduler = Arc::downgrade(action_scheduler);
     tokio::spawn(async move {
@@ -0,0 +1,259 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use bytes::Bytes;
+use error::{Code, Error};
+use lru::LruCache;
+use native_link_config::schedulers::{ExecutionOrdering, ExecutionStatisticsConfig, ExecutionStatisticsKey};
+use native_link_store::ac_utils::{get_and_decode_digest, name_to_digest};
+use native_link_util::action_messages::{ActionInfo, ActionResult};
+use native_link_util::common::{log, DigestInfo};
+use native_link_util::store_trait::Store;
+use parking_lot::Mutex;
+use prost::Message;
+
+/// Default number of keys the execution durations are kept in memory for.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+/// Default number of most recent execution durations remembered per key.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_SAMPLES: usize = 100;
+
+/// Weight of a newly recorded execution duration in the moving average of its key.
+const NEW_DURATION_WEIGHT: f64 = 0.25;
+
+/// What the execution durations of an action are recorded by.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum HistoryKey {
+    Command(DigestInfo),
+    Mnemonic(String),
+}
+
+impl HistoryKey {
+    /// The digest the moving average of the key is persisted under in the store.
+    /// Durations of a command are stored under the digest of the command, like
+    /// results in the action cache are stored under the digest of the action.
+    fn store_digest(&self) -> Result<DigestInfo, Error> {
+        match self {
+            HistoryKey::Command(command_digest) => Ok(*command_digest),
+            HistoryKey::Mnemonic(mnemonic) => name_to_digest(mnemonic),
+        }
+    }
+}
+
+impl fmt::Display for HistoryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryKey::Command(command_digest) => {
+                write!(f, "command {}-{}", command_digest.hash_str(), command_digest.size_bytes)
+            }
+            HistoryKey::Mnemonic(mnemonic) => write!(f, "mnemonic {mnemonic}"),
+        }
+    }
+}
+
+/// The execution durations recorded for a key.
+struct DurationHistory {
+    /// Moving average of all durations, which is what is persisted.
+    average: Duration,
+    /// The most recent durations, oldest first. Only kept in memory.
+    samples: VecDeque<Duration>,
+}
+
+/// Records how long actions take to execute. The history is used to estimate how
+/// long queued actions will take to execute and to decide when a running action
+/// is straggling. The moving averages are kept in memory and, if a store is
+/// configured, persisted in the store as `google.protobuf.Duration`.
+pub struct ExecutionStatistics {
+    key: ExecutionStatisticsKey,
+    ordering: ExecutionOrdering,
+    store: Option<Arc<dyn Store>>,
+    max_samples: usize,
+    durations: Arc<Mutex<LruCache<HistoryKey, DurationHistory>>>,
+}
+
+impl ExecutionStatistics {
+    /// Creates an empty history. `max_samples` is the number of most recent
+    /// durations remembered per key, 0 means the default.
+    pub fn new(config: &ExecutionStatisticsConfig, max_samples: usize, store: Option<Arc<dyn Store>>) -> Self {
+        let mut max_entries = config.max_entries;
+        if max_entries == 0 {
+            max_entries = DEFAULT_MAX_ENTRIES;
+        }
+        let mut max_samples = max_samples;
+        if max_samples == 0 {
+            max_samples = DEFAULT_MAX_SAMPLES;
+        }
+        Self {
+            key: config.key,
+            ordering: config.ordering,
+            store,
+            max_samples,
+            durations: Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(max_entries).unwrap()))),
+        }
+    }
+
+    fn key_for(&self, action_info: &ActionInfo) -> HistoryKey {
+        match (self.key, &action_info.action_mnemonic) {
+            (ExecutionStatisticsKey::mnemonic, Some(action_mnemonic)) => HistoryKey::Mnemonic(action_mnemonic.clone()),
+            _ => HistoryKey::Command(action_info.command_digest),
+        }
+    }
+
+    /// Returns how long the action is expected to execute for, or None if no
+    /// execution of an action with the same key was recorded.
+    pub async fn expected_duration(&self, action_info: &ActionInfo) -> Option<Duration> {
+        let key = self.key_for(action_info);
+        if let Some(history) = self.durations.lock().get(&key) {
+            return Some(history.average);
+        }
+        let store = self.store.as_ref()?;
+        let store_digest = match key.store_digest() {
+            Ok(store_digest) => store_digest,
+            Err(err) => {
+                log::warn!("Could not compute the store key of the execution duration of {key} : {err:?}");
+                return None;
+            }
+        };
+        let result = get_and_decode_digest::<prost_types::Duration>(Pin::new(store.as_ref()), &store_digest).await;
+        let duration = match result {
+            Ok(duration) => Duration::try_from(duration).ok()?,
+            Err(err) => {
+                if err.code != Code::NotFound {
+                    log::warn!("Could not load execution duration of {key} : {err:?}");
+                }
+                return None;
+            }
+        };
+        // A duration may have been recorded while loading, which is more recent.
+        let mut durations = self.durations.lock();
+        let history = durations.get_or_insert_mut(key, || DurationHistory {
+            average: duration,
+            samples: VecDeque::new(),
+        });
+        Some(history.average)
+    }
+
+    /// Returns the most recently recorded durations of actions with the same key
+    /// as the action, oldest first. Durations loaded from the store are not included.
+    pub fn recent_durations(&self, action_info: &ActionInfo) -> Vec<Duration> {
+        self.durations
+            .lock()
+            .peek(&self.key_for(action_info))
+            .map(|history| history.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Records how long a successful execution of the action took. The new
+    /// estimate is persisted in the background. If the key was not in memory,
+    /// the average persisted in the store is loaded and blended in first.
+    pub fn record_execution(&self, action_info: &ActionInfo, action_result: &ActionResult) {
+        let metadata = &action_result.execution_metadata;
+        if action_result.error.is_some() || metadata.worker_start_timestamp == UNIX_EPOCH {
+            return;
+        }
+        let Ok(duration) = metadata
+            .worker_completed_timestamp
+            .duration_since(metadata.worker_start_timestamp)
+        else {
+            return;
+        };
+        let key = self.key_for(action_info);
+        let (average, is_new) = {
+            let mut durations = self.durations.lock();
+            let is_new = !durations.contains(&key);
+            let history = durations.get_or_insert_mut(key.clone(), || DurationHistory {
+                average: duration,
+                samples: VecDeque::new(),
+            });
+            if !is_new {
+                history.average =
+                    history.average.mul_f64(1. - NEW_DURATION_WEIGHT) + duration.mul_f64(NEW_DURATION_WEIGHT);
+            }
+            if history.samples.len() >= self.max_samples {
+                history.samples.pop_front();
+            }
+            history.samples.push_back(duration);
+            (history.average, is_new)
+        };
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        let durations = self.durations.clone();
+        tokio::spawn(async move {
+            let store_digest = match key.store_digest() {
+                Ok(store_digest) => store_digest,
+                Err(err) => {
+                    log::warn!("Could not persist execution duration of {key} : {err:?}");
+                    return;
+                }
+            };
+            let mut average = average;
+            if is_new {
+                let result =
+                    get_and_decode_digest::<prost_types::Duration>(Pin::new(store.as_ref()), &store_digest).await;
+                match result.map(Duration::try_from) {
+                    Ok(Ok(stored_average)) => {
+                        let mut durations = durations.lock();
+                        // Durations recorded while loading are blended in as well.
+                        let current_average = durations.peek(&key).map_or(average, |history| history.average);
+                        average = stored_average.mul_f64(1. - NEW_DURATION_WEIGHT)
+                            + current_average.mul_f64(NEW_DURATION_WEIGHT);
+                        if let Some(history) = durations.peek_mut(&key) {
+                            history.average = average;
+                        }
+                    }
+                    Ok(Err(_)) => {}
+                    Err(err) if err.code == Code::NotFound => {}
+                    Err(err) => {
+                        // Don't overwrite the history in the store with a single execution.
+                        log::warn!("Could not load execution duration of {key} : {err:?}");
+                        return;
+                    }
+                }
+            }
+            let Ok(average) = prost_types::Duration::try_from(average) else {
+                return;
+            };
+            let result = Pin::new(store.as_ref())
+                .update_oneshot(store_digest, Bytes::from(average.encode_to_vec()))
+                .await;
+            if let Err(err) = result {
+                log::warn!("Could not persist execution duration of {key} : {err:?}");
+            }
+        });
+    }
+
+    /// Returns the key queued actions of the same priority are sorted by to run
+    /// them in the configured order. Actions without an expected duration are
+    /// sorted first, since nothing is known about them.
+    pub fn sort_key(&self, expected_duration: Option<Duration>) -> i128 {
+        let Some(expected_duration) = expected_duration else {
+            return i128::MIN;
+        };
+        let nanos = i128::try_from(expected_duration.as_nanos()).unwrap_or(i128::MAX);
+        match self.ordering {
+            ExecutionOrdering::shortest_job_first => nanos,
+            ExecutionOrdering::critical_path_first => -nanos,
+        }
+    }
+}
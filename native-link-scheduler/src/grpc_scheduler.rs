@@ -14,13 +14,16 @@
 
 use std::collections::HashMap;
 use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use error::{make_err, Code, Error, ResultExt};
+use error::{error_if, make_err, Code, Error, ResultExt};
 use futures::stream::unfold;
 use futures::TryFutureExt;
+use lru::LruCache;
 use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionState, DEFAULT_EXECUTION_PRIORITY};
 use native_link_util::common::log;
 use native_link_util::retry::{ExponentialBackoff, Retrier, RetryResult};
@@ -41,9 +44,49 @@ use tonic::{transport, Request, Streaming};
 use crate::action_scheduler::ActionScheduler;
 use crate::platform_property_manager::PlatformPropertyManager;
 
-pub struct GrpcScheduler {
+/// How long an upstream is avoided for new actions after a connection error.
+const UNHEALTHY_UPSTREAM_DURATION: Duration = Duration::from_secs(10);
+
+/// Maximum number of actions to remember which upstream they were sent to.
+const MAX_TRACKED_OPERATIONS: usize = 100_000;
+
+struct Upstream {
+    endpoint: String,
     capabilities_client: CapabilitiesClient<transport::Channel>,
     execution_client: ExecutionClient<transport::Channel>,
+    /// If set, the upstream recently failed with a connection error and should
+    /// not be sent new actions until this time.
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Upstream {
+    fn is_healthy(&self) -> bool {
+        !self
+            .unhealthy_until
+            .lock()
+            .is_some_and(|unhealthy_until| unhealthy_until > Instant::now())
+    }
+
+    /// Marks the upstream as unhealthy if the error is caused by the connection to it.
+    fn record_error(&self, err: Error) -> Error {
+        if err.code == Code::Unavailable {
+            log::warn!(
+                "Upstream scheduler {} is unavailable, failing over to other upstreams: {}",
+                self.endpoint,
+                err
+            );
+            *self.unhealthy_until.lock() = Some(Instant::now() + UNHEALTHY_UPSTREAM_DURATION);
+        }
+        err
+    }
+}
+
+pub struct GrpcScheduler {
+    upstreams: Vec<Upstream>,
+    /// Index used to balance new actions across the upstreams.
+    next_upstream_index: AtomicUsize,
+    /// The index of the upstream each recently added action was sent to.
+    operation_upstreams: Mutex<LruCache<String, usize>>,
     platform_property_managers: Mutex<HashMap<String, Arc<PlatformPropertyManager>>>,
     jitter_fn: Box<dyn Fn(Duration) -> Duration + Send + Sync>,
     retry: native_link_config::stores::Retry,
@@ -70,14 +113,29 @@ impl GrpcScheduler {
         config: &native_link_config::schedulers::GrpcScheduler,
         jitter_fn: Box<dyn Fn(Duration) -> Duration + Send + Sync>,
     ) -> Result<Self, Error> {
-        let endpoint = transport::Channel::balance_list(std::iter::once(
-            transport::Endpoint::new(config.endpoint.clone())
-                .err_tip(|| format!("Could not parse {} in GrpcScheduler", config.endpoint))?,
-        ));
+        let endpoints = config
+            .endpoints
+            .iter()
+            .chain(std::iter::once(&config.endpoint).filter(|endpoint| !endpoint.is_empty()));
+        let mut upstreams = Vec::with_capacity(config.endpoints.len() + 1);
+        for endpoint in endpoints {
+            let channel = transport::Channel::balance_list(std::iter::once(
+                transport::Endpoint::new(endpoint.clone())
+                    .err_tip(|| format!("Could not parse {endpoint} in GrpcScheduler"))?,
+            ));
+            upstreams.push(Upstream {
+                endpoint: endpoint.clone(),
+                capabilities_client: CapabilitiesClient::new(channel.clone()),
+                execution_client: ExecutionClient::new(channel),
+                unhealthy_until: Mutex::new(None),
+            });
+        }
+        error_if!(upstreams.is_empty(), "Expected at least 1 endpoint in GrpcScheduler");
 
         Ok(Self {
-            capabilities_client: CapabilitiesClient::new(endpoint.clone()),
-            execution_client: ExecutionClient::new(endpoint),
+            upstreams,
+            next_upstream_index: AtomicUsize::new(0),
+            operation_upstreams: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_TRACKED_OPERATIONS).unwrap())),
             platform_property_managers: Mutex::new(HashMap::new()),
             jitter_fn,
             retry: config.retry.clone(),
@@ -85,6 +143,16 @@ impl GrpcScheduler {
         })
     }
 
+    /// Returns the index of the next healthy upstream in round robin order. If all
+    /// upstreams are unhealthy, the next upstream is returned regardless.
+    fn next_upstream_index(&self) -> usize {
+        let start_index = self.next_upstream_index.fetch_add(1, Ordering::Relaxed);
+        (0..self.upstreams.len())
+            .map(|offset| (start_index + offset) % self.upstreams.len())
+            .find(|index| self.upstreams[*index].is_healthy())
+            .unwrap_or(start_index % self.upstreams.len())
+    }
+
     async fn perform_request<F, Fut, R, I>(&self, input: I, mut request: F) -> Result<R, Error>
     where
         F: FnMut(I) -> Fut + Send + Copy,
@@ -100,12 +168,13 @@ impl GrpcScheduler {
                 retry_config,
                 unfold(input, move |input| async move {
                     let input_clone = input.clone();
-                    Some((
-                        request(input_clone)
-                            .await
-                            .map_or_else(RetryResult::Retry, RetryResult::Ok),
-                        input,
-                    ))
+                    let result = match request(input_clone).await {
+                        Ok(result) => RetryResult::Ok(result),
+                        // The upstream answered, asking again won't change the answer.
+                        Err(err) if err.code == Code::NotFound => RetryResult::Err(err),
+                        Err(err) => RetryResult::Retry(err),
+                    };
+                    Some((result, input))
                 }),
             )
             .await
@@ -159,13 +228,15 @@ impl ActionScheduler for GrpcScheduler {
 
         self.perform_request(instance_name, |instance_name| async move {
             // Not in the cache, lookup the capabilities with the upstream.
-            let capabilities = self
+            let upstream = &self.upstreams[self.next_upstream_index()];
+            let capabilities = upstream
                 .capabilities_client
                 .clone()
                 .get_capabilities(GetCapabilitiesRequest {
                     instance_name: instance_name.to_string(),
                 })
-                .await?
+                .await
+                .map_err(|status| upstream.record_error(status.into()))?
                 .into_inner();
             let platform_property_manager = Arc::new(PlatformPropertyManager::new(
                 capabilities
@@ -202,43 +273,71 @@ impl ActionScheduler for GrpcScheduler {
             results_cache_policy: None,
             digest_function: digest_function::Value::Sha256.into(),
         };
-        let result_stream = self
+        let (upstream_index, result_stream) = self
             .perform_request(request, |request| async move {
-                self.execution_client
+                // Every attempt picks the next upstream, so failing upstreams are skipped.
+                let upstream_index = self.next_upstream_index();
+                let upstream = &self.upstreams[upstream_index];
+                upstream
+                    .execution_client
                     .clone()
                     .execute(Request::new(request))
                     .await
-                    .err_tip(|| "Sending action to upstream scheduler")
+                    .map_err(|status| upstream.record_error(status.into()))
+                    .err_tip(|| format!("Sending action to upstream scheduler {}", upstream.endpoint))
+                    .map(|result_stream| (upstream_index, result_stream))
             })
-            .await?
-            .into_inner();
-        Self::stream_state(result_stream).await
+            .await?;
+        let rx = Self::stream_state(result_stream.into_inner()).await?;
+        self.operation_upstreams
+            .lock()
+            .put(rx.borrow().unique_qualifier.action_name(), upstream_index);
+        Ok(rx)
     }
 
     async fn find_existing_action(
         &self,
         unique_qualifier: &ActionInfoHashKey,
     ) -> Option<watch::Receiver<Arc<ActionState>>> {
-        let request = WaitExecutionRequest {
-            name: unique_qualifier.action_name(),
+        let action_name = unique_qualifier.action_name();
+        // Only the upstream the action was sent to knows about it. If we don't know
+        // which one that is, ask all of them.
+        let upstream_indexes: Vec<usize> = match self.operation_upstreams.lock().get(&action_name) {
+            Some(upstream_index) => vec![*upstream_index],
+            None => (0..self.upstreams.len()).collect(),
         };
-        let result_stream = self
-            .perform_request(request, |request| async move {
-                self.execution_client
-                    .clone()
-                    .wait_execution(Request::new(request))
-                    .await
-                    .err_tip(|| "While getting wait_execution stream")
-            })
-            .and_then(|result_stream| Self::stream_state(result_stream.into_inner()))
-            .await;
-        match result_stream {
-            Ok(result_stream) => Some(result_stream),
-            Err(err) => {
-                log::warn!("Error response looking up action with upstream scheduler: {}", err);
-                None
+        for upstream_index in upstream_indexes {
+            let upstream = &self.upstreams[upstream_index];
+            let request = WaitExecutionRequest {
+                name: action_name.clone(),
+            };
+            let result_stream = self
+                .perform_request(request, |request| async move {
+                    upstream
+                        .execution_client
+                        .clone()
+                        .wait_execution(Request::new(request))
+                        .await
+                        .map_err(|status| upstream.record_error(status.into()))
+                        .err_tip(|| "While getting wait_execution stream")
+                })
+                .and_then(|result_stream| Self::stream_state(result_stream.into_inner()))
+                .await;
+            match result_stream {
+                Ok(result_stream) => {
+                    self.operation_upstreams.lock().put(action_name, upstream_index);
+                    return Some(result_stream);
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Error response looking up action with upstream scheduler {}: {}",
+                        upstream.endpoint,
+                        err
+                    );
+                }
             }
         }
+        None
     }
 
     async fn clean_recently_completed_actions(&self) {}
@@ -14,10 +14,15 @@
 
 pub mod action_scheduler;
 pub mod cache_lookup_scheduler;
+pub mod completed_actions_store;
 pub mod default_scheduler_factory;
+pub mod execution_statistics;
 pub mod grpc_scheduler;
 pub mod platform_property_manager;
 pub mod property_modifier_scheduler;
+pub mod router_scheduler;
+pub mod scheduler_events;
 pub mod simple_scheduler;
 pub mod worker;
+pub mod worker_pool_demand;
 pub mod worker_scheduler;
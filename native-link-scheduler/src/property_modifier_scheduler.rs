@@ -17,17 +17,194 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use error::{Error, ResultExt};
-use native_link_config::schedulers::{PropertyModification, PropertyType};
+use error::{make_input_err, Error, ResultExt};
+use native_link_config::schedulers::{
+    PlatformPropertyAddition, PlatformPropertyCondition, PropertyModification, PropertyType,
+};
 use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionState};
+use native_link_util::platform_properties::PlatformPropertyValue;
 use parking_lot::Mutex;
+use regex::Regex;
 use tokio::sync::watch;
 
 use crate::action_scheduler::ActionScheduler;
 use crate::platform_property_manager::PlatformPropertyManager;
 
+/// A `PlatformPropertyCondition` with its regex compiled.
+struct Condition {
+    name: String,
+    value: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl Condition {
+    fn new(config: &PlatformPropertyCondition) -> Result<Self, Error> {
+        let regex = config
+            .regex
+            .as_ref()
+            .map(|regex| {
+                Regex::new(regex)
+                    .map_err(|e| make_input_err!("Invalid regex '{}' for '{}' : {:?}", regex, config.name, e))
+            })
+            .transpose()?;
+        Ok(Self {
+            name: config.name.clone(),
+            value: config.value.clone(),
+            regex,
+        })
+    }
+
+    fn matches(&self, properties: &HashMap<String, PlatformPropertyValue>) -> bool {
+        let Some(prop_value) = properties.get(&self.name) else {
+            return false;
+        };
+        let prop_value = prop_value.as_str();
+        if let Some(value) = &self.value {
+            if value != prop_value.as_ref() {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&prop_value) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A `PropertyModification` with its regexes compiled.
+enum Modification {
+    Add(PlatformPropertyAddition),
+    AddIfAbsent(PlatformPropertyAddition),
+    Remove(String),
+    Rewrite {
+        source: String,
+        regex: Regex,
+        name: String,
+        value: String,
+    },
+    Reject(Condition),
+    Conditional(Condition, Vec<Modification>),
+}
+
+impl Modification {
+    fn new(config: &PropertyModification) -> Result<Self, Error> {
+        Ok(match config {
+            PropertyModification::Add(addition) => Self::Add(addition.clone()),
+            PropertyModification::AddIfAbsent(addition) => Self::AddIfAbsent(addition.clone()),
+            PropertyModification::Remove(name) => Self::Remove(name.clone()),
+            PropertyModification::Rewrite(rewrite) => Self::Rewrite {
+                source: rewrite.source.clone(),
+                regex: Regex::new(&rewrite.regex).map_err(|e| {
+                    make_input_err!("Invalid regex '{}' for '{}' : {:?}", rewrite.regex, rewrite.source, e)
+                })?,
+                name: rewrite.name.clone(),
+                value: rewrite.value.clone(),
+            },
+            PropertyModification::Reject(condition) => Self::Reject(Condition::new(condition)?),
+            PropertyModification::Conditional(conditional) => Self::Conditional(
+                Condition::new(&conditional.condition)?,
+                make_modifications(&conditional.modifications)?,
+            ),
+        })
+    }
+}
+
+fn make_modifications(configs: &[PropertyModification]) -> Result<Vec<Modification>, Error> {
+    configs.iter().map(Modification::new).collect()
+}
+
+/// Adds the properties that are matched on, but may not be known to the nested scheduler,
+/// to the known properties, so actions with them can be accepted.
+fn add_matched_properties(modifications: &[Modification], known_properties: &mut HashMap<String, PropertyType>) {
+    for modification in modifications {
+        match modification {
+            Modification::Remove(name) | Modification::Rewrite { source: name, .. } => {
+                known_properties.entry(name.into()).or_insert(PropertyType::Priority);
+            }
+            Modification::Reject(condition) => {
+                known_properties
+                    .entry(condition.name.clone())
+                    .or_insert(PropertyType::Priority);
+            }
+            Modification::Conditional(condition, modifications) => {
+                known_properties
+                    .entry(condition.name.clone())
+                    .or_insert(PropertyType::Priority);
+                add_matched_properties(modifications, known_properties);
+            }
+            Modification::Add(_) | Modification::AddIfAbsent(_) => (),
+        }
+    }
+}
+
+/// Performs the modifications in order on the properties.
+fn apply_modifications(
+    modifications: &[Modification],
+    properties: &mut HashMap<String, PlatformPropertyValue>,
+    platform_property_manager: &PlatformPropertyManager,
+) -> Result<(), Error> {
+    for modification in modifications {
+        match modification {
+            Modification::Add(addition) => {
+                properties.insert(
+                    addition.name.clone(),
+                    platform_property_manager.make_prop_value(&addition.name, &addition.value)?,
+                );
+            }
+            Modification::AddIfAbsent(addition) => {
+                if !properties.contains_key(&addition.name) {
+                    properties.insert(
+                        addition.name.clone(),
+                        platform_property_manager.make_prop_value(&addition.name, &addition.value)?,
+                    );
+                }
+            }
+            Modification::Remove(name) => {
+                properties.remove(name);
+            }
+            Modification::Rewrite {
+                source,
+                regex,
+                name,
+                value,
+            } => {
+                let Some(source_value) = properties.get(source) else {
+                    continue;
+                };
+                let source_value = source_value.as_str().into_owned();
+                let Some(captures) = regex.captures(&source_value) else {
+                    continue;
+                };
+                let mut new_value = String::new();
+                captures.expand(value, &mut new_value);
+                properties.insert(
+                    name.clone(),
+                    platform_property_manager.make_prop_value(name, &new_value)?,
+                );
+            }
+            Modification::Reject(condition) => {
+                if condition.matches(properties) {
+                    return Err(make_input_err!(
+                        "Platform property '{}' with value '{}' is not allowed",
+                        condition.name,
+                        properties[&condition.name].as_str()
+                    ));
+                }
+            }
+            Modification::Conditional(condition, modifications) => {
+                if condition.matches(properties) {
+                    apply_modifications(modifications, properties, platform_property_manager)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct PropertyModifierScheduler {
-    modifications: Vec<native_link_config::schedulers::PropertyModification>,
+    modifications: Vec<Modification>,
     scheduler: Arc<dyn ActionScheduler>,
     property_managers: Mutex<HashMap<String, Arc<PlatformPropertyManager>>>,
 }
@@ -36,12 +213,12 @@ impl PropertyModifierScheduler {
     pub fn new(
         config: &native_link_config::schedulers::PropertyModifierScheduler,
         scheduler: Arc<dyn ActionScheduler>,
-    ) -> Self {
-        Self {
-            modifications: config.modifications.clone(),
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            modifications: make_modifications(&config.modifications).err_tip(|| "In PropertyModifierScheduler::new")?,
             scheduler,
             property_managers: Mutex::new(HashMap::new()),
-        }
+        })
     }
 }
 
@@ -56,14 +233,7 @@ impl ActionScheduler for PropertyModifierScheduler {
         }
         let property_manager = self.scheduler.get_platform_property_manager(instance_name).await?;
         let mut known_properties = property_manager.get_known_properties().clone();
-        for modification in &self.modifications {
-            match modification {
-                PropertyModification::Remove(name) => {
-                    known_properties.entry(name.into()).or_insert(PropertyType::Priority);
-                }
-                PropertyModification::Add(_) => (),
-            }
-        }
+        add_matched_properties(&self.modifications, &mut known_properties);
         let property_manager = {
             let mut property_managers = self.property_managers.lock();
             match property_managers.entry(instance_name.into()) {
@@ -84,17 +254,12 @@ impl ActionScheduler for PropertyModifierScheduler {
             .get_platform_property_manager(&action_info.unique_qualifier.instance_name)
             .await
             .err_tip(|| "In PropertyModifierScheduler::add_action")?;
-        for modification in &self.modifications {
-            match modification {
-                PropertyModification::Add(addition) => action_info.platform_properties.properties.insert(
-                    addition.name.clone(),
-                    platform_property_manager
-                        .make_prop_value(&addition.name, &addition.value)
-                        .err_tip(|| "In PropertyModifierScheduler::add_action")?,
-                ),
-                PropertyModification::Remove(name) => action_info.platform_properties.properties.remove(name),
-            };
-        }
+        apply_modifications(
+            &self.modifications,
+            &mut action_info.platform_properties.properties,
+            &platform_property_manager,
+        )
+        .err_tip(|| "In PropertyModifierScheduler::add_action")?;
         self.scheduler.add_action(action_info).await
     }
 
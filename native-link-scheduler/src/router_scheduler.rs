@@ -0,0 +1,202 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use error::{make_input_err, Error, ResultExt};
+use native_link_config::schedulers::RouterRoute;
+use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionState};
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+use crate::action_scheduler::ActionScheduler;
+use crate::platform_property_manager::PlatformPropertyManager;
+
+struct Route {
+    config: RouterRoute,
+    scheduler: Arc<dyn ActionScheduler>,
+}
+
+impl Route {
+    /// Returns true if the action should be forwarded to the scheduler of this route.
+    fn matches(&self, action_info: &ActionInfo) -> bool {
+        if let Some(instance_name) = &self.config.instance_name {
+            if instance_name != action_info.instance_name() {
+                return false;
+            }
+        }
+        self.config.platform_properties.iter().all(|(name, value)| {
+            action_info
+                .platform_properties
+                .properties
+                .get(name)
+                .is_some_and(|prop_value| prop_value.as_str() == value.as_str())
+        })
+    }
+}
+
+pub struct RouterScheduler {
+    routes: Vec<Route>,
+    fallback_scheduler: Option<Arc<dyn ActionScheduler>>,
+    property_managers: Mutex<HashMap<String, Arc<PlatformPropertyManager>>>,
+}
+
+impl RouterScheduler {
+    pub fn new(
+        config: &native_link_config::schedulers::RouterScheduler,
+        action_schedulers: &HashMap<String, Arc<dyn ActionScheduler>>,
+    ) -> Result<Self, Error> {
+        let get_scheduler = |name: &String| {
+            action_schedulers
+                .get(name)
+                .cloned()
+                .ok_or_else(|| make_input_err!("Scheduler '{name}' used in router does not exist"))
+        };
+        let routes = config
+            .routes
+            .iter()
+            .map(|route_config| {
+                Ok(Route {
+                    config: route_config.clone(),
+                    scheduler: get_scheduler(&route_config.scheduler)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let fallback_scheduler = config
+            .fallback_scheduler
+            .as_ref()
+            .map(get_scheduler)
+            .transpose()
+            .err_tip(|| "In RouterScheduler::new for 'fallback_scheduler'")?;
+        Ok(Self {
+            routes,
+            fallback_scheduler,
+            property_managers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns every scheduler actions may be forwarded to without duplicates, in the order
+    /// they are referenced in the config.
+    fn schedulers(&self) -> Vec<&Arc<dyn ActionScheduler>> {
+        let mut schedulers: Vec<&Arc<dyn ActionScheduler>> = Vec::new();
+        let all_schedulers = self
+            .routes
+            .iter()
+            .map(|route| &route.scheduler)
+            .chain(self.fallback_scheduler.iter());
+        for scheduler in all_schedulers {
+            // Compare the data pointers only, the same scheduler may have different vtables.
+            let scheduler_ptr = Arc::as_ptr(scheduler).cast::<()>();
+            if !schedulers
+                .iter()
+                .any(|known_scheduler| Arc::as_ptr(known_scheduler).cast::<()>() == scheduler_ptr)
+            {
+                schedulers.push(scheduler);
+            }
+        }
+        schedulers
+    }
+
+    /// Returns the scheduler of the first route matching the action.
+    fn route_action(&self, action_info: &ActionInfo) -> Result<&Arc<dyn ActionScheduler>, Error> {
+        self.routes
+            .iter()
+            .find(|route| route.matches(action_info))
+            .map(|route| &route.scheduler)
+            .or(self.fallback_scheduler.as_ref())
+            .ok_or_else(|| {
+                make_input_err!(
+                    "No scheduler route matches action {} in instance '{}'",
+                    action_info.digest().hash_str(),
+                    action_info.instance_name()
+                )
+            })
+    }
+}
+
+#[async_trait]
+impl ActionScheduler for RouterScheduler {
+    async fn get_platform_property_manager(&self, instance_name: &str) -> Result<Arc<PlatformPropertyManager>, Error> {
+        {
+            let property_managers = self.property_managers.lock();
+            if let Some(property_manager) = property_managers.get(instance_name) {
+                return Ok(property_manager.clone());
+            }
+        }
+        let mut known_properties = HashMap::new();
+        for scheduler in self.schedulers() {
+            let property_manager = scheduler
+                .get_platform_property_manager(instance_name)
+                .await
+                .err_tip(|| "In RouterScheduler::get_platform_property_manager")?;
+            for (name, property_type) in property_manager.get_known_properties() {
+                match known_properties.entry(name.clone()) {
+                    Entry::Vacant(new_entry) => {
+                        new_entry.insert(*property_type);
+                    }
+                    Entry::Occupied(old_entry) => {
+                        if old_entry.get() != property_type {
+                            return Err(make_input_err!(
+                                "Platform property '{}' is {:?} in one routed scheduler, but {:?} in another",
+                                name,
+                                old_entry.get(),
+                                property_type
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        let property_manager = {
+            let mut property_managers = self.property_managers.lock();
+            match property_managers.entry(instance_name.into()) {
+                Entry::Vacant(new_entry) => {
+                    let property_manager = Arc::new(PlatformPropertyManager::new(known_properties));
+                    new_entry.insert(property_manager.clone());
+                    property_manager
+                }
+                // We lost the race, use the other manager.
+                Entry::Occupied(old_entry) => old_entry.get().clone(),
+            }
+        };
+        Ok(property_manager)
+    }
+
+    async fn add_action(&self, action_info: ActionInfo) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
+        let scheduler = self
+            .route_action(&action_info)
+            .err_tip(|| "In RouterScheduler::add_action")?;
+        scheduler.add_action(action_info).await
+    }
+
+    async fn find_existing_action(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+    ) -> Option<watch::Receiver<Arc<ActionState>>> {
+        for scheduler in self.schedulers() {
+            if let Some(rx) = scheduler.find_existing_action(unique_qualifier).await {
+                return Some(rx);
+            }
+        }
+        None
+    }
+
+    async fn clean_recently_completed_actions(&self) {
+        // The schedulers actions are forwarded to are top level schedulers, which
+        // already clean up after themselves.
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use error::{make_err, Code, Error, ResultExt};
+use native_link_util::action_messages::ActionInfoHashKey;
+use native_link_util::common::{log, DigestInfo};
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    SchedulerEvent as ProtoSchedulerEvent, SchedulerEventType as ProtoSchedulerEventType,
+};
+use serde::{Serialize, Serializer};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+use crate::worker::WorkerId;
+
+/// How long to wait before writing to an event log again after a write failed.
+const EVENT_LOG_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The kind of a `SchedulerEvent`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulerEventType {
+    /// A new action was added to the queue.
+    ActionAdded,
+    /// An action was added while an identical action was already queued or running.
+    ActionJoinedExisting,
+    /// An action was sent to a worker to be executed.
+    ActionMatched,
+    /// An execution of an action failed and the action was put back in the queue.
+    ActionRetried,
+    /// An action finished, either successfully or with an error.
+    ActionCompleted,
+    /// A worker was removed from the pool of workers.
+    WorkerEvicted,
+}
+
+impl From<SchedulerEventType> for ProtoSchedulerEventType {
+    fn from(event_type: SchedulerEventType) -> Self {
+        match event_type {
+            SchedulerEventType::ActionAdded => Self::ActionAdded,
+            SchedulerEventType::ActionJoinedExisting => Self::ActionJoinedExisting,
+            SchedulerEventType::ActionMatched => Self::ActionMatched,
+            SchedulerEventType::ActionRetried => Self::ActionRetried,
+            SchedulerEventType::ActionCompleted => Self::ActionCompleted,
+            SchedulerEventType::WorkerEvicted => Self::WorkerEvicted,
+        }
+    }
+}
+
+fn serialize_timestamp<S: Serializer>(timestamp: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let millis = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    serializer.serialize_u64(u64::try_from(millis).unwrap_or(u64::MAX))
+}
+
+fn serialize_digest<S: Serializer>(digest: &Option<DigestInfo>, serializer: S) -> Result<S::Ok, S::Error> {
+    match digest {
+        Some(digest) => serializer.serialize_str(&format!("{}-{}", digest.hash_str(), digest.size_bytes)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A structured event about what a scheduler did with an action or worker.
+/// Serialized as a single JSON object, with the timestamp in milliseconds since
+/// the unix epoch and the action digest as `<hash>-<size>`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SchedulerEvent {
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub timestamp: SystemTime,
+    pub event_type: SchedulerEventType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "serialize_digest")]
+    pub action_digest: Option<DigestInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl SchedulerEvent {
+    /// Creates an event about the action that happened now.
+    pub fn for_action(event_type: SchedulerEventType, action_info_hash_key: &ActionInfoHashKey) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            event_type,
+            instance_name: Some(action_info_hash_key.instance_name.clone()),
+            action_digest: Some(action_info_hash_key.digest),
+            worker_id: None,
+            attempts: None,
+            exit_code: None,
+            message: None,
+        }
+    }
+
+    /// Creates an event about the worker that happened now.
+    pub fn for_worker(event_type: SchedulerEventType, worker_id: &WorkerId) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            event_type,
+            instance_name: None,
+            action_digest: None,
+            worker_id: Some(worker_id.to_string()),
+            attempts: None,
+            exit_code: None,
+            message: None,
+        }
+    }
+}
+
+impl From<&SchedulerEvent> for ProtoSchedulerEvent {
+    fn from(event: &SchedulerEvent) -> Self {
+        Self {
+            timestamp: Some(event.timestamp.into()),
+            event_type: ProtoSchedulerEventType::from(event.event_type).into(),
+            instance_name: event.instance_name.clone().unwrap_or_default(),
+            action_digest: event.action_digest.map(Into::into),
+            worker_id: event.worker_id.clone().unwrap_or_default(),
+            attempts: event.attempts.unwrap_or_default() as u64,
+            exit_code: event.exit_code,
+            message: event.message.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Appends the line to the file at `path`, opening the file first if it is not open.
+async fn write_event_line(file: &mut Option<File>, path: &str, line: &[u8]) -> Result<(), Error> {
+    let file = match file {
+        Some(file) => file,
+        None => file.insert(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| make_err!(Code::Internal, "Could not open scheduler event log '{path}' : {e:?}"))?,
+        ),
+    };
+    file.write_all(line)
+        .await
+        .err_tip(|| format!("Could not write to scheduler event log '{path}'"))
+}
+
+async fn append_events_to_file(path: &str, mut events_rx: mpsc::Receiver<Arc<SchedulerEvent>>) {
+    let mut file = None;
+    // Stops once the scheduler went away.
+    while let Some(event) = events_rx.recv().await {
+        let mut line = match serde_json::to_vec(event.as_ref()) {
+            Ok(line) => line,
+            Err(e) => {
+                log::error!("Could not serialize scheduler event : {e:?}");
+                continue;
+            }
+        };
+        line.push(b'\n');
+        while let Err(err) = write_event_line(&mut file, path, &line).await {
+            log::error!("{err:?}, retrying in {EVENT_LOG_RETRY_DELAY:?}");
+            // The file is opened again, in case it was moved or deleted.
+            file = None;
+            sleep(EVENT_LOG_RETRY_DELAY).await;
+        }
+    }
+}
+
+/// Spawns a task that appends the events sent to the returned sender to the file at
+/// `path` as newline delimited JSON, until the sender is dropped. Up to `capacity`
+/// events are buffered while the file is written or can not be written to.
+pub fn spawn_ndjson_file_sink(path: String, capacity: usize) -> mpsc::Sender<Arc<SchedulerEvent>> {
+    let (events_tx, events_rx) = mpsc::channel(capacity);
+    tokio::spawn(async move { append_events_to_file(&path, events_rx).await });
+    events_tx
+}
@@ -12,42 +12,59 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::cmp;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use error::{error_if, make_err, make_input_err, Code, Error, ResultExt};
 use futures::Future;
 use hashbrown::{HashMap, HashSet};
 use lru::LruCache;
-use native_link_config::schedulers::WorkerAllocationStrategy;
+use native_link_config::schedulers::{
+    HedgingConfig, PreemptionConfig, PriorityAgingConfig, SchedulerEventSink, WorkerAllocationStrategy,
+    WorkerQuarantineConfig,
+};
 use native_link_util::action_messages::{
-    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionMetadata,
+    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionAttempt, ExecutionMetadata,
+    QueuePosition,
 };
-use native_link_util::common::log;
+use native_link_util::common::{log, DigestInfo};
 use native_link_util::metrics_utils::{
     AsyncCounterWrapper, Collector, CollectorState, CounterWithTime, FuncCounterWrapper, MetricsComponent, Registry,
 };
-use native_link_util::platform_properties::PlatformPropertyValue;
+use native_link_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
+use native_link_util::store_trait::Store;
 use parking_lot::{Mutex, MutexGuard};
-use tokio::sync::{watch, Notify};
+use tokio::sync::{broadcast, mpsc, watch, Notify};
 use tokio::task::JoinHandle;
 use tokio::time::Duration;
 
 use crate::action_scheduler::ActionScheduler;
+use crate::completed_actions_store::CompletedActionsStore;
+use crate::execution_statistics::ExecutionStatistics;
 use crate::platform_property_manager::PlatformPropertyManager;
+use crate::scheduler_events::{spawn_ndjson_file_sink, SchedulerEvent, SchedulerEventType};
 use crate::worker::{Worker, WorkerId, WorkerTimestamp, WorkerUpdate};
+use crate::worker_pool_demand::PlatformPropertiesDemand;
 use crate::worker_scheduler::WorkerScheduler;
 
 /// Default timeout for workers in seconds.
 /// If this changes, remember to change the documentation in the config.
 const DEFAULT_WORKER_TIMEOUT_S: u64 = 5;
 
+/// Default amount of time a draining worker may finish its running actions in seconds.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_WORKER_DRAIN_TIMEOUT_S: u64 = 600;
+
+/// Number of scheduler events buffered for each subscriber before the oldest ones are dropped,
+/// and for each event sink before new events are dropped.
+const EVENT_CHANNEL_CAPACITY: usize = 10_000;
+
 /// Default timeout for recently completed actions in seconds.
 /// If this changes, remember to change the documentation in the config.
 const DEFAULT_RETAIN_COMPLETED_FOR_S: u64 = 60;
@@ -56,6 +73,84 @@ const DEFAULT_RETAIN_COMPLETED_FOR_S: u64 = 60;
 /// If this changes, remember to change the documentation in the config.
 const DEFAULT_MAX_JOB_RETRIES: usize = 3;
 
+/// Default percentile of historical execution durations used to decide when to hedge.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_HEDGING_DURATION_PERCENTILE: f32 = 95.;
+
+/// Default multiplier applied to the percentile duration when deciding when to hedge.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_HEDGING_DURATION_MULTIPLIER: f32 = 1.5;
+
+/// Default number of historical executions required before a command is hedged.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_HEDGING_MIN_SAMPLES: usize = 5;
+
+/// How often running actions are checked to see if they should be hedged.
+const HEDGING_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Amount of time a worker has to report that it stopped the losing execution of a
+/// hedged action before the execution is no longer counted against the worker.
+const KILLED_HEDGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default number of internal errors within the error window that quarantines a worker.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_QUARANTINE_MAX_INTERNAL_ERRORS: usize = 3;
+
+/// Default amount of time internal errors are remembered for in seconds.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_QUARANTINE_ERROR_WINDOW_S: u64 = 60;
+
+/// Default amount of time a worker is quarantined for the first time in seconds.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_QUARANTINE_INITIAL_BACKOFF_S: u64 = 30;
+
+/// Default maximum amount of time a worker is quarantined for in seconds.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_QUARANTINE_MAX_BACKOFF_S: u64 = 600;
+
+/// Default amount of time an action waits in the queue before its priority is increased in seconds.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_PRIORITY_AGING_INTERVAL_S: u64 = 60;
+
+/// Default amount the priority of an action is increased by every aging interval.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_PRIORITY_AGING_INCREMENT: i32 = 1;
+
+/// Default minimum difference in priority required for an action to preempt another.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_PREEMPTION_MIN_PRIORITY_DIFFERENCE: i32 = 1;
+
+/// Maximum number of distinct combinations of `metrics_platform_property_keys` values
+/// metrics are kept for. Further combinations are counted as `OTHER_PLATFORM_PROPERTY_CLASS`.
+/// If this changes, remember to change the documentation in the config.
+const MAX_PLATFORM_PROPERTY_CLASSES: usize = 1000;
+
+/// Value of every platform property of the class actions are counted in once
+/// `MAX_PLATFORM_PROPERTY_CLASSES` is reached.
+/// If this changes, remember to change the documentation in the config.
+const OTHER_PLATFORM_PROPERTY_CLASS: &str = "__other__";
+
+/// Default number of times an action is run again after a flaky execution.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_FLAKY_MAX_RETRIES: usize = 3;
+
+/// Default interval in seconds in which queue positions are sent to clients.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_QUEUE_POSITION_UPDATE_INTERVAL_S: u64 = 10;
+
+/// Turns a platform property key into a valid metrics label name. Label names may
+/// only contain alphanumeric characters and underscores and may not start with a digit.
+fn metrics_label_name(key: &str) -> String {
+    let label_name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if label_name.is_empty() || label_name.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("_{label_name}");
+    }
+    label_name
+}
+
 /// An action that is being awaited on and last known state.
 struct AwaitedAction {
     action_info: Arc<ActionInfo>,
@@ -67,18 +162,443 @@ struct AwaitedAction {
     /// Possible last error set by the worker. If empty and attempts is set, it may be due to
     /// something like a worker timeout.
     last_error: Option<Error>,
+    /// How long the action is expected to execute for, if executions of similar actions
+    /// have been recorded.
+    expected_duration: Option<Duration>,
+    /// Number of times the action was run again because an execution failed in a flaky way.
+    flaky_retries: usize,
+    /// The executions that failed in a flaky way, which are added to the final result.
+    previous_attempts: Vec<ExecutionAttempt>,
+}
+
+/// Metrics of the actions that request the same values for the platform property keys
+/// the metrics are broken down by.
+#[derive(Default)]
+struct PlatformPropertyClassMetrics {
+    /// Number of times an action could not be matched to any worker.
+    match_failures: u64,
+    /// Number of actions sent to a worker.
+    matched_actions: u64,
+    /// Total time the matched actions waited since they were added to the scheduler.
+    total_queue_wait: Duration,
 }
 
 /// Holds the relationship of a worker that is executing a specific action.
 struct RunningAction {
     worker_id: WorkerId,
+    /// Worker speculatively executing a second copy of the action, if the action was hedged.
+    hedge_worker_id: Option<WorkerId>,
+    /// Time the action was sent to `worker_id`.
+    start_time: SystemTime,
     action: AwaitedAction,
 }
 
+/// The running actions, also ordered by priority and start time so the action to
+/// preempt is found without visiting all running actions.
+#[derive(Default)]
+struct ActiveActions {
+    actions: HashMap<Arc<ActionInfo>, RunningAction>,
+    /// Lowest priority first, then the most recently started first.
+    by_priority: BTreeSet<(i32, cmp::Reverse<SystemTime>, Arc<ActionInfo>)>,
+}
+
+impl ActiveActions {
+    fn priority_key(
+        action_info: &Arc<ActionInfo>,
+        running_action: &RunningAction,
+    ) -> (i32, cmp::Reverse<SystemTime>, Arc<ActionInfo>) {
+        (
+            action_info.priority,
+            cmp::Reverse(running_action.start_time),
+            action_info.clone(),
+        )
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&RunningAction>
+    where
+        Q: ?Sized + Hash + Eq,
+        Arc<ActionInfo>: Borrow<Q>,
+    {
+        self.actions.get(key)
+    }
+
+    // Note: `start_time` must not be changed through the returned reference, or
+    // `by_priority` gets out of sync.
+    fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut RunningAction>
+    where
+        Q: ?Sized + Hash + Eq,
+        Arc<ActionInfo>: Borrow<Q>,
+    {
+        self.actions.get_mut(key)
+    }
+
+    fn insert(&mut self, action_info: Arc<ActionInfo>, running_action: RunningAction) {
+        self.by_priority
+            .insert(Self::priority_key(&action_info, &running_action));
+        if let Some(old_running_action) = self.actions.insert(action_info.clone(), running_action) {
+            if old_running_action.start_time != self.actions[&action_info].start_time {
+                self.by_priority
+                    .remove(&Self::priority_key(&action_info, &old_running_action));
+            }
+        }
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<RunningAction>
+    where
+        Q: ?Sized + Hash + Eq,
+        Arc<ActionInfo>: Borrow<Q>,
+    {
+        self.remove_entry(key).map(|(_, running_action)| running_action)
+    }
+
+    fn remove_entry<Q>(&mut self, key: &Q) -> Option<(Arc<ActionInfo>, RunningAction)>
+    where
+        Q: ?Sized + Hash + Eq,
+        Arc<ActionInfo>: Borrow<Q>,
+    {
+        let (action_info, running_action) = self.actions.remove_entry(key)?;
+        self.by_priority
+            .remove(&Self::priority_key(&action_info, &running_action));
+        Some((action_info, running_action))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&Arc<ActionInfo>, &RunningAction)> {
+        self.actions.iter()
+    }
+
+    /// Iterates the running actions with the lowest priority first, and the most
+    /// recently started first among actions of the same priority.
+    fn iter_by_priority(&self) -> impl Iterator<Item = (&Arc<ActionInfo>, &RunningAction)> {
+        self.by_priority
+            .iter()
+            .map(|(_, _, action_info)| (action_info, &self.actions[action_info]))
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &Arc<ActionInfo>> {
+        self.actions.keys()
+    }
+
+    fn len(&self) -> usize {
+        self.actions.len()
+    }
+}
+
+/// Decides when a running action is straggling and should be executed a second time,
+/// based on how long similar actions took to execute in the past.
+struct Hedging {
+    duration_percentile: f32,
+    duration_multiplier: f32,
+    min_samples: usize,
+}
+
+impl Hedging {
+    fn new(config: &HedgingConfig) -> Self {
+        let mut duration_percentile = config.duration_percentile;
+        if duration_percentile <= 0. {
+            duration_percentile = DEFAULT_HEDGING_DURATION_PERCENTILE;
+        }
+        let mut duration_multiplier = config.duration_multiplier;
+        if duration_multiplier <= 0. {
+            duration_multiplier = DEFAULT_HEDGING_DURATION_MULTIPLIER;
+        }
+        let mut min_samples = config.min_samples;
+        if min_samples == 0 {
+            min_samples = DEFAULT_HEDGING_MIN_SAMPLES;
+        }
+        Self {
+            duration_percentile: duration_percentile.min(100.),
+            duration_multiplier,
+            min_samples,
+        }
+    }
+
+    /// Returns how long an action may execute before it is hedged given the recent
+    /// durations of similar actions, or None if not enough of them have been seen yet.
+    fn threshold(&self, mut durations: Vec<Duration>) -> Option<Duration> {
+        if durations.is_empty() || durations.len() < self.min_samples {
+            return None;
+        }
+        durations.sort_unstable();
+        let rank = (self.duration_percentile / 100. * durations.len() as f32).ceil() as usize;
+        let percentile_duration = durations[rank.clamp(1, durations.len()) - 1];
+        Some(percentile_duration.mul_f32(self.duration_multiplier))
+    }
+}
+
+/// Decides when workers that cause internal errors are quarantined and for how long.
+struct WorkerQuarantine {
+    max_internal_errors: usize,
+    error_window_s: u64,
+    initial_backoff_s: u64,
+    max_backoff_s: u64,
+}
+
+impl WorkerQuarantine {
+    fn new(config: &WorkerQuarantineConfig) -> Self {
+        let mut max_internal_errors = config.max_internal_errors;
+        if max_internal_errors == 0 {
+            max_internal_errors = DEFAULT_QUARANTINE_MAX_INTERNAL_ERRORS;
+        }
+        let mut error_window_s = config.error_window_s;
+        if error_window_s == 0 {
+            error_window_s = DEFAULT_QUARANTINE_ERROR_WINDOW_S;
+        }
+        let mut initial_backoff_s = config.initial_backoff_s;
+        if initial_backoff_s == 0 {
+            initial_backoff_s = DEFAULT_QUARANTINE_INITIAL_BACKOFF_S;
+        }
+        let mut max_backoff_s = config.max_backoff_s;
+        if max_backoff_s == 0 {
+            max_backoff_s = DEFAULT_QUARANTINE_MAX_BACKOFF_S;
+        }
+        Self {
+            max_internal_errors,
+            error_window_s,
+            initial_backoff_s,
+            max_backoff_s,
+        }
+    }
+
+    /// Records an internal error caused by the worker and quarantines the worker if it
+    /// caused too many of them recently. Returns true if the worker was quarantined.
+    fn record_internal_error(&self, worker: &mut Worker, now: WorkerTimestamp) -> bool {
+        worker.recent_internal_errors.push_back(now);
+        let window_start = now.saturating_sub(self.error_window_s);
+        while worker
+            .recent_internal_errors
+            .front()
+            .is_some_and(|&timestamp| timestamp < window_start)
+        {
+            worker.recent_internal_errors.pop_front();
+        }
+        if worker.is_quarantined() || worker.recent_internal_errors.len() < self.max_internal_errors {
+            return false;
+        }
+        let backoff_s = self
+            .initial_backoff_s
+            .saturating_mul(1 << cmp::min(worker.quarantine_count, 32))
+            .min(self.max_backoff_s);
+        worker.quarantined_until = Some(now + backoff_s);
+        worker.quarantine_count += 1;
+        worker.recent_internal_errors.clear();
+        true
+    }
+}
+
+/// Increases the effective priority of actions the longer they are queued.
+struct PriorityAging {
+    aging_interval_s: u64,
+    priority_increment: i64,
+    max_priority_increase: i64,
+}
+
+impl PriorityAging {
+    fn new(config: &PriorityAgingConfig) -> Self {
+        let mut aging_interval_s = config.aging_interval_s;
+        if aging_interval_s == 0 {
+            aging_interval_s = DEFAULT_PRIORITY_AGING_INTERVAL_S;
+        }
+        let mut priority_increment = config.priority_increment;
+        if priority_increment == 0 {
+            priority_increment = DEFAULT_PRIORITY_AGING_INCREMENT;
+        }
+        let mut max_priority_increase = config.max_priority_increase;
+        if max_priority_increase == 0 {
+            max_priority_increase = i32::MAX;
+        }
+        Self {
+            aging_interval_s,
+            priority_increment: i64::from(priority_increment),
+            max_priority_increase: i64::from(max_priority_increase),
+        }
+    }
+
+    /// Returns the number of aging intervals since the unix epoch.
+    fn step(&self, time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / self.aging_interval_s
+    }
+
+    /// Returns the number of steps after which the priority stops increasing.
+    fn steps_to_max_priority_increase(&self) -> u64 {
+        if self.priority_increment <= 0 {
+            return u64::MAX;
+        }
+        let steps = (self.max_priority_increase + self.priority_increment - 1) / self.priority_increment;
+        u64::try_from(steps).unwrap_or(0)
+    }
+}
+
+/// Sorts queued actions in the order they are matched to workers in.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct QueueKey {
+    priority: cmp::Reverse<i64>,
+    /// See `ExecutionStatistics::sort_key()`.
+    duration_key: i128,
+    /// Among equal keys, the action first in the `queued_actions` order goes first.
+    action_info: cmp::Reverse<Arc<ActionInfo>>,
+}
+
+/// Keeps the queued actions in the order they are matched to workers in.
+struct QueueOrder {
+    priority_aging: Option<PriorityAging>,
+    /// Actions whose priority still increases with aging, keyed by their priority at
+    /// step 0. All of them age at the same time, so their order does not change.
+    aging: BTreeSet<QueueKey>,
+    /// Same entries as `aging`, by the step the action was queued in.
+    aging_by_step: BTreeSet<(u64, QueueKey)>,
+    /// Actions at their final priority, keyed by it.
+    aged: BTreeSet<QueueKey>,
+}
+
+impl QueueOrder {
+    fn new(priority_aging: Option<PriorityAging>) -> Self {
+        Self {
+            priority_aging,
+            aging: BTreeSet::new(),
+            aging_by_step: BTreeSet::new(),
+            aged: BTreeSet::new(),
+        }
+    }
+
+    fn aged_key(&self, action_info: Arc<ActionInfo>, duration_key: i128) -> QueueKey {
+        let max_priority_increase = self
+            .priority_aging
+            .as_ref()
+            .map_or(0, |priority_aging| priority_aging.max_priority_increase);
+        QueueKey {
+            priority: cmp::Reverse(i64::from(action_info.priority) + max_priority_increase),
+            duration_key,
+            action_info: cmp::Reverse(action_info),
+        }
+    }
+
+    fn aging_key(&self, action_info: Arc<ActionInfo>, duration_key: i128) -> Option<(u64, QueueKey)> {
+        let priority_aging = self.priority_aging.as_ref()?;
+        let step = priority_aging.step(action_info.insert_timestamp);
+        let priority = i64::from(action_info.priority)
+            .saturating_sub(priority_aging.priority_increment.saturating_mul(step as i64));
+        Some((
+            step,
+            QueueKey {
+                priority: cmp::Reverse(priority),
+                duration_key,
+                action_info: cmp::Reverse(action_info),
+            },
+        ))
+    }
+
+    fn insert(&mut self, action_info: Arc<ActionInfo>, duration_key: i128) {
+        match self.aging_key(action_info.clone(), duration_key) {
+            Some((step, key)) => {
+                self.aging_by_step.insert((step, key.clone()));
+                self.aging.insert(key);
+            }
+            None => {
+                let key = self.aged_key(action_info, duration_key);
+                self.aged.insert(key);
+            }
+        }
+    }
+
+    fn remove(&mut self, action_info: &Arc<ActionInfo>, duration_key: i128) {
+        if let Some((step, key)) = self.aging_key(action_info.clone(), duration_key) {
+            if self.aging.remove(&key) {
+                self.aging_by_step.remove(&(step, key));
+                return;
+            }
+        }
+        let key = self.aged_key(action_info.clone(), duration_key);
+        self.aged.remove(&key);
+    }
+
+    /// Returns the queued actions in the order they are matched to workers in at `now`.
+    fn match_order(&mut self, now: SystemTime) -> Vec<Arc<ActionInfo>> {
+        let priority_increase = match &self.priority_aging {
+            Some(priority_aging) => {
+                let step = priority_aging.step(now);
+                // Move the actions that reached their final priority.
+                let last_aging_step = step.saturating_sub(priority_aging.steps_to_max_priority_increase());
+                while self
+                    .aging_by_step
+                    .first()
+                    .is_some_and(|(queued_step, _)| *queued_step <= last_aging_step)
+                {
+                    let (_, key) = self.aging_by_step.pop_first().unwrap();
+                    self.aging.remove(&key);
+                    let aged_key = self.aged_key(key.action_info.0, key.duration_key);
+                    self.aged.insert(aged_key);
+                }
+                priority_aging.priority_increment.saturating_mul(step as i64)
+            }
+            None => 0,
+        };
+
+        // Merge the actions that still age with the aged ones.
+        let mut action_infos = Vec::with_capacity(self.aging.len() + self.aged.len());
+        let mut aging = self.aging.iter().peekable();
+        let mut aged = self.aged.iter().peekable();
+        loop {
+            let key = match (aging.peek(), aged.peek()) {
+                (Some(aging_key), Some(aged_key)) => {
+                    let aging_priority = cmp::Reverse(aging_key.priority.0.saturating_add(priority_increase));
+                    let aging_first = (aging_priority, aging_key.duration_key, &aging_key.action_info)
+                        <= (aged_key.priority, aged_key.duration_key, &aged_key.action_info);
+                    if aging_first {
+                        aging.next()
+                    } else {
+                        aged.next()
+                    }
+                }
+                (Some(_), None) => aging.next(),
+                (None, Some(_)) => aged.next(),
+                (None, None) => break,
+            };
+            action_infos.extend(key.map(|key| key.action_info.0.clone()));
+        }
+        action_infos
+    }
+}
+
+/// Decides which running actions may be preempted by a queued action.
+struct Preemption {
+    min_priority_difference: i32,
+    max_preemptible_priority: i32,
+}
+
+impl Preemption {
+    fn new(config: &PreemptionConfig) -> Self {
+        let mut min_priority_difference = config.min_priority_difference;
+        if min_priority_difference == 0 {
+            min_priority_difference = DEFAULT_PREEMPTION_MIN_PRIORITY_DIFFERENCE;
+        }
+        Self {
+            min_priority_difference,
+            max_preemptible_priority: config.max_preemptible_priority,
+        }
+    }
+
+    /// Returns true if an action with `priority` may preempt a running action with `running_priority`.
+    fn can_preempt(&self, priority: i32, running_priority: i32) -> bool {
+        running_priority <= self.max_preemptible_priority
+            && priority.saturating_sub(running_priority) >= self.min_priority_difference
+    }
+}
+
+/// A running action that was killed to make room for a higher priority action, but
+/// the worker has not yet reported that it stopped running it.
+struct PendingPreemption {
+    worker_id: WorkerId,
+    preempted: ActionInfoHashKey,
+}
+
 struct Workers {
     workers: LruCache<WorkerId, Worker>,
     /// The allocation strategy for workers.
     allocation_strategy: WorkerAllocationStrategy,
+    /// Workers by the input roots they recently materialized, so workers
+    /// with the input root of an action are found without visiting all
+    /// workers.
+    input_root_workers: HashMap<DigestInfo, HashSet<WorkerId>>,
 }
 
 impl Workers {
@@ -86,6 +606,7 @@ impl Workers {
         Self {
             workers: LruCache::unbounded(),
             allocation_strategy,
+            input_root_workers: HashMap::new(),
         }
     }
 
@@ -109,7 +630,9 @@ impl Workers {
     /// Note: This function will not do any task matching.
     fn add_worker(&mut self, worker: Worker) -> Result<(), Error> {
         let worker_id = worker.id;
-        self.workers.put(worker_id, worker);
+        if let Some(old_worker) = self.workers.put(worker_id, worker) {
+            self.unindex_input_roots(&worker_id, &old_worker.recent_input_root_digests);
+        }
 
         // Worker is not cloneable, and we do not want to send the initial connection results until
         // we have added it to the map, or we might get some strange race conditions due to the way
@@ -131,32 +654,98 @@ impl Workers {
     /// Note: The caller is responsible for any rescheduling of any tasks that might be
     /// running.
     fn remove_worker(&mut self, worker_id: &WorkerId) -> Option<Worker> {
-        self.workers.pop(worker_id)
+        let worker = self.workers.pop(worker_id)?;
+        self.unindex_input_roots(worker_id, &worker.recent_input_root_digests);
+        Some(worker)
     }
 
-    /// Attempts to find a worker that is capable of running this action.
+    /// Attempts to find a worker that is capable of running this action and is not already
+    /// running another execution of it. Workers that recently materialized the same input
+    /// root are preferred, otherwise the allocation strategy decides.
     // TODO(blaise.bruer) This algorithm is not very efficient. Simple testing using a tree-like
     // structure showed worse performance on a 10_000 worker * 7 properties * 1000 queued tasks
     // simulation of worst cases in a single threaded environment.
-    fn find_worker_for_action_mut<'a>(&'a mut self, awaited_action: &AwaitedAction) -> Option<&'a mut Worker> {
-        assert!(matches!(awaited_action.current_state.stage, ActionStage::Queued));
-        let action_properties = &awaited_action.action_info.platform_properties;
-        let mut workers_iter = self.workers.iter_mut();
-        let workers_iter =
-            match self.allocation_strategy {
-                // Use rfind to get the least recently used that satisfies the properties.
-                WorkerAllocationStrategy::LeastRecentlyUsed => workers_iter
-                    .rfind(|(_, w)| !w.is_paused && action_properties.is_satisfied_by(&w.platform_properties)),
-                // Use find to get the most recently used that satisfies the properties.
-                WorkerAllocationStrategy::MostRecentlyUsed => workers_iter
-                    .find(|(_, w)| !w.is_paused && action_properties.is_satisfied_by(&w.platform_properties)),
+    fn find_worker_for_action_mut<'a>(
+        &'a mut self,
+        action_info: &ActionInfo,
+        excluded_worker_id: Option<&WorkerId>,
+    ) -> Option<&'a mut Worker> {
+        let action_properties = &action_info.platform_properties;
+        let is_capable = |id: &WorkerId, w: &Worker| {
+            !w.is_paused
+                && !w.is_quarantined()
+                && !w.is_draining()
+                && excluded_worker_id != Some(id)
+                && !w.running_action_infos.contains(&action_info.unique_qualifier)
+                && action_properties.is_satisfied_by(&w.platform_properties)
+        };
+        let preferred_worker_id = self
+            .input_root_workers
+            .get(&action_info.input_root_digest)
+            .and_then(|worker_ids| {
+                worker_ids
+                    .iter()
+                    .filter(|id| self.workers.peek(id).is_some_and(|w| is_capable(id, w)))
+                    // The lowest id, so the choice does not depend on the hash order.
+                    .min_by_key(|id| id.0)
+                    .copied()
+            });
+        let worker_id = preferred_worker_id.or_else(|| {
+            let mut workers_iter: Box<dyn Iterator<Item = (&WorkerId, &Worker)>> = match self.allocation_strategy {
+                // Iterate in reverse to visit the least recently used workers first.
+                WorkerAllocationStrategy::LeastRecentlyUsed => Box::new(self.workers.iter().rev()),
+                // Iterate forward to visit the most recently used workers first.
+                WorkerAllocationStrategy::MostRecentlyUsed => Box::new(self.workers.iter()),
             };
-        let worker_id = workers_iter.map(|(_, w)| &w.id);
+            workers_iter.find(|(id, w)| is_capable(id, w)).map(|(id, _)| *id)
+        })?;
         // We need to "touch" the worker to ensure it gets re-ordered in the LRUCache, since it was selected.
-        if let Some(&worker_id) = worker_id {
-            self.workers.get_mut(&worker_id)
-        } else {
-            None
+        self.workers.get_mut(&worker_id)
+    }
+
+    /// Replaces the recently materialized input roots reported by the worker.
+    fn set_recent_input_roots(
+        &mut self,
+        worker_id: &WorkerId,
+        recent_input_root_digests: Vec<DigestInfo>,
+    ) -> Result<(), Error> {
+        let worker = self.workers.peek_mut(worker_id).ok_or_else(|| {
+            make_input_err!(
+                "Worker not found in worker map in set_recent_input_roots() {}",
+                worker_id
+            )
+        })?;
+        let old_input_root_digests = std::mem::replace(
+            &mut worker.recent_input_root_digests,
+            recent_input_root_digests.into_iter().collect(),
+        );
+        for input_root_digest in &worker.recent_input_root_digests {
+            self.input_root_workers
+                .entry(*input_root_digest)
+                .or_default()
+                .insert(*worker_id);
+        }
+        let removed_input_root_digests: HashSet<DigestInfo> = old_input_root_digests
+            .difference(&worker.recent_input_root_digests)
+            .copied()
+            .collect();
+        self.unindex_input_roots(worker_id, &removed_input_root_digests);
+        Ok(())
+    }
+
+    /// Removes the worker from the index of workers by input roots.
+    fn unindex_input_roots<'b>(
+        &mut self,
+        worker_id: &WorkerId,
+        input_root_digests: impl IntoIterator<Item = &'b DigestInfo>,
+    ) {
+        for input_root_digest in input_root_digests {
+            if let Some(worker_ids) = self.input_root_workers.get_mut(input_root_digest) {
+                worker_ids.remove(worker_id);
+                if worker_ids.is_empty() {
+                    self.input_root_workers.remove(input_root_digest);
+                }
+            }
         }
     }
 }
@@ -200,25 +789,95 @@ struct SimpleSchedulerImpl {
     // modify the other.
     queued_actions_set: HashSet<Arc<ActionInfo>>,
     queued_actions: BTreeMap<Arc<ActionInfo>, AwaitedAction>,
+    /// The keys of `queued_actions` in the order they are matched to workers in. Only
+    /// modified with `enqueue_action()` and `dequeue_action()`.
+    queue_order: QueueOrder,
     workers: Workers,
-    active_actions: HashMap<Arc<ActionInfo>, RunningAction>,
+    active_actions: ActiveActions,
     // These actions completed recently but had no listener, they might have
     // completed while the caller was thinking about calling wait_execution, so
     // keep their completion state around for a while to send back.
-    // TODO(#192) Revisit if this is the best way to handle recently completed actions.
+    // Once they are cleaned up from here, they are still found in the
+    // `completed_actions_store` if one is configured.
     recently_completed_actions: HashSet<CompletedAction>,
     /// The duration that actions are kept in recently_completed_actions for.
     retain_completed_for: Duration,
+    /// Where the final state of completed actions is persisted, if configured.
+    completed_actions_store: Option<Arc<CompletedActionsStore>>,
     /// Timeout of how long to evict workers if no response in this given amount of time in seconds.
     worker_timeout_s: u64,
+    /// Amount of time in seconds a draining worker may finish its running actions for.
+    worker_drain_timeout_s: u64,
     /// Default times a job can retry before failing.
     max_job_retries: usize,
+    /// Times an action is run again after a flaky execution, if flaky retries are enabled.
+    max_flaky_retries: Option<usize>,
+    /// Hedging policy, if hedging is enabled.
+    hedging: Option<Hedging>,
+    /// Quarantine policy for workers causing internal errors, if enabled.
+    worker_quarantine: Option<WorkerQuarantine>,
+    /// Preemption policy for running actions, if enabled.
+    preemption: Option<Preemption>,
+    /// Preempted actions that are still running on their worker, keyed by the action
+    /// they were preempted for.
+    pending_preemptions: HashMap<ActionInfoHashKey, PendingPreemption>,
+    /// Losing executions of hedged actions the workers were asked to kill, but did not
+    /// report back for yet, and when they were killed.
+    killed_hedges: HashMap<(WorkerId, ActionInfoHashKey), SystemTime>,
+    /// Execution duration history, if queued actions are ordered by their expected
+    /// duration or hedging is enabled.
+    execution_statistics: Option<Arc<ExecutionStatistics>>,
+    /// Platform property keys the metrics are broken down by.
+    metrics_platform_property_keys: Vec<String>,
+    /// Metrics per distinct values of `metrics_platform_property_keys` requested by actions.
+    platform_property_class_metrics: HashMap<Vec<String>, PlatformPropertyClassMetrics>,
+    /// Sends scheduler events to the configured sinks and subscribers.
+    events: broadcast::Sender<Arc<SchedulerEvent>>,
+    /// Sends scheduler events to the configured sinks, which write them in the background.
+    event_sinks: Vec<mpsc::Sender<Arc<SchedulerEvent>>>,
     /// Notify task<->worker matching engine that work needs to be done.
     tasks_or_workers_change_notify: Arc<Notify>,
     metrics: Arc<Metrics>,
 }
 
 impl SimpleSchedulerImpl {
+    /// Sends the event made by `make_event` to the event sinks and subscribers. The
+    /// event is only made if anyone is listening.
+    fn publish_event(&self, make_event: impl FnOnce() -> SchedulerEvent) {
+        if self.events.receiver_count() == 0 && self.event_sinks.is_empty() {
+            return;
+        }
+        let event = Arc::new(make_event());
+        for event_sink in &self.event_sinks {
+            if let Err(mpsc::error::TrySendError::Full(_)) = event_sink.try_send(event.clone()) {
+                self.metrics.scheduler_events_dropped.inc();
+            }
+        }
+        // Only fails if there are no subscribers.
+        let _ = self.events.send(event);
+    }
+
+    /// Adds the action to `queued_actions` and `queue_order`.
+    fn enqueue_action(&mut self, action_info: Arc<ActionInfo>, awaited_action: AwaitedAction) {
+        let duration_key = self.duration_key(&awaited_action);
+        self.queue_order.insert(action_info.clone(), duration_key);
+        self.queued_actions.insert(action_info, awaited_action);
+    }
+
+    /// Removes the action from `queued_actions` and `queue_order`.
+    fn dequeue_action(&mut self, action_info: &ActionInfo) -> Option<(Arc<ActionInfo>, AwaitedAction)> {
+        let (action_info, awaited_action) = self.queued_actions.remove_entry(action_info)?;
+        let duration_key = self.duration_key(&awaited_action);
+        self.queue_order.remove(&action_info, duration_key);
+        Some((action_info, awaited_action))
+    }
+
+    fn duration_key(&self, awaited_action: &AwaitedAction) -> i128 {
+        self.execution_statistics.as_ref().map_or(0, |execution_statistics| {
+            execution_statistics.sort_key(awaited_action.expected_duration)
+        })
+    }
+
     fn subscribe_to_channel(awaited_action: &AwaitedAction) -> watch::Receiver<Arc<ActionState>> {
         let rx = awaited_action.notify_channel.subscribe();
         // TODO: Fix this when fixed upstream tokio-rs/tokio#5871
@@ -235,18 +894,25 @@ impl SimpleSchedulerImpl {
     /// If the task cannot be executed immediately it will be queued for execution
     /// based on priority and other metrics.
     /// All further updates to the action will be provided through `listener`.
-    fn add_action(&mut self, action_info: ActionInfo) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
+    fn add_action(
+        &mut self,
+        action_info: ActionInfo,
+        expected_duration: Option<Duration>,
+    ) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
         // Check to see if the action is running, if it is and cacheable, merge the actions.
         if let Some(running_action) = self.active_actions.get_mut(&action_info) {
             self.metrics.add_action_joined_running_action.inc();
-            return Ok(Self::subscribe_to_channel(&running_action.action));
+            let rx = Self::subscribe_to_channel(&running_action.action);
+            self.publish_event(|| {
+                SchedulerEvent::for_action(SchedulerEventType::ActionJoinedExisting, &action_info.unique_qualifier)
+            });
+            return Ok(rx);
         }
 
         // Check to see if the action is queued, if it is and cacheable, merge the actions.
         if let Some(mut arc_action_info) = self.queued_actions_set.take(&action_info) {
             let (original_action_info, queued_action) = self
-                .queued_actions
-                .remove_entry(&arc_action_info)
+                .dequeue_action(&arc_action_info)
                 .err_tip(|| "Internal error queued_actions and queued_actions_set should match")?;
             self.metrics.add_action_joined_queued_action.inc();
 
@@ -263,8 +929,11 @@ impl SimpleSchedulerImpl {
 
             // Even if we fail to send our action to the client, we need to add this action back to the
             // queue because it was remove earlier.
-            self.queued_actions.insert(arc_action_info.clone(), queued_action);
+            self.enqueue_action(arc_action_info.clone(), queued_action);
             self.queued_actions_set.insert(arc_action_info);
+            self.publish_event(|| {
+                SchedulerEvent::for_action(SchedulerEventType::ActionJoinedExisting, &action_info.unique_qualifier)
+            });
             return Ok(rx);
         }
 
@@ -275,11 +944,15 @@ impl SimpleSchedulerImpl {
         let current_state = Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         });
 
         let (tx, rx) = watch::channel(current_state.clone());
+        self.publish_event(|| {
+            SchedulerEvent::for_action(SchedulerEventType::ActionAdded, &action_info.unique_qualifier)
+        });
         self.queued_actions_set.insert(action_info.clone());
-        self.queued_actions.insert(
+        self.enqueue_action(
             action_info.clone(),
             AwaitedAction {
                 action_info,
@@ -287,6 +960,9 @@ impl SimpleSchedulerImpl {
                 notify_channel: tx,
                 attempts: 0,
                 last_error: None,
+                expected_duration,
+                flaky_retries: 0,
+                previous_attempts: Vec::new(),
             },
         );
 
@@ -325,8 +1001,15 @@ impl SimpleSchedulerImpl {
         match self.active_actions.remove(action_info) {
             Some(running_action) => {
                 let mut awaited_action = running_action.action;
+                let attempts = awaited_action.attempts;
                 let send_result = if awaited_action.attempts >= self.max_job_retries {
                     self.metrics.retry_action_max_attempts_reached.inc();
+                    self.publish_event(|| SchedulerEvent {
+                        worker_id: Some(worker_id.to_string()),
+                        attempts: Some(attempts),
+                        message: Some(format!("Attempted to execute too many times : {err:?}")),
+                        ..SchedulerEvent::for_action(SchedulerEventType::ActionCompleted, &action_info.unique_qualifier)
+                    });
                     Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Completed(ActionResult {
                         execution_metadata: ExecutionMetadata {
                             worker: format!("{worker_id}"),
@@ -338,15 +1021,24 @@ impl SimpleSchedulerImpl {
                         ))),
                         ..ActionResult::default()
                     });
+                    if let Some(completed_actions_store) = &self.completed_actions_store {
+                        completed_actions_store.persist(awaited_action.current_state.as_ref().clone());
+                    }
                     awaited_action.notify_channel.send(awaited_action.current_state.clone())
                     // Do not put the action back in the queue here, as this action attempted to run too many
                     // times.
                 } else {
                     self.metrics.retry_action.inc();
+                    self.publish_event(|| SchedulerEvent {
+                        worker_id: Some(worker_id.to_string()),
+                        attempts: Some(attempts),
+                        message: Some(format!("{err:?}")),
+                        ..SchedulerEvent::for_action(SchedulerEventType::ActionRetried, &action_info.unique_qualifier)
+                    });
                     Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Queued;
                     let send_result = awaited_action.notify_channel.send(awaited_action.current_state.clone());
                     self.queued_actions_set.insert(action_info.clone());
-                    self.queued_actions.insert(action_info.clone(), awaited_action);
+                    self.enqueue_action(action_info.clone(), awaited_action);
                     send_result
                 };
 
@@ -368,23 +1060,470 @@ impl SimpleSchedulerImpl {
         }
     }
 
+    /// Puts the action back in the queue after the execution on `worker_id` failed in a
+    /// flaky way. If the action is hedged, the other execution is waited for instead.
+    fn retry_flaky_action(
+        &mut self,
+        action_info: Arc<ActionInfo>,
+        mut running_action: RunningAction,
+        worker_id: &WorkerId,
+        action_result: &ActionResult,
+        flaky_failure_reason: String,
+    ) {
+        self.metrics.flaky_retries.inc();
+        log::info!(
+            "Retrying action {} after flaky execution on worker {} : {}",
+            action_info.digest().hash_str(),
+            worker_id,
+            flaky_failure_reason
+        );
+        let attempts = running_action.action.attempts;
+        self.publish_event(|| SchedulerEvent {
+            worker_id: Some(worker_id.to_string()),
+            attempts: Some(attempts),
+            message: Some(format!("Flaky execution : {flaky_failure_reason}")),
+            ..SchedulerEvent::for_action(SchedulerEventType::ActionRetried, &action_info.unique_qualifier)
+        });
+        running_action.action.flaky_retries += 1;
+        running_action.action.previous_attempts.push(ExecutionAttempt {
+            worker: worker_id.to_string(),
+            worker_start_timestamp: action_result.execution_metadata.worker_start_timestamp,
+            worker_completed_timestamp: action_result.execution_metadata.worker_completed_timestamp,
+            exit_code: action_result.exit_code,
+            stdout_digest: action_result.stdout_digest,
+            stderr_digest: action_result.stderr_digest,
+            flaky_failure_reason,
+        });
+        if let Some(worker) = self.workers.workers.get_mut(worker_id) {
+            worker.complete_action(&action_info);
+        }
+
+        let other_worker_id = if running_action.worker_id == *worker_id {
+            running_action.hedge_worker_id
+        } else {
+            Some(running_action.worker_id)
+        };
+        if let Some(other_worker_id) = other_worker_id {
+            // The other execution of the hedged action may still succeed.
+            running_action.worker_id = other_worker_id;
+            running_action.hedge_worker_id = None;
+            self.active_actions.insert(action_info, running_action);
+        } else {
+            // Flaky executions are not counted as attempts towards `max_job_retries`.
+            let mut awaited_action = running_action.action;
+            awaited_action.attempts -= 1;
+            Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Queued;
+            if awaited_action
+                .notify_channel
+                .send(awaited_action.current_state.clone())
+                .is_err()
+            {
+                log::warn!(
+                    "Action {} has no more listeners during retry_flaky_action()",
+                    action_info.digest().hash_str()
+                );
+            }
+            self.queued_actions_set.insert(action_info.clone());
+            self.enqueue_action(action_info, awaited_action);
+        }
+        self.remove_worker_if_drained(worker_id);
+        self.tasks_or_workers_change_notify.notify_one();
+    }
+
     /// Evicts the worker from the pool and puts items back into the queue if anything was being executed on it.
     fn immediate_evict_worker(&mut self, worker_id: &WorkerId, err: Error) {
         if let Some(mut worker) = self.workers.remove_worker(worker_id) {
             self.metrics.workers_evicted.inc();
+            self.publish_event(|| SchedulerEvent {
+                message: Some(format!("{err:?}")),
+                ..SchedulerEvent::for_worker(SchedulerEventType::WorkerEvicted, worker_id)
+            });
+            self.pending_preemptions
+                .retain(|_, pending_preemption| pending_preemption.worker_id != *worker_id);
             // We don't care if we fail to send message to worker, this is only a best attempt.
             let _ = worker.notify_update(WorkerUpdate::Disconnect);
             // We create a temporary Vec to avoid doubt about a possible code
             // path touching the worker.running_action_infos elsewhere.
             for action_info in worker.running_action_infos.drain() {
                 self.metrics.workers_evicted_with_running_action.inc();
-                self.retry_action(&action_info, worker_id, err.clone());
+                self.worker_dropped_action(worker_id, &action_info, err.clone());
             }
         }
         // Note: Calling this many time is very cheap, it'll only trigger `do_try_match` once.
         self.tasks_or_workers_change_notify.notify_one();
     }
 
+    /// Called when a worker will no longer execute an action. If the action was hedged, the
+    /// other execution carries on by itself, otherwise the action is retried.
+    fn worker_dropped_action(&mut self, worker_id: &WorkerId, action_info: &Arc<ActionInfo>, err: Error) {
+        if let Some(running_action) = self.active_actions.get_mut(action_info) {
+            if running_action.hedge_worker_id == Some(*worker_id) {
+                running_action.hedge_worker_id = None;
+                return;
+            }
+            if running_action.worker_id != *worker_id {
+                // The worker was holding on to a killed execution, the action itself
+                // is running on another worker.
+                return;
+            }
+            if let Some(hedge_worker_id) = running_action.hedge_worker_id.take() {
+                running_action.worker_id = hedge_worker_id;
+                return;
+            }
+        }
+        self.retry_action(action_info, worker_id, err);
+    }
+
+    /// Releases an action from a worker that was asked to kill it because the other
+    /// execution of the hedged action already completed. Returns false if the worker
+    /// is not holding on to such an action.
+    fn release_killed_action(&mut self, worker_id: &WorkerId, action_info_hash_key: &ActionInfoHashKey) -> bool {
+        let Some(worker) = self.workers.workers.peek_mut(worker_id) else {
+            return false;
+        };
+        let Some(action_info) = worker.running_action_infos.get(action_info_hash_key).cloned() else {
+            return false;
+        };
+        worker.complete_action(&action_info);
+        self.killed_hedges.remove(&(*worker_id, action_info_hash_key.clone()));
+        self.pending_preemptions.retain(|_, pending_preemption| {
+            pending_preemption.worker_id != *worker_id || pending_preemption.preempted != *action_info_hash_key
+        });
+        self.remove_worker_if_drained(worker_id);
+        self.tasks_or_workers_change_notify.notify_one();
+        true
+    }
+
+    /// Kills a running action of lower priority than the given queued action to make room for
+    /// it and puts the killed action back in the queue. The queued action is matched once the
+    /// worker reported that it stopped running the killed action.
+    fn preempt_running_action_for(&mut self, action_info: &ActionInfo) {
+        let Some(preemption) = &self.preemption else {
+            return;
+        };
+        if self.pending_preemptions.contains_key(&action_info.unique_qualifier) {
+            return;
+        }
+        // Prefer preempting the lowest priority action that started most recently, since it
+        // likely lost the least amount of work.
+        let preempted_action_info = self
+            .active_actions
+            .iter_by_priority()
+            // Later actions have a higher priority, so they can't be preempted either.
+            .take_while(|(running_action_info, _)| {
+                preemption.can_preempt(action_info.priority, running_action_info.priority)
+            })
+            .find(|(running_action_info, running_action)| {
+                running_action.hedge_worker_id.is_none()
+                    && self
+                        .workers
+                        .workers
+                        .peek(&running_action.worker_id)
+                        .is_some_and(|worker| {
+                            !worker.is_quarantined()
+                                && !worker.is_draining()
+                                && worker.can_run_after_releasing(&action_info.platform_properties, running_action_info)
+                        })
+            })
+            .map(|(running_action_info, _)| running_action_info.clone());
+        let Some(preempted_action_info) = preempted_action_info else {
+            return;
+        };
+        let (preempted_action_info, running_action) = self.active_actions.remove_entry(&preempted_action_info).unwrap();
+        let worker_id = running_action.worker_id;
+        self.metrics.preempted_actions.inc();
+        log::info!(
+            "Preempting action {} on worker {} for action {}",
+            preempted_action_info.digest().hash_str(),
+            worker_id,
+            action_info.digest().hash_str()
+        );
+
+        // Preemption is not the action's fault, so don't count it as an attempt.
+        let mut awaited_action = running_action.action;
+        awaited_action.attempts -= 1;
+        let attempts = awaited_action.attempts;
+        self.publish_event(|| SchedulerEvent {
+            worker_id: Some(worker_id.to_string()),
+            attempts: Some(attempts),
+            message: Some(format!("Preempted by action {}", action_info.digest().hash_str())),
+            ..SchedulerEvent::for_action(
+                SchedulerEventType::ActionRetried,
+                &preempted_action_info.unique_qualifier,
+            )
+        });
+        Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Queued;
+        let send_result = awaited_action.notify_channel.send(awaited_action.current_state.clone());
+        if send_result.is_err() {
+            log::warn!(
+                "Action {} has no more listeners during preempt_running_action_for()",
+                preempted_action_info.digest().hash_str()
+            );
+        }
+        self.queued_actions_set.insert(preempted_action_info.clone());
+        self.enqueue_action(preempted_action_info.clone(), awaited_action);
+
+        // The action stays assigned to the worker until the worker reports back on it.
+        self.pending_preemptions.insert(
+            action_info.unique_qualifier.clone(),
+            PendingPreemption {
+                worker_id,
+                preempted: preempted_action_info.unique_qualifier.clone(),
+            },
+        );
+        if let Some(worker) = self.workers.workers.peek_mut(&worker_id) {
+            // We don't care if we fail to send message to worker, this is only a best attempt.
+            let _ = worker.notify_update(WorkerUpdate::KillAction(preempted_action_info));
+        }
+    }
+
+    /// Stops assigning new actions to the worker. The worker is removed from the pool
+    /// once it finished its running actions or the drain timeout expired.
+    fn drain_worker(&mut self, worker_id: &WorkerId, now_timestamp: WorkerTimestamp) -> Result<(), Error> {
+        let worker = self
+            .workers
+            .workers
+            .peek_mut(worker_id)
+            .ok_or_else(|| make_input_err!("Worker not found in worker map in drain_worker() {}", worker_id))?;
+        if !worker.is_draining() {
+            self.metrics.workers_drained.inc();
+            worker.draining_until = Some(now_timestamp + self.worker_drain_timeout_s);
+            log::info!(
+                "Worker {} is going away, draining {} running actions",
+                worker_id,
+                worker.running_action_infos.len()
+            );
+        }
+        self.remove_worker_if_drained(worker_id);
+        Ok(())
+    }
+
+    /// Removes the worker from the pool if it is draining and has no more running actions.
+    fn remove_worker_if_drained(&mut self, worker_id: &WorkerId) {
+        let is_drained = self
+            .workers
+            .workers
+            .peek(worker_id)
+            .is_some_and(|worker| worker.is_draining() && !worker.has_actions());
+        if is_drained {
+            log::info!("Worker {worker_id} finished draining, removing from pool");
+            self.workers.remove_worker(worker_id);
+        }
+    }
+
+    /// Counts an internal error against the worker, possibly quarantining it.
+    fn record_worker_internal_error(&mut self, worker_id: &WorkerId) {
+        let Some(worker_quarantine) = &self.worker_quarantine else {
+            return;
+        };
+        let Some(worker) = self.workers.workers.peek_mut(worker_id) else {
+            return;
+        };
+        // Worker timestamps come from the clock of the caller, which is also what
+        // re-admission in `remove_timedout_workers()` compares against.
+        let now = worker.last_update_timestamp;
+        if worker_quarantine.record_internal_error(worker, now) {
+            self.metrics.workers_quarantined.inc();
+            log::warn!(
+                "Worker {} caused too many internal errors, quarantining it for {} seconds",
+                worker_id,
+                worker.quarantined_until.unwrap_or(now) - now
+            );
+        }
+    }
+
+    /// Releases the losing executions of hedged actions whose workers did not report that
+    /// they stopped them within `KILLED_HEDGE_TIMEOUT`, so they do not hold on to the
+    /// workers forever.
+    fn release_unanswered_killed_hedges(&mut self, now: SystemTime) {
+        let expired_killed_hedges: Vec<(WorkerId, ActionInfoHashKey)> = self
+            .killed_hedges
+            .iter()
+            .filter(|(_, killed_at)| now.duration_since(**killed_at).unwrap_or_default() >= KILLED_HEDGE_TIMEOUT)
+            .map(|(killed_hedge, _)| killed_hedge.clone())
+            .collect();
+        for (worker_id, action_info_hash_key) in expired_killed_hedges {
+            self.killed_hedges.remove(&(worker_id, action_info_hash_key.clone()));
+            if self.release_killed_action(&worker_id, &action_info_hash_key) {
+                log::warn!(
+                    "Worker {} did not report that it killed action {}, releasing it",
+                    worker_id,
+                    action_info_hash_key.digest.hash_str()
+                );
+            }
+        }
+    }
+
+    /// Starts a second execution on another worker of every action that has been
+    /// executing for much longer than previous executions of its command.
+    fn hedge_straggling_actions(&mut self, now: SystemTime) {
+        self.release_unanswered_killed_hedges(now);
+        let (Some(hedging), Some(execution_statistics)) = (&self.hedging, &self.execution_statistics) else {
+            return;
+        };
+        let straggling_action_infos: Vec<Arc<ActionInfo>> = self
+            .active_actions
+            .iter()
+            .filter(|(action_info, running_action)| {
+                running_action.hedge_worker_id.is_none()
+                    && hedging
+                        .threshold(execution_statistics.recent_durations(action_info))
+                        .is_some_and(|threshold| {
+                            now.duration_since(running_action.start_time).unwrap_or_default() > threshold
+                        })
+            })
+            .map(|(action_info, _)| action_info.clone())
+            .collect();
+        for action_info in straggling_action_infos {
+            let Some(running_action) = self.active_actions.get(&action_info) else {
+                continue;
+            };
+            let worker_id = running_action.worker_id;
+            let Some(worker) = self.workers.find_worker_for_action_mut(&action_info, Some(&worker_id)) else {
+                continue;
+            };
+            let hedge_worker_id = worker.id;
+            if worker
+                .notify_update(WorkerUpdate::RunAction(action_info.clone()))
+                .is_err()
+            {
+                let err = make_err!(
+                    Code::Internal,
+                    "Worker command failed, removing worker {}",
+                    hedge_worker_id
+                );
+                log::warn!("{:?}", err);
+                self.immediate_evict_worker(&hedge_worker_id, err);
+                continue;
+            }
+            log::info!(
+                "Hedging action {} on worker {} because it is taking too long on worker {}",
+                action_info.digest().hash_str(),
+                hedge_worker_id,
+                worker_id
+            );
+            self.metrics.hedged_actions.inc();
+            if let Some(running_action) = self.active_actions.get_mut(&action_info) {
+                running_action.hedge_worker_id = Some(hedge_worker_id);
+            }
+            self.publish_event(|| SchedulerEvent {
+                worker_id: Some(hedge_worker_id.to_string()),
+                message: Some(format!("Hedging execution on worker {worker_id}")),
+                ..SchedulerEvent::for_action(SchedulerEventType::ActionMatched, &action_info.unique_qualifier)
+            });
+        }
+    }
+
+    /// Returns the values of `metrics_platform_property_keys` the action requested, or None
+    /// if the metrics are not broken down by platform properties. Once metrics are kept for
+    /// `MAX_PLATFORM_PROPERTY_CLASSES` classes, actions of new classes are counted as
+    /// `OTHER_PLATFORM_PROPERTY_CLASS`.
+    fn platform_property_class(&self, action_info: &ActionInfo) -> Option<Vec<String>> {
+        if self.metrics_platform_property_keys.is_empty() {
+            return None;
+        }
+        let properties = &action_info.platform_properties.properties;
+        let class: Vec<String> = self
+            .metrics_platform_property_keys
+            .iter()
+            .map(|key| {
+                properties
+                    .get(key)
+                    .map_or_else(String::new, |value| value.as_str().into_owned())
+            })
+            .collect();
+        if self.platform_property_class_metrics.len() >= MAX_PLATFORM_PROPERTY_CLASSES
+            && !self.platform_property_class_metrics.contains_key(&class)
+        {
+            return Some(vec![
+                OTHER_PLATFORM_PROPERTY_CLASS.to_string();
+                self.metrics_platform_property_keys.len()
+            ]);
+        }
+        Some(class)
+    }
+
+    /// Publishes the metrics broken down by `metrics_platform_property_keys`.
+    fn gather_platform_property_class_metrics(&self, c: &mut CollectorState) {
+        #[derive(Default)]
+        struct ClassCounts {
+            queued_actions: u64,
+            executing_actions: u64,
+            queue_waits_s: Vec<u64>,
+        }
+        let now = SystemTime::now();
+        let mut class_counts: HashMap<Vec<String>, ClassCounts> = HashMap::new();
+        for action_info in self.queued_actions.keys() {
+            let Some(class) = self.platform_property_class(action_info) else {
+                return;
+            };
+            let counts = class_counts.entry(class).or_default();
+            counts.queued_actions += 1;
+            counts.queue_waits_s.push(
+                now.duration_since(action_info.insert_timestamp)
+                    .unwrap_or_default()
+                    .as_secs(),
+            );
+        }
+        for action_info in self.active_actions.keys() {
+            let Some(class) = self.platform_property_class(action_info) else {
+                return;
+            };
+            class_counts.entry(class).or_default().executing_actions += 1;
+        }
+        for class in self.platform_property_class_metrics.keys() {
+            class_counts.entry(class.clone()).or_default();
+        }
+
+        for (class, counts) in class_counts {
+            let labels: Vec<(Cow<'static, str>, Cow<'static, str>)> = self
+                .metrics_platform_property_keys
+                .iter()
+                .zip(class.iter())
+                .map(|(key, value)| (metrics_label_name(key).into(), value.clone().into()))
+                .collect();
+            c.publish_with_labels(
+                "platform_property_queued_actions",
+                &counts.queued_actions,
+                "The number of queued actions with these platform properties.",
+                labels.clone(),
+            );
+            c.publish_with_labels(
+                "platform_property_executing_actions",
+                &counts.executing_actions,
+                "The number of running actions with these platform properties.",
+                labels.clone(),
+            );
+            c.publish_stats_with_labels(
+                "platform_property_queue_wait_seconds",
+                counts.queue_waits_s.into_iter(),
+                "How long the queued actions with these platform properties have been waiting since they were added.",
+                labels.clone(),
+            );
+            let Some(class_metrics) = self.platform_property_class_metrics.get(&class) else {
+                continue;
+            };
+            c.publish_with_labels(
+                "platform_property_match_failures",
+                &class_metrics.match_failures,
+                "The number of times an action with these platform properties could not be matched to any worker.",
+                labels.clone(),
+            );
+            c.publish_with_labels(
+                "platform_property_matched_actions",
+                &class_metrics.matched_actions,
+                "The number of actions with these platform properties sent to a worker.",
+                labels.clone(),
+            );
+            c.publish_with_labels(
+                "platform_property_queue_wait_seconds_total",
+                &class_metrics.total_queue_wait,
+                "The total time actions with these platform properties waited before being sent to a worker.",
+                labels,
+            );
+        }
+    }
+
     // TODO(blaise.bruer) This is an O(n*m) (aka n^2) algorithm. In theory we can create a map
     // of capabilities of each worker and then try and match the actions to the worker using
     // the map lookup (ie. map reduce).
@@ -394,7 +1533,7 @@ impl SimpleSchedulerImpl {
         // to add `drain_filter`, which would in theory solve this problem, but because we need
         // to iterate the items in reverse it becomes more difficult (and it is currently an
         // unstable feature [see: https://github.com/rust-lang/rust/issues/70530]).
-        let action_infos: Vec<Arc<ActionInfo>> = self.queued_actions.keys().rev().cloned().collect();
+        let action_infos = self.queue_order.match_order(SystemTime::now());
         for action_info in action_infos {
             let Some(awaited_action) = self.queued_actions.get(action_info.as_ref()) else {
                 log::error!(
@@ -403,9 +1542,18 @@ impl SimpleSchedulerImpl {
                 );
                 continue;
             };
-            let Some(worker) = self.workers.find_worker_for_action_mut(awaited_action) else {
-                // No worker found, check the next action to see if there's a
-                // matching one for that.
+            assert!(matches!(awaited_action.current_state.stage, ActionStage::Queued));
+            let Some(worker) = self.workers.find_worker_for_action_mut(&action_info, None) else {
+                if let Some(class) = self.platform_property_class(&action_info) {
+                    self.platform_property_class_metrics
+                        .entry(class)
+                        .or_default()
+                        .match_failures += 1;
+                }
+                // No worker found, make room for the action if it is allowed to
+                // preempt a running action and check the next action to see if
+                // there's a matching one for that.
+                self.preempt_running_action_for(&action_info);
                 continue;
             };
             let worker_id = worker.id;
@@ -422,12 +1570,14 @@ impl SimpleSchedulerImpl {
             }
 
             // At this point everything looks good, so remove it from the queue and add it to active actions.
-            let (action_info, mut awaited_action) = self.queued_actions.remove_entry(action_info.as_ref()).unwrap();
+            let (action_info, mut awaited_action) = self.dequeue_action(action_info.as_ref()).unwrap();
             assert!(
                 self.queued_actions_set.remove(&action_info),
                 "queued_actions_set should always have same keys as queued_actions"
             );
-            Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Executing;
+            let current_state = Arc::make_mut(&mut awaited_action.current_state);
+            current_state.stage = ActionStage::Executing;
+            current_state.queue_position = None;
             let send_result = awaited_action.notify_channel.send(awaited_action.current_state.clone());
             if send_result.is_err() {
                 // Don't remove this task, instead we keep them around for a bit just in case
@@ -439,16 +1589,174 @@ impl SimpleSchedulerImpl {
                 );
             }
             awaited_action.attempts += 1;
+            let attempts = awaited_action.attempts;
+            self.publish_event(|| SchedulerEvent {
+                worker_id: Some(worker_id.to_string()),
+                attempts: Some(attempts),
+                ..SchedulerEvent::for_action(SchedulerEventType::ActionMatched, &action_info.unique_qualifier)
+            });
+            if let Some(class) = self.platform_property_class(&action_info) {
+                let class_metrics = self.platform_property_class_metrics.entry(class).or_default();
+                class_metrics.matched_actions += 1;
+                class_metrics.total_queue_wait += SystemTime::now()
+                    .duration_since(action_info.insert_timestamp)
+                    .unwrap_or_default();
+            }
             self.active_actions.insert(
                 action_info.clone(),
                 RunningAction {
                     worker_id,
+                    hedge_worker_id: None,
+                    start_time: SystemTime::now(),
                     action: awaited_action,
                 },
             );
         }
     }
 
+    /// Returns when `worker` is expected to be able to start an action with the given
+    /// platform properties, or None if it can not run such actions or it is unknown when
+    /// its running actions finish.
+    fn expected_worker_availability(
+        &self,
+        worker: &Worker,
+        platform_properties: &PlatformProperties,
+        now: SystemTime,
+    ) -> Option<SystemTime> {
+        if worker.is_quarantined() || worker.is_draining() {
+            return None;
+        }
+        if platform_properties.is_satisfied_by(&worker.platform_properties) {
+            return Some(now);
+        }
+        worker
+            .running_action_infos
+            .iter()
+            .filter(|running_action_info| worker.can_run_after_releasing(platform_properties, running_action_info))
+            .filter_map(|running_action_info| {
+                let running_action = self.active_actions.get(running_action_info)?;
+                let expected_end = running_action.start_time + running_action.action.expected_duration?;
+                Some(cmp::max(expected_end, now))
+            })
+            .min()
+    }
+
+    /// Sends the clients of every queued action the position of the action among the
+    /// queued actions with the same platform properties and when it is expected to be
+    /// sent to a worker. The start time is estimated by handing the actions, in the
+    /// order they are matched in, to whichever capable worker is expected to be
+    /// available first. Clients are only notified if the position or estimate changed.
+    fn publish_queue_positions(&mut self, now: SystemTime) {
+        // Actions with equal platform properties, in the order they are matched in.
+        let mut property_classes: Vec<(PlatformProperties, Vec<Arc<ActionInfo>>)> = Vec::new();
+        for action_info in self.queue_order.match_order(now) {
+            let platform_properties = &action_info.platform_properties;
+            match property_classes
+                .iter_mut()
+                .find(|(props, _)| props == platform_properties)
+            {
+                Some((_, action_infos)) => action_infos.push(action_info),
+                None => property_classes.push((platform_properties.clone(), vec![action_info])),
+            }
+        }
+
+        let mut queue_positions = Vec::with_capacity(self.queued_actions.len());
+        for (platform_properties, action_infos) in property_classes {
+            let mut worker_availabilities: BinaryHeap<cmp::Reverse<SystemTime>> = self
+                .workers
+                .workers
+                .iter()
+                .filter_map(|(_, worker)| self.expected_worker_availability(worker, &platform_properties, now))
+                .map(cmp::Reverse)
+                .collect();
+            let queue_length = action_infos.len() as u64;
+            for (position, action_info) in action_infos.into_iter().enumerate() {
+                let estimated_start_timestamp = worker_availabilities.pop().map(|cmp::Reverse(start)| start);
+                // Once the duration of an action is unknown, so is the start of the
+                // actions after it on the same worker.
+                let expected_duration = self
+                    .queued_actions
+                    .get(action_info.as_ref())
+                    .and_then(|awaited_action| awaited_action.expected_duration);
+                if let (Some(start), Some(expected_duration)) = (estimated_start_timestamp, expected_duration) {
+                    worker_availabilities.push(cmp::Reverse(start + expected_duration));
+                }
+                queue_positions.push((
+                    action_info,
+                    QueuePosition {
+                        position: position as u64,
+                        queue_length,
+                        estimated_start_timestamp,
+                    },
+                ));
+            }
+        }
+
+        for (action_info, queue_position) in queue_positions {
+            let Some(awaited_action) = self.queued_actions.get_mut(action_info.as_ref()) else {
+                continue;
+            };
+            if awaited_action.current_state.queue_position.as_ref() == Some(&queue_position) {
+                continue;
+            }
+            Arc::make_mut(&mut awaited_action.current_state).queue_position = Some(queue_position);
+            // The action may have no listeners while waiting for a client to reconnect.
+            let _ = awaited_action.notify_channel.send(awaited_action.current_state.clone());
+        }
+    }
+
+    /// Returns the demand per distinct set of platform properties of queued or executing
+    /// actions, ordered by the platform properties.
+    fn worker_pool_demand(&self) -> Vec<PlatformPropertiesDemand> {
+        // The platform properties of the first action with the same signature are kept to
+        // find the workers that satisfy them.
+        let mut demands: BTreeMap<BTreeMap<String, String>, (&PlatformProperties, PlatformPropertiesDemand)> =
+            BTreeMap::new();
+        let queued = self.queued_actions.keys().map(|action_info| (action_info, true));
+        let executing = self.active_actions.keys().map(|action_info| (action_info, false));
+        for (action_info, is_queued) in queued.chain(executing) {
+            let platform_properties = &action_info.platform_properties;
+            let signature: BTreeMap<String, String> = platform_properties
+                .properties
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_str().into_owned()))
+                .collect();
+            let (_, demand) = demands.entry(signature.clone()).or_insert_with(|| {
+                (
+                    platform_properties,
+                    PlatformPropertiesDemand {
+                        platform_properties: signature,
+                        ..Default::default()
+                    },
+                )
+            });
+            if is_queued {
+                demand.queued_actions += 1;
+            } else {
+                demand.executing_actions += 1;
+            }
+        }
+        demands
+            .into_values()
+            .map(|(platform_properties, mut demand)| {
+                demand.idle_workers = self
+                    .workers
+                    .workers
+                    .iter()
+                    .filter(|(_, worker)| {
+                        !worker.has_actions()
+                            && !worker.is_paused
+                            && !worker.is_quarantined()
+                            && !worker.is_draining()
+                            && platform_properties.is_satisfied_by(&worker.platform_properties)
+                    })
+                    .count() as u64;
+                demand.recommended_additional_workers = demand.queued_actions.saturating_sub(demand.idle_workers);
+                demand
+            })
+            .collect()
+    }
+
     fn update_action_with_internal_error(
         &mut self,
         worker_id: &WorkerId,
@@ -457,12 +1765,37 @@ impl SimpleSchedulerImpl {
     ) {
         self.metrics.update_action_with_internal_error.inc();
         let Some((action_info, mut running_action)) = self.active_actions.remove_entry(action_info_hash_key) else {
+            if self.release_killed_action(worker_id, action_info_hash_key) {
+                return;
+            }
             self.metrics.update_action_with_internal_error_no_action.inc();
             log::error!("Could not find action info in active actions : {action_info_hash_key:?}");
             return;
         };
 
         let due_to_backpressure = err.code == Code::ResourceExhausted;
+        let is_running_on_worker =
+            running_action.worker_id == *worker_id || running_action.hedge_worker_id == Some(*worker_id);
+        if !is_running_on_worker && self.release_killed_action(worker_id, action_info_hash_key) {
+            self.active_actions.insert(action_info, running_action);
+            return;
+        }
+        if is_running_on_worker && !due_to_backpressure {
+            self.record_worker_internal_error(worker_id);
+        }
+        if is_running_on_worker && running_action.hedge_worker_id.is_some() {
+            // The other execution of this hedged action is still running, so let it finish.
+            log::warn!("Internal error for hedged action on worker {}: {}", worker_id, err);
+            self.active_actions.insert(action_info.clone(), running_action);
+            self.worker_dropped_action(worker_id, &action_info, err);
+            if let Some(worker) = self.workers.workers.get_mut(worker_id) {
+                worker.complete_action(&action_info);
+            }
+            self.remove_worker_if_drained(worker_id);
+            self.tasks_or_workers_change_notify.notify_one();
+            return;
+        }
+
         // Don't count a backpressure failure as an attempt for an action.
         if due_to_backpressure {
             self.metrics.update_action_with_internal_error_backpressure.inc();
@@ -498,6 +1831,7 @@ impl SimpleSchedulerImpl {
 
         // Re-queue the action or fail on max attempts.
         self.retry_action(&action_info, worker_id, err);
+        self.remove_worker_if_drained(worker_id);
     }
 
     fn update_action(
@@ -517,12 +1851,18 @@ impl SimpleSchedulerImpl {
             return Err(err);
         }
 
-        let (action_info, mut running_action) = self
-            .active_actions
-            .remove_entry(action_info_hash_key)
+        let maybe_running_action = self.active_actions.remove_entry(action_info_hash_key);
+        if maybe_running_action.is_none() && self.release_killed_action(worker_id, action_info_hash_key) {
+            return Ok(());
+        }
+        let (action_info, mut running_action) = maybe_running_action
             .err_tip(|| format!("Could not find action info in active actions : {action_info_hash_key:?}"))?;
 
-        if running_action.worker_id != *worker_id {
+        if running_action.worker_id != *worker_id && running_action.hedge_worker_id != Some(*worker_id) {
+            if self.release_killed_action(worker_id, action_info_hash_key) {
+                self.active_actions.insert(action_info, running_action);
+                return Ok(());
+            }
             self.metrics.update_action_from_wrong_worker.inc();
             let err = make_err!(
                 Code::Internal,
@@ -536,6 +1876,28 @@ impl SimpleSchedulerImpl {
             return Err(err);
         }
 
+        let mut action_stage = action_stage;
+        if let ActionStage::Completed(action_result) = &mut action_stage {
+            if let Some(flaky_failure_reason) = action_result.flaky_failure_reason.take() {
+                if matches!(self.max_flaky_retries, Some(max_flaky_retries) if running_action.action.flaky_retries < max_flaky_retries)
+                {
+                    self.retry_flaky_action(
+                        action_info,
+                        running_action,
+                        worker_id,
+                        action_result,
+                        flaky_failure_reason,
+                    );
+                    return Ok(());
+                }
+            }
+            if !running_action.action.previous_attempts.is_empty() {
+                let mut previous_attempts = std::mem::take(&mut running_action.action.previous_attempts);
+                previous_attempts.append(&mut action_result.execution_metadata.previous_attempts);
+                action_result.execution_metadata.previous_attempts = previous_attempts;
+            }
+        }
+
         Arc::make_mut(&mut running_action.action.current_state).stage = action_stage;
 
         let send_result = running_action
@@ -557,6 +1919,51 @@ impl SimpleSchedulerImpl {
             return Ok(());
         }
 
+        // The other execution of a hedged action is no longer needed, so kill it.
+        let other_worker_id = if running_action.worker_id == *worker_id {
+            running_action.hedge_worker_id
+        } else {
+            self.metrics.hedged_actions_won.inc();
+            Some(running_action.worker_id)
+        };
+        if let Some(other_worker_id) = other_worker_id {
+            if let Some(other_worker) = self.workers.workers.peek_mut(&other_worker_id) {
+                // We don't care if we fail to send message to worker, this is only a best attempt.
+                let _ = other_worker.notify_update(WorkerUpdate::KillAction(action_info.clone()));
+                self.killed_hedges.insert(
+                    (other_worker_id, action_info.unique_qualifier.clone()),
+                    SystemTime::now(),
+                );
+            }
+        }
+        if let (Some(execution_statistics), ActionStage::Completed(action_result)) =
+            (&self.execution_statistics, &running_action.action.current_state.stage)
+        {
+            execution_statistics.record_execution(&action_info, action_result);
+        }
+
+        let completed_successfully = matches!(
+            &running_action.action.current_state.stage,
+            ActionStage::Completed(action_result) if action_result.error.is_none()
+        );
+        self.publish_event(|| {
+            let mut event = SchedulerEvent {
+                worker_id: Some(worker_id.to_string()),
+                attempts: Some(running_action.action.attempts),
+                ..SchedulerEvent::for_action(SchedulerEventType::ActionCompleted, &action_info.unique_qualifier)
+            };
+            if let ActionStage::Completed(action_result) = &running_action.action.current_state.stage {
+                match &action_result.error {
+                    Some(err) => event.message = Some(format!("{err:?}")),
+                    None => event.exit_code = Some(action_result.exit_code),
+                }
+            }
+            event
+        });
+
+        if let Some(completed_actions_store) = &self.completed_actions_store {
+            completed_actions_store.persist(running_action.action.current_state.as_ref().clone());
+        }
         // Keep in case this is asked for soon.
         self.recently_completed_actions.insert(CompletedAction {
             completed_time: SystemTime::now(),
@@ -569,12 +1976,26 @@ impl SimpleSchedulerImpl {
             .get_mut(worker_id)
             .ok_or_else(|| make_input_err!("WorkerId '{}' does not exist in workers map", worker_id))?;
         worker.complete_action(&action_info);
+        if completed_successfully {
+            worker.quarantine_count = 0;
+        }
+        self.remove_worker_if_drained(worker_id);
         self.tasks_or_workers_change_notify.notify_one();
 
         Ok(())
     }
 }
 
+/// The stores a `SimpleScheduler` persists state in. Each store is only used if
+/// the part of the config it belongs to is set.
+#[derive(Default, Clone)]
+pub struct SimpleSchedulerStores {
+    /// Where the recorded execution durations are persisted.
+    pub execution_statistics: Option<Arc<dyn Store>>,
+    /// Where the final state of completed actions is persisted.
+    pub completed_actions: Option<Arc<dyn Store>>,
+}
+
 /// Engine used to manage the queued/running tasks and relationship with
 /// the worker nodes. All state on how the workers and actions are interacting
 /// should be held in this struct.
@@ -582,14 +2003,20 @@ pub struct SimpleScheduler {
     inner: Arc<Mutex<SimpleSchedulerImpl>>,
     platform_property_manager: Arc<PlatformPropertyManager>,
     task_worker_matching_future: JoinHandle<()>,
+    hedging_future: Option<JoinHandle<()>>,
+    queue_position_future: Option<JoinHandle<()>>,
+    /// Estimates how long new actions execute for, if queued actions are ordered by it.
+    execution_statistics: Option<Arc<ExecutionStatistics>>,
+    completed_actions_store: Option<Arc<CompletedActionsStore>>,
+    events: broadcast::Sender<Arc<SchedulerEvent>>,
     metrics: Arc<Metrics>,
 }
 
 impl SimpleScheduler {
     #[inline]
     #[must_use]
-    pub fn new(scheduler_cfg: &native_link_config::schedulers::SimpleScheduler) -> Self {
-        Self::new_with_callback(scheduler_cfg, || {
+    pub fn new(scheduler_cfg: &native_link_config::schedulers::SimpleScheduler, stores: SimpleSchedulerStores) -> Self {
+        Self::new_with_stores_and_callback(scheduler_cfg, stores, || {
             // The cost of running `do_try_match()` is very high, but constant
             // in relation to the number of changes that have happened. This means
             // that grabbing this lock to process `do_try_match()` should always
@@ -604,6 +2031,15 @@ impl SimpleScheduler {
     pub fn new_with_callback<Fut: Future<Output = ()> + Send, F: Fn() -> Fut + Send + Sync + 'static>(
         scheduler_cfg: &native_link_config::schedulers::SimpleScheduler,
         on_matching_engine_run: F,
+    ) -> Self {
+        Self::new_with_stores_and_callback(scheduler_cfg, SimpleSchedulerStores::default(), on_matching_engine_run)
+    }
+
+    /// Same as `new_with_callback()`, but persists state in `stores`.
+    pub fn new_with_stores_and_callback<Fut: Future<Output = ()> + Send, F: Fn() -> Fut + Send + Sync + 'static>(
+        scheduler_cfg: &native_link_config::schedulers::SimpleScheduler,
+        stores: SimpleSchedulerStores,
+        on_matching_engine_run: F,
     ) -> Self {
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(
             scheduler_cfg.supported_platform_properties.clone().unwrap_or_default(),
@@ -614,6 +2050,11 @@ impl SimpleScheduler {
             worker_timeout_s = DEFAULT_WORKER_TIMEOUT_S;
         }
 
+        let mut worker_drain_timeout_s = scheduler_cfg.worker_drain_timeout_s;
+        if worker_drain_timeout_s == 0 {
+            worker_drain_timeout_s = DEFAULT_WORKER_DRAIN_TIMEOUT_S;
+        }
+
         let mut retain_completed_for_s = scheduler_cfg.retain_completed_for_s;
         if retain_completed_for_s == 0 {
             retain_completed_for_s = DEFAULT_RETAIN_COMPLETED_FOR_S;
@@ -624,6 +2065,37 @@ impl SimpleScheduler {
             max_job_retries = DEFAULT_MAX_JOB_RETRIES;
         }
 
+        let max_flaky_retries = scheduler_cfg.flaky_retry.map(|flaky_retry| {
+            if flaky_retry.max_retries == 0 {
+                DEFAULT_FLAKY_MAX_RETRIES
+            } else {
+                flaky_retry.max_retries
+            }
+        });
+
+        // Hedging and the ordering of queued actions share one execution duration history.
+        let execution_statistics = (scheduler_cfg.execution_statistics.is_some() || scheduler_cfg.hedging.is_some())
+            .then(|| {
+                Arc::new(ExecutionStatistics::new(
+                    &scheduler_cfg.execution_statistics.clone().unwrap_or_default(),
+                    scheduler_cfg.hedging.map_or(0, |hedging| hedging.max_samples),
+                    stores.execution_statistics,
+                ))
+            });
+        let completed_actions_store = stores
+            .completed_actions
+            .map(|store| Arc::new(CompletedActionsStore::new(store)));
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        // The sinks stop once the scheduler is dropped.
+        let event_sinks = scheduler_cfg
+            .event_sinks
+            .iter()
+            .map(|event_sink| match event_sink {
+                SchedulerEventSink::ndjson_file(path) => spawn_ndjson_file_sink(path.clone(), EVENT_CHANNEL_CAPACITY),
+            })
+            .collect();
+
         let tasks_or_workers_change_notify = Arc::new(Notify::new());
 
         let metrics = Arc::new(Metrics::default());
@@ -631,16 +2103,60 @@ impl SimpleScheduler {
         let inner = Arc::new(Mutex::new(SimpleSchedulerImpl {
             queued_actions_set: HashSet::new(),
             queued_actions: BTreeMap::new(),
+            queue_order: QueueOrder::new(scheduler_cfg.priority_aging.as_ref().map(PriorityAging::new)),
             workers: Workers::new(scheduler_cfg.allocation_strategy),
-            active_actions: HashMap::new(),
+            active_actions: ActiveActions::default(),
             recently_completed_actions: HashSet::new(),
             retain_completed_for: Duration::new(retain_completed_for_s, 0),
+            completed_actions_store: completed_actions_store.clone(),
             worker_timeout_s,
+            worker_drain_timeout_s,
             max_job_retries,
+            max_flaky_retries,
+            hedging: scheduler_cfg.hedging.as_ref().map(Hedging::new),
+            worker_quarantine: scheduler_cfg.worker_quarantine.as_ref().map(WorkerQuarantine::new),
+            preemption: scheduler_cfg.preemption.as_ref().map(Preemption::new),
+            pending_preemptions: HashMap::new(),
+            killed_hedges: HashMap::new(),
+            execution_statistics: execution_statistics.clone(),
+            metrics_platform_property_keys: scheduler_cfg.metrics_platform_property_keys.clone(),
+            platform_property_class_metrics: HashMap::new(),
+            events: events.clone(),
+            event_sinks,
             tasks_or_workers_change_notify: tasks_or_workers_change_notify.clone(),
             metrics: metrics.clone(),
         }));
         let weak_inner = Arc::downgrade(&inner);
+        let hedging_future = scheduler_cfg.hedging.is_some().then(|| {
+            let weak_inner = weak_inner.clone();
+            tokio::spawn(async move {
+                // Break out of the loop only when the inner is dropped.
+                loop {
+                    tokio::time::sleep(HEDGING_CHECK_INTERVAL).await;
+                    let Some(inner_mux) = weak_inner.upgrade() else {
+                        return;
+                    };
+                    inner_mux.lock().hedge_straggling_actions(SystemTime::now());
+                }
+            })
+        });
+        let queue_position_future = scheduler_cfg.queue_position.map(|queue_position| {
+            let mut update_interval_s = queue_position.update_interval_s;
+            if update_interval_s == 0 {
+                update_interval_s = DEFAULT_QUEUE_POSITION_UPDATE_INTERVAL_S;
+            }
+            let weak_inner = weak_inner.clone();
+            tokio::spawn(async move {
+                // Break out of the loop only when the inner is dropped.
+                loop {
+                    tokio::time::sleep(Duration::from_secs(update_interval_s)).await;
+                    let Some(inner_mux) = weak_inner.upgrade() else {
+                        return;
+                    };
+                    inner_mux.lock().publish_queue_positions(SystemTime::now());
+                }
+            })
+        });
         Self {
             inner,
             platform_property_manager,
@@ -667,6 +2183,11 @@ impl SimpleScheduler {
                 }
                 // Unreachable.
             }),
+            hedging_future,
+            queue_position_future,
+            execution_statistics: execution_statistics.filter(|_| scheduler_cfg.execution_statistics.is_some()),
+            completed_actions_store,
+            events,
             metrics,
         }
     }
@@ -689,6 +2210,20 @@ impl SimpleScheduler {
         worker.keep_alive()
     }
 
+    /// Hedges actions that would be considered straggling at the given time. Should only be
+    /// used in unit tests, hedging is otherwise done periodically when it is enabled.
+    pub fn hedge_straggling_actions_for_test(&self, now: SystemTime) {
+        let mut inner = self.get_inner_lock();
+        inner.hedge_straggling_actions(now);
+    }
+
+    /// Sends the queue positions of queued actions as if it was `now`. Should only be used
+    /// in unit tests, positions are otherwise sent periodically when it is enabled.
+    pub fn publish_queue_positions_for_test(&self, now: SystemTime) {
+        let mut inner = self.get_inner_lock();
+        inner.publish_queue_positions(now);
+    }
+
     fn get_inner_lock(&self) -> MutexGuard<'_, SimpleSchedulerImpl> {
         // We don't use one of the wrappers because we only want to capture the time spent,
         // nothing else beacuse this is a hot path.
@@ -709,18 +2244,38 @@ impl ActionScheduler for SimpleScheduler {
     }
 
     async fn add_action(&self, action_info: ActionInfo) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
+        // Loading the expected duration may need to read from a store, so it is done
+        // before taking the lock.
+        let expected_duration = match &self.execution_statistics {
+            Some(execution_statistics) => execution_statistics.expected_duration(&action_info).await,
+            None => None,
+        };
         let mut inner = self.get_inner_lock();
-        self.metrics.add_action.wrap(move || inner.add_action(action_info))
+        self.metrics
+            .add_action
+            .wrap(move || inner.add_action(action_info, expected_duration))
     }
 
     async fn find_existing_action(
         &self,
         unique_qualifier: &ActionInfoHashKey,
     ) -> Option<watch::Receiver<Arc<ActionState>>> {
-        let inner = self.get_inner_lock();
-        let result = inner
-            .find_existing_action(unique_qualifier)
-            .or_else(|| inner.find_recently_completed_action(unique_qualifier));
+        let mut result = {
+            let inner = self.get_inner_lock();
+            inner
+                .find_existing_action(unique_qualifier)
+                .or_else(|| inner.find_recently_completed_action(unique_qualifier))
+        };
+        if let (None, Some(completed_actions_store)) = (&result, &self.completed_actions_store) {
+            // The store may be remote, so it is only read without holding the lock.
+            match completed_actions_store.get(unique_qualifier).await {
+                Ok(action_state) => result = action_state.map(|state| watch::channel(Arc::new(state)).1),
+                Err(err) => log::warn!(
+                    "Could not load completed action {} : {err:?}",
+                    unique_qualifier.action_name()
+                ),
+            }
+        }
         if result.is_some() {
             self.metrics.existing_actions_found.inc();
         } else {
@@ -791,6 +2346,18 @@ impl WorkerScheduler for SimpleScheduler {
             .err_tip(|| "Error refreshing lifetime in worker_keep_alive_received()")
     }
 
+    async fn update_worker_recent_input_roots(
+        &self,
+        worker_id: &WorkerId,
+        recent_input_root_digests: Vec<DigestInfo>,
+    ) -> Result<(), Error> {
+        let mut inner = self.get_inner_lock();
+        inner
+            .workers
+            .set_recent_input_roots(worker_id, recent_input_root_digests)
+            .err_tip(|| "Error updating recent input roots in update_worker_recent_input_roots()")
+    }
+
     async fn remove_worker(&self, worker_id: WorkerId) {
         let mut inner = self.get_inner_lock();
         inner.immediate_evict_worker(
@@ -799,6 +2366,13 @@ impl WorkerScheduler for SimpleScheduler {
         );
     }
 
+    async fn drain_worker(&self, worker_id: WorkerId, now_timestamp: WorkerTimestamp) -> Result<(), Error> {
+        let mut inner = self.get_inner_lock();
+        inner
+            .drain_worker(&worker_id, now_timestamp)
+            .err_tip(|| "Error while draining worker in drain_worker()")
+    }
+
     async fn remove_timedout_workers(&self, now_timestamp: WorkerTimestamp) -> Result<(), Error> {
         let mut inner = self.get_inner_lock();
         self.metrics.remove_timedout_workers.wrap(move || {
@@ -811,11 +2385,15 @@ impl WorkerScheduler for SimpleScheduler {
                 .rev()
                 .map_while(|(worker_id, worker)| {
                     if worker.last_update_timestamp <= now_timestamp - inner.worker_timeout_s {
-                        Some(*worker_id)
+                        Some((worker_id, worker))
                     } else {
                         None
                     }
                 })
+                // Workers may stop sending keep alives while they shut down, so draining
+                // workers are only removed once their drain timeout expired below.
+                .filter(|(_, worker)| !worker.is_draining())
+                .map(|(worker_id, _)| *worker_id)
                 .collect();
             for worker_id in &worker_ids_to_remove {
                 let err = make_err!(Code::Internal, "Worker {worker_id} timed out, removing from pool");
@@ -823,10 +2401,49 @@ impl WorkerScheduler for SimpleScheduler {
                 inner.immediate_evict_worker(worker_id, err);
             }
 
+            // Remove draining workers that did not finish their running actions in time.
+            let drained_worker_ids_to_remove: Vec<WorkerId> = inner
+                .workers
+                .workers
+                .iter()
+                .filter(|(_, worker)| worker.draining_until.is_some_and(|until| until <= now_timestamp))
+                .map(|(worker_id, _)| *worker_id)
+                .collect();
+            for worker_id in &drained_worker_ids_to_remove {
+                let err = make_err!(
+                    Code::Internal,
+                    "Worker {worker_id} did not finish draining in time, removing from pool"
+                );
+                log::warn!("{:?}", err);
+                inner.immediate_evict_worker(worker_id, err);
+            }
+
+            // Re-admit quarantined workers whose backoff expired.
+            let mut readmitted_workers = false;
+            for (worker_id, worker) in inner.workers.workers.iter_mut() {
+                if worker.quarantined_until.is_some_and(|until| until <= now_timestamp) {
+                    log::info!("Worker {worker_id} is no longer quarantined");
+                    worker.quarantined_until = None;
+                    readmitted_workers = true;
+                }
+            }
+            if readmitted_workers {
+                inner.tasks_or_workers_change_notify.notify_one();
+            }
+
             Ok(())
         })
     }
 
+    fn subscribe_to_events(&self) -> Option<broadcast::Receiver<Arc<SchedulerEvent>>> {
+        Some(self.events.subscribe())
+    }
+
+    fn worker_pool_demand(&self) -> Option<Vec<PlatformPropertiesDemand>> {
+        let inner = self.get_inner_lock();
+        Some(inner.worker_pool_demand())
+    }
+
     fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {
         // We do not register anything here because we only want to register metrics
         // once and we rely on the `ActionScheduler::register_metrics()` to do that.
@@ -836,6 +2453,12 @@ impl WorkerScheduler for SimpleScheduler {
 impl Drop for SimpleScheduler {
     fn drop(&mut self) {
         self.task_worker_matching_future.abort();
+        if let Some(hedging_future) = &self.hedging_future {
+            hedging_future.abort();
+        }
+        if let Some(queue_position_future) = &self.queue_position_future {
+            queue_position_future.abort();
+        }
     }
 }
 
@@ -855,6 +2478,16 @@ impl MetricsComponent for SimpleScheduler {
                 &inner.workers.workers.len(),
                 "The number workers active.",
             );
+            c.publish(
+                "quarantined_workers_total",
+                &inner
+                    .workers
+                    .workers
+                    .iter()
+                    .filter(|(_, worker)| worker.is_quarantined())
+                    .count(),
+                "The number of workers that are not assigned new actions because they caused too many internal errors.",
+            );
             c.publish(
                 "active_actions_total",
                 &inner.active_actions.len(),
@@ -914,6 +2547,7 @@ impl MetricsComponent for SimpleScheduler {
                     ],
                 );
             }
+            inner.gather_platform_property_class_metrics(c);
             // Note: We don't publish queued_actions because it can be very large.
             // Note: We don't publish recently completed actions because it can be very large.
         }
@@ -983,6 +2617,13 @@ struct Metrics {
     retry_action_max_attempts_reached: CounterWithTime,
     retry_action_no_more_listeners: CounterWithTime,
     retry_action_but_action_missing: CounterWithTime,
+    hedged_actions: CounterWithTime,
+    hedged_actions_won: CounterWithTime,
+    workers_quarantined: CounterWithTime,
+    workers_drained: CounterWithTime,
+    preempted_actions: CounterWithTime,
+    flaky_retries: CounterWithTime,
+    scheduler_events_dropped: CounterWithTime,
     add_action_joined_running_action: CounterWithTime,
     add_action_joined_queued_action: CounterWithTime,
     add_action_new_action_created: CounterWithTime,
@@ -1109,6 +2750,41 @@ impl Metrics {
                 vec![("result".into(), "action_missing".into())],
             );
         }
+        c.publish(
+            "workers_quarantined",
+            &self.workers_quarantined,
+            "The number of times a worker was quarantined because of too many internal errors.",
+        );
+        c.publish(
+            "preempted_actions",
+            &self.preempted_actions,
+            "The number of running actions that were put back in the queue to make room for higher priority actions.",
+        );
+        c.publish(
+            "scheduler_events_dropped",
+            &self.scheduler_events_dropped,
+            "The number of scheduler events not sent to an event sink because the sink fell too far behind.",
+        );
+        c.publish(
+            "flaky_retries",
+            &self.flaky_retries,
+            "The number of executions that failed in a flaky way and were run again.",
+        );
+        c.publish(
+            "workers_drained",
+            &self.workers_drained,
+            "The number of workers that stopped receiving new actions because they are going away.",
+        );
+        c.publish(
+            "hedged_actions",
+            &self.hedged_actions,
+            "The number of actions that were speculatively executed on a second worker.",
+        );
+        c.publish(
+            "hedged_actions_won",
+            &self.hedged_actions_won,
+            "The number of hedged actions where the second execution completed first.",
+        );
         {
             c.publish_with_labels(
                 "add_action",
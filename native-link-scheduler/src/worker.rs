@@ -12,17 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use error::{make_err, make_input_err, Code, Error, ResultExt};
 use native_link_util::action_messages::ActionInfo;
+use native_link_util::common::DigestInfo;
 use native_link_util::metrics_utils::{CollectorState, CounterWithTime, FuncCounterWrapper, MetricsComponent};
 use native_link_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
 use proto::com::github::trace_machina::native_link::remote_execution::{
-    update_for_worker, ConnectionResult, StartExecute, UpdateForWorker,
+    update_for_worker, ConnectionResult, KillActionRequest, StartExecute, UpdateForWorker,
 };
 use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
@@ -70,6 +71,10 @@ pub enum WorkerUpdate {
 
     /// Request that the worker is no longer in the pool and may discard any jobs.
     Disconnect,
+
+    /// Requests that the worker kill this running action. The action stays assigned
+    /// to the worker until the worker reports back on it.
+    KillAction(Arc<ActionInfo>),
 }
 
 /// Represents a connection to a worker and used as the medium to
@@ -95,6 +100,24 @@ pub struct Worker {
     /// Whether the worker rejected the last action due to back pressure.
     pub is_paused: bool,
 
+    /// Timestamps of the internal errors this worker recently caused.
+    pub recent_internal_errors: VecDeque<WorkerTimestamp>,
+
+    /// If set, the worker is quarantined and will not be assigned new actions
+    /// until this time.
+    pub quarantined_until: Option<WorkerTimestamp>,
+
+    /// Number of times the worker was quarantined since it last completed an action.
+    pub quarantine_count: u32,
+
+    /// If set, the worker is going away. It will not be assigned new actions and
+    /// is removed once its running actions finish or at this time, whichever is first.
+    pub draining_until: Option<WorkerTimestamp>,
+
+    /// Input root digests the worker last reported as recently materialized.
+    /// Used to prefer workers that likely already have an action's inputs cached.
+    pub recent_input_root_digests: HashSet<DigestInfo>,
+
     /// Stats about the worker.
     metrics: Arc<Metrics>,
 }
@@ -119,6 +142,19 @@ fn reduce_platform_properties(parent_props: &mut PlatformProperties, reduction_p
     }
 }
 
+/// Restores the platform properties available on the worker after an action that reduced them
+/// is no longer running.
+fn restore_platform_properties(parent_props: &mut PlatformProperties, restore_props: &PlatformProperties) {
+    for (property, prop_value) in &restore_props.properties {
+        if let PlatformPropertyValue::Minimum(value) = prop_value {
+            let worker_props = &mut parent_props.properties;
+            if let PlatformPropertyValue::Minimum(worker_value) = worker_props.get_mut(property).unwrap() {
+                *worker_value += value;
+            }
+        }
+    }
+}
+
 impl Worker {
     pub fn new(
         id: WorkerId,
@@ -133,12 +169,18 @@ impl Worker {
             running_action_infos: HashSet::new(),
             last_update_timestamp: timestamp,
             is_paused: false,
+            recent_internal_errors: VecDeque::new(),
+            quarantined_until: None,
+            quarantine_count: 0,
+            draining_until: None,
+            recent_input_root_digests: HashSet::new(),
             metrics: Arc::new(Metrics {
                 connected_timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
                 actions_completed: CounterWithTime::default(),
                 run_action: FuncCounterWrapper::default(),
                 keep_alive: FuncCounterWrapper::default(),
                 notify_disconnect: CounterWithTime::default(),
+                kill_action: CounterWithTime::default(),
             }),
         }
     }
@@ -163,6 +205,17 @@ impl Worker {
                 self.metrics.notify_disconnect.inc();
                 send_msg_to_worker(&mut self.tx, update_for_worker::Update::Disconnect(()))
             }
+            WorkerUpdate::KillAction(action_info) => {
+                self.metrics.kill_action.inc();
+                send_msg_to_worker(
+                    &mut self.tx,
+                    update_for_worker::Update::KillActionRequest(KillActionRequest {
+                        instance_name: action_info.instance_name().clone(),
+                        action_digest: Some((*action_info.digest()).into()),
+                        salt: *action_info.salt(),
+                    }),
+                )
+            }
         }
     }
 
@@ -196,7 +249,7 @@ impl Worker {
 
     pub fn complete_action(&mut self, action_info: &Arc<ActionInfo>) {
         self.running_action_infos.remove(action_info);
-        self.restore_platform_properties(&action_info.platform_properties);
+        restore_platform_properties(&mut self.platform_properties, &action_info.platform_properties);
         self.is_paused = false;
         self.metrics.actions_completed.inc();
     }
@@ -205,15 +258,20 @@ impl Worker {
         !self.running_action_infos.is_empty()
     }
 
-    fn restore_platform_properties(&mut self, props: &PlatformProperties) {
-        for (property, prop_value) in &props.properties {
-            if let PlatformPropertyValue::Minimum(value) = prop_value {
-                let worker_props = &mut self.platform_properties.properties;
-                if let PlatformPropertyValue::Minimum(worker_value) = worker_props.get_mut(property).unwrap() {
-                    *worker_value += value;
-                }
-            }
-        }
+    pub const fn is_quarantined(&self) -> bool {
+        self.quarantined_until.is_some()
+    }
+
+    pub const fn is_draining(&self) -> bool {
+        self.draining_until.is_some()
+    }
+
+    /// Returns whether an action with the given platform properties could run on this worker
+    /// if `running_action_info` was no longer running on it.
+    pub fn can_run_after_releasing(&self, props: &PlatformProperties, running_action_info: &ActionInfo) -> bool {
+        let mut platform_properties = self.platform_properties.clone();
+        restore_platform_properties(&mut platform_properties, &running_action_info.platform_properties);
+        props.is_satisfied_by(&platform_properties)
     }
 }
 
@@ -238,6 +296,7 @@ struct Metrics {
     run_action: FuncCounterWrapper,
     keep_alive: FuncCounterWrapper,
     notify_disconnect: CounterWithTime,
+    kill_action: CounterWithTime,
 }
 
 impl MetricsComponent for Worker {
@@ -272,6 +331,12 @@ impl MetricsComponent for Worker {
             "The number of notify_disconnect sent to this worker.",
             vec![("worker_id".into(), format!("{}", self.id).into())],
         );
+        c.publish_with_labels(
+            "kill_action",
+            &self.metrics.kill_action,
+            "The number of kill_action requests sent to this worker.",
+            vec![("worker_id".into(), format!("{}", self.id).into())],
+        );
 
         // Publish info about current state of worker.
         c.publish_with_labels(
@@ -280,6 +345,18 @@ impl MetricsComponent for Worker {
             "If this worker is paused.",
             vec![("worker_id".into(), format!("{}", self.id).into())],
         );
+        c.publish_with_labels(
+            "is_quarantined",
+            &self.is_quarantined(),
+            "If this worker is quarantined because of too many internal errors.",
+            vec![("worker_id".into(), format!("{}", self.id).into())],
+        );
+        c.publish_with_labels(
+            "is_draining",
+            &self.is_draining(),
+            "If this worker is going away and only finishing its running actions.",
+            vec![("worker_id".into(), format!("{}", self.id).into())],
+        );
         for action_info in self.running_action_infos.iter() {
             let action_name = action_info.unique_qualifier.action_name().to_string();
             c.publish_with_labels(
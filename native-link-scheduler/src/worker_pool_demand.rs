@@ -0,0 +1,48 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use proto::com::github::trace_machina::native_link::remote_execution::PlatformPropertiesDemand as ProtoPlatformPropertiesDemand;
+use serde::Serialize;
+
+/// How much work a scheduler has for the workers that satisfy a set of platform
+/// properties. Used by external autoscalers to size pools of workers.
+/// Serialized as a single JSON object.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlatformPropertiesDemand {
+    /// The platform properties requested by the actions.
+    pub platform_properties: BTreeMap<String, String>,
+    /// Number of actions waiting for a worker.
+    pub queued_actions: u64,
+    /// Number of actions running on a worker.
+    pub executing_actions: u64,
+    /// Number of workers that satisfy the platform properties and are not running
+    /// any action.
+    pub idle_workers: u64,
+    /// Number of queued actions that can not be sent to an idle worker.
+    pub recommended_additional_workers: u64,
+}
+
+impl From<PlatformPropertiesDemand> for ProtoPlatformPropertiesDemand {
+    fn from(demand: PlatformPropertiesDemand) -> Self {
+        Self {
+            platform_properties: demand.platform_properties.into_iter().collect(),
+            queued_actions: demand.queued_actions,
+            executing_actions: demand.executing_actions,
+            idle_workers: demand.idle_workers,
+            recommended_additional_workers: demand.recommended_additional_workers,
+        }
+    }
+}
@@ -17,10 +17,14 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use error::Error;
 use native_link_util::action_messages::{ActionInfoHashKey, ActionStage};
+use native_link_util::common::DigestInfo;
 use native_link_util::metrics_utils::Registry;
+use tokio::sync::broadcast;
 
 use crate::platform_property_manager::PlatformPropertyManager;
+use crate::scheduler_events::SchedulerEvent;
 use crate::worker::{Worker, WorkerId, WorkerTimestamp};
+use crate::worker_pool_demand::PlatformPropertiesDemand;
 
 /// WorkerScheduler interface is responsible for interactions between the scheduler
 /// and worker related operations.
@@ -52,13 +56,38 @@ pub trait WorkerScheduler: Sync + Send + Unpin {
     /// Event for when the keep alive message was received from the worker.
     async fn worker_keep_alive_received(&self, worker_id: &WorkerId, timestamp: WorkerTimestamp) -> Result<(), Error>;
 
+    /// Replaces the set of input root digests the worker reports as recently
+    /// materialized. Used to prefer workers that likely have the inputs cached.
+    async fn update_worker_recent_input_roots(
+        &self,
+        worker_id: &WorkerId,
+        recent_input_root_digests: Vec<DigestInfo>,
+    ) -> Result<(), Error>;
+
     /// Removes worker from pool and reschedule any tasks that might be running on it.
     async fn remove_worker(&self, worker_id: WorkerId);
 
-    /// Removes timed out workers from the pool. This is called periodically by an
-    /// external source.
+    /// Stops assigning new actions to the worker, but lets it finish and report on the
+    /// actions it is running. The worker is removed from the pool once it is idle or
+    /// its drain timeout expired.
+    async fn drain_worker(&self, worker_id: WorkerId, now_timestamp: WorkerTimestamp) -> Result<(), Error>;
+
+    /// Removes timed out workers and workers that did not finish draining in time from
+    /// the pool and re-admits quarantined workers whose quarantine expired. This is called periodically by an external source.
     async fn remove_timedout_workers(&self, now_timestamp: WorkerTimestamp) -> Result<(), Error>;
 
+    /// Subscribes to the events of the scheduler, or returns None if the scheduler
+    /// does not emit events.
+    fn subscribe_to_events(&self) -> Option<broadcast::Receiver<Arc<SchedulerEvent>>> {
+        None
+    }
+
+    /// Returns the current demand per distinct set of platform properties of queued
+    /// or executing actions, or None if the scheduler does not track it.
+    fn worker_pool_demand(&self) -> Option<Vec<PlatformPropertiesDemand>> {
+        None
+    }
+
     /// Register the metrics for the worker scheduler.
     fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {}
 }
@@ -18,12 +18,13 @@ use std::time::{Duration, SystemTime};
 
 use error::Error;
 use native_link_util::action_messages::{
-    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionMetadata,
+    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionAttempt, ExecutionMetadata,
+    OutputTruncation, QueuePosition, ResourceUsage,
 };
 use native_link_util::common::DigestInfo;
 use native_link_util::digest_hasher::DigestHasherFunc;
 use native_link_util::platform_properties::PlatformProperties;
-use proto::build::bazel::remote::execution::v2::ExecuteResponse;
+use proto::build::bazel::remote::execution::v2::{ExecuteResponse, ExecutedActionMetadata};
 use proto::google::longrunning::{operation, Operation};
 use proto::google::rpc::Status;
 
@@ -51,6 +52,7 @@ mod action_messages_tests {
             },
             // Result is only populated if has_action_result.
             stage: ActionStage::Completed(ActionResult::default()),
+            queue_position: None,
         };
         let operation: Operation = action_state.clone().into();
 
@@ -68,6 +70,29 @@ mod action_messages_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn queue_position_round_trips_through_operation_test() -> Result<(), Error> {
+        let action_state = ActionState {
+            unique_qualifier: ActionInfoHashKey {
+                instance_name: "foo_instance".to_string(),
+                digest: DigestInfo::new([1u8; 32], 5),
+                salt: 0,
+            },
+            stage: ActionStage::Queued,
+            queue_position: Some(QueuePosition {
+                position: 3,
+                queue_length: 7,
+                estimated_start_timestamp: Some(make_system_time(60)),
+            }),
+        };
+        let operation: Operation = action_state.clone().into();
+
+        let action_state_round_trip: ActionState = operation.try_into()?;
+        assert_eq!(action_state, action_state_round_trip);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn execute_response_status_message_is_some_on_success_test() -> Result<(), Error> {
         let execute_response: ExecuteResponse = ActionStage::Completed(ActionResult {
@@ -89,10 +114,14 @@ mod action_messages_tests {
                 execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         })
         .into();
 
@@ -102,6 +131,71 @@ mod action_messages_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn previous_attempts_round_trip_through_auxiliary_metadata_test() -> Result<(), Error> {
+        let execution_metadata = ExecutionMetadata {
+            worker: "foo_worker_id".to_string(),
+            previous_attempts: vec![ExecutionAttempt {
+                worker: "bar_worker_id".to_string(),
+                worker_start_timestamp: make_system_time(1),
+                worker_completed_timestamp: make_system_time(2),
+                exit_code: 1,
+                stdout_digest: DigestInfo::new([2u8; 32], 5),
+                stderr_digest: DigestInfo::new([3u8; 32], 5),
+                flaky_failure_reason: "Exit code 1 is configured as flaky".to_string(),
+            }],
+            ..Default::default()
+        };
+        let proto_metadata: ExecutedActionMetadata = execution_metadata.clone().into();
+        assert_eq!(proto_metadata.auxiliary_metadata.len(), 1);
+
+        let execution_metadata_round_trip: ExecutionMetadata = proto_metadata.try_into()?;
+        assert_eq!(execution_metadata, execution_metadata_round_trip);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resource_usage_round_trips_through_auxiliary_metadata_test() -> Result<(), Error> {
+        let execution_metadata = ExecutionMetadata {
+            worker: "foo_worker_id".to_string(),
+            resource_usage: Some(ResourceUsage {
+                peak_memory_bytes: 1024,
+                user_cpu_time: Duration::from_millis(1500),
+                system_cpu_time: Duration::from_micros(250),
+                oom_kills: 1,
+            }),
+            ..Default::default()
+        };
+        let proto_metadata: ExecutedActionMetadata = execution_metadata.clone().into();
+        assert_eq!(proto_metadata.auxiliary_metadata.len(), 1);
+
+        let execution_metadata_round_trip: ExecutionMetadata = proto_metadata.try_into()?;
+        assert_eq!(execution_metadata, execution_metadata_round_trip);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_truncations_round_trip_through_auxiliary_metadata_test() -> Result<(), Error> {
+        let execution_metadata = ExecutionMetadata {
+            worker: "foo_worker_id".to_string(),
+            output_truncations: vec![OutputTruncation {
+                stream_name: "stderr".to_string(),
+                kept_bytes: 4,
+                total_bytes: 100,
+            }],
+            ..Default::default()
+        };
+        let proto_metadata: ExecutedActionMetadata = execution_metadata.clone().into();
+        assert_eq!(proto_metadata.auxiliary_metadata.len(), 1);
+
+        let execution_metadata_round_trip: ExecutionMetadata = proto_metadata.try_into()?;
+        assert_eq!(execution_metadata, execution_metadata_round_trip);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn highest_priority_action_first() -> Result<(), Error> {
         const INSTANCE_NAME: &str = "foobar_instance_name";
@@ -123,6 +217,7 @@ mod action_messages_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         });
         let lowest_priority_action = Arc::new(ActionInfo {
             command_digest: DigestInfo::new([0u8; 32], 0),
@@ -141,6 +236,7 @@ mod action_messages_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         });
         let mut action_set = BTreeSet::<Arc<ActionInfo>>::new();
         action_set.insert(lowest_priority_action.clone());
@@ -175,6 +271,7 @@ mod action_messages_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         });
         let current_action = Arc::new(ActionInfo {
             command_digest: DigestInfo::new([0u8; 32], 0),
@@ -193,6 +290,7 @@ mod action_messages_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         });
         let mut action_set = BTreeSet::<Arc<ActionInfo>>::new();
         action_set.insert(current_action.clone());
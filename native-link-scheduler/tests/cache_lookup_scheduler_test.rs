@@ -123,6 +123,7 @@ mod cache_lookup_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let _ = join!(
             context.cache_scheduler.add_action(action_info),
@@ -143,6 +144,7 @@ mod cache_lookup_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let mut skip_cache_action = action_info.clone();
         skip_cache_action.skip_cache_lookup = true;
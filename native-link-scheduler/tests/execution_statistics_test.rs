@@ -0,0 +1,231 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+mod utils {
+    pub(crate) mod scheduler_utils;
+}
+
+use error::Error;
+use native_link_config::schedulers::{ExecutionOrdering, ExecutionStatisticsConfig, ExecutionStatisticsKey};
+use native_link_scheduler::execution_statistics::ExecutionStatistics;
+use native_link_store::ac_utils::get_and_decode_digest;
+use native_link_store::memory_store::MemoryStore;
+use native_link_util::action_messages::{ActionResult, ExecutionMetadata};
+use native_link_util::common::DigestInfo;
+use native_link_util::store_trait::Store;
+use utils::scheduler_utils::make_base_action_info;
+
+fn make_action_result(execution_duration_s: u64) -> ActionResult {
+    ActionResult {
+        execution_metadata: ExecutionMetadata {
+            worker_start_timestamp: UNIX_EPOCH + Duration::from_secs(1),
+            worker_completed_timestamp: UNIX_EPOCH + Duration::from_secs(1 + execution_duration_s),
+            ..ExecutionMetadata::default()
+        },
+        ..ActionResult::default()
+    }
+}
+
+#[cfg(test)]
+mod execution_statistics_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn records_moving_average_of_durations() -> Result<(), Error> {
+        let execution_statistics = ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, None);
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        assert_eq!(execution_statistics.expected_duration(&action_info).await, None);
+
+        execution_statistics.record_execution(&action_info, &make_action_result(100));
+        assert_eq!(
+            execution_statistics.expected_duration(&action_info).await,
+            Some(Duration::from_secs(100))
+        );
+
+        // New durations only move the estimate part of the way.
+        execution_statistics.record_execution(&action_info, &make_action_result(20));
+        assert_eq!(
+            execution_statistics.expected_duration(&action_info).await,
+            Some(Duration::from_secs(80))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_executions_are_not_recorded() -> Result<(), Error> {
+        let execution_statistics = ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, None);
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        execution_statistics.record_execution(
+            &action_info,
+            &ActionResult {
+                error: Some(error::make_err!(error::Code::Internal, "Some error")),
+                ..make_action_result(100)
+            },
+        );
+        assert_eq!(execution_statistics.expected_duration(&action_info).await, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mnemonic_key_shares_duration_between_commands() -> Result<(), Error> {
+        let execution_statistics = ExecutionStatistics::new(
+            &ExecutionStatisticsConfig {
+                key: ExecutionStatisticsKey::mnemonic,
+                ..Default::default()
+            },
+            0,
+            None,
+        );
+        let mut first_action_info = make_base_action_info(UNIX_EPOCH);
+        first_action_info.action_mnemonic = Some("CppCompile".to_string());
+        let mut second_action_info = make_base_action_info(UNIX_EPOCH);
+        second_action_info.action_mnemonic = Some("CppCompile".to_string());
+        second_action_info.command_digest = DigestInfo::new([1u8; 32], 100);
+        let mut no_mnemonic_action_info = make_base_action_info(UNIX_EPOCH);
+        no_mnemonic_action_info.command_digest = DigestInfo::new([1u8; 32], 100);
+
+        execution_statistics.record_execution(&first_action_info, &make_action_result(10));
+        assert_eq!(
+            execution_statistics.expected_duration(&second_action_info).await,
+            Some(Duration::from_secs(10))
+        );
+        // Actions without a mnemonic fall back to their command digest.
+        assert_eq!(
+            execution_statistics.expected_duration(&no_mnemonic_action_info).await,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_most_recent_durations() -> Result<(), Error> {
+        let execution_statistics = ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 2, None);
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        assert_eq!(
+            execution_statistics.recent_durations(&action_info),
+            Vec::<Duration>::new()
+        );
+
+        for execution_duration_s in [1, 2, 3] {
+            execution_statistics.record_execution(&action_info, &make_action_result(execution_duration_s));
+        }
+        assert_eq!(
+            execution_statistics.recent_durations(&action_info),
+            vec![Duration::from_secs(2), Duration::from_secs(3)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn durations_are_persisted_in_store() -> Result<(), Error> {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new(&native_link_config::stores::MemoryStore::default()));
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        {
+            let execution_statistics =
+                ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, Some(store.clone()));
+            execution_statistics.record_execution(&action_info, &make_action_result(42));
+        }
+
+        // A new instance, eg: after a restart, loads the duration from the store.
+        let execution_statistics =
+            ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, Some(store.clone()));
+        let mut expected_duration = None;
+        for _ in 0..100 {
+            // The duration is persisted in the background.
+            expected_duration = execution_statistics.expected_duration(&action_info).await;
+            if expected_duration.is_some() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(expected_duration, Some(Duration::from_secs(42)));
+        // Durations of a command are stored under the digest of the command.
+        assert!(Pin::new(store.as_ref())
+            .has(action_info.command_digest)
+            .await?
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persisted_duration_is_blended_with_new_durations() -> Result<(), Error> {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new(&native_link_config::stores::MemoryStore::default()));
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        let load_stored_duration = || async {
+            get_and_decode_digest::<prost_types::Duration>(Pin::new(store.as_ref()), &action_info.command_digest)
+                .await
+                .ok()
+                .and_then(|duration| Duration::try_from(duration).ok())
+        };
+        {
+            let execution_statistics =
+                ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, Some(store.clone()));
+            execution_statistics.record_execution(&action_info, &make_action_result(100));
+        }
+        for _ in 0..100 {
+            // The duration is persisted in the background.
+            if load_stored_duration().await.is_some() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(load_stored_duration().await, Some(Duration::from_secs(100)));
+
+        // A new instance that records an execution before loading the duration does
+        // not replace the persisted history with the single execution.
+        let execution_statistics =
+            ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, Some(store.clone()));
+        execution_statistics.record_execution(&action_info, &make_action_result(20));
+        for _ in 0..100 {
+            if load_stored_duration().await == Some(Duration::from_secs(80)) {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(load_stored_duration().await, Some(Duration::from_secs(80)));
+        assert_eq!(
+            execution_statistics.expected_duration(&action_info).await,
+            Some(Duration::from_secs(80))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sort_key_follows_ordering() -> Result<(), Error> {
+        let shortest_job_first = ExecutionStatistics::new(&ExecutionStatisticsConfig::default(), 0, None);
+        let critical_path_first = ExecutionStatistics::new(
+            &ExecutionStatisticsConfig {
+                ordering: ExecutionOrdering::critical_path_first,
+                ..Default::default()
+            },
+            0,
+            None,
+        );
+        let short = Some(Duration::from_secs(1));
+        let long = Some(Duration::from_secs(100));
+
+        assert!(shortest_job_first.sort_key(short) < shortest_job_first.sort_key(long));
+        assert!(critical_path_first.sort_key(long) < critical_path_first.sort_key(short));
+        // Actions without recorded executions always go first.
+        assert!(shortest_job_first.sort_key(None) < shortest_job_first.sort_key(short));
+        assert!(critical_path_first.sort_key(None) < critical_path_first.sort_key(long));
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+mod utils {
+    pub(crate) mod scheduler_utils;
+}
+
+use error::Error;
+use futures::Stream;
+use native_link_config::stores::Retry;
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::grpc_scheduler::GrpcScheduler;
+use native_link_util::action_messages::{ActionInfoHashKey, ActionStage, ActionState};
+use native_link_util::common::DigestInfo;
+use parking_lot::Mutex;
+use proto::build::bazel::remote::execution::v2::execution_server::{Execution, ExecutionServer};
+use proto::build::bazel::remote::execution::v2::{ExecuteRequest, WaitExecutionRequest};
+use proto::google::longrunning::Operation;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use utils::scheduler_utils::{make_base_action_info, INSTANCE_NAME};
+
+type OperationStream = Pin<Box<dyn Stream<Item = Result<Operation, Status>> + Send>>;
+
+/// Upstream scheduler that queues every action and remembers it.
+#[derive(Default)]
+struct FakeUpstream {
+    execute_requests: AtomicUsize,
+    wait_execution_requests: AtomicUsize,
+    operations: Mutex<HashSet<String>>,
+}
+
+struct FakeExecution(Arc<FakeUpstream>);
+
+fn queued_operation(unique_qualifier: ActionInfoHashKey) -> OperationStream {
+    let operation: Operation = ActionState {
+        unique_qualifier,
+        stage: ActionStage::Queued,
+        queue_position: None,
+    }
+    .into();
+    Box::pin(futures::stream::once(async move { Ok(operation) }))
+}
+
+#[async_trait::async_trait]
+impl Execution for FakeExecution {
+    type ExecuteStream = OperationStream;
+    type WaitExecutionStream = OperationStream;
+
+    async fn execute(&self, request: Request<ExecuteRequest>) -> Result<Response<OperationStream>, Status> {
+        self.0.execute_requests.fetch_add(1, Ordering::Relaxed);
+        let request = request.into_inner();
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: request.instance_name,
+            digest: request
+                .action_digest
+                .ok_or_else(|| Status::invalid_argument("No action digest"))?
+                .try_into()?,
+            salt: 0,
+        };
+        self.0.operations.lock().insert(unique_qualifier.action_name());
+        Ok(Response::new(queued_operation(unique_qualifier)))
+    }
+
+    async fn wait_execution(
+        &self,
+        request: Request<WaitExecutionRequest>,
+    ) -> Result<Response<OperationStream>, Status> {
+        self.0.wait_execution_requests.fetch_add(1, Ordering::Relaxed);
+        let name = request.into_inner().name;
+        if !self.0.operations.lock().contains(&name) {
+            return Err(Status::not_found(format!("Unknown operation {name}")));
+        }
+        Ok(Response::new(queued_operation(name.as_str().try_into()?)))
+    }
+}
+
+/// Returns an address nothing listens on.
+fn unused_address() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn start_upstream() -> (String, Arc<FakeUpstream>) {
+    let address = unused_address();
+    let upstream = Arc::new(FakeUpstream::default());
+    tokio::spawn(
+        Server::builder()
+            .add_service(ExecutionServer::new(FakeExecution(upstream.clone())))
+            .serve(address),
+    );
+    while tokio::net::TcpStream::connect(address).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    (format!("http://{address}"), upstream)
+}
+
+fn make_grpc_scheduler(endpoints: Vec<String>) -> Result<GrpcScheduler, Error> {
+    GrpcScheduler::new_with_jitter(
+        &native_link_config::schedulers::GrpcScheduler {
+            endpoints,
+            retry: Retry {
+                max_retries: 3,
+                delay: 0.,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Box::new(|delay| delay),
+    )
+}
+
+fn make_hash_key(hash: u8) -> ActionInfoHashKey {
+    ActionInfoHashKey {
+        instance_name: INSTANCE_NAME.to_string(),
+        digest: DigestInfo::new([hash; 32], 512),
+        salt: 0,
+    }
+}
+
+async fn add_action(scheduler: &GrpcScheduler, hash: u8) -> Result<(), Error> {
+    let mut action_info = make_base_action_info(UNIX_EPOCH);
+    action_info.unique_qualifier = make_hash_key(hash);
+    scheduler.add_action(action_info).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod grpc_scheduler_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn add_action_balances_across_endpoints() -> Result<(), Error> {
+        let (endpoint1, upstream1) = start_upstream().await;
+        let (endpoint2, upstream2) = start_upstream().await;
+        let scheduler = make_grpc_scheduler(vec![endpoint1, endpoint2])?;
+
+        add_action(&scheduler, 1).await?;
+        add_action(&scheduler, 2).await?;
+
+        assert_eq!(upstream1.execute_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(upstream2.execute_requests.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_fails_over_to_available_endpoint() -> Result<(), Error> {
+        let (endpoint, upstream) = start_upstream().await;
+        let unavailable_endpoint = format!("http://{}", unused_address());
+        let scheduler = make_grpc_scheduler(vec![unavailable_endpoint, endpoint])?;
+
+        // The first attempt goes to the unavailable endpoint, which is then avoided.
+        add_action(&scheduler, 1).await?;
+        add_action(&scheduler, 2).await?;
+        add_action(&scheduler, 3).await?;
+
+        assert_eq!(upstream.execute_requests.load(Ordering::Relaxed), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn find_existing_action_asks_owning_endpoint_only() -> Result<(), Error> {
+        let (endpoint1, upstream1) = start_upstream().await;
+        let (endpoint2, upstream2) = start_upstream().await;
+        let scheduler = make_grpc_scheduler(vec![endpoint1, endpoint2])?;
+
+        add_action(&scheduler, 1).await?;
+        add_action(&scheduler, 2).await?;
+        for hash in [1, 2] {
+            let rx = scheduler.find_existing_action(&make_hash_key(hash)).await;
+            assert_eq!(rx.map(|rx| rx.borrow().stage.clone()), Some(ActionStage::Queued));
+        }
+
+        assert_eq!(upstream1.wait_execution_requests.load(Ordering::Relaxed), 1);
+        assert_eq!(upstream2.wait_execution_requests.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn find_existing_action_of_unknown_owner_does_not_retry_not_found() -> Result<(), Error> {
+        let (endpoint1, upstream1) = start_upstream().await;
+        let (endpoint2, upstream2) = start_upstream().await;
+        let scheduler = make_grpc_scheduler(vec![endpoint1.clone(), endpoint2.clone()])?;
+        add_action(&scheduler, 1).await?;
+
+        // A scheduler that did not send the action has to ask every upstream.
+        let other_scheduler = make_grpc_scheduler(vec![endpoint1, endpoint2])?;
+        let rx = other_scheduler.find_existing_action(&make_hash_key(1)).await;
+        assert_eq!(rx.map(|rx| rx.borrow().stage.clone()), Some(ActionStage::Queued));
+        assert!(other_scheduler.find_existing_action(&make_hash_key(2)).await.is_none());
+
+        // The action was sent to the first upstream, which is asked first. The unknown
+        // action is asked for once per upstream.
+        assert_eq!(upstream1.wait_execution_requests.load(Ordering::Relaxed), 2);
+        assert_eq!(upstream2.wait_execution_requests.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+}
@@ -24,7 +24,10 @@ mod utils {
 
 use error::Error;
 use futures::join;
-use native_link_config::schedulers::{PlatformPropertyAddition, PropertyModification, PropertyType};
+use native_link_config::schedulers::{
+    ConditionalPropertyModification, PlatformPropertyAddition, PlatformPropertyCondition, PlatformPropertyRewrite,
+    PropertyModification, PropertyType,
+};
 use native_link_scheduler::action_scheduler::ActionScheduler;
 use native_link_scheduler::platform_property_manager::PlatformPropertyManager;
 use native_link_scheduler::property_modifier_scheduler::PropertyModifierScheduler;
@@ -44,11 +47,9 @@ fn make_modifier_scheduler(modifications: Vec<PropertyModification>) -> TestCont
     let mock_scheduler = Arc::new(MockActionScheduler::new());
     let config = native_link_config::schedulers::PropertyModifierScheduler {
         modifications,
-        scheduler: Box::new(native_link_config::schedulers::SchedulerConfig::simple(
-            native_link_config::schedulers::SimpleScheduler::default(),
-        )),
+        scheduler: Box::new(native_link_config::schedulers::SchedulerConfig::simple(Box::default())),
     };
-    let modifier_scheduler = PropertyModifierScheduler::new(&config, mock_scheduler.clone());
+    let modifier_scheduler = PropertyModifierScheduler::new(&config, mock_scheduler.clone()).unwrap();
     TestContext {
         mock_scheduler,
         modifier_scheduler,
@@ -73,6 +74,7 @@ mod property_modifier_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
             name.clone(),
@@ -109,6 +111,7 @@ mod property_modifier_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
             name.clone(),
@@ -143,6 +146,7 @@ mod property_modifier_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
             name.clone(),
@@ -177,6 +181,7 @@ mod property_modifier_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
             name,
@@ -206,6 +211,7 @@ mod property_modifier_scheduler_tests {
         let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
             unique_qualifier: action_info.unique_qualifier.clone(),
             stage: ActionStage::Queued,
+            queue_position: None,
         }));
         let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::new()));
         let (_, _, action_info) = join!(
@@ -219,6 +225,185 @@ mod property_modifier_scheduler_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn add_action_add_if_absent_keeps_existing_property() -> Result<(), Error> {
+        let name = "name".to_string();
+        let original_value = "value".to_string();
+        let context = make_modifier_scheduler(vec![PropertyModification::AddIfAbsent(PlatformPropertyAddition {
+            name: name.clone(),
+            value: "default".to_string(),
+        })]);
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info
+            .platform_properties
+            .properties
+            .insert(name.clone(), PlatformPropertyValue::Exact(original_value.clone()));
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Queued,
+            queue_position: None,
+        }));
+        let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            name.clone(),
+            PropertyType::Exact,
+        )])));
+        let (_, _, action_info) = join!(
+            context.modifier_scheduler.add_action(action_info),
+            context
+                .mock_scheduler
+                .expect_get_platform_property_manager(Ok(platform_property_manager)),
+            context.mock_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+        );
+        assert_eq!(
+            HashMap::from([(name, PlatformPropertyValue::Exact(original_value))]),
+            action_info.platform_properties.properties
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_rewrite_uses_capture_groups() -> Result<(), Error> {
+        let source = "container-image".to_string();
+        let source_value = "docker://foo:1.2".to_string();
+        let name = "os_family".to_string();
+        let context = make_modifier_scheduler(vec![PropertyModification::Rewrite(PlatformPropertyRewrite {
+            source: source.clone(),
+            regex: "^(docker)://".to_string(),
+            name: name.clone(),
+            value: "linux-$1".to_string(),
+        })]);
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info
+            .platform_properties
+            .properties
+            .insert(source.clone(), PlatformPropertyValue::Unknown(source_value.clone()));
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Queued,
+            queue_position: None,
+        }));
+        let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            name.clone(),
+            PropertyType::Exact,
+        )])));
+        let (_, _, action_info) = join!(
+            context.modifier_scheduler.add_action(action_info),
+            context
+                .mock_scheduler
+                .expect_get_platform_property_manager(Ok(platform_property_manager)),
+            context.mock_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+        );
+        assert_eq!(
+            HashMap::from([
+                (source, PlatformPropertyValue::Unknown(source_value)),
+                (name, PlatformPropertyValue::Exact("linux-docker".to_string())),
+            ]),
+            action_info.platform_properties.properties
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_conditional_only_applies_when_matched() -> Result<(), Error> {
+        let condition_name = "pool".to_string();
+        let name = "name".to_string();
+        let value = "value".to_string();
+        let context = make_modifier_scheduler(vec![PropertyModification::Conditional(
+            ConditionalPropertyModification {
+                condition: PlatformPropertyCondition {
+                    name: condition_name.clone(),
+                    value: Some("gpu".to_string()),
+                    regex: None,
+                },
+                modifications: vec![PropertyModification::Add(PlatformPropertyAddition {
+                    name: name.clone(),
+                    value: value.clone(),
+                })],
+            },
+        )]);
+        let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            name.clone(),
+            PropertyType::Exact,
+        )])));
+        {
+            // Condition matches, so the property is added.
+            let mut action_info = make_base_action_info(UNIX_EPOCH);
+            action_info.platform_properties.properties.insert(
+                condition_name.clone(),
+                PlatformPropertyValue::Unknown("gpu".to_string()),
+            );
+            let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+                unique_qualifier: action_info.unique_qualifier.clone(),
+                stage: ActionStage::Queued,
+                queue_position: None,
+            }));
+            let (_, _, action_info) = join!(
+                context.modifier_scheduler.add_action(action_info),
+                context
+                    .mock_scheduler
+                    .expect_get_platform_property_manager(Ok(platform_property_manager)),
+                context.mock_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+            );
+            assert_eq!(
+                HashMap::from([
+                    (
+                        condition_name.clone(),
+                        PlatformPropertyValue::Unknown("gpu".to_string())
+                    ),
+                    (name, PlatformPropertyValue::Exact(value)),
+                ]),
+                action_info.platform_properties.properties
+            );
+        }
+        {
+            // Condition does not match, the property manager is cached.
+            let mut action_info = make_base_action_info(UNIX_EPOCH);
+            action_info.platform_properties.properties.insert(
+                condition_name.clone(),
+                PlatformPropertyValue::Unknown("cpu".to_string()),
+            );
+            let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+                unique_qualifier: action_info.unique_qualifier.clone(),
+                stage: ActionStage::Queued,
+                queue_position: None,
+            }));
+            let (_, action_info) = join!(
+                context.modifier_scheduler.add_action(action_info),
+                context.mock_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+            );
+            assert_eq!(
+                HashMap::from([(condition_name, PlatformPropertyValue::Unknown("cpu".to_string()))]),
+                action_info.platform_properties.properties
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_reject_returns_invalid_argument() -> Result<(), Error> {
+        let name = "privileged".to_string();
+        let context = make_modifier_scheduler(vec![PropertyModification::Reject(PlatformPropertyCondition {
+            name: name.clone(),
+            value: None,
+            regex: Some("^(true|1)$".to_string()),
+        })]);
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info
+            .platform_properties
+            .properties
+            .insert(name, PlatformPropertyValue::Unknown("true".to_string()));
+        let platform_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::new()));
+        let (result, _) = join!(
+            context.modifier_scheduler.add_action(action_info),
+            context
+                .mock_scheduler
+                .expect_get_platform_property_manager(Ok(platform_property_manager)),
+        );
+        let err = result.expect_err("Expected action to be rejected");
+        assert_eq!(err.code, error::Code::InvalidArgument);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn find_existing_action_call_passed() -> Result<(), Error> {
         let context = make_modifier_scheduler(vec![]);
@@ -0,0 +1,294 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+mod utils {
+    pub(crate) mod mock_scheduler;
+    pub(crate) mod scheduler_utils;
+}
+
+use error::{Code, Error};
+use futures::join;
+use native_link_config::schedulers::{PropertyModifierScheduler, PropertyType, RouterRoute, SchedulerConfig};
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::default_scheduler_factory::scheduler_creation_order;
+use native_link_scheduler::platform_property_manager::PlatformPropertyManager;
+use native_link_scheduler::router_scheduler::RouterScheduler;
+use native_link_util::action_messages::{ActionStage, ActionState};
+use native_link_util::platform_properties::PlatformPropertyValue;
+use tokio::sync::watch;
+use utils::mock_scheduler::MockActionScheduler;
+use utils::scheduler_utils::{make_base_action_info, INSTANCE_NAME};
+
+const LINUX_SCHEDULER_NAME: &str = "linux";
+const REMOTE_SCHEDULER_NAME: &str = "remote";
+
+struct TestContext {
+    linux_scheduler: Arc<MockActionScheduler>,
+    remote_scheduler: Arc<MockActionScheduler>,
+    router_scheduler: RouterScheduler,
+}
+
+fn make_router_scheduler(fallback_scheduler: Option<&str>) -> Result<TestContext, Error> {
+    let linux_scheduler = Arc::new(MockActionScheduler::new());
+    let remote_scheduler = Arc::new(MockActionScheduler::new());
+    let action_schedulers: HashMap<String, Arc<dyn ActionScheduler>> = HashMap::from([
+        (
+            LINUX_SCHEDULER_NAME.to_string(),
+            linux_scheduler.clone() as Arc<dyn ActionScheduler>,
+        ),
+        (
+            REMOTE_SCHEDULER_NAME.to_string(),
+            remote_scheduler.clone() as Arc<dyn ActionScheduler>,
+        ),
+    ]);
+    let config = native_link_config::schedulers::RouterScheduler {
+        routes: vec![
+            RouterRoute {
+                instance_name: None,
+                platform_properties: HashMap::from([("OSFamily".to_string(), "macos".to_string())]),
+                scheduler: REMOTE_SCHEDULER_NAME.to_string(),
+            },
+            RouterRoute {
+                instance_name: Some(INSTANCE_NAME.to_string()),
+                platform_properties: HashMap::new(),
+                scheduler: LINUX_SCHEDULER_NAME.to_string(),
+            },
+        ],
+        fallback_scheduler: fallback_scheduler.map(str::to_string),
+    };
+    let router_scheduler = RouterScheduler::new(&config, &action_schedulers)?;
+    Ok(TestContext {
+        linux_scheduler,
+        remote_scheduler,
+        router_scheduler,
+    })
+}
+
+fn make_router_config(schedulers: &[&str]) -> SchedulerConfig {
+    SchedulerConfig::router(native_link_config::schedulers::RouterScheduler {
+        routes: schedulers
+            .iter()
+            .map(|scheduler| RouterRoute {
+                instance_name: None,
+                platform_properties: HashMap::new(),
+                scheduler: scheduler.to_string(),
+            })
+            .collect(),
+        fallback_scheduler: None,
+    })
+}
+
+#[cfg(test)]
+mod router_scheduler_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn add_action_uses_first_matching_route() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info.platform_properties.properties.insert(
+            "OSFamily".to_string(),
+            PlatformPropertyValue::Exact("macos".to_string()),
+        );
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Queued,
+            queue_position: None,
+        }));
+        let (result, forwarded_action_info) = join!(
+            context.router_scheduler.add_action(action_info.clone()),
+            context.remote_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+        );
+        assert_eq!(result?.borrow().stage, ActionStage::Queued);
+        assert_eq!(forwarded_action_info, action_info);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_matches_instance_name() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Queued,
+            queue_position: None,
+        }));
+        let (result, forwarded_action_info) = join!(
+            context.router_scheduler.add_action(action_info.clone()),
+            context.linux_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+        );
+        assert_eq!(result?.borrow().stage, ActionStage::Queued);
+        assert_eq!(forwarded_action_info, action_info);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_without_matching_route_uses_fallback() -> Result<(), Error> {
+        let context = make_router_scheduler(Some(REMOTE_SCHEDULER_NAME))?;
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info.unique_qualifier.instance_name = "other_instance_name".to_string();
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Queued,
+            queue_position: None,
+        }));
+        let (result, forwarded_action_info) = join!(
+            context.router_scheduler.add_action(action_info.clone()),
+            context.remote_scheduler.expect_add_action(Ok(forward_watch_channel_rx)),
+        );
+        assert_eq!(result?.borrow().stage, ActionStage::Queued);
+        assert_eq!(forwarded_action_info, action_info);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_action_without_matching_route_errors() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let mut action_info = make_base_action_info(UNIX_EPOCH);
+        action_info.unique_qualifier.instance_name = "other_instance_name".to_string();
+        let result = context.router_scheduler.add_action(action_info).await;
+        assert_eq!(result.err().map(|e| e.code), Some(Code::InvalidArgument));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_scheduler_name_errors() -> Result<(), Error> {
+        let result = make_router_scheduler(Some("does_not_exist"));
+        assert_eq!(result.err().map(|e| e.code), Some(Code::InvalidArgument));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_platform_property_manager_merges_properties() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let remote_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            "OSFamily".to_string(),
+            PropertyType::Exact,
+        )])));
+        let linux_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([
+            ("OSFamily".to_string(), PropertyType::Exact),
+            ("cpu_count".to_string(), PropertyType::Minimum),
+        ])));
+        let (property_manager, _, _) = join!(
+            context.router_scheduler.get_platform_property_manager(INSTANCE_NAME),
+            context
+                .remote_scheduler
+                .expect_get_platform_property_manager(Ok(remote_property_manager)),
+            context
+                .linux_scheduler
+                .expect_get_platform_property_manager(Ok(linux_property_manager)),
+        );
+        assert_eq!(
+            property_manager?.get_known_properties(),
+            &HashMap::from([
+                ("OSFamily".to_string(), PropertyType::Exact),
+                ("cpu_count".to_string(), PropertyType::Minimum),
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_platform_property_manager_with_conflicting_types_errors() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let remote_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            "cpu_count".to_string(),
+            PropertyType::Exact,
+        )])));
+        let linux_property_manager = Arc::new(PlatformPropertyManager::new(HashMap::from([(
+            "cpu_count".to_string(),
+            PropertyType::Minimum,
+        )])));
+        let (property_manager, _, _) = join!(
+            context.router_scheduler.get_platform_property_manager(INSTANCE_NAME),
+            context
+                .remote_scheduler
+                .expect_get_platform_property_manager(Ok(remote_property_manager)),
+            context
+                .linux_scheduler
+                .expect_get_platform_property_manager(Ok(linux_property_manager)),
+        );
+        assert_eq!(property_manager.err().map(|e| e.code), Some(Code::InvalidArgument));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn find_existing_action_asks_every_scheduler() -> Result<(), Error> {
+        let context = make_router_scheduler(None)?;
+        let action_info = make_base_action_info(UNIX_EPOCH);
+        let (_forward_watch_channel_tx, forward_watch_channel_rx) = watch::channel(Arc::new(ActionState {
+            unique_qualifier: action_info.unique_qualifier.clone(),
+            stage: ActionStage::Executing,
+            queue_position: None,
+        }));
+        let (result, _, _) = join!(
+            context
+                .router_scheduler
+                .find_existing_action(&action_info.unique_qualifier),
+            context.remote_scheduler.expect_find_existing_action(None),
+            context
+                .linux_scheduler
+                .expect_find_existing_action(Some(forward_watch_channel_rx)),
+        );
+        assert_eq!(result.map(|rx| rx.borrow().stage.clone()), Some(ActionStage::Executing));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn schedulers_are_created_after_nested_router_targets() -> Result<(), Error> {
+        let schedulers = HashMap::from([
+            (
+                "a_modifier".to_string(),
+                SchedulerConfig::property_modifier(PropertyModifierScheduler {
+                    modifications: Vec::new(),
+                    scheduler: Box::new(make_router_config(&["b_router"])),
+                }),
+            ),
+            ("b_router".to_string(), make_router_config(&["c_simple"])),
+            ("c_simple".to_string(), SchedulerConfig::simple(Box::default())),
+        ]);
+        assert_eq!(
+            scheduler_creation_order(&schedulers)?,
+            vec!["c_simple".to_string(), "b_router".to_string(), "a_modifier".to_string()]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cyclic_router_references_error() -> Result<(), Error> {
+        let schedulers = HashMap::from([
+            ("a".to_string(), make_router_config(&["b"])),
+            ("b".to_string(), make_router_config(&["a"])),
+        ]);
+        let err = scheduler_creation_order(&schedulers).unwrap_err();
+        assert_eq!(err.code, Code::InvalidArgument);
+        assert!(err.to_string().contains("a -> b -> a"), "{err:?}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unresolved_router_reference_errors() -> Result<(), Error> {
+        let schedulers = HashMap::from([("a".to_string(), make_router_config(&["does_not_exist"]))]);
+        let err = scheduler_creation_order(&schedulers).unwrap_err();
+        assert_eq!(err.code, Code::InvalidArgument);
+        assert!(err.to_string().contains("'does_not_exist'"), "{err:?}");
+        Ok(())
+    }
+}
@@ -19,6 +19,7 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use error::{make_err, Code, Error, ResultExt};
 use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::scheduler_events::SchedulerEventType;
 use native_link_util::action_messages::{
     ActionInfoHashKey, ActionResult, ActionStage, ActionState, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath,
     SymlinkInfo, INTERNAL_ERROR_EXIT_CODE,
@@ -27,13 +28,16 @@ use native_link_util::platform_properties::{PlatformProperties, PlatformProperty
 mod utils {
     pub(crate) mod scheduler_utils;
 }
-use native_link_scheduler::simple_scheduler::SimpleScheduler;
+use native_link_scheduler::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
 use native_link_scheduler::worker::{Worker, WorkerId};
 use native_link_scheduler::worker_scheduler::WorkerScheduler;
+use native_link_store::memory_store::MemoryStore;
 use native_link_util::common::DigestInfo;
+use native_link_util::metrics_utils::Registry;
+use native_link_util::store_trait::Store;
 use proto::build::bazel::remote::execution::v2::{digest_function, ExecuteRequest};
 use proto::com::github::trace_machina::native_link::remote_execution::{
-    update_for_worker, ConnectionResult, StartExecute, UpdateForWorker,
+    update_for_worker, ConnectionResult, KillActionRequest, StartExecute, UpdateForWorker,
 };
 use tokio::sync::{mpsc, watch};
 use utils::scheduler_utils::{make_base_action_info, INSTANCE_NAME};
@@ -138,6 +142,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Executing,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -198,6 +203,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Executing,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -247,11 +253,13 @@ mod scheduler_tests {
             // Name is a random string, so we ignore it and just make it the same.
             unique_qualifier: unique_qualifier.clone(),
             stage: ActionStage::Executing,
+            queue_position: None,
         };
         let mut expected_action_state2 = ActionState {
             // Name is a random string, so we ignore it and just make it the same.
             unique_qualifier,
             stage: ActionStage::Executing,
+            queue_position: None,
         };
 
         let execution_request_for_worker1 = UpdateForWorker {
@@ -380,6 +388,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Queued,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -410,6 +419,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Executing,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -420,6 +430,112 @@ mod scheduler_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn worker_with_recent_input_root_is_preferred_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0003);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0004);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let input_root_digest = DigestInfo::new([88u8; 32], 256);
+
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+        // Without the report, the least recently used worker (worker1) would get the action.
+        scheduler
+            .update_worker_recent_input_roots(&WORKER_ID2, vec![input_root_digest])
+            .await?;
+
+        let insert_timestamp = make_system_time(1);
+        let mut action_info = make_base_action_info(insert_timestamp);
+        action_info.unique_qualifier.digest = action_digest;
+        action_info.input_root_digest = input_root_digest;
+        let _client_rx = scheduler.add_action(action_info).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+
+        {
+            // Worker with the input root cached should have been sent an execute command.
+            let expected_msg_for_worker = UpdateForWorker {
+                update: Some(update_for_worker::Update::StartAction(StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        instance_name: INSTANCE_NAME.to_string(),
+                        skip_cache_lookup: true,
+                        action_digest: Some(action_digest.into()),
+                        digest_function: digest_function::Value::Sha256.into(),
+                        ..Default::default()
+                    }),
+                    salt: 0,
+                    queued_timestamp: Some(insert_timestamp.into()),
+                })),
+            };
+            let msg_for_worker = rx_from_worker2.recv().await.unwrap();
+            assert_eq!(msg_for_worker, expected_msg_for_worker);
+        }
+
+        // Our first worker should have no updates over this test.
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn worker_with_replaced_input_roots_is_not_preferred_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0006);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0007);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let input_root_digest = DigestInfo::new([88u8; 32], 256);
+
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+        scheduler
+            .update_worker_recent_input_roots(&WORKER_ID2, vec![input_root_digest])
+            .await?;
+        // The second report replaces the first one, so worker2 no longer has the input root.
+        scheduler
+            .update_worker_recent_input_roots(&WORKER_ID2, vec![DigestInfo::new([87u8; 32], 256)])
+            .await?;
+
+        let mut action_info = make_base_action_info(make_system_time(1));
+        action_info.unique_qualifier.digest = action_digest;
+        action_info.input_root_digest = input_root_digest;
+        let _client_rx = scheduler.add_action(action_info).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+
+        // The least recently used worker should get the action.
+        let msg_for_worker = rx_from_worker1.recv().await.unwrap();
+        assert!(matches!(
+            msg_for_worker.update,
+            Some(update_for_worker::Update::StartAction(_))
+        ));
+        assert_eq!(rx_from_worker2.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_recent_input_roots_for_unknown_worker_errors_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0005);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let result = scheduler
+            .update_worker_recent_input_roots(&WORKER_ID, vec![DigestInfo::new([88u8; 32], 256)])
+            .await;
+        assert_eq!(result.map_err(|e| e.code), Err(Code::InvalidArgument));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn cacheable_items_join_same_action_queued_test() -> Result<(), Error> {
         const WORKER_ID: WorkerId = WorkerId(0x0010_0009);
@@ -437,6 +553,7 @@ mod scheduler_tests {
                 salt: 0,
             }, // Will be filled later.
             stage: ActionStage::Queued,
+            queue_position: None,
         };
 
         let insert_timestamp1 = make_system_time(1);
@@ -541,6 +658,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Queued,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -583,6 +701,7 @@ mod scheduler_tests {
                 salt: 0,
             },
             stage: ActionStage::Executing,
+            queue_position: None,
         };
 
         let execution_request_for_worker = UpdateForWorker {
@@ -686,6 +805,7 @@ mod scheduler_tests {
                 name_or_path: NameOrPath::Name("hello".to_string()),
                 digest: DigestInfo::new([5u8; 32], 18),
                 is_executable: true,
+                node_properties: None,
             }],
             output_folders: vec![DirectoryInfo {
                 path: "123".to_string(),
@@ -713,10 +833,14 @@ mod scheduler_tests {
                 execution_completed_timestamp: make_system_time(11),
                 output_upload_start_timestamp: make_system_time(12),
                 output_upload_completed_timestamp: make_system_time(13),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         scheduler
             .update_action(
@@ -733,6 +857,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Completed(action_result),
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -787,6 +912,7 @@ mod scheduler_tests {
                 name_or_path: NameOrPath::Name("hello".to_string()),
                 digest: DigestInfo::new([5u8; 32], 18),
                 is_executable: true,
+                node_properties: None,
             }],
             output_folders: vec![DirectoryInfo {
                 path: "123".to_string(),
@@ -814,10 +940,14 @@ mod scheduler_tests {
                 execution_completed_timestamp: make_system_time(11),
                 output_upload_start_timestamp: make_system_time(12),
                 output_upload_completed_timestamp: make_system_time(13),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         scheduler
             .update_action(
@@ -839,6 +969,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Completed(action_result),
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -901,10 +1032,14 @@ mod scheduler_tests {
                 execution_completed_timestamp: make_system_time(11),
                 output_upload_start_timestamp: make_system_time(12),
                 output_upload_completed_timestamp: make_system_time(13),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         let update_action_result = scheduler
             .update_action(
@@ -957,6 +1092,7 @@ mod scheduler_tests {
                 salt: 0,
             }, // Will be filled later.
             stage: ActionStage::Executing,
+            queue_position: None,
         };
 
         let insert_timestamp = make_system_time(1);
@@ -1015,10 +1151,14 @@ mod scheduler_tests {
                 execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
 
         scheduler
@@ -1120,10 +1260,14 @@ mod scheduler_tests {
                 execution_completed_timestamp: make_system_time(11),
                 output_upload_start_timestamp: make_system_time(12),
                 output_upload_completed_timestamp: make_system_time(13),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
 
         // Tell scheduler our first task is completed.
@@ -1152,6 +1296,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Completed(action_result.clone()),
+                queue_position: None,
             };
             // We now know the name of the action so populate it.
             expected_action_state.unique_qualifier = action_state.unique_qualifier.clone();
@@ -1191,6 +1336,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Completed(action_result.clone()),
+                queue_position: None,
             };
             // We now know the name of the action so populate it.
             expected_action_state.unique_qualifier = action_state.unique_qualifier.clone();
@@ -1305,6 +1451,7 @@ mod scheduler_tests {
                 // Name is a random string, so we ignore it and just make it the same.
                 unique_qualifier: action_state.unique_qualifier.clone(),
                 stage: ActionStage::Queued,
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -1352,6 +1499,9 @@ mod scheduler_tests {
                         execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                         output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                         output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                        previous_attempts: Vec::new(),
+                        resource_usage: None,
+                        output_truncations: Vec::new(),
                     },
                     server_logs: HashMap::default(),
                     error: Some(err.merge(make_err!(
@@ -1359,7 +1509,9 @@ mod scheduler_tests {
                         "Job cancelled because it attempted to execute too many times and failed"
                     ))),
                     message: String::new(),
+                    flaky_failure_reason: None,
                 }),
+                queue_position: None,
             };
             assert_eq!(action_state.as_ref(), &expected_action_state);
         }
@@ -1404,4 +1556,1513 @@ mod scheduler_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn straggling_action_is_hedged_on_another_worker_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0101);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0102);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                hedging: Some(native_link_config::schedulers::HedgingConfig {
+                    duration_percentile: 100.,
+                    duration_multiplier: 2.,
+                    min_samples: 1,
+                    max_samples: 10,
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+
+        let make_action_result = |worker_id: WorkerId, execution_duration_s: u64| ActionResult {
+            execution_metadata: ExecutionMetadata {
+                worker: worker_id.to_string(),
+                worker_start_timestamp: make_system_time(0),
+                worker_completed_timestamp: make_system_time(execution_duration_s),
+                ..ExecutionMetadata::default()
+            },
+            ..ActionResult::default()
+        };
+
+        // Run an action to completion so the scheduler learns how long its command takes.
+        let first_action_digest = DigestInfo::new([98u8; 32], 512);
+        let _first_client_rx = setup_action(
+            &scheduler,
+            first_action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let first_action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: first_action_digest,
+            salt: 0,
+        };
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &first_action_info_hash_key,
+                ActionStage::Completed(make_action_result(WORKER_ID1, 10)),
+            )
+            .await?;
+
+        // The next action with the same command goes to the least recently used worker.
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // Not running for long enough yet, so it should not be hedged.
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(15));
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        // Running for more than twice as long as usual, so it should be hedged on the other worker.
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(25));
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        // Only one hedge is made per action.
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(50));
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        // The hedged execution completes first.
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+        let action_result = make_action_result(WORKER_ID1, 5);
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &action_info_hash_key,
+                ActionStage::Completed(action_result.clone()),
+            )
+            .await?;
+        assert_eq!(
+            client_rx.borrow_and_update().stage,
+            ActionStage::Completed(action_result.clone())
+        );
+
+        {
+            // The straggling execution should be killed.
+            let expected_msg_for_worker = UpdateForWorker {
+                update: Some(update_for_worker::Update::KillActionRequest(KillActionRequest {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    action_digest: Some(action_digest.into()),
+                    salt: 0,
+                })),
+            };
+            let msg_for_worker = rx_from_worker2.recv().await.unwrap();
+            assert_eq!(msg_for_worker, expected_msg_for_worker);
+        }
+
+        // The killed execution reporting back is not an error and does not change the result.
+        scheduler
+            .update_action_with_internal_error(
+                &WORKER_ID2,
+                &action_info_hash_key,
+                make_err!(Code::Aborted, "Action was killed"),
+            )
+            .await;
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID2));
+        assert_eq!(client_rx.borrow().stage, ActionStage::Completed(action_result));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn killed_hedge_is_released_when_worker_does_not_answer_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0105);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0106);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                hedging: Some(native_link_config::schedulers::HedgingConfig {
+                    duration_percentile: 100.,
+                    duration_multiplier: 2.,
+                    min_samples: 1,
+                    max_samples: 10,
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+
+        let make_action_result = |worker_id: WorkerId, execution_duration_s: u64| ActionResult {
+            execution_metadata: ExecutionMetadata {
+                worker: worker_id.to_string(),
+                worker_start_timestamp: make_system_time(0),
+                worker_completed_timestamp: make_system_time(execution_duration_s),
+                ..ExecutionMetadata::default()
+            },
+            ..ActionResult::default()
+        };
+
+        // Run an action to completion so the scheduler learns how long its command takes.
+        let first_action_digest = DigestInfo::new([98u8; 32], 512);
+        let _first_client_rx = setup_action(
+            &scheduler,
+            first_action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: first_action_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(make_action_result(WORKER_ID1, 10)),
+            )
+            .await?;
+
+        // The next action straggles on worker2 and is hedged on worker1, which completes it.
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let _client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(25));
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: action_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(make_action_result(WORKER_ID1, 5)),
+            )
+            .await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::KillActionRequest(_)) => { /* Success */ }
+            v => panic!("Expected KillActionRequest, got : {v:?}"),
+        }
+
+        // Worker2 never reports back, so a draining worker2 is kept for the killed execution.
+        scheduler.drain_worker(WORKER_ID2, NOW_TIME).await?;
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(30));
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID2));
+
+        // Once the worker had long enough to answer, the killed execution is released.
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(120));
+        assert!(!scheduler.contains_worker_for_test(&WORKER_ID2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hedged_action_survives_internal_error_of_one_execution_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0103);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0104);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                hedging: Some(native_link_config::schedulers::HedgingConfig {
+                    duration_percentile: 100.,
+                    duration_multiplier: 1.,
+                    min_samples: 1,
+                    max_samples: 10,
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+
+        let first_action_digest = DigestInfo::new([98u8; 32], 512);
+        let _first_client_rx = setup_action(
+            &scheduler,
+            first_action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: first_action_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(ActionResult {
+                    execution_metadata: ExecutionMetadata {
+                        worker_start_timestamp: make_system_time(0),
+                        worker_completed_timestamp: make_system_time(10),
+                        ..ExecutionMetadata::default()
+                    },
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler.hedge_straggling_actions_for_test(SystemTime::now() + Duration::from_secs(20));
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // The original execution fails, but the hedged execution is still running.
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+        scheduler
+            .update_action_with_internal_error(
+                &WORKER_ID2,
+                &action_info_hash_key,
+                make_err!(Code::Internal, "Some error"),
+            )
+            .await;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+        // The action must not have been requeued onto the failed worker.
+        assert_eq!(rx_from_worker2.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        // The hedged execution now owns the action and can complete it.
+        let action_result = ActionResult {
+            exit_code: 1,
+            ..ActionResult::default()
+        };
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &action_info_hash_key,
+                ActionStage::Completed(action_result.clone()),
+            )
+            .await?;
+        assert_eq!(
+            client_rx.borrow_and_update().stage,
+            ActionStage::Completed(action_result)
+        );
+        // Nothing to kill since the other execution already failed.
+        assert_eq!(rx_from_worker2.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn worker_quarantined_after_repeated_internal_errors_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0201);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0202);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                max_job_retries: 10,
+                worker_quarantine: Some(native_link_config::schedulers::WorkerQuarantineConfig {
+                    max_internal_errors: 2,
+                    initial_backoff_s: 100,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest1 = DigestInfo::new([91u8; 32], 512);
+        let action_digest2 = DigestInfo::new([92u8; 32], 512);
+
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut client_rx1 = setup_action(
+            &scheduler,
+            action_digest1,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        let mut client_rx2 = setup_action(
+            &scheduler,
+            action_digest2,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        for _ in 0..2 {
+            match rx_from_worker1.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+        }
+
+        // Both actions fail on the worker, which quarantines it.
+        for action_digest in [action_digest1, action_digest2] {
+            scheduler
+                .update_action_with_internal_error(
+                    &WORKER_ID1,
+                    &ActionInfoHashKey {
+                        instance_name: INSTANCE_NAME.to_string(),
+                        digest: action_digest,
+                        salt: 0,
+                    },
+                    make_err!(Code::Internal, "Some error"),
+                )
+                .await;
+        }
+        assert_eq!(client_rx1.borrow_and_update().stage, ActionStage::Queued);
+        assert_eq!(client_rx2.borrow_and_update().stage, ActionStage::Queued);
+
+        // The retries go to the new worker even though the quarantined worker is least recently used.
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+        for _ in 0..2 {
+            match rx_from_worker2.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+        }
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        // The worker is still quarantined before the backoff expires.
+        scheduler.worker_keep_alive_received(&WORKER_ID1, NOW_TIME + 10).await?;
+        scheduler.worker_keep_alive_received(&WORKER_ID2, NOW_TIME + 10).await?;
+        scheduler.remove_timedout_workers(NOW_TIME + 10).await?;
+        let _client_rx3 = setup_action(
+            &scheduler,
+            DigestInfo::new([93u8; 32], 512),
+            PlatformProperties::default(),
+            make_system_time(3),
+        )
+        .await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        // Once the backoff expires the worker is assigned actions again.
+        scheduler
+            .worker_keep_alive_received(&WORKER_ID1, NOW_TIME + 200)
+            .await?;
+        scheduler
+            .worker_keep_alive_received(&WORKER_ID2, NOW_TIME + 200)
+            .await?;
+        scheduler.remove_timedout_workers(NOW_TIME + 200).await?;
+        let _client_rx4 = setup_action(
+            &scheduler,
+            DigestInfo::new([94u8; 32], 512),
+            PlatformProperties::default(),
+            make_system_time(4),
+        )
+        .await?;
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn draining_worker_finishes_running_action_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0301);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest1 = DigestInfo::new([95u8; 32], 512);
+        let action_digest2 = DigestInfo::new([96u8; 32], 512);
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client_rx1 = setup_action(
+            &scheduler,
+            action_digest1,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // Once the worker is going away it is no longer assigned new actions.
+        scheduler.drain_worker(WORKER_ID, NOW_TIME).await?;
+        let mut client_rx2 = setup_action(
+            &scheduler,
+            action_digest2,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        assert_eq!(client_rx2.borrow_and_update().stage, ActionStage::Queued);
+        assert_eq!(rx_from_worker.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID));
+
+        // The running action may still report its result, after which the worker is removed.
+        let action_result = ActionResult {
+            execution_metadata: ExecutionMetadata {
+                worker: WORKER_ID.to_string(),
+                ..ExecutionMetadata::default()
+            },
+            ..ActionResult::default()
+        };
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: action_digest1,
+                    salt: 0,
+                },
+                ActionStage::Completed(action_result.clone()),
+            )
+            .await?;
+        assert_eq!(
+            client_rx1.borrow_and_update().stage,
+            ActionStage::Completed(action_result)
+        );
+        assert!(!scheduler.contains_worker_for_test(&WORKER_ID));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn draining_worker_removed_after_drain_timeout_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0302);
+        const DRAIN_TIMEOUT_S: u64 = 30;
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                worker_timeout_s: WORKER_TIMEOUT_S,
+                worker_drain_timeout_s: DRAIN_TIMEOUT_S,
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([97u8; 32], 512);
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler.drain_worker(WORKER_ID, NOW_TIME).await?;
+
+        // The worker is kept while the drain timeout has not expired.
+        scheduler
+            .remove_timedout_workers(NOW_TIME + DRAIN_TIMEOUT_S - 1)
+            .await?;
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID));
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+
+        // Afterwards it is removed and its running action is rescheduled.
+        scheduler.remove_timedout_workers(NOW_TIME + DRAIN_TIMEOUT_S).await?;
+        assert!(!scheduler.contains_worker_for_test(&WORKER_ID));
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Queued);
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::Disconnect(())) => { /* Success */ }
+            v => panic!("Expected Disconnect, got : {v:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn draining_worker_not_removed_for_missed_keep_alives_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0303);
+        const DRAIN_TIMEOUT_S: u64 = WORKER_TIMEOUT_S * 3;
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                worker_timeout_s: WORKER_TIMEOUT_S,
+                worker_drain_timeout_s: DRAIN_TIMEOUT_S,
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([98u8; 32], 512);
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler.drain_worker(WORKER_ID, NOW_TIME).await?;
+
+        // The worker stopped sending keep alives, but keeps running its action while draining.
+        scheduler
+            .remove_timedout_workers(NOW_TIME + WORKER_TIMEOUT_S * 2)
+            .await?;
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID));
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+
+        // It is removed once the drain timeout expired.
+        scheduler.remove_timedout_workers(NOW_TIME + DRAIN_TIMEOUT_S).await?;
+        assert!(!scheduler.contains_worker_for_test(&WORKER_ID));
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aged_low_priority_action_runs_before_new_high_priority_action_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0401);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                priority_aging: Some(native_link_config::schedulers::PriorityAgingConfig {
+                    aging_interval_s: 10,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let low_priority_action_digest = DigestInfo::new([81u8; 32], 512);
+        let high_priority_action_digest = DigestInfo::new([82u8; 32], 512);
+
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+
+        // The low priority action was queued a long time ago, the high priority one just now.
+        let mut action_info = make_base_action_info(make_system_time(1));
+        action_info.platform_properties = platform_properties.clone();
+        action_info.unique_qualifier.digest = low_priority_action_digest;
+        let mut low_priority_client_rx = scheduler.add_action(action_info).await?;
+        let mut action_info = make_base_action_info(SystemTime::now());
+        action_info.priority = 5;
+        action_info.platform_properties = platform_properties.clone();
+        action_info.unique_qualifier.digest = high_priority_action_digest;
+        let mut high_priority_client_rx = scheduler.add_action(action_info).await?;
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, platform_properties).await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(start_execute)) => {
+                assert_eq!(
+                    start_execute.execute_request.unwrap().action_digest,
+                    Some(low_priority_action_digest.into())
+                );
+            }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(low_priority_client_rx.borrow_and_update().stage, ActionStage::Executing);
+        assert_eq!(high_priority_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aged_priority_is_limited_by_max_priority_increase_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0403);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                priority_aging: Some(native_link_config::schedulers::PriorityAgingConfig {
+                    aging_interval_s: 10,
+                    max_priority_increase: 2,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let low_priority_action_digest = DigestInfo::new([85u8; 32], 512);
+        let high_priority_action_digest = DigestInfo::new([86u8; 32], 512);
+
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+
+        // The low priority action was queued a long time ago, but only gains 2 priority.
+        let mut action_info = make_base_action_info(make_system_time(1));
+        action_info.platform_properties = platform_properties.clone();
+        action_info.unique_qualifier.digest = low_priority_action_digest;
+        let mut low_priority_client_rx = scheduler.add_action(action_info).await?;
+        let mut action_info = make_base_action_info(SystemTime::now());
+        action_info.priority = 5;
+        action_info.platform_properties = platform_properties.clone();
+        action_info.unique_qualifier.digest = high_priority_action_digest;
+        let mut high_priority_client_rx = scheduler.add_action(action_info).await?;
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, platform_properties).await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(start_execute)) => {
+                assert_eq!(
+                    start_execute.execute_request.unwrap().action_digest,
+                    Some(high_priority_action_digest.into())
+                );
+            }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(
+            high_priority_client_rx.borrow_and_update().stage,
+            ActionStage::Executing
+        );
+        assert_eq!(low_priority_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn preempted_action_is_requeued_without_consuming_attempt_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0402);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                max_job_retries: 2,
+                preemption: Some(native_link_config::schedulers::PreemptionConfig::default()),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let low_priority_action_digest = DigestInfo::new([83u8; 32], 512);
+        let high_priority_action_digest = DigestInfo::new([84u8; 32], 512);
+        let make_hash_key = |digest: DigestInfo| ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest,
+            salt: 0,
+        };
+
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, platform_properties.clone()).await?;
+        let mut low_priority_client_rx = setup_action(
+            &scheduler,
+            low_priority_action_digest,
+            platform_properties.clone(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // The high priority action can't run anywhere, so the low priority one is killed.
+        let mut action_info = make_base_action_info(make_system_time(2));
+        action_info.priority = 5;
+        action_info.platform_properties = platform_properties;
+        action_info.unique_qualifier.digest = high_priority_action_digest;
+        let mut high_priority_client_rx = scheduler.add_action(action_info).await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::KillActionRequest(kill_action_request)) => {
+                assert_eq!(
+                    kill_action_request.action_digest,
+                    Some(low_priority_action_digest.into())
+                );
+            }
+            v => panic!("Expected KillActionRequest, got : {v:?}"),
+        }
+        assert_eq!(low_priority_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        // Once the worker reports the killed action, the high priority action starts.
+        scheduler
+            .update_action_with_internal_error(
+                &WORKER_ID,
+                &make_hash_key(low_priority_action_digest),
+                make_err!(Code::Aborted, "Action was killed"),
+            )
+            .await;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(
+            high_priority_client_rx.borrow_and_update().stage,
+            ActionStage::Executing
+        );
+        assert_eq!(low_priority_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        // The low priority action runs again after the high priority one is done.
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &make_hash_key(high_priority_action_digest),
+                ActionStage::Completed(ActionResult {
+                    execution_metadata: ExecutionMetadata {
+                        worker: WORKER_ID.to_string(),
+                        ..ExecutionMetadata::default()
+                    },
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // The preemption did not count as an attempt, so one more retry is still allowed.
+        scheduler
+            .update_action_with_internal_error(
+                &WORKER_ID,
+                &make_hash_key(low_priority_action_digest),
+                make_err!(Code::Internal, "Some error"),
+            )
+            .await;
+        assert_eq!(low_priority_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lowest_priority_running_action_is_preempted_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x0010_0404);
+        const WORKER_ID2: WorkerId = WorkerId(0x0010_0405);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                preemption: Some(native_link_config::schedulers::PreemptionConfig {
+                    max_preemptible_priority: 10,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+        let mut rx_from_worker1 = setup_new_worker(&scheduler, WORKER_ID1, platform_properties.clone()).await?;
+        let mut rx_from_worker2 = setup_new_worker(&scheduler, WORKER_ID2, platform_properties.clone()).await?;
+
+        let add_action = |digest: DigestInfo, priority: i32, insert_timestamp: SystemTime| {
+            let mut action_info = make_base_action_info(insert_timestamp);
+            action_info.priority = priority;
+            action_info.platform_properties = platform_properties.clone();
+            action_info.unique_qualifier.digest = digest;
+            scheduler.add_action(action_info)
+        };
+        let _medium_priority_client_rx = add_action(DigestInfo::new([80u8; 32], 512), 1, make_system_time(1)).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let low_priority_action_digest = DigestInfo::new([81u8; 32], 512);
+        let _low_priority_client_rx = add_action(low_priority_action_digest, 0, make_system_time(2)).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        // Both running actions may be preempted, but the one with the lowest priority is killed.
+        let _high_priority_client_rx = add_action(DigestInfo::new([82u8; 32], 512), 5, make_system_time(3)).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::KillActionRequest(kill_action_request)) => {
+                assert_eq!(
+                    kill_action_request.action_digest,
+                    Some(low_priority_action_digest.into())
+                );
+            }
+            v => panic!("Expected KillActionRequest, got : {v:?}"),
+        }
+        assert_eq!(rx_from_worker1.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shortest_expected_job_runs_first_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0403);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                execution_statistics: Some(native_link_config::schedulers::ExecutionStatisticsConfig::default()),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let long_command_digest = DigestInfo::new([85u8; 32], 512);
+        let short_command_digest = DigestInfo::new([86u8; 32], 512);
+        let make_hash_key = |digest: DigestInfo| ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest,
+            salt: 0,
+        };
+        let make_action_result = |execution_duration_s: u64| ActionResult {
+            execution_metadata: ExecutionMetadata {
+                worker: WORKER_ID.to_string(),
+                worker_start_timestamp: make_system_time(0),
+                worker_completed_timestamp: make_system_time(execution_duration_s),
+                ..ExecutionMetadata::default()
+            },
+            ..ActionResult::default()
+        };
+
+        // The worker can only run one action at a time.
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, platform_properties.clone()).await?;
+
+        // Run an action of each command to completion so the scheduler learns how long they take.
+        for (action_digest, command_digest, execution_duration_s) in [
+            (DigestInfo::new([87u8; 32], 512), long_command_digest, 100),
+            (DigestInfo::new([88u8; 32], 512), short_command_digest, 1),
+        ] {
+            let mut action_info = make_base_action_info(make_system_time(1));
+            action_info.platform_properties = platform_properties.clone();
+            action_info.command_digest = command_digest;
+            action_info.unique_qualifier.digest = action_digest;
+            let _client_rx = scheduler.add_action(action_info).await?;
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            scheduler
+                .update_action(
+                    &WORKER_ID,
+                    &make_hash_key(action_digest),
+                    ActionStage::Completed(make_action_result(execution_duration_s)),
+                )
+                .await?;
+        }
+
+        // Occupy the worker, then queue the long action before the short action.
+        let blocking_action_digest = DigestInfo::new([89u8; 32], 512);
+        let _blocking_client_rx = setup_action(
+            &scheduler,
+            blocking_action_digest,
+            platform_properties.clone(),
+            make_system_time(2),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let long_action_digest = DigestInfo::new([90u8; 32], 512);
+        let short_action_digest = DigestInfo::new([91u8; 32], 512);
+        let mut action_info = make_base_action_info(make_system_time(3));
+        action_info.platform_properties = platform_properties.clone();
+        action_info.command_digest = long_command_digest;
+        action_info.unique_qualifier.digest = long_action_digest;
+        let mut long_client_rx = scheduler.add_action(action_info).await?;
+        let mut action_info = make_base_action_info(make_system_time(4));
+        action_info.platform_properties = platform_properties;
+        action_info.command_digest = short_command_digest;
+        action_info.unique_qualifier.digest = short_action_digest;
+        let mut short_client_rx = scheduler.add_action(action_info).await?;
+
+        // The short action runs first even though it was queued last.
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &make_hash_key(blocking_action_digest),
+                ActionStage::Completed(make_action_result(10)),
+            )
+            .await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(start_execute)) => {
+                assert_eq!(
+                    start_execute.execute_request.unwrap().action_digest,
+                    Some(short_action_digest.into())
+                );
+            }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(short_client_rx.borrow_and_update().stage, ActionStage::Executing);
+        assert_eq!(long_client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scheduler_events_are_published_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0404);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let mut events_rx = scheduler.subscribe_to_events().unwrap();
+        let action_digest = DigestInfo::new([92u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let _client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let _joined_client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &action_info_hash_key,
+                ActionStage::Completed(ActionResult {
+                    exit_code: 3,
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+        scheduler.remove_worker(WORKER_ID).await;
+
+        let event = events_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, SchedulerEventType::ActionAdded);
+        assert_eq!(event.action_digest, Some(action_digest));
+        assert_eq!(event.instance_name.as_deref(), Some(INSTANCE_NAME));
+        let event = events_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, SchedulerEventType::ActionMatched);
+        assert_eq!(event.worker_id, Some(WORKER_ID.to_string()));
+        assert_eq!(event.attempts, Some(1));
+        let event = events_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, SchedulerEventType::ActionJoinedExisting);
+        let event = events_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, SchedulerEventType::ActionCompleted);
+        assert_eq!(event.exit_code, Some(3));
+        let event = events_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, SchedulerEventType::WorkerEvicted);
+        assert_eq!(event.worker_id, Some(WORKER_ID.to_string()));
+        assert_eq!(event.action_digest, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scheduler_events_are_written_to_ndjson_file_test() -> Result<(), Error> {
+        let path = format!(
+            "{}/scheduler_events_{}.ndjson",
+            std::env::var("TEST_TMPDIR").unwrap_or(std::env::temp_dir().to_str().unwrap().to_string()),
+            rand::random::<u64>()
+        );
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                event_sinks: vec![native_link_config::schedulers::SchedulerEventSink::ndjson_file(
+                    path.clone(),
+                )],
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([93u8; 32], 512);
+        let _client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+
+        let mut contents = String::new();
+        for _ in 0..100 {
+            // Events are written in the background.
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if contents.ends_with('\n') {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["event_type"], "action_added");
+        assert_eq!(event["instance_name"], INSTANCE_NAME);
+        assert_eq!(
+            event["action_digest"],
+            format!("{}-{}", action_digest.hash_str(), action_digest.size_bytes)
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scheduler_events_are_written_once_ndjson_file_can_be_opened_test() -> Result<(), Error> {
+        let directory = format!(
+            "{}/scheduler_events_{}",
+            std::env::var("TEST_TMPDIR").unwrap_or(std::env::temp_dir().to_str().unwrap().to_string()),
+            rand::random::<u64>()
+        );
+        let path = format!("{directory}/events.ndjson");
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                event_sinks: vec![native_link_config::schedulers::SchedulerEventSink::ndjson_file(
+                    path.clone(),
+                )],
+                ..Default::default()
+            },
+            || async move {},
+        );
+        // The directory of the file does not exist yet, so the event can't be written.
+        let _client_rx = setup_action(
+            &scheduler,
+            DigestInfo::new([95u8; 32], 512),
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut contents = String::new();
+        for _ in 0..500 {
+            // Writing the event is retried in the background.
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if contents.ends_with('\n') {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["event_type"], "action_added");
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completed_action_is_loaded_from_store_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0505);
+
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new(&native_link_config::stores::MemoryStore::default()));
+        let make_scheduler = || {
+            SimpleScheduler::new_with_stores_and_callback(
+                &native_link_config::schedulers::SimpleScheduler {
+                    completed_actions_store: Some("COMPLETED_ACTIONS_STORE".to_string()),
+                    ..Default::default()
+                },
+                SimpleSchedulerStores {
+                    completed_actions: Some(store.clone()),
+                    ..Default::default()
+                },
+                || async move {},
+            )
+        };
+        let action_digest = DigestInfo::new([94u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+        let action_result = ActionResult {
+            exit_code: 7,
+            ..ActionResult::default()
+        };
+
+        {
+            let scheduler = make_scheduler();
+            let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+            let _client_rx = setup_action(
+                &scheduler,
+                action_digest,
+                PlatformProperties::default(),
+                make_system_time(1),
+            )
+            .await?;
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            scheduler
+                .update_action(
+                    &WORKER_ID,
+                    &action_info_hash_key,
+                    ActionStage::Completed(action_result.clone()),
+                )
+                .await?;
+        }
+
+        // A new scheduler, eg: after a restart or another replica, answers from the store.
+        let scheduler = make_scheduler();
+        let mut maybe_rx = None;
+        for _ in 0..100 {
+            // The state is persisted in the background.
+            maybe_rx = scheduler.find_existing_action(&action_info_hash_key).await;
+            if maybe_rx.is_some() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        let action_state = maybe_rx.expect("Completed action should be in store").borrow().clone();
+        assert_eq!(
+            action_state.as_ref(),
+            &ActionState {
+                unique_qualifier: action_info_hash_key,
+                stage: ActionStage::Completed(action_result),
+                queue_position: None,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flaky_action_is_retried_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678_9111);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                flaky_retry: Some(native_link_config::schedulers::FlakyRetryConfig { max_retries: 1 }),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+        let make_flaky_result = |stdout_byte: u8| ActionResult {
+            output_files: Vec::default(),
+            output_folders: Vec::default(),
+            output_file_symlinks: Vec::default(),
+            output_directory_symlinks: Vec::default(),
+            exit_code: 1,
+            stdout_digest: DigestInfo::new([stdout_byte; 32], 19),
+            stderr_digest: DigestInfo::new([7u8; 32], 20),
+            execution_metadata: ExecutionMetadata {
+                worker: WORKER_ID.to_string(),
+                queued_timestamp: make_system_time(5),
+                worker_start_timestamp: make_system_time(6),
+                worker_completed_timestamp: make_system_time(7),
+                input_fetch_start_timestamp: make_system_time(8),
+                input_fetch_completed_timestamp: make_system_time(9),
+                execution_start_timestamp: make_system_time(10),
+                execution_completed_timestamp: make_system_time(11),
+                output_upload_start_timestamp: make_system_time(12),
+                output_upload_completed_timestamp: make_system_time(13),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
+            },
+            server_logs: HashMap::default(),
+            error: None,
+            message: String::new(),
+            flaky_failure_reason: Some("Exit code 1 is configured as flaky".to_string()),
+        };
+
+        for stdout_byte in [5u8, 6u8] {
+            // Other tests check full data. We only care if we got StartAction.
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+            scheduler
+                .update_action(
+                    &WORKER_ID,
+                    &action_info_hash_key,
+                    ActionStage::Completed(make_flaky_result(stdout_byte)),
+                )
+                .await?;
+        }
+
+        {
+            // The second flaky execution is over the limit, so it is the result.
+            let action_state = client_rx.borrow_and_update();
+            let ActionStage::Completed(action_result) = &action_state.stage else {
+                panic!("Expected Completed, got : {:?}", action_state.stage);
+            };
+            assert_eq!(action_result.stdout_digest, DigestInfo::new([6u8; 32], 19));
+            assert_eq!(action_result.execution_metadata.previous_attempts.len(), 1);
+            let previous_attempt = &action_result.execution_metadata.previous_attempts[0];
+            assert_eq!(previous_attempt.stdout_digest, DigestInfo::new([5u8; 32], 19));
+            assert_eq!(
+                previous_attempt.flaky_failure_reason,
+                "Exit code 1 is configured as flaky"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn queue_position_is_published_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0405);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                execution_statistics: Some(native_link_config::schedulers::ExecutionStatisticsConfig::default()),
+                queue_position: Some(native_link_config::schedulers::QueuePositionConfig::default()),
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let command_digest = DigestInfo::new([92u8; 32], 512);
+        let make_action_info = |action_digest: DigestInfo, platform_properties: &PlatformProperties, insert_time| {
+            let mut action_info = make_base_action_info(make_system_time(insert_time));
+            action_info.platform_properties = platform_properties.clone();
+            action_info.command_digest = command_digest;
+            action_info.unique_qualifier.digest = action_digest;
+            action_info
+        };
+
+        // The worker can only run one action at a time.
+        let mut properties = HashMap::new();
+        properties.insert("prop1".to_string(), PlatformPropertyValue::Minimum(1));
+        let platform_properties = PlatformProperties { properties };
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, platform_properties.clone()).await?;
+
+        // Run an action of the command to completion so the scheduler learns how long it takes.
+        let learning_action_digest = DigestInfo::new([93u8; 32], 512);
+        let _learning_client_rx = scheduler
+            .add_action(make_action_info(learning_action_digest, &platform_properties, 1))
+            .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: learning_action_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(ActionResult {
+                    execution_metadata: ExecutionMetadata {
+                        worker: WORKER_ID.to_string(),
+                        worker_start_timestamp: make_system_time(0),
+                        worker_completed_timestamp: make_system_time(10),
+                        ..ExecutionMetadata::default()
+                    },
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+
+        // Occupy the worker, then queue two more actions behind it.
+        let mut running_client_rx = scheduler
+            .add_action(make_action_info(
+                DigestInfo::new([94u8; 32], 512),
+                &platform_properties,
+                2,
+            ))
+            .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let mut first_client_rx = scheduler
+            .add_action(make_action_info(
+                DigestInfo::new([95u8; 32], 512),
+                &platform_properties,
+                3,
+            ))
+            .await?;
+        let mut second_client_rx = scheduler
+            .add_action(make_action_info(
+                DigestInfo::new([96u8; 32], 512),
+                &platform_properties,
+                4,
+            ))
+            .await?;
+        assert_eq!(first_client_rx.borrow_and_update().queue_position, None);
+
+        let now = SystemTime::now();
+        scheduler.publish_queue_positions_for_test(now);
+
+        assert!(
+            first_client_rx.has_changed().unwrap(),
+            "Queued client should be notified"
+        );
+        let first_position = first_client_rx.borrow_and_update().queue_position.clone().unwrap();
+        let second_position = second_client_rx.borrow_and_update().queue_position.clone().unwrap();
+        assert_eq!((first_position.position, first_position.queue_length), (0, 2));
+        assert_eq!((second_position.position, second_position.queue_length), (1, 2));
+        // The first action starts once the running action is expected to finish and the
+        // second one after the first one is expected to finish.
+        let first_start = first_position.estimated_start_timestamp.unwrap();
+        let second_start = second_position.estimated_start_timestamp.unwrap();
+        assert!(first_start > now, "{first_start:?} should be after {now:?}");
+        assert_eq!(
+            second_start.duration_since(first_start).unwrap(),
+            Duration::from_secs(10)
+        );
+        assert_eq!(running_client_rx.borrow_and_update().queue_position, None);
+
+        // Nothing changed, so clients are not notified again.
+        scheduler.publish_queue_positions_for_test(now);
+        assert!(!first_client_rx.has_changed().unwrap(), "Client should not be notified");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn metrics_are_broken_down_by_platform_properties_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x0010_0406);
+
+        let scheduler = Arc::new(SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                metrics_platform_property_keys: vec!["pool".to_string()],
+                ..Default::default()
+            },
+            || async move {},
+        ));
+        let make_platform_properties = |pool: &str| PlatformProperties {
+            properties: HashMap::from([("pool".to_string(), PlatformPropertyValue::Exact(pool.to_string()))]),
+        };
+
+        // Only the small pool has a worker, so the action for the large pool can not be matched.
+        let mut rx_from_worker = setup_new_worker(&scheduler, WORKER_ID, make_platform_properties("small")).await?;
+        let _small_client_rx = setup_action(
+            &scheduler,
+            DigestInfo::new([97u8; 32], 512),
+            make_platform_properties("small"),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        let _large_client_rx = setup_action(
+            &scheduler,
+            DigestInfo::new([98u8; 32], 512),
+            make_platform_properties("large"),
+            make_system_time(2),
+        )
+        .await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+
+        let mut registry = Registry::default();
+        ActionScheduler::register_metrics(scheduler.clone(), &mut registry);
+        let mut metrics = String::new();
+        prometheus_client::encoding::text::encode(&mut metrics, &registry).unwrap();
+
+        for expected_metric in [
+            "platform_property_executing_actions{pool=\"small\"} 1",
+            "platform_property_matched_actions{pool=\"small\"} 1",
+            "platform_property_queued_actions{pool=\"large\"} 1",
+            "platform_property_match_failures{pool=\"large\"} 1",
+        ] {
+            assert!(
+                metrics.lines().any(|line| line.ends_with(expected_metric)),
+                "Expected '{expected_metric}' in metrics : {metrics}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn platform_property_label_names_are_sanitized_test() -> Result<(), Error> {
+        let scheduler = Arc::new(SimpleScheduler::new_with_callback(
+            &native_link_config::schedulers::SimpleScheduler {
+                metrics_platform_property_keys: vec!["1-pool".to_string()],
+                ..Default::default()
+            },
+            || async move {},
+        ));
+        let _client_rx = setup_action(
+            &scheduler,
+            DigestInfo::new([99u8; 32], 512),
+            PlatformProperties {
+                properties: HashMap::from([("1-pool".to_string(), PlatformPropertyValue::Exact("small".to_string()))]),
+            },
+            make_system_time(1),
+        )
+        .await?;
+
+        let mut registry = Registry::default();
+        ActionScheduler::register_metrics(scheduler.clone(), &mut registry);
+        let mut metrics = String::new();
+        prometheus_client::encoding::text::encode(&mut metrics, &registry).unwrap();
+
+        // Label names may not start with a digit or contain dashes.
+        let expected_metric = "platform_property_queued_actions{_1_pool=\"small\"} 1";
+        assert!(
+            metrics.lines().any(|line| line.ends_with(expected_metric)),
+            "Expected '{expected_metric}' in metrics : {metrics}"
+        );
+        Ok(())
+    }
 }
@@ -40,5 +40,6 @@ pub fn make_base_action_info(insert_timestamp: SystemTime) -> ActionInfo {
         },
         skip_cache_lookup: false,
         digest_function: DigestHasherFunc::Sha256,
+        action_mnemonic: None,
     }
 }
@@ -28,14 +28,36 @@ use native_link_util::common::{log, DigestInfo};
 use native_link_util::digest_hasher::DigestHasherFunc;
 use native_link_util::platform_properties::PlatformProperties;
 use native_link_util::store_trait::Store;
+use prost::Message;
 use proto::build::bazel::remote::execution::v2::execution_server::{Execution, ExecutionServer as Server};
-use proto::build::bazel::remote::execution::v2::{Action, Command, ExecuteRequest, WaitExecutionRequest};
+use proto::build::bazel::remote::execution::v2::{
+    Action, Command, ExecuteRequest, RequestMetadata, WaitExecutionRequest,
+};
 use proto::google::longrunning::Operation;
 use rand::{thread_rng, Rng};
 use tokio::sync::watch;
 use tokio_stream::wrappers::WatchStream;
 use tonic::{Request, Response, Status};
 
+/// The name of the header clients send the `RequestMetadata` in.
+const REQUEST_METADATA_HEADER: &str = "build.bazel.remote.execution.v2.requestmetadata-bin";
+
+/// Returns the `RequestMetadata` the client sent along with the request, if any.
+fn request_metadata<T>(request: &Request<T>) -> Option<RequestMetadata> {
+    let value = request.metadata().get_bin(REQUEST_METADATA_HEADER)?;
+    match value
+        .to_bytes()
+        .map_err(|e| make_input_err!("{e:?}"))
+        .and_then(|bytes| RequestMetadata::decode(bytes).map_err(|e| make_input_err!("{e:?}")))
+    {
+        Ok(request_metadata) => Some(request_metadata),
+        Err(err) => {
+            log::warn!("Could not decode {REQUEST_METADATA_HEADER} header : {err:?}");
+            None
+        }
+    }
+}
+
 struct InstanceInfo {
     scheduler: Arc<dyn ActionScheduler>,
     cas_store: Arc<dyn Store>,
@@ -125,6 +147,7 @@ impl InstanceInfo {
             },
             skip_cache_lookup,
             digest_function,
+            action_mnemonic: None,
         })
     }
 }
@@ -174,6 +197,9 @@ impl ExecutionServer {
     }
 
     async fn inner_execute(&self, request: Request<ExecuteRequest>) -> Result<Response<ExecuteStream>, Error> {
+        let action_mnemonic = request_metadata(&request)
+            .map(|request_metadata| request_metadata.action_mnemonic)
+            .filter(|action_mnemonic| !action_mnemonic.is_empty());
         let execute_req = request.into_inner();
         let instance_name = execute_req.instance_name;
 
@@ -194,7 +220,7 @@ impl ExecutionServer {
             .map_or(DEFAULT_EXECUTION_PRIORITY, |p| p.priority);
 
         let action = get_and_decode_digest::<Action>(instance_info.cas_pin(), &digest).await?;
-        let action_info = instance_info
+        let mut action_info = instance_info
             .build_action_info(
                 instance_name,
                 digest,
@@ -207,6 +233,7 @@ impl ExecutionServer {
                     .err_tip(|| "Could not convert digest function in inner_execute()")?,
             )
             .await?;
+        action_info.action_mnemonic = action_mnemonic;
 
         let rx = instance_info
             .scheduler
@@ -17,4 +17,7 @@ pub mod bytestream_server;
 pub mod capabilities_server;
 pub mod cas_server;
 pub mod execution_server;
+pub mod operations_server;
+pub mod scheduler_events_server;
 pub mod worker_api_server;
+pub mod worker_pool_demand_server;
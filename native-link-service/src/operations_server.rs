@@ -0,0 +1,127 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use error::{make_err, Code, Error, ResultExt};
+use native_link_config::cas_server::{InstanceName, OperationsConfig};
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_util::action_messages::ActionInfoHashKey;
+use native_link_util::common::log;
+use proto::google::longrunning::operations_server::{Operations, OperationsServer as Server};
+use proto::google::longrunning::{
+    CancelOperationRequest, DeleteOperationRequest, GetOperationRequest, ListOperationsRequest, ListOperationsResponse,
+    Operation, WaitOperationRequest,
+};
+use tonic::{Request, Response, Status};
+
+/// Serves the google.longrunning.Operations service for operations started through
+/// the execution service. Only GetOperation is implemented, the other methods
+/// return Unimplemented with the reason they are not supported.
+pub struct OperationsServer {
+    schedulers: HashMap<InstanceName, Arc<dyn ActionScheduler>>,
+}
+
+impl OperationsServer {
+    pub fn new(
+        config: &HashMap<InstanceName, OperationsConfig>,
+        scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
+    ) -> Result<Self, Error> {
+        let mut schedulers = HashMap::with_capacity(config.len());
+        for (instance_name, operations_cfg) in config {
+            let scheduler = scheduler_map
+                .get(&operations_cfg.scheduler)
+                .err_tip(|| {
+                    format!(
+                        "Scheduler needs config for '{}' because it exists in operations",
+                        operations_cfg.scheduler
+                    )
+                })?
+                .clone();
+            schedulers.insert(instance_name.to_string(), scheduler);
+        }
+        Ok(Self { schedulers })
+    }
+
+    pub fn into_service(self) -> Server<OperationsServer> {
+        Server::new(self)
+    }
+
+    async fn inner_get_operation(&self, request: GetOperationRequest) -> Result<Response<Operation>, Error> {
+        let unique_qualifier = ActionInfoHashKey::try_from(request.name.as_str())
+            .err_tip(|| "Decoding operation name into ActionInfoHashKey")?;
+        let scheduler = self.schedulers.get(&unique_qualifier.instance_name).ok_or_else(|| {
+            make_err!(
+                Code::NotFound,
+                "No scheduler with the instance name {}",
+                unique_qualifier.instance_name
+            )
+        })?;
+        let rx = scheduler
+            .find_existing_action(&unique_qualifier)
+            .await
+            .ok_or_else(|| make_err!(Code::NotFound, "Failed to find operation {}", request.name))?;
+        let action_state = rx.borrow().as_ref().clone();
+        Ok(Response::new(action_state.into()))
+    }
+}
+
+#[tonic::async_trait]
+impl Operations for OperationsServer {
+    async fn list_operations(
+        &self,
+        _request: Request<ListOperationsRequest>,
+    ) -> Result<Response<ListOperationsResponse>, Status> {
+        Err(Status::unimplemented(
+            "list_operations is not supported, schedulers do not keep an index of all operations",
+        ))
+    }
+
+    async fn get_operation(&self, grpc_request: Request<GetOperationRequest>) -> Result<Response<Operation>, Status> {
+        log::info!("\x1b[0;31mget_operation Req\x1b[0m: {:?}", grpc_request.get_ref());
+        let now = Instant::now();
+        let resp = self
+            .inner_get_operation(grpc_request.into_inner())
+            .await
+            .err_tip(|| "Failed on get_operation() command")
+            .map_err(|e| e.into());
+        let d = now.elapsed().as_secs_f32();
+        if let Err(err) = &resp {
+            log::error!("\x1b[0;31mget_operation Resp\x1b[0m: {} {:?}", d, err);
+        } else {
+            log::info!("\x1b[0;31mget_operation Resp\x1b[0m: {}", d);
+        }
+        resp
+    }
+
+    async fn delete_operation(&self, _request: Request<DeleteOperationRequest>) -> Result<Response<()>, Status> {
+        Err(Status::unimplemented(
+            "delete_operation is not supported, completed operations expire on their own",
+        ))
+    }
+
+    async fn cancel_operation(&self, _request: Request<CancelOperationRequest>) -> Result<Response<()>, Status> {
+        Err(Status::unimplemented(
+            "cancel_operation is not supported, actions may be shared by multiple clients and cannot be cancelled",
+        ))
+    }
+
+    async fn wait_operation(&self, _request: Request<WaitOperationRequest>) -> Result<Response<Operation>, Status> {
+        Err(Status::unimplemented(
+            "wait_operation is not supported, use WaitExecution of the execution service instead",
+        ))
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use error::{make_input_err, Error, ResultExt};
+use futures::{Stream, StreamExt};
+use native_link_config::cas_server::SchedulerEventsConfig;
+use native_link_scheduler::worker_scheduler::WorkerScheduler;
+use native_link_util::common::log;
+use proto::com::github::trace_machina::native_link::remote_execution::scheduler_events_server::{
+    SchedulerEvents, SchedulerEventsServer as Server,
+};
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    SchedulerEvent, SubscribeSchedulerEventsRequest,
+};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tonic::{Request, Response, Status};
+
+pub type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SchedulerEvent, Status>> + Send + 'static>>;
+
+pub struct SchedulerEventsServer {
+    scheduler: Arc<dyn WorkerScheduler>,
+}
+
+impl SchedulerEventsServer {
+    pub fn new(
+        config: &SchedulerEventsConfig,
+        schedulers: &HashMap<String, Arc<dyn WorkerScheduler>>,
+    ) -> Result<Self, Error> {
+        let scheduler = schedulers
+            .get(&config.scheduler)
+            .err_tip(|| {
+                format!(
+                    "Scheduler needs config for '{}' because it exists in scheduler_events",
+                    config.scheduler
+                )
+            })?
+            .clone();
+        Ok(Self { scheduler })
+    }
+
+    pub fn into_service(self) -> Server<SchedulerEventsServer> {
+        Server::new(self)
+    }
+
+    fn inner_subscribe(&self, request: SubscribeSchedulerEventsRequest) -> Result<Response<SubscribeStream>, Error> {
+        let events_rx = self
+            .scheduler
+            .subscribe_to_events()
+            .ok_or_else(|| make_input_err!("Scheduler does not emit events"))?;
+        let instance_name = request.instance_name;
+        let stream = BroadcastStream::new(events_rx).filter_map(move |maybe_event| {
+            let maybe_event = match maybe_event {
+                Ok(event) => {
+                    // Worker events are not tied to an instance, so they are always sent.
+                    let is_wanted = instance_name.is_empty()
+                        || !matches!(&event.instance_name, Some(event_instance_name) if *event_instance_name != instance_name);
+                    if is_wanted {
+                        Some(Ok(SchedulerEvent::from(event.as_ref())))
+                    } else {
+                        None
+                    }
+                }
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    log::warn!("Scheduler events subscriber could not keep up, dropped {skipped} events");
+                    None
+                }
+            };
+            futures::future::ready(maybe_event)
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[tonic::async_trait]
+impl SchedulerEvents for SchedulerEventsServer {
+    type SubscribeStream = SubscribeStream;
+    async fn subscribe(
+        &self,
+        grpc_request: Request<SubscribeSchedulerEventsRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let now = Instant::now();
+        log::info!("\x1b[0;31msubscribe Req\x1b[0m: {:?}", grpc_request.get_ref());
+        let resp = self.inner_subscribe(grpc_request.into_inner());
+        let d = now.elapsed().as_secs_f32();
+        if let Err(err) = resp.as_ref() {
+            log::error!("\x1b[0;31msubscribe Resp\x1b[0m: {} {:?}", d, err);
+        } else {
+            log::info!("\x1b[0;31msubscribe Resp\x1b[0m: {}", d);
+        }
+        resp.map_err(|e| e.into())
+    }
+}
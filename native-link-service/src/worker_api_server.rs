@@ -23,7 +23,7 @@ use futures::Stream;
 use native_link_config::cas_server::WorkerApiConfig;
 use native_link_scheduler::worker::{Worker, WorkerId};
 use native_link_scheduler::worker_scheduler::WorkerScheduler;
-use native_link_util::action_messages::ActionInfoHashKey;
+use native_link_util::action_messages::{ActionInfoHashKey, ActionStage};
 use native_link_util::common::{log, DigestInfo};
 use native_link_util::platform_properties::PlatformProperties;
 use proto::com::github::trace_machina::native_link::remote_execution::worker_api_server::{
@@ -166,12 +166,28 @@ impl WorkerApiServer {
             .worker_keep_alive_received(&worker_id, (self.now_fn)()?.as_secs())
             .await
             .err_tip(|| "Could not process keep_alive from worker in inner_keep_alive()")?;
+        let recent_input_root_digests = keep_alive_request
+            .recent_input_root_digests
+            .into_iter()
+            .map(DigestInfo::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .err_tip(|| "Could not convert recent_input_root_digests in inner_keep_alive()")?;
+        self.scheduler
+            .update_worker_recent_input_roots(&worker_id, recent_input_root_digests)
+            .await
+            .err_tip(|| "Could not update recent input roots of worker in inner_keep_alive()")?;
         Ok(Response::new(()))
     }
 
     async fn inner_going_away(&self, going_away_request: GoingAwayRequest) -> Result<Response<()>, Error> {
         let worker_id: WorkerId = going_away_request.worker_id.try_into()?;
-        self.scheduler.remove_worker(worker_id).await;
+        // The worker may still send results for the actions it is running, so let it
+        // finish them instead of removing it right away.
+        let now = (self.now_fn)()?.as_secs();
+        self.scheduler
+            .drain_worker(worker_id, now)
+            .await
+            .err_tip(|| "Could not drain worker in inner_going_away()")?;
         Ok(Response::new(()))
     }
 
@@ -192,9 +208,14 @@ impl WorkerApiServer {
             .err_tip(|| "Expected result to exist in ExecuteResult")?
         {
             execute_result::Result::ExecuteResponse(finished_result) => {
-                let action_stage = finished_result
+                let mut action_stage = finished_result
                     .try_into()
                     .err_tip(|| "Failed to convert ExecuteResponse into an ActionStage")?;
+                if let ActionStage::Completed(action_result) = &mut action_stage {
+                    if !execute_result.flaky_failure_reason.is_empty() {
+                        action_result.flaky_failure_reason = Some(execute_result.flaky_failure_reason);
+                    }
+                }
                 self.scheduler
                     .update_action(&worker_id, &action_info_hash_key, action_stage)
                     .await
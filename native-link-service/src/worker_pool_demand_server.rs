@@ -0,0 +1,98 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use error::{make_err, make_input_err, Code, Error, ResultExt};
+use native_link_config::cas_server::WorkerPoolDemandConfig;
+use native_link_scheduler::worker_pool_demand::PlatformPropertiesDemand;
+use native_link_scheduler::worker_scheduler::WorkerScheduler;
+use native_link_util::common::log;
+use proto::com::github::trace_machina::native_link::remote_execution::worker_pool_demand_server::{
+    WorkerPoolDemand, WorkerPoolDemandServer as Server,
+};
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    GetWorkerPoolDemandRequest, GetWorkerPoolDemandResponse,
+};
+use tonic::{Request, Response, Status};
+
+#[derive(Clone)]
+pub struct WorkerPoolDemandServer {
+    scheduler: Arc<dyn WorkerScheduler>,
+}
+
+impl WorkerPoolDemandServer {
+    pub fn new(
+        config: &WorkerPoolDemandConfig,
+        schedulers: &HashMap<String, Arc<dyn WorkerScheduler>>,
+    ) -> Result<Self, Error> {
+        let scheduler = schedulers
+            .get(&config.scheduler)
+            .err_tip(|| {
+                format!(
+                    "Scheduler needs config for '{}' because it exists in worker_pool_demand",
+                    config.scheduler
+                )
+            })?
+            .clone();
+        Ok(Self { scheduler })
+    }
+
+    pub fn into_service(self) -> Server<WorkerPoolDemandServer> {
+        Server::new(self)
+    }
+
+    fn demands(&self) -> Result<Vec<PlatformPropertiesDemand>, Error> {
+        self.scheduler
+            .worker_pool_demand()
+            .ok_or_else(|| make_input_err!("Scheduler does not track worker pool demand"))
+    }
+
+    /// Returns the demand serialized as JSON in the same shape as
+    /// `GetWorkerPoolDemandResponse`, as served over HTTP.
+    pub fn demands_json(&self) -> Result<String, Error> {
+        serde_json::to_string(&serde_json::json!({ "demands": self.demands()? }))
+            .map_err(|e| make_err!(Code::Internal, "Could not serialize worker pool demand : {e:?}"))
+    }
+
+    fn inner_get_worker_pool_demand(&self) -> Result<Response<GetWorkerPoolDemandResponse>, Error> {
+        Ok(Response::new(GetWorkerPoolDemandResponse {
+            demands: self.demands()?.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl WorkerPoolDemand for WorkerPoolDemandServer {
+    async fn get_worker_pool_demand(
+        &self,
+        grpc_request: Request<GetWorkerPoolDemandRequest>,
+    ) -> Result<Response<GetWorkerPoolDemandResponse>, Status> {
+        let now = Instant::now();
+        log::info!(
+            "\x1b[0;31mget_worker_pool_demand Req\x1b[0m: {:?}",
+            grpc_request.get_ref()
+        );
+        let resp = self.inner_get_worker_pool_demand();
+        let d = now.elapsed().as_secs_f32();
+        if let Err(err) = resp.as_ref() {
+            log::error!("\x1b[0;31mget_worker_pool_demand Resp\x1b[0m: {} {:?}", d, err);
+        } else {
+            log::info!("\x1b[0;31mget_worker_pool_demand Resp\x1b[0m: {}", d);
+        }
+        resp.map_err(|e| e.into())
+    }
+}
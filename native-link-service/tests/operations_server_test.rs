@@ -0,0 +1,149 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use error::{Code, Error};
+use native_link_config::cas_server::OperationsConfig;
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
+use native_link_service::operations_server::OperationsServer;
+use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionStage, ActionState};
+use native_link_util::common::DigestInfo;
+use native_link_util::digest_hasher::DigestHasherFunc;
+use native_link_util::platform_properties::PlatformProperties;
+use proto::google::longrunning::operations_server::Operations;
+use proto::google::longrunning::{
+    CancelOperationRequest, DeleteOperationRequest, GetOperationRequest, ListOperationsRequest,
+};
+use tonic::Request;
+
+const INSTANCE_NAME: &str = "foo_instance_name";
+const SCHEDULER_NAME: &str = "MAIN_SCHEDULER";
+
+fn make_operations_server() -> Result<(Arc<SimpleScheduler>, OperationsServer), Error> {
+    let scheduler = Arc::new(SimpleScheduler::new(
+        &native_link_config::schedulers::SimpleScheduler::default(),
+        SimpleSchedulerStores::default(),
+    ));
+    let mut schedulers: HashMap<String, Arc<dyn ActionScheduler>> = HashMap::new();
+    schedulers.insert(SCHEDULER_NAME.to_string(), scheduler.clone());
+    let mut config = HashMap::new();
+    config.insert(
+        INSTANCE_NAME.to_string(),
+        OperationsConfig {
+            scheduler: SCHEDULER_NAME.to_string(),
+        },
+    );
+    let server = OperationsServer::new(&config, &schedulers)?;
+    Ok((scheduler, server))
+}
+
+fn make_action_info(unique_qualifier: ActionInfoHashKey) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties: PlatformProperties {
+            properties: HashMap::new(),
+        },
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: UNIX_EPOCH,
+        unique_qualifier,
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+        action_mnemonic: None,
+    }
+}
+
+#[cfg(test)]
+pub mod operations_server_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn get_operation_returns_state_of_action() -> Result<(), Error> {
+        let (scheduler, server) = make_operations_server()?;
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: DigestInfo::new([1u8; 32], 1),
+            salt: 0,
+        };
+        let _client_rx = scheduler.add_action(make_action_info(unique_qualifier.clone())).await?;
+
+        let operation = server
+            .get_operation(Request::new(GetOperationRequest {
+                name: unique_qualifier.action_name(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(operation.name, unique_qualifier.action_name());
+        assert!(!operation.done);
+        assert_eq!(
+            ActionState::try_from(operation)?,
+            ActionState {
+                unique_qualifier,
+                stage: ActionStage::Queued,
+                queue_position: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_operation_of_unknown_action_is_not_found() -> Result<(), Error> {
+        let (_scheduler, server) = make_operations_server()?;
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: DigestInfo::new([2u8; 32], 2),
+            salt: 0,
+        };
+        let result = server
+            .get_operation(Request::new(GetOperationRequest {
+                name: unique_qualifier.action_name(),
+            }))
+            .await;
+        assert_eq!(result.map_err(|e| e.code()).err(), Some(Code::NotFound.into()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsupported_methods_are_unimplemented() -> Result<(), Error> {
+        let (_scheduler, server) = make_operations_server()?;
+        let name = "foo".to_string();
+        let codes = [
+            server
+                .list_operations(Request::new(ListOperationsRequest::default()))
+                .await
+                .err()
+                .map(|status| status.code()),
+            server
+                .delete_operation(Request::new(DeleteOperationRequest { name: name.clone() }))
+                .await
+                .err()
+                .map(|status| status.code()),
+            server
+                .cancel_operation(Request::new(CancelOperationRequest { name }))
+                .await
+                .err()
+                .map(|status| status.code()),
+        ];
+        assert_eq!(codes, [Some(Code::Unimplemented.into()); 3]);
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use error::Error;
+use native_link_config::cas_server::SchedulerEventsConfig;
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
+use native_link_scheduler::worker_scheduler::WorkerScheduler;
+use native_link_service::scheduler_events_server::SchedulerEventsServer;
+use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey};
+use native_link_util::common::DigestInfo;
+use native_link_util::digest_hasher::DigestHasherFunc;
+use native_link_util::platform_properties::PlatformProperties;
+use proto::com::github::trace_machina::native_link::remote_execution::scheduler_events_server::SchedulerEvents;
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    SchedulerEventType, SubscribeSchedulerEventsRequest,
+};
+use tokio_stream::StreamExt;
+use tonic::Request;
+
+const SCHEDULER_NAME: &str = "MAIN_SCHEDULER";
+
+fn make_schedulers() -> (Arc<SimpleScheduler>, HashMap<String, Arc<dyn WorkerScheduler>>) {
+    let scheduler = Arc::new(SimpleScheduler::new(
+        &native_link_config::schedulers::SimpleScheduler::default(),
+        SimpleSchedulerStores::default(),
+    ));
+    let mut schedulers: HashMap<String, Arc<dyn WorkerScheduler>> = HashMap::new();
+    schedulers.insert(SCHEDULER_NAME.to_string(), scheduler.clone());
+    (scheduler, schedulers)
+}
+
+fn make_action_info(instance_name: &str, action_digest: DigestInfo) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties: PlatformProperties {
+            properties: HashMap::new(),
+        },
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: UNIX_EPOCH,
+        unique_qualifier: ActionInfoHashKey {
+            instance_name: instance_name.to_string(),
+            digest: action_digest,
+            salt: 0,
+        },
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+        action_mnemonic: None,
+    }
+}
+
+#[cfg(test)]
+pub mod scheduler_events_server_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn subscribe_streams_events_of_instance() -> Result<(), Error> {
+        let (scheduler, schedulers) = make_schedulers();
+        let server = SchedulerEventsServer::new(
+            &SchedulerEventsConfig {
+                scheduler: SCHEDULER_NAME.to_string(),
+            },
+            &schedulers,
+        )?;
+        let mut stream = server
+            .subscribe(Request::new(SubscribeSchedulerEventsRequest {
+                instance_name: "foo_instance".to_string(),
+            }))
+            .await?
+            .into_inner();
+
+        let wanted_digest = DigestInfo::new([2u8; 32], 2);
+        let _other_rx = scheduler
+            .add_action(make_action_info("bar_instance", DigestInfo::new([1u8; 32], 1)))
+            .await?;
+        let _wanted_rx = scheduler
+            .add_action(make_action_info("foo_instance", wanted_digest))
+            .await?;
+
+        // The event of the other instance is filtered out.
+        let event = stream.next().await.unwrap()?;
+        assert_eq!(event.event_type(), SchedulerEventType::ActionAdded);
+        assert_eq!(event.instance_name, "foo_instance");
+        assert_eq!(event.action_digest, Some(wanted_digest.into()));
+        assert!(event.timestamp.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_scheduler_is_error() -> Result<(), Error> {
+        let (_scheduler, schedulers) = make_schedulers();
+        let result = SchedulerEventsServer::new(
+            &SchedulerEventsConfig {
+                scheduler: "UNKNOWN_SCHEDULER".to_string(),
+            },
+            &schedulers,
+        );
+        assert!(result.is_err(), "Expected error, got : {:?}", result.err());
+        Ok(())
+    }
+}
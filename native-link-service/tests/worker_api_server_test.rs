@@ -19,7 +19,7 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use error::{Error, ResultExt};
 use native_link_config::cas_server::WorkerApiConfig;
 use native_link_scheduler::action_scheduler::ActionScheduler;
-use native_link_scheduler::simple_scheduler::SimpleScheduler;
+use native_link_scheduler::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
 use native_link_scheduler::worker::WorkerId;
 use native_link_scheduler::worker_scheduler::WorkerScheduler;
 use native_link_service::worker_api_server::{ConnectWorkerStream, NowFn, WorkerApiServer};
@@ -33,7 +33,7 @@ use proto::build::bazel::remote::execution::v2::{
 };
 use proto::com::github::trace_machina::native_link::remote_execution::worker_api_server::WorkerApi;
 use proto::com::github::trace_machina::native_link::remote_execution::{
-    execute_result, update_for_worker, ExecuteResult, KeepAliveRequest, SupportedProperties,
+    execute_result, update_for_worker, ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties,
 };
 use proto::google::rpc::Status as ProtoStatus;
 use tokio_stream::StreamExt;
@@ -56,10 +56,13 @@ fn static_now_fn() -> Result<Duration, Error> {
 async fn setup_api_server(worker_timeout: u64, now_fn: NowFn) -> Result<TestContext, Error> {
     const SCHEDULER_NAME: &str = "DUMMY_SCHEDULE_NAME";
 
-    let scheduler = Arc::new(SimpleScheduler::new(&native_link_config::schedulers::SimpleScheduler {
-        worker_timeout_s: worker_timeout,
-        ..Default::default()
-    }));
+    let scheduler = Arc::new(SimpleScheduler::new(
+        &native_link_config::schedulers::SimpleScheduler {
+            worker_timeout_s: worker_timeout,
+            ..Default::default()
+        },
+        SimpleSchedulerStores::default(),
+    ));
 
     let mut schedulers: HashMap<String, Arc<dyn WorkerScheduler>> = HashMap::new();
     schedulers.insert(SCHEDULER_NAME.to_string(), scheduler.clone());
@@ -173,6 +176,7 @@ pub mod keep_alive_tests {
                 .worker_api_server
                 .keep_alive(Request::new(KeepAliveRequest {
                     worker_id: test_context.worker_id.to_string(),
+                    ..Default::default()
                 }))
                 .await
                 .err_tip(|| "Error sending keep alive")?;
@@ -236,6 +240,23 @@ pub mod going_away_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    pub async fn going_away_removes_idle_worker_test() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_api_server(BASE_WORKER_TIMEOUT_S, Box::new(static_now_fn)).await?;
+
+        test_context
+            .worker_api_server
+            .going_away(Request::new(GoingAwayRequest {
+                worker_id: test_context.worker_id.to_string(),
+            }))
+            .await?;
+
+        let worker_exists = test_context.scheduler.contains_worker_for_test(&test_context.worker_id);
+        assert!(!worker_exists, "Expected idle worker to be removed from worker map");
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -273,6 +294,7 @@ pub mod execution_response_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         };
         let mut client_action_state_receiver = test_context.scheduler.add_action(action_info).await?;
 
@@ -343,6 +365,7 @@ pub mod execution_response_tests {
                 server_logs,
                 message: "TODO(blaise.bruer) We should put a reference something like bb_browser".to_string(),
             })),
+            flaky_failure_reason: String::new(),
         };
         {
             // Ensure our client thinks we are executing.
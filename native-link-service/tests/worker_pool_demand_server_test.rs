@@ -0,0 +1,188 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use error::Error;
+use maplit::hashmap;
+use native_link_config::cas_server::WorkerPoolDemandConfig;
+use native_link_scheduler::action_scheduler::ActionScheduler;
+use native_link_scheduler::simple_scheduler::{SimpleScheduler, SimpleSchedulerStores};
+use native_link_scheduler::worker::{Worker, WorkerId};
+use native_link_scheduler::worker_scheduler::WorkerScheduler;
+use native_link_service::worker_pool_demand_server::WorkerPoolDemandServer;
+use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey};
+use native_link_util::common::DigestInfo;
+use native_link_util::digest_hasher::DigestHasherFunc;
+use native_link_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
+use proto::com::github::trace_machina::native_link::remote_execution::worker_pool_demand_server::WorkerPoolDemand;
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    update_for_worker, GetWorkerPoolDemandRequest, PlatformPropertiesDemand,
+};
+use tokio::sync::mpsc;
+use tonic::Request;
+
+const SCHEDULER_NAME: &str = "MAIN_SCHEDULER";
+
+fn make_schedulers() -> (Arc<SimpleScheduler>, HashMap<String, Arc<dyn WorkerScheduler>>) {
+    let scheduler = Arc::new(SimpleScheduler::new(
+        &native_link_config::schedulers::SimpleScheduler::default(),
+        SimpleSchedulerStores::default(),
+    ));
+    let mut schedulers: HashMap<String, Arc<dyn WorkerScheduler>> = HashMap::new();
+    schedulers.insert(SCHEDULER_NAME.to_string(), scheduler.clone());
+    (scheduler, schedulers)
+}
+
+fn make_platform_properties(os: &str) -> PlatformProperties {
+    PlatformProperties {
+        properties: HashMap::from([("os".to_string(), PlatformPropertyValue::Exact(os.to_string()))]),
+    }
+}
+
+fn make_action_info(action_digest: DigestInfo, os: &str) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties: make_platform_properties(os),
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: UNIX_EPOCH,
+        unique_qualifier: ActionInfoHashKey {
+            instance_name: "foo_instance".to_string(),
+            digest: action_digest,
+            salt: 0,
+        },
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+        action_mnemonic: None,
+    }
+}
+
+#[cfg(test)]
+pub mod worker_pool_demand_server_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn demand_is_reported_per_platform_properties() -> Result<(), Error> {
+        let (scheduler, schedulers) = make_schedulers();
+        let server = WorkerPoolDemandServer::new(
+            &WorkerPoolDemandConfig {
+                scheduler: SCHEDULER_NAME.to_string(),
+                path: String::new(),
+            },
+            &schedulers,
+        )?;
+
+        // One linux worker, which runs the first linux action. The second linux
+        // action and the mac action have to wait.
+        let (tx, mut rx_from_worker) = mpsc::unbounded_channel();
+        scheduler
+            .add_worker(Worker::new(WorkerId(1), make_platform_properties("linux"), tx, 0))
+            .await?;
+        let _linux_rx = scheduler
+            .add_action(make_action_info(DigestInfo::new([1u8; 32], 1), "linux"))
+            .await?;
+        loop {
+            if let Some(update_for_worker::Update::StartAction(_)) = rx_from_worker.recv().await.unwrap().update {
+                break;
+            }
+        }
+        let _queued_linux_rx = scheduler
+            .add_action(make_action_info(DigestInfo::new([2u8; 32], 2), "linux"))
+            .await?;
+        let _mac_rx = scheduler
+            .add_action(make_action_info(DigestInfo::new([3u8; 32], 3), "mac"))
+            .await?;
+
+        let response = server
+            .get_worker_pool_demand(Request::new(GetWorkerPoolDemandRequest {}))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.demands,
+            vec![
+                PlatformPropertiesDemand {
+                    platform_properties: hashmap! { "os".to_string() => "linux".to_string() },
+                    queued_actions: 1,
+                    executing_actions: 1,
+                    idle_workers: 0,
+                    recommended_additional_workers: 1,
+                },
+                PlatformPropertiesDemand {
+                    platform_properties: hashmap! { "os".to_string() => "mac".to_string() },
+                    queued_actions: 1,
+                    executing_actions: 0,
+                    idle_workers: 0,
+                    recommended_additional_workers: 1,
+                },
+            ]
+        );
+
+        // The same demand is served as JSON over HTTP.
+        let json: serde_json::Value = serde_json::from_str(&server.demands_json()?).unwrap();
+        assert_eq!(json["demands"][0]["platform_properties"]["os"], "linux");
+        assert_eq!(json["demands"][0]["recommended_additional_workers"], 1);
+        assert_eq!(json["demands"][1]["platform_properties"]["os"], "mac");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_workers_not_satisfying_properties_are_not_counted() -> Result<(), Error> {
+        let (scheduler, schedulers) = make_schedulers();
+        let server = WorkerPoolDemandServer::new(
+            &WorkerPoolDemandConfig {
+                scheduler: SCHEDULER_NAME.to_string(),
+                path: String::new(),
+            },
+            &schedulers,
+        )?;
+
+        // The worker is idle, but it can not run the queued action.
+        let (tx, _rx_from_worker) = mpsc::unbounded_channel();
+        scheduler
+            .add_worker(Worker::new(WorkerId(1), make_platform_properties("linux"), tx, 0))
+            .await?;
+        let _mac_rx = scheduler
+            .add_action(make_action_info(DigestInfo::new([3u8; 32], 3), "mac"))
+            .await?;
+        let response = server
+            .get_worker_pool_demand(Request::new(GetWorkerPoolDemandRequest {}))
+            .await?
+            .into_inner();
+        assert_eq!(response.demands.len(), 1);
+        assert_eq!(response.demands[0].idle_workers, 0);
+        assert_eq!(response.demands[0].recommended_additional_workers, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_scheduler_is_error() -> Result<(), Error> {
+        let (_scheduler, schedulers) = make_schedulers();
+        let result = WorkerPoolDemandServer::new(
+            &WorkerPoolDemandConfig {
+                scheduler: "UNKNOWN_SCHEDULER".to_string(),
+                path: String::new(),
+            },
+            &schedulers,
+        );
+        assert!(result.is_err(), "Expected error, got : {:?}", result.err());
+        Ok(())
+    }
+}
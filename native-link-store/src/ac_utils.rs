@@ -26,7 +26,7 @@ use futures::future::join;
 use futures::{Future, FutureExt};
 use native_link_util::buf_channel::{make_buf_channel_pair, DropCloserWriteHalf};
 use native_link_util::common::{fs, DigestInfo};
-use native_link_util::digest_hasher::DigestHasher;
+use native_link_util::digest_hasher::{default_digest_hasher_func, DigestHasher};
 use native_link_util::store_trait::{Store, UploadSizeInfo};
 use prost::Message;
 use tokio::io::{AsyncRead, AsyncReadExt};
@@ -78,6 +78,15 @@ pub fn message_to_digest<'a>(
     Ok(hasher.finalize_digest(i64::try_from(buf.len())?))
 }
 
+/// Computes the digest an entry that is looked up by a name (eg: an operation
+/// name) instead of by its content is stored under. This is the digest a blob
+/// holding the name would have in the CAS with the default digest function.
+pub fn name_to_digest(name: &str) -> Result<DigestInfo, Error> {
+    let mut hasher = DigestHasher::from(default_digest_hasher_func());
+    hasher.update(name.as_bytes());
+    Ok(hasher.finalize_digest(i64::try_from(name.len())?))
+}
+
 /// Takes a proto message and will serialize it and upload it to the provided store.
 pub async fn serialize_and_upload_message<'a, T: Message>(
     message: &'a T,
@@ -28,7 +28,7 @@ use futures::stream::{StreamExt, TryStreamExt};
 use futures::{Future, TryFutureExt};
 use native_link_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
 use native_link_util::common::{fs, log, DigestInfo};
-use native_link_util::evicting_map::{EvictingMap, LenEntry};
+use native_link_util::evicting_map::{EvictingMap, LenEntry, PinLease};
 use native_link_util::metrics_utils::{Collector, CollectorState, MetricsComponent, Registry};
 use native_link_util::store_trait::{Store, UploadSizeInfo};
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
@@ -435,6 +435,8 @@ async fn prune_temp_path(temp_path: &str) -> Result<(), Error> {
     Ok(())
 }
 
+pub type FilesystemStoreLease<'a, Fe = FileEntryImpl> = PinLease<'a, Arc<Fe>, SystemTime>;
+
 pub struct FilesystemStore<Fe: FileEntry = FileEntryImpl> {
     shared_context: Arc<SharedContext>,
     evicting_map: EvictingMap<Arc<Fe>, SystemTime>,
@@ -484,6 +486,20 @@ impl<Fe: FileEntry> FilesystemStore<Fe> {
             .ok_or_else(|| make_err!(Code::NotFound, "{} not found in filesystem store", digest.hash_str()))
     }
 
+    /// Removes the file of the digest from the store, eg: because its content
+    /// is known to no longer match the digest. Returns if it was in the store.
+    pub async fn remove_entry_for_digest(&self, digest: &DigestInfo) -> bool {
+        self.evicting_map.remove(digest).await
+    }
+
+    /// Creates a lease that keeps the files of the digests pinned with it
+    /// from being evicted until it is dropped, eg: while they are hardlinked
+    /// into the work directory of an action. Digests may be pinned before
+    /// their files are in the store.
+    pub fn lease(&self) -> FilesystemStoreLease<'_, Fe> {
+        self.evicting_map.lease()
+    }
+
     async fn update_file<'a>(
         self: Pin<&'a Self>,
         mut entry: Fe,
@@ -521,14 +537,27 @@ impl<Fe: FileEntry> FilesystemStore<Fe> {
             file_size += data_len as u64;
         }
 
-        resumeable_temp_file
+        let temp_file = resumeable_temp_file
             .as_writer()
             .await
-            .err_tip(|| "in filesystem_store::update_file")?
-            .as_ref()
+            .err_tip(|| "in filesystem_store::update_file")?;
+        let temp_file = temp_file.as_ref();
+        temp_file
             .sync_all()
             .await
             .err_tip(|| "Failed to sync_data in filesystem store")?;
+        // The content never changes once it is in the store, and files of
+        // the store may be hardlinked to where they could be written to.
+        let mut permissions = temp_file
+            .metadata()
+            .await
+            .err_tip(|| "Failed to read permissions in filesystem store")?
+            .permissions();
+        permissions.set_readonly(true);
+        temp_file
+            .set_permissions(permissions)
+            .await
+            .err_tip(|| "Failed to make file read-only in filesystem store")?;
 
         drop(resumeable_temp_file);
 
@@ -27,8 +27,12 @@ use prost::Message;
 use prost_types::Any;
 use proto::build::bazel::remote::execution::v2::{
     execution_stage, Action, ActionResult as ProtoActionResult, ExecuteOperationMetadata, ExecuteRequest,
-    ExecuteResponse, ExecutedActionMetadata, FileNode, LogFile, OutputDirectory, OutputFile, OutputSymlink,
-    SymlinkNode,
+    ExecuteResponse, ExecutedActionMetadata, FileNode, LogFile, NodeProperties as ProtoNodeProperties, OutputDirectory,
+    OutputFile, OutputSymlink, SymlinkNode,
+};
+use proto::com::github::trace_machina::native_link::remote_execution::{
+    ExecutionAttempt as ProtoExecutionAttempt, OutputTruncation as ProtoOutputTruncation,
+    QueuePosition as ProtoQueuePosition, ResourceUsage as ProtoResourceUsage,
 };
 use proto::google::longrunning::operation::Result as LongRunningResult;
 use proto::google::longrunning::Operation;
@@ -150,6 +154,10 @@ pub struct ActionInfo {
 
     /// The digest function this action expects.
     pub digest_function: DigestHasherFunc,
+
+    /// The mnemonic of the action (eg: `CppCompile`) from the `RequestMetadata`
+    /// the client sent along with the action, if any.
+    pub action_mnemonic: Option<String>,
 }
 
 impl ActionInfo {
@@ -206,6 +214,7 @@ impl ActionInfo {
             skip_cache_lookup: execute_request.skip_cache_lookup,
             digest_function: DigestHasherFunc::try_from(execute_request.digest_function)
                 .err_tip(|| "Could not find digest_function in try_from_action_and_execute_request_with_salt")?,
+            action_mnemonic: None,
         })
     }
 }
@@ -318,6 +327,41 @@ impl Ord for NameOrPath {
     }
 }
 
+/// Properties of a file or directory that were requested with
+/// `Command::output_node_properties`.
+/// This struct must be compatible with `NodeProperties` in `remote_execution.proto`,
+/// except that string-based properties are not supported.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct NodeProperties {
+    pub mtime: Option<SystemTime>,
+    pub unix_mode: Option<u32>,
+}
+
+impl From<NodeProperties> for ProtoNodeProperties {
+    fn from(val: NodeProperties) -> Self {
+        Self {
+            properties: Vec::default(), // Not supported.
+            mtime: val.mtime.map(Into::into),
+            unix_mode: val.unix_mode,
+        }
+    }
+}
+
+impl TryFrom<ProtoNodeProperties> for NodeProperties {
+    type Error = Error;
+
+    fn try_from(val: ProtoNodeProperties) -> Result<Self, Error> {
+        Ok(Self {
+            mtime: val
+                .mtime
+                .map(SystemTime::try_from)
+                .transpose()
+                .err_tip(|| "Could not convert mtime in NodeProperties")?,
+            unix_mode: val.unix_mode,
+        })
+    }
+}
+
 /// Represents an individual file and associated metadata.
 /// This struct must be 100% compatible with `OutputFile` and `FileNode` structs
 /// in `remote_execution.proto`.
@@ -326,6 +370,7 @@ pub struct FileInfo {
     pub name_or_path: NameOrPath,
     pub digest: DigestInfo,
     pub is_executable: bool,
+    pub node_properties: Option<NodeProperties>,
 }
 
 //TODO: Make this TryFrom.
@@ -338,7 +383,7 @@ impl From<FileInfo> for FileNode {
             name,
             digest: Some((&val.digest).into()),
             is_executable: val.is_executable,
-            node_properties: Option::default(), // Not supported.
+            node_properties: val.node_properties.map(Into::into),
         }
     }
 }
@@ -354,6 +399,7 @@ impl TryFrom<OutputFile> for FileInfo {
                 .err_tip(|| "Expected digest to exist on OutputFile")?
                 .try_into()?,
             is_executable: output_file.is_executable,
+            node_properties: output_file.node_properties.map(TryInto::try_into).transpose()?,
         })
     }
 }
@@ -369,7 +415,7 @@ impl From<FileInfo> for OutputFile {
             digest: Some((&val.digest).into()),
             is_executable: val.is_executable,
             contents: Bytes::default(),
-            node_properties: Option::default(), // Not supported.
+            node_properties: val.node_properties.map(Into::into),
         }
     }
 }
@@ -464,6 +510,174 @@ impl From<DirectoryInfo> for OutputDirectory {
     }
 }
 
+/// A previous execution of an action that failed in a way that was considered
+/// flaky, so the action was run again.
+/// This struct must be 100% compatible with `ExecutionAttempt` in `worker_api.proto`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ExecutionAttempt {
+    pub worker: String,
+    pub worker_start_timestamp: SystemTime,
+    pub worker_completed_timestamp: SystemTime,
+    pub exit_code: i32,
+    pub stdout_digest: DigestInfo,
+    pub stderr_digest: DigestInfo,
+    pub flaky_failure_reason: String,
+}
+
+impl From<ExecutionAttempt> for ProtoExecutionAttempt {
+    fn from(val: ExecutionAttempt) -> Self {
+        Self {
+            worker: val.worker,
+            worker_start_timestamp: Some(val.worker_start_timestamp.into()),
+            worker_completed_timestamp: Some(val.worker_completed_timestamp.into()),
+            exit_code: val.exit_code,
+            stdout_digest: Some(val.stdout_digest.into()),
+            stderr_digest: Some(val.stderr_digest.into()),
+            flaky_failure_reason: val.flaky_failure_reason,
+        }
+    }
+}
+
+impl TryFrom<ProtoExecutionAttempt> for ExecutionAttempt {
+    type Error = Error;
+
+    fn try_from(val: ProtoExecutionAttempt) -> Result<Self, Error> {
+        Ok(Self {
+            worker: val.worker,
+            worker_start_timestamp: val
+                .worker_start_timestamp
+                .err_tip(|| "Expected worker_start_timestamp to exist in ExecutionAttempt")?
+                .try_into()?,
+            worker_completed_timestamp: val
+                .worker_completed_timestamp
+                .err_tip(|| "Expected worker_completed_timestamp to exist in ExecutionAttempt")?
+                .try_into()?,
+            exit_code: val.exit_code,
+            stdout_digest: val
+                .stdout_digest
+                .err_tip(|| "Expected stdout_digest to exist in ExecutionAttempt")?
+                .try_into()?,
+            stderr_digest: val
+                .stderr_digest
+                .err_tip(|| "Expected stderr_digest to exist in ExecutionAttempt")?
+                .try_into()?,
+            flaky_failure_reason: val.flaky_failure_reason,
+        })
+    }
+}
+
+/// Where a queued action is in the queue of its scheduler.
+/// This struct must be 100% compatible with `QueuePosition` in `worker_api.proto`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct QueuePosition {
+    /// Number of queued actions with the same platform properties that will be
+    /// sent to a worker before this action.
+    pub position: u64,
+    /// Number of queued actions with the same platform properties, including this action.
+    pub queue_length: u64,
+    /// When the action is expected to be sent to a worker, if it can be estimated.
+    pub estimated_start_timestamp: Option<SystemTime>,
+}
+
+impl From<QueuePosition> for ProtoQueuePosition {
+    fn from(val: QueuePosition) -> Self {
+        Self {
+            position: val.position,
+            queue_length: val.queue_length,
+            estimated_start_timestamp: val.estimated_start_timestamp.map(Into::into),
+        }
+    }
+}
+
+impl TryFrom<ProtoQueuePosition> for QueuePosition {
+    type Error = Error;
+
+    fn try_from(val: ProtoQueuePosition) -> Result<Self, Error> {
+        Ok(Self {
+            position: val.position,
+            queue_length: val.queue_length,
+            estimated_start_timestamp: val
+                .estimated_start_timestamp
+                .map(SystemTime::try_from)
+                .transpose()
+                .err_tip(|| "Could not convert estimated_start_timestamp in QueuePosition")?,
+        })
+    }
+}
+
+/// Resources used by an execution.
+/// This struct must be 100% compatible with `ResourceUsage` in `worker_api.proto`.
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct ResourceUsage {
+    pub peak_memory_bytes: u64,
+    pub user_cpu_time: Duration,
+    pub system_cpu_time: Duration,
+    pub oom_kills: u64,
+}
+
+impl From<ResourceUsage> for ProtoResourceUsage {
+    fn from(val: ResourceUsage) -> Self {
+        Self {
+            peak_memory_bytes: val.peak_memory_bytes,
+            user_cpu_time: prost_types::Duration::try_from(val.user_cpu_time).ok(),
+            system_cpu_time: prost_types::Duration::try_from(val.system_cpu_time).ok(),
+            oom_kills: val.oom_kills,
+        }
+    }
+}
+
+impl TryFrom<ProtoResourceUsage> for ResourceUsage {
+    type Error = Error;
+
+    fn try_from(val: ProtoResourceUsage) -> Result<Self, Error> {
+        Ok(Self {
+            peak_memory_bytes: val.peak_memory_bytes,
+            user_cpu_time: val
+                .user_cpu_time
+                .map(Duration::try_from)
+                .transpose()
+                .map_err(|e| make_input_err!("Could not convert user_cpu_time in ResourceUsage : {e:?}"))?
+                .unwrap_or_default(),
+            system_cpu_time: val
+                .system_cpu_time
+                .map(Duration::try_from)
+                .transpose()
+                .map_err(|e| make_input_err!("Could not convert system_cpu_time in ResourceUsage : {e:?}"))?
+                .unwrap_or_default(),
+            oom_kills: val.oom_kills,
+        })
+    }
+}
+
+/// An output stream of an execution that was truncated by the worker.
+/// This struct must be 100% compatible with `OutputTruncation` in `worker_api.proto`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct OutputTruncation {
+    pub stream_name: String,
+    pub kept_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl From<OutputTruncation> for ProtoOutputTruncation {
+    fn from(val: OutputTruncation) -> Self {
+        Self {
+            stream_name: val.stream_name,
+            kept_bytes: val.kept_bytes,
+            total_bytes: val.total_bytes,
+        }
+    }
+}
+
+impl From<ProtoOutputTruncation> for OutputTruncation {
+    fn from(val: ProtoOutputTruncation) -> Self {
+        Self {
+            stream_name: val.stream_name,
+            kept_bytes: val.kept_bytes,
+            total_bytes: val.total_bytes,
+        }
+    }
+}
+
 /// Represents the metadata associated with the execution result.
 /// This struct must be 100% compatible with `ExecutedActionMetadata`.
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -478,6 +692,15 @@ pub struct ExecutionMetadata {
     pub execution_completed_timestamp: SystemTime,
     pub output_upload_start_timestamp: SystemTime,
     pub output_upload_completed_timestamp: SystemTime,
+    /// Executions of the action before this one that failed in a flaky way.
+    /// Sent as `ExecutionAttempt` in `auxiliary_metadata`.
+    pub previous_attempts: Vec<ExecutionAttempt>,
+    /// Resources used by the execution, if the worker measured them.
+    /// Sent as `ResourceUsage` in `auxiliary_metadata`.
+    pub resource_usage: Option<ResourceUsage>,
+    /// Output streams of the execution the worker truncated.
+    /// Sent as `OutputTruncation` in `auxiliary_metadata`.
+    pub output_truncations: Vec<OutputTruncation>,
 }
 
 impl Default for ExecutionMetadata {
@@ -493,6 +716,9 @@ impl Default for ExecutionMetadata {
             execution_completed_timestamp: SystemTime::UNIX_EPOCH,
             output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
             output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+            previous_attempts: Vec::new(),
+            resource_usage: None,
+            output_truncations: Vec::new(),
         }
     }
 }
@@ -515,7 +741,20 @@ impl From<ExecutionMetadata> for ExecutedActionMetadata {
                 .duration_since(val.execution_start_timestamp)
                 .ok()
                 .and_then(|duration| prost_types::Duration::try_from(duration).ok()),
-            auxiliary_metadata: Vec::default(),
+            auxiliary_metadata: val
+                .previous_attempts
+                .into_iter()
+                .map(|attempt| to_any(&ProtoExecutionAttempt::from(attempt)))
+                .chain(
+                    val.resource_usage
+                        .map(|resource_usage| to_any(&ProtoResourceUsage::from(resource_usage))),
+                )
+                .chain(
+                    val.output_truncations
+                        .into_iter()
+                        .map(|output_truncation| to_any(&ProtoOutputTruncation::from(output_truncation))),
+                )
+                .collect(),
         }
     }
 }
@@ -562,6 +801,28 @@ impl TryFrom<ExecutedActionMetadata> for ExecutionMetadata {
                 .output_upload_completed_timestamp
                 .err_tip(|| "Expected output_upload_completed_timestamp to exist in ExecutedActionMetadata")?
                 .try_into()?,
+            previous_attempts: eam
+                .auxiliary_metadata
+                .iter()
+                // Other auxiliary metadata is not ours to interpret.
+                .filter(|any| any.type_url == ProtoExecutionAttempt::TYPE_URL)
+                .map(|any| from_any::<ProtoExecutionAttempt>(any).and_then(ExecutionAttempt::try_from))
+                .collect::<Result<Vec<_>, _>>()
+                .err_tip(|| "Could not decode ExecutionAttempt in ExecutedActionMetadata")?,
+            resource_usage: eam
+                .auxiliary_metadata
+                .iter()
+                .find(|any| any.type_url == ProtoResourceUsage::TYPE_URL)
+                .map(|any| from_any::<ProtoResourceUsage>(any).and_then(ResourceUsage::try_from))
+                .transpose()
+                .err_tip(|| "Could not decode ResourceUsage in ExecutedActionMetadata")?,
+            output_truncations: eam
+                .auxiliary_metadata
+                .iter()
+                .filter(|any| any.type_url == ProtoOutputTruncation::TYPE_URL)
+                .map(|any| from_any::<ProtoOutputTruncation>(any).map(OutputTruncation::from))
+                .collect::<Result<Vec<_>, _>>()
+                .err_tip(|| "Could not decode OutputTruncation in ExecutedActionMetadata")?,
         })
     }
 }
@@ -584,6 +845,10 @@ pub struct ActionResult {
     pub server_logs: HashMap<String, DigestInfo>,
     pub error: Option<Error>,
     pub message: String,
+    /// Set by the worker if the execution failed in a way it is configured to
+    /// consider flaky. Not part of the `ActionResult` proto, it is sent in
+    /// `ExecuteResult` instead.
+    pub flaky_failure_reason: Option<String>,
 }
 
 impl Default for ActionResult {
@@ -607,10 +872,14 @@ impl Default for ActionResult {
                 execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: Default::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         }
     }
 }
@@ -814,6 +1083,7 @@ impl TryFrom<ProtoActionResult> for ActionResult {
             server_logs: Default::default(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         })
     }
 }
@@ -856,6 +1126,7 @@ impl TryFrom<ExecuteResponse> for ActionStage {
                 .clone()
                 .and_then(|v| if v.code == 0 { None } else { Some(v.into()) }),
             message: execute_response.message,
+            flaky_failure_reason: None,
         };
 
         if execute_response.cached_result {
@@ -878,6 +1149,26 @@ impl TypeUrl for ExecuteOperationMetadata {
     const TYPE_URL: &'static str = "type.googleapis.com/build.bazel.remote.execution.v2.ExecuteOperationMetadata";
 }
 
+impl TypeUrl for ProtoExecutionAttempt {
+    const TYPE_URL: &'static str =
+        "type.googleapis.com/com.github.trace_machina.native_link.remote_execution.ExecutionAttempt";
+}
+
+impl TypeUrl for ProtoQueuePosition {
+    const TYPE_URL: &'static str =
+        "type.googleapis.com/com.github.trace_machina.native_link.remote_execution.QueuePosition";
+}
+
+impl TypeUrl for ProtoResourceUsage {
+    const TYPE_URL: &'static str =
+        "type.googleapis.com/com.github.trace_machina.native_link.remote_execution.ResourceUsage";
+}
+
+impl TypeUrl for ProtoOutputTruncation {
+    const TYPE_URL: &'static str =
+        "type.googleapis.com/com.github.trace_machina.native_link.remote_execution.OutputTruncation";
+}
+
 fn from_any<T>(message: &Any) -> Result<T, Error>
 where
     T: TypeUrl + Default,
@@ -956,9 +1247,19 @@ impl TryFrom<Operation> for ActionState {
             }
         };
 
+        let queue_position = metadata
+            .partial_execution_metadata
+            .iter()
+            .flat_map(|partial_execution_metadata| partial_execution_metadata.auxiliary_metadata.iter())
+            .find(|any| any.type_url == ProtoQueuePosition::TYPE_URL)
+            .map(|any| from_any::<ProtoQueuePosition>(any).and_then(QueuePosition::try_from))
+            .transpose()
+            .err_tip(|| "Could not decode QueuePosition in upstream operation metadata")?;
+
         Ok(Self {
             unique_qualifier,
             stage,
+            queue_position,
         })
     }
 }
@@ -969,6 +1270,10 @@ impl TryFrom<Operation> for ActionState {
 pub struct ActionState {
     pub stage: ActionStage,
     pub unique_qualifier: ActionInfoHashKey,
+    /// Where the action is in the queue, if the scheduler publishes it. Only
+    /// set while the action is queued. Sent as `QueuePosition` in the
+    /// `partial_execution_metadata` of `ExecuteOperationMetadata`.
+    pub queue_position: Option<QueuePosition>,
 }
 
 impl ActionState {
@@ -1001,7 +1306,10 @@ impl From<ActionState> for Operation {
             // TODO(blaise.bruer) We should support stderr/stdout streaming.
             stdout_stream_name: String::default(),
             stderr_stream_name: String::default(),
-            partial_execution_metadata: None,
+            partial_execution_metadata: val.queue_position.map(|queue_position| ExecutedActionMetadata {
+                auxiliary_metadata: vec![to_any(&ProtoQueuePosition::from(queue_position))],
+                ..Default::default()
+            }),
         };
 
         Self {
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -23,6 +24,7 @@ use futures::stream::FuturesUnordered;
 use futures::{future, StreamExt};
 use lru::LruCache;
 use native_link_config::stores::EvictionPolicy;
+use parking_lot::Mutex as SyncMutex;
 use serde::{Deserialize, Serialize};
 
 use crate::common::{log, DigestInfo};
@@ -115,6 +117,12 @@ impl<T: LenEntry + Send + Sync> LenEntry for Arc<T> {
 
 struct State<T: LenEntry + Debug> {
     lru: LruCache<DigestInfo, EvictionItem<T>>,
+    /// Pinned entries the eviction came across. They are kept out of `lru`,
+    /// so every eviction does not have to skip them again, and are moved
+    /// back once they are used or no longer pinned.
+    pinned: HashMap<DigestInfo, EvictionItem<T>>,
+    /// Size of the entries in `pinned`.
+    pinned_size: u64,
     sum_store_size: u64,
 
     // Metrics.
@@ -125,10 +133,67 @@ struct State<T: LenEntry + Debug> {
     removed_bytes: Counter,
     removed_items: CounterWithTime,
     lifetime_inserted_bytes: Counter,
+    over_limits_with_pins: CounterWithTime,
+}
+
+impl<T: LenEntry + Debug> State<T> {
+    /// Number of entries, pinned or not.
+    fn len(&self) -> usize {
+        self.lru.len() + self.pinned.len()
+    }
+
+    fn peek(&self, digest: &DigestInfo) -> Option<&EvictionItem<T>> {
+        self.lru.peek(digest).or_else(|| self.pinned.get(digest))
+    }
+
+    /// Same as `peek()`, but marks the entry as recently used. Entries that
+    /// were set aside are moved back to the LRU for this.
+    fn get_mut(&mut self, digest: &DigestInfo) -> Option<&mut EvictionItem<T>> {
+        if let Some(eviction_item) = self.remove_pinned(digest) {
+            self.lru.put(*digest, eviction_item);
+        }
+        self.lru.get_mut(digest)
+    }
+
+    /// Returns the replaced entry if any.
+    fn put(&mut self, digest: DigestInfo, eviction_item: EvictionItem<T>) -> Option<EvictionItem<T>> {
+        let old_pinned_item = self.remove_pinned(&digest);
+        self.lru.put(digest, eviction_item).or(old_pinned_item)
+    }
+
+    fn pop(&mut self, digest: &DigestInfo) -> Option<EvictionItem<T>> {
+        self.lru.pop(digest).or_else(|| self.remove_pinned(digest))
+    }
+
+    fn remove_pinned(&mut self, digest: &DigestInfo) -> Option<EvictionItem<T>> {
+        let eviction_item = self.pinned.remove(digest)?;
+        self.pinned_size -= eviction_item.data.len() as u64;
+        Some(eviction_item)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&DigestInfo, &EvictionItem<T>)> {
+        self.lru.iter().chain(self.pinned.iter())
+    }
+}
+
+/// Digests pinned by `PinLease`s.
+#[derive(Default)]
+struct Pins {
+    /// Number of leases each pinned digest is held by.
+    counts: HashMap<DigestInfo, usize>,
+    /// Pinned digests whose entries were moved to `State::pinned`. They may
+    /// have been moved back to the LRU since, when they were used.
+    set_aside: HashSet<DigestInfo>,
+    /// Digests of `set_aside` that are no longer pinned.
+    released: HashSet<DigestInfo>,
 }
 
 pub struct EvictingMap<T: LenEntry + Debug, I: InstantWrapper> {
     state: Mutex<State<T>>,
+    /// Pinned entries are skipped when evicting, even if they are inserted
+    /// after pinning. This is a sync lock so leases can release their pins
+    /// on drop.
+    pins: SyncMutex<Pins>,
     anchor_time: I,
     max_bytes: u64,
     evict_bytes: u64,
@@ -147,6 +212,8 @@ where
             // function on the LenEntry properly.
             state: Mutex::new(State {
                 lru: LruCache::unbounded(),
+                pinned: HashMap::new(),
+                pinned_size: 0,
                 sum_store_size: 0,
                 evicted_bytes: Counter::default(),
                 evicted_items: CounterWithTime::default(),
@@ -155,7 +222,9 @@ where
                 removed_bytes: Counter::default(),
                 removed_items: CounterWithTime::default(),
                 lifetime_inserted_bytes: Counter::default(),
+                over_limits_with_pins: CounterWithTime::default(),
             }),
+            pins: SyncMutex::new(Pins::default()),
             anchor_time,
             max_bytes: config.max_bytes as u64,
             evict_bytes: config.evict_bytes as u64,
@@ -167,10 +236,10 @@ where
     pub async fn build_lru_index(&self) -> SerializedLRU {
         let state = self.state.lock().await;
         let mut serialized_lru = SerializedLRU {
-            data: Vec::with_capacity(state.lru.len()),
+            data: Vec::with_capacity(state.len()),
             anchor_time: self.anchor_time.unix_timestamp(),
         };
-        for (digest, eviction_item) in state.lru.iter() {
+        for (digest, eviction_item) in state.iter() {
             serialized_lru.data.push((*digest, eviction_item.seconds_since_anchor));
         }
         serialized_lru
@@ -180,6 +249,11 @@ where
         let mut state = self.state.lock().await;
         self.anchor_time = I::from_secs(seiralized_lru.anchor_time);
         state.lru.clear();
+        state.pinned.clear();
+        state.pinned_size = 0;
+        let pins = self.pins.get_mut();
+        pins.set_aside.clear();
+        pins.released.clear();
         for (digest, seconds_since_anchor) in seiralized_lru.data {
             let entry = entry_builder(&digest);
             state.lru.put(
@@ -205,14 +279,71 @@ where
         is_over_size || old_item_exists || is_over_count
     }
 
+    /// Moves the entries that are no longer pinned back to the LRU. They
+    /// were not used since they were set aside, so they are still the least
+    /// recently used ones.
+    fn return_released_items(&self, state: &mut State<T>) {
+        let mut pins = self.pins.lock();
+        let pins = &mut *pins;
+        for digest in pins.released.drain() {
+            // Pinned again since it was released.
+            if pins.counts.contains_key(&digest) {
+                pins.set_aside.insert(digest);
+                continue;
+            }
+            if let Some(eviction_item) = state.remove_pinned(&digest) {
+                state.lru.put(digest, eviction_item);
+                state.lru.demote(&digest);
+            }
+        }
+    }
+
+    /// Moves pinned entries from the end of the LRU to `State::pinned`, so
+    /// the least recently used entry is one that can be evicted.
+    fn set_aside_pinned_items(&self, state: &mut State<T>) {
+        let mut pins = self.pins.lock();
+        if pins.counts.is_empty() {
+            return;
+        }
+        while let Some((digest, _)) = state.lru.peek_lru() {
+            if !pins.counts.contains_key(digest) {
+                return;
+            }
+            let digest = *digest;
+            let eviction_item = state.lru.pop(&digest).expect("Tried to peek() then pop() but failed");
+            state.pinned_size += eviction_item.data.len() as u64;
+            state.pinned.insert(digest, eviction_item);
+            pins.set_aside.insert(digest);
+        }
+    }
+
+    /// Reports when only pinned entries are left, but the store is still over
+    /// its limits.
+    fn check_pinned_over_limits(&self, state: &mut State<T>) {
+        let is_over_size = self.max_bytes != 0 && state.sum_store_size > self.max_bytes;
+        let is_over_count = self.max_count != 0 && (state.len() as u64) > self.max_count;
+        if !is_over_size && !is_over_count {
+            return;
+        }
+        state.over_limits_with_pins.inc();
+        log::warn!(
+            "\x1b[0;31mEvicting Map\x1b[0m: Over the limits, {} pinned items of {} bytes can not be evicted",
+            state.pinned.len(),
+            state.pinned_size
+        );
+    }
+
     async fn evict_items(&self, state: &mut State<T>) {
-        let Some((_, mut peek_entry)) = state.lru.peek_lru() else {
+        self.return_released_items(state);
+        self.set_aside_pinned_items(state);
+        let Some((_, peek_entry)) = state.lru.peek_lru() else {
+            self.check_pinned_over_limits(state);
             return;
         };
 
         let max_bytes = if self.max_bytes != 0
             && self.evict_bytes != 0
-            && self.should_evict(state.lru.len(), peek_entry, state.sum_store_size, self.max_bytes)
+            && self.should_evict(state.len(), peek_entry, state.sum_store_size, self.max_bytes)
         {
             if self.max_bytes > self.evict_bytes {
                 self.max_bytes - self.evict_bytes
@@ -223,26 +354,35 @@ where
             self.max_bytes
         };
 
-        while self.should_evict(state.lru.len(), peek_entry, state.sum_store_size, max_bytes) {
-            let (key, eviction_item) = state.lru.pop_lru().expect("Tried to peek() then pop() but failed");
+        while let Some((peek_key, peek_entry)) = state.lru.peek_lru() {
+            if !self.should_evict(state.len(), peek_entry, state.sum_store_size, max_bytes) {
+                return;
+            }
+            let peek_key = *peek_key;
+            let eviction_item = state.lru.pop(&peek_key).expect("Tried to peek() then pop() but failed");
             state.sum_store_size -= eviction_item.data.len() as u64;
             state.evicted_items.inc();
             state.evicted_bytes.add(eviction_item.data.len() as u64);
             // Note: See comment in `unref()` requring global lock of insert/remove.
             eviction_item.data.unref().await;
-            log::info!("\x1b[0;31mEvicting Map\x1b[0m: Evicting {}", key.hash_str());
+            log::info!("\x1b[0;31mEvicting Map\x1b[0m: Evicting {}", peek_key.hash_str());
+            self.set_aside_pinned_items(state);
+        }
+        self.check_pinned_over_limits(state);
+    }
 
-            peek_entry = if let Some((_, entry)) = state.lru.peek_lru() {
-                entry
-            } else {
-                return;
-            };
+    /// Creates a lease that keeps the digests pinned with it from being
+    /// evicted until it is dropped. Explicit removals are not affected.
+    pub fn lease(&self) -> PinLease<'_, T, I> {
+        PinLease {
+            evicting_map: self,
+            digests: SyncMutex::new(Vec::new()),
         }
     }
 
     pub async fn size_for_key(&self, digest: &DigestInfo) -> Option<usize> {
         let mut state = self.state.lock().await;
-        let Some(entry) = state.lru.get_mut(digest) else {
+        let Some(entry) = state.get_mut(digest) else {
             return None;
         };
         entry.seconds_since_anchor = self.anchor_time.elapsed().as_secs() as i32;
@@ -259,7 +399,7 @@ where
             .iter()
             .zip(results.iter_mut())
             .filter_map(|(digest, result)| {
-                let Some(entry) = state.lru.get_mut(digest) else {
+                let Some(entry) = state.get_mut(digest) else {
                     return None;
                 };
                 entry.seconds_since_anchor = seconds_since_anchor;
@@ -279,7 +419,7 @@ where
 
     pub async fn get(&self, digest: &DigestInfo) -> Option<T> {
         let mut state = self.state.lock().await;
-        if let Some(entry) = state.lru.get_mut(digest) {
+        if let Some(entry) = state.get_mut(digest) {
             entry.seconds_since_anchor = self.anchor_time.elapsed().as_secs() as i32;
             let data = entry.data.clone();
             drop(state);
@@ -331,7 +471,7 @@ where
                 data,
             };
 
-            if let Some(old_item) = state.lru.put(digest, eviction_item) {
+            if let Some(old_item) = state.put(digest, eviction_item) {
                 state.sum_store_size -= old_item.data.len() as u64;
                 state.replaced_items.inc();
                 state.replaced_bytes.add(old_item.data.len() as u64);
@@ -352,7 +492,7 @@ where
     }
 
     async fn inner_remove(&self, state: &mut State<T>, digest: &DigestInfo) -> bool {
-        if let Some(entry) = state.lru.pop(digest) {
+        if let Some(entry) = state.pop(digest) {
             let data_len = entry.data.len() as u64;
             state.sum_store_size -= data_len;
             state.removed_items.inc();
@@ -368,7 +508,7 @@ where
     /// in an atomic fashion.
     pub async fn remove_if<F: FnOnce(&T) -> bool>(&self, digest: &DigestInfo, cond: F) -> bool {
         let mut state = self.state.lock().await;
-        if let Some(entry) = state.lru.get(digest) {
+        if let Some(entry) = state.peek(digest) {
             if !cond(&entry.data) {
                 return false;
             }
@@ -376,6 +516,62 @@ where
         }
         false
     }
+
+    /// Removes all entries `cond` returns true for and returns how many were
+    /// removed. This scans the whole map.
+    pub async fn remove_all_if<F: FnMut(&T) -> bool>(&self, mut cond: F) -> usize {
+        let mut state = self.state.lock().await;
+        let digests: Vec<DigestInfo> = state
+            .iter()
+            .filter(|(_, entry)| cond(&entry.data))
+            .map(|(digest, _)| *digest)
+            .collect();
+        for digest in &digests {
+            self.inner_remove(&mut state, digest).await;
+        }
+        digests.len()
+    }
+}
+
+/// Digests pinned in an `EvictingMap`, see `EvictingMap::lease()`. The pins
+/// are released when the lease is dropped.
+pub struct PinLease<'a, T: LenEntry + Debug, I: InstantWrapper> {
+    evicting_map: &'a EvictingMap<T, I>,
+    digests: SyncMutex<Vec<DigestInfo>>,
+}
+
+impl<'a, T: LenEntry + Debug, I: InstantWrapper> PinLease<'a, T, I> {
+    /// Pins the digest, it does not need to be in the map yet.
+    pub fn pin(&self, digest: DigestInfo) {
+        *self.evicting_map.pins.lock().counts.entry(digest).or_insert(0) += 1;
+        self.digests.lock().push(digest);
+    }
+}
+
+impl<'a, T: LenEntry + Debug, I: InstantWrapper> Drop for PinLease<'a, T, I> {
+    fn drop(&mut self) {
+        let digests = self.digests.get_mut();
+        if digests.is_empty() {
+            return;
+        }
+        // Entries that were kept over the limits are evicted with the next
+        // insert.
+        let mut pins = self.evicting_map.pins.lock();
+        let pins = &mut *pins;
+        for digest in digests.drain(..) {
+            if let Some(count) = pins.counts.get_mut(&digest) {
+                *count -= 1;
+                if *count == 0 {
+                    pins.counts.remove(&digest);
+                    // Only entries that were set aside have to be moved
+                    // back to the LRU.
+                    if pins.set_aside.remove(&digest) {
+                        pins.released.insert(digest);
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T, I> {
@@ -401,6 +597,11 @@ impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T,
             &self.max_count,
             "Maximum number of items to keep in the store",
         );
+        c.publish(
+            "pinned_items_total",
+            &self.pins.lock().counts.len(),
+            "Number of digests pinned by in-flight leases",
+        );
         futures::executor::block_on(async move {
             let state = self.state.lock().await;
             c.publish(
@@ -408,7 +609,17 @@ impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T,
                 &state.sum_store_size,
                 "Total size of all items in the store",
             );
-            c.publish("items_in_store_total", &state.lru.len(), "Mumber of items in the store");
+            c.publish("items_in_store_total", &state.len(), "Mumber of items in the store");
+            c.publish(
+                "pinned_bytes",
+                &state.pinned_size,
+                "Size of the pinned items the eviction had to skip",
+            );
+            c.publish(
+                "over_limits_with_pins_total",
+                &state.over_limits_with_pins,
+                "Number of times the store stayed over its limits because the remaining items were pinned",
+            );
             c.publish(
                 "oldest_item_timestamp",
                 &state
@@ -314,6 +314,14 @@ pub async fn hard_link(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<(
     tokio::fs::hard_link(src, dst).await.map_err(|e| e.into())
 }
 
+pub async fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<u64, Error> {
+    let _permit = OPEN_FILE_SEMAPHORE
+        .acquire()
+        .await
+        .map_err(|e| make_err!(Code::Internal, "Open file semaphore closed {:?}", e))?;
+    tokio::fs::copy(from, to).await.map_err(|e| e.into())
+}
+
 pub async fn set_permissions(src: impl AsRef<Path>, perm: std::fs::Permissions) -> Result<(), Error> {
     let _permit = OPEN_FILE_SEMAPHORE
         .acquire()
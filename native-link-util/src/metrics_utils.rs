@@ -157,6 +157,22 @@ impl CollectorState {
         N: Debug + 'static,
         T: Into<NumericalMetric<N>> + Ord + Copy + std::fmt::Display,
         NumericalMetric<N>: EncodeMetric,
+    {
+        self.publish_stats_with_labels(name, data, help, vec![]);
+    }
+
+    /// Same as publish_stats() but with labels.
+    #[inline]
+    pub fn publish_stats_with_labels<N, T>(
+        &mut self,
+        name: impl Into<String> + Clone,
+        data: impl Iterator<Item = T>,
+        help: impl Into<String> + Clone,
+        labels: Labels,
+    ) where
+        N: Debug + 'static,
+        T: Into<NumericalMetric<N>> + Ord + Copy + std::fmt::Display,
+        NumericalMetric<N>: EncodeMetric,
     {
         let mut data = data.collect::<Vec<T>>();
         if data.is_empty() {
@@ -169,8 +185,9 @@ impl CollectorState {
         ] {
             let index = (i * data_len) as usize;
             let value = data.get(if index < data.len() { index } else { index - 1 }).unwrap();
-            let labels = vec![("quantile".into(), format!("{:.2}", i).into())];
-            self.publish_number(name.clone(), *value, help.clone(), labels);
+            let mut quantile_labels = labels.clone();
+            quantile_labels.push(("quantile".into(), format!("{:.2}", i).into()));
+            self.publish_number(name.clone(), *value, help.clone(), quantile_labels);
         }
     }
 
@@ -22,7 +22,7 @@ use error::Error;
 use mock_instant::{Instant as MockInstant, MockClock};
 use native_link_config::stores::EvictionPolicy;
 use native_link_util::common::DigestInfo;
-use native_link_util::evicting_map::{EvictingMap, InstantWrapper, LenEntry};
+use native_link_util::evicting_map::{EvictingMap, InstantWrapper, LenEntry, SerializedLRU};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct BytesWrapper(Bytes);
@@ -120,6 +120,188 @@ mod evicting_map_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn pinned_items_are_not_evicted_until_lease_is_dropped() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 2,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let lease = evicting_map.lease();
+        // Pinned before it is inserted.
+        lease.pin(DigestInfo::try_new(HASH1, 0)?);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH1, 0)?, Bytes::new().into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH2, 0)?, Bytes::new().into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH3, 0)?, Bytes::new().into())
+            .await;
+
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH1, 0)?).await,
+            Some(0),
+            "Expected map to have pinned item 1"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH2, 0)?).await,
+            None,
+            "Expected map to not have item 2"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH3, 0)?).await,
+            Some(0),
+            "Expected map to have item 3"
+        );
+
+        drop(lease);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH4, 0)?, Bytes::new().into())
+            .await;
+
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH1, 0)?).await,
+            None,
+            "Expected map to not have item 1 after the lease was dropped"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH3, 0)?).await,
+            Some(0),
+            "Expected map to have item 3"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH4, 0)?).await,
+            Some(0),
+            "Expected map to have item 4"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pinned_items_are_kept_over_the_limits() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 1,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let lease = evicting_map.lease();
+        lease.pin(DigestInfo::try_new(HASH1, 0)?);
+        lease.pin(DigestInfo::try_new(HASH2, 0)?);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH1, 0)?, Bytes::new().into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH2, 0)?, Bytes::new().into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH3, 0)?, Bytes::new().into())
+            .await;
+
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH3, 0)?).await,
+            None,
+            "Expected map to not have item 3"
+        );
+        // Pinned items can not be evicted, even if they are over the limits.
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH1, 0)?).await,
+            Some(0),
+            "Expected map to have pinned item 1"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH2, 0)?).await,
+            Some(0),
+            "Expected map to have pinned item 2"
+        );
+
+        drop(lease);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH4, 0)?, Bytes::new().into())
+            .await;
+
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH1, 0)?).await,
+            None,
+            "Expected map to not have item 1 after the lease was dropped"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH2, 0)?).await,
+            None,
+            "Expected map to not have item 2 after the lease was dropped"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH4, 0)?).await,
+            Some(0),
+            "Expected map to have item 4"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn items_pinned_again_after_release_are_not_evicted() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 1,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let lease = evicting_map.lease();
+        lease.pin(DigestInfo::try_new(HASH1, 0)?);
+        lease.pin(DigestInfo::try_new(HASH1, 0)?);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH1, 0)?, Bytes::new().into())
+            .await;
+        // Sets item 1 aside, because it is pinned.
+        evicting_map
+            .insert(DigestInfo::try_new(HASH2, 0)?, Bytes::new().into())
+            .await;
+
+        // Released and pinned again before the next eviction.
+        drop(lease);
+        let lease = evicting_map.lease();
+        lease.pin(DigestInfo::try_new(HASH1, 0)?);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH3, 0)?, Bytes::new().into())
+            .await;
+
+        // Looked up through the index, so item 1 is not moved back to the
+        // LRU by the lookup.
+        let digests = |map: SerializedLRU| map.data.into_iter().map(|(digest, _)| digest).collect::<Vec<_>>();
+        assert_eq!(
+            digests(evicting_map.build_lru_index().await),
+            vec![DigestInfo::try_new(HASH1, 0)?],
+            "Expected map to only have pinned item 1"
+        );
+
+        drop(lease);
+        evicting_map
+            .insert(DigestInfo::try_new(HASH4, 0)?, Bytes::new().into())
+            .await;
+
+        assert_eq!(
+            digests(evicting_map.build_lru_index().await),
+            vec![DigestInfo::try_new(HASH4, 0)?],
+            "Expected map to only have item 4 after the lease was dropped"
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn insert_purges_at_max_bytes() -> Result<(), Error> {
         let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
@@ -469,6 +651,50 @@ mod evicting_map_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn remove_all_if_removes_matching_items() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 0,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        evicting_map
+            .insert(DigestInfo::try_new(HASH1, 0)?, Bytes::from_static(b"remove").into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH2, 0)?, Bytes::from_static(b"keep").into())
+            .await;
+        evicting_map
+            .insert(DigestInfo::try_new(HASH3, 0)?, Bytes::from_static(b"remove").into())
+            .await;
+
+        let removed = evicting_map
+            .remove_all_if(|value| value.0 == Bytes::from_static(b"remove"))
+            .await;
+        assert_eq!(removed, 2);
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH1, 0)?).await,
+            None,
+            "Expected map to not have item 1"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH2, 0)?).await,
+            Some(4),
+            "Expected map to have item 2"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&DigestInfo::try_new(HASH3, 0)?).await,
+            None,
+            "Expected map to not have item 3"
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn build_lru_index_and_reload() -> Result<(), Error> {
         let mut evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
@@ -0,0 +1,267 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use error::{make_input_err, Code, Error, ResultExt};
+use native_link_config::cas_server::CgroupConfig;
+use native_link_util::action_messages::{ActionInfo, ResourceUsage};
+use native_link_util::common::log;
+use tokio::process;
+
+use crate::worker_utils::{or_default, property_value, DEFAULT_CPU_COUNT_PROPERTY, DEFAULT_MEMORY_KB_PROPERTY};
+
+/// Platform property with the maximum number of processes of an action.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_PIDS_PROPERTY: &str = "max_pids";
+
+/// Period the CPU quota of an action is given for.
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// How often to try to remove the cgroup of an action while its killed
+/// processes are exiting.
+const REMOVE_ATTEMPTS: usize = 100;
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Creates a cgroup v2 per action below a configured cgroup. The limits of
+/// the cgroups are taken from the platform properties of the actions.
+///
+/// Note: All operations are on cgroupfs, which does not block, so they are
+/// not moved to blocking threads.
+pub struct Cgroups {
+    path: PathBuf,
+    memory_kb_property: String,
+    cpu_count_property: String,
+    max_pids_property: String,
+}
+
+impl Cgroups {
+    pub fn new(config: &CgroupConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            memory_kb_property: or_default(&config.memory_kb_property, DEFAULT_MEMORY_KB_PROPERTY),
+            cpu_count_property: or_default(&config.cpu_count_property, DEFAULT_CPU_COUNT_PROPERTY),
+            max_pids_property: or_default(&config.max_pids_property, DEFAULT_MAX_PIDS_PROPERTY),
+        }
+    }
+
+    /// Creates the cgroup `name` with the limits `action_info` requested.
+    pub fn create_action_cgroup(&self, name: &str, action_info: &ActionInfo) -> Result<ActionCgroup, Error> {
+        std::fs::create_dir_all(&self.path).err_tip(|| format!("Could not create cgroup {:?}", self.path))?;
+        // Enable all controllers we use that are available, so the cgroups of
+        // the actions can be limited and accounted.
+        let available_controllers = std::fs::read_to_string(self.path.join("cgroup.controllers")).err_tip(|| {
+            format!(
+                "Could not read controllers of cgroup {:?}, is it a cgroup v2?",
+                self.path
+            )
+        })?;
+        let subtree_control = available_controllers
+            .split_whitespace()
+            .filter(|controller| ["cpu", "memory", "pids"].contains(controller))
+            .map(|controller| format!("+{controller}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !subtree_control.is_empty() {
+            std::fs::write(self.path.join("cgroup.subtree_control"), subtree_control)
+                .err_tip(|| format!("Could not enable controllers in cgroup {:?}", self.path))?;
+        }
+
+        let path = self.path.join(name);
+        std::fs::create_dir(&path).err_tip(|| format!("Could not create cgroup {path:?}"))?;
+        let action_cgroup = ActionCgroup { path: Some(path) };
+        if let Some(memory_kb) = property_value(action_info, &self.memory_kb_property)? {
+            let memory_bytes = memory_kb
+                .checked_mul(1024)
+                .ok_or_else(|| make_input_err!("Platform property {} is too large", self.memory_kb_property))?;
+            action_cgroup.write("memory.max", memory_bytes.to_string())?;
+            // Kill all processes of the action if one of them runs out of
+            // memory, there is no point in continuing the action.
+            action_cgroup.write("memory.oom.group", "1")?;
+            // Swap is not available on every host.
+            if let Err(e) = action_cgroup.write("memory.swap.max", "0") {
+                if e.code != Code::NotFound {
+                    return Err(e);
+                }
+            }
+        }
+        if let Some(cpu_count) = property_value(action_info, &self.cpu_count_property)? {
+            let cpu_quota_usec = cpu_count
+                .checked_mul(CPU_PERIOD_USEC)
+                .ok_or_else(|| make_input_err!("Platform property {} is too large", self.cpu_count_property))?;
+            action_cgroup.write("cpu.max", format!("{cpu_quota_usec} {CPU_PERIOD_USEC}"))?;
+        }
+        if let Some(max_pids) = property_value(action_info, &self.max_pids_property)? {
+            action_cgroup.write("pids.max", max_pids.to_string())?;
+        }
+        Ok(action_cgroup)
+    }
+}
+
+/// The cgroup of a single action. It is removed when dropped, killing all
+/// processes that are still in it.
+pub struct ActionCgroup {
+    path: Option<PathBuf>,
+}
+
+impl ActionCgroup {
+    fn path(&self) -> &Path {
+        self.path.as_ref().expect("ActionCgroup path only taken on removal")
+    }
+
+    fn write(&self, file: &str, contents: impl AsRef<[u8]>) -> Result<(), Error> {
+        let path = self.path().join(file);
+        std::fs::write(&path, contents).err_tip(|| format!("Could not write {path:?}"))
+    }
+
+    /// Reads a file of the cgroup. Files of controllers that are not enabled
+    /// do not exist, in which case `None` is returned.
+    fn read(&self, file: &str) -> Result<Option<String>, Error> {
+        let path = self.path().join(file);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).err_tip(|| format!("Could not read {path:?}")),
+        }
+    }
+
+    /// Configures `command` to be moved into this cgroup before it executes.
+    pub fn apply(&self, command: &mut process::Command) -> Result<(), Error> {
+        let procs_path = CString::new(self.path().join("cgroup.procs").as_os_str().as_bytes())
+            .map_err(|e| make_input_err!("Invalid cgroup path {:?} : {e:?}", self.path()))?;
+        // SAFETY: Only performs system calls on data that was prepared before
+        // the fork.
+        unsafe {
+            command.pre_exec(move || {
+                let fd = libc::open(procs_path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Writing 0 moves the writing process.
+                let result = libc::write(fd, b"0".as_ptr().cast(), 1);
+                let result = if result < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(())
+                };
+                libc::close(fd);
+                result
+            });
+        }
+        Ok(())
+    }
+
+    /// Pids of the processes in the cgroup.
+    pub fn pids(&self) -> Result<Vec<u32>, Error> {
+        let Some(procs) = self.read("cgroup.procs")? else {
+            return Ok(Vec::new());
+        };
+        procs
+            .lines()
+            .map(|pid| parse_number(pid).map(|pid| pid as u32))
+            .collect()
+    }
+
+    /// Kills all processes in the cgroup, including the ones that left the
+    /// process group of the action.
+    pub fn kill(&self) -> Result<(), Error> {
+        // cgroup.kill is not available on older kernels, where a process
+        // forked while the pids are killed one by one escapes.
+        if self.write("cgroup.kill", "1").is_ok() {
+            return Ok(());
+        }
+        for pid in self.pids()? {
+            // SAFETY: kill() has no memory safety requirements.
+            if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } == -1 {
+                let e = io::Error::last_os_error();
+                // ESRCH means the process already exited.
+                if e.raw_os_error() != Some(libc::ESRCH) {
+                    return Err(e).err_tip(|| format!("Could not kill process {pid} of cgroup {:?}", self.path()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The resources used by all processes that ran in this cgroup.
+    pub fn resource_usage(&self) -> Result<ResourceUsage, Error> {
+        let mut resource_usage = ResourceUsage::default();
+        if let Some(peak) = self.read("memory.peak")? {
+            resource_usage.peak_memory_bytes = parse_number(&peak)?;
+        }
+        let keyed_value = |contents: &str, key: &str| -> Result<u64, Error> {
+            contents
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .find(|(name, _)| *name == key)
+                .map_or(Ok(0), |(_, value)| parse_number(value))
+        };
+        if let Some(cpu_stat) = self.read("cpu.stat")? {
+            resource_usage.user_cpu_time = Duration::from_micros(keyed_value(&cpu_stat, "user_usec")?);
+            resource_usage.system_cpu_time = Duration::from_micros(keyed_value(&cpu_stat, "system_usec")?);
+        }
+        if let Some(memory_events) = self.read("memory.events")? {
+            resource_usage.oom_kills = keyed_value(&memory_events, "oom_kill")?;
+        }
+        Ok(resource_usage)
+    }
+
+    /// Kills all processes in the cgroup and removes it.
+    pub async fn remove(mut self) -> Result<(), Error> {
+        let path = self.path.take().expect("ActionCgroup removed twice");
+        remove_cgroup(path).await
+    }
+}
+
+fn parse_number(value: &str) -> Result<u64, Error> {
+    value
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| make_input_err!("Could not parse cgroup value {value:?} : {e:?}"))
+}
+
+async fn remove_cgroup(path: PathBuf) -> Result<(), Error> {
+    // cgroup.kill is not available on older kernels, the processes of the
+    // action have exited by now in the usual case anyway.
+    let _ = std::fs::write(path.join("cgroup.kill"), "1");
+    let mut attempt = 0;
+    loop {
+        match std::fs::remove_dir(&path) {
+            Ok(()) => return Ok(()),
+            // The killed processes are still exiting.
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) && attempt < REMOVE_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(REMOVE_RETRY_DELAY).await;
+            }
+            Err(e) => return Err(e).err_tip(|| format!("Could not remove cgroup {path:?}")),
+        }
+    }
+}
+
+impl Drop for ActionCgroup {
+    fn drop(&mut self) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+        tokio::spawn(async move {
+            if let Err(e) = remove_cgroup(path).await {
+                log::error!("{e:?}");
+            }
+        });
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::process::{ExitStatus, Output};
+#[cfg(target_os = "linux")]
+use std::sync::Once;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use error::{Error, ResultExt};
+use parking_lot::{const_mutex, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process;
+
+/// How often exited processes that were reparented to the worker are reaped.
+#[cfg(target_os = "linux")]
+const ORPHAN_REAP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pids of the children the worker spawned itself. Their exit status is
+/// collected by tokio, so they must not be reaped by `reap_orphans`.
+static OWNED_PIDS: Mutex<Vec<u32>> = const_mutex(Vec::new());
+
+/// A child process spawned with `spawn`. The exit status of the process is
+/// left to tokio until this is dropped.
+pub struct Child {
+    child: process::Child,
+    pid: Option<u32>,
+}
+
+/// Spawns the command. Every child process of the worker has to be spawned
+/// through here, or its exit status may be taken by `reap_orphans`.
+pub fn spawn(command: &mut process::Command) -> io::Result<Child> {
+    // Held until the pid is recorded, so a child that exits right away is
+    // not reaped in the meantime.
+    let mut owned_pids = OWNED_PIDS.lock();
+    let child = command.spawn()?;
+    let pid = child.id();
+    owned_pids.extend(pid);
+    Ok(Child { child, pid })
+}
+
+/// Spawns the command and waits for it to exit.
+pub async fn status(command: &mut process::Command) -> io::Result<ExitStatus> {
+    spawn(command)?.wait().await
+}
+
+/// Spawns the command and collects its output. Unlike with
+/// `tokio::process::Command::output`, stdout and stderr are only collected if
+/// they were set to `Stdio::piped()`.
+pub async fn output(command: &mut process::Command) -> io::Result<Output> {
+    spawn(command)?.wait_with_output().await
+}
+
+async fn read_to_end(reader: Option<impl AsyncRead + Unpin>) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    if let Some(mut reader) = reader {
+        reader.read_to_end(&mut buffer).await?;
+    }
+    Ok(buffer)
+}
+
+impl Child {
+    /// Waits for the process to exit and collects its stdout and stderr, if
+    /// they were piped.
+    pub async fn wait_with_output(mut self) -> io::Result<Output> {
+        let (stdout, stderr) = (self.child.stdout.take(), self.child.stderr.take());
+        let (status, stdout, stderr) = tokio::try_join!(self.child.wait(), read_to_end(stdout), read_to_end(stderr))?;
+        Ok(Output { status, stdout, stderr })
+    }
+}
+
+impl Deref for Child {
+    type Target = process::Child;
+
+    fn deref(&self) -> &Self::Target {
+        &self.child
+    }
+}
+
+impl DerefMut for Child {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.child
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        // Processes that were dropped before tokio collected their exit status
+        // are reaped by tokio in the background, which gives up on processes
+        // that were reaped by `reap_orphans` instead.
+        if let Some(pid) = self.pid {
+            OWNED_PIDS.lock().retain(|owned_pid| *owned_pid != pid);
+        }
+    }
+}
+
+/// Reaps the exited children of the worker it did not spawn itself, which
+/// were reparented to the worker because it is a child subreaper.
+#[cfg(target_os = "linux")]
+fn reap_orphans() {
+    let owned_pids = OWNED_PIDS.lock();
+    loop {
+        // SAFETY: siginfo_t is a plain C struct.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        // The exited child is only looked at, so children owned by tokio are
+        // not reaped.
+        // SAFETY: `info` is valid for writes.
+        let result = unsafe { libc::waitid(libc::P_ALL, 0, &mut info, libc::WEXITED | libc::WNOHANG | libc::WNOWAIT) };
+        // SAFETY: `info` was initialized above.
+        let pid = unsafe { info.si_pid() };
+        if result == -1 || pid == 0 {
+            return;
+        }
+        // The kernel returns the same child until it is reaped, so the other
+        // children are reaped once tokio collected this one.
+        if owned_pids.contains(&(pid as u32)) {
+            return;
+        }
+        // SAFETY: waitpid() has no memory safety requirements.
+        unsafe { libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) };
+    }
+}
+
+/// Makes the worker a child subreaper, so processes that actions leave
+/// behind are reparented to the worker instead of init, and starts reaping
+/// them once they exit. Only has an effect the first time it is called.
+#[cfg(target_os = "linux")]
+pub fn become_subreaper() -> Result<(), Error> {
+    static REAPER: Once = Once::new();
+    let mut result = Ok(());
+    REAPER.call_once(|| {
+        // SAFETY: PR_SET_CHILD_SUBREAPER has no memory safety requirements.
+        if unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1) } == -1 {
+            result = Err(io::Error::last_os_error()).err_tip(|| "Could not make worker a child subreaper");
+            return;
+        }
+        // A thread instead of a task, so reaping does not depend on the
+        // runtime the worker was created in.
+        std::thread::spawn(|| loop {
+            std::thread::sleep(ORPHAN_REAP_INTERVAL);
+            reap_orphans();
+        });
+    });
+    result
+}
@@ -0,0 +1,313 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::process::Stdio;
+use std::sync::Arc;
+
+use error::{make_err, make_input_err, Code, Error, ResultExt};
+use native_link_config::cas_server::{ContainerConfig, ExecutionUserConfig};
+use native_link_util::action_messages::ActionInfo;
+use native_link_util::common::{fs, log};
+use parking_lot::Mutex;
+use regex::Regex;
+use tokio::io::AsyncWriteExt;
+use tokio::process;
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, Semaphore};
+
+use crate::child_process;
+use crate::worker_utils::{or_default, property_value, DEFAULT_CPU_COUNT_PROPERTY, DEFAULT_MEMORY_KB_PROPERTY};
+
+/// The OCI runtime actions are executed with.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_RUNTIME_CMD: &str = "docker";
+
+/// Platform property with the image an action is executed in.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_IMAGE_PROPERTY: &str = "container-image";
+
+/// Prefix Bazel and other clients use for images in `container-image`.
+const DOCKER_IMAGE_PREFIX: &str = "docker://";
+
+/// Format of the image references the runtime is invoked with, eg:
+/// "registry.example.com:5000/example/image:1@sha256:...". Images are
+/// checked against it, so an image can not be taken for an option.
+const IMAGE_REFERENCE_PATTERN: &str = concat!(
+    // Registry.
+    r"^(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?)*(?::[0-9]+)?/)?",
+    // Repository.
+    r"[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*(?:/[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*)*",
+    // Tag.
+    r"(?::\w[\w.-]{0,127})?",
+    // Digest.
+    r"(?:@[A-Za-z][A-Za-z0-9]*(?:[-_+.][A-Za-z][A-Za-z0-9]*)*:[0-9a-fA-F]{32,})?$",
+);
+
+/// State shared by the actions executed in containers of the same image.
+struct ContainerImage {
+    /// Set once the image is available to the runtime. Stays unset if
+    /// pulling the image failed, so the next action tries again.
+    pulled: OnceCell<()>,
+    /// Limits the actions executed at the same time in the image, if
+    /// configured.
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+/// Executes actions in containers of the image from their platform
+/// properties by invoking an OCI runtime with a docker compatible command
+/// line.
+pub struct ContainerRuntime {
+    /// The runtime command and its arguments.
+    runtime_cmd: Vec<String>,
+    image_property: String,
+    image_reference: Regex,
+    memory_kb_property: String,
+    cpu_count_property: String,
+    run_arguments: Vec<String>,
+    max_concurrent_actions_per_image: usize,
+    /// User the actions run as, None for the user of the worker.
+    execution_user: Option<ExecutionUserConfig>,
+    images: Mutex<HashMap<String, Arc<ContainerImage>>>,
+}
+
+impl ContainerRuntime {
+    pub fn new(config: &ContainerConfig, execution_user: Option<ExecutionUserConfig>) -> Result<Self, Error> {
+        let runtime_cmd = if config.runtime_cmd.is_empty() {
+            DEFAULT_RUNTIME_CMD
+        } else {
+            &config.runtime_cmd
+        };
+        let runtime_cmd = shlex::split(runtime_cmd)
+            .filter(|runtime_cmd| !runtime_cmd.is_empty())
+            .ok_or_else(|| make_input_err!("Could not parse container runtime_cmd '{runtime_cmd}'"))?;
+        Ok(Self {
+            runtime_cmd,
+            image_property: or_default(&config.image_property, DEFAULT_IMAGE_PROPERTY),
+            image_reference: Regex::new(IMAGE_REFERENCE_PATTERN)
+                .map_err(|e| make_err!(Code::Internal, "Could not compile image reference pattern : {e:?}"))?,
+            memory_kb_property: or_default(&config.memory_kb_property, DEFAULT_MEMORY_KB_PROPERTY),
+            cpu_count_property: or_default(&config.cpu_count_property, DEFAULT_CPU_COUNT_PROPERTY),
+            run_arguments: config.run_arguments.clone(),
+            max_concurrent_actions_per_image: config.max_concurrent_actions_per_image,
+            execution_user,
+            images: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the image the action is executed in, or None if it is
+    /// executed on the host.
+    pub fn image(&self, action_info: &ActionInfo) -> Result<Option<String>, Error> {
+        let Some(image) = action_info.platform_properties.properties.get(&self.image_property) else {
+            return Ok(None);
+        };
+        let image = image.as_str();
+        let image = image.strip_prefix(DOCKER_IMAGE_PREFIX).unwrap_or(&image);
+        if image.is_empty() {
+            return Ok(None);
+        }
+        if !self.image_reference.is_match(image) {
+            return Err(make_input_err!(
+                "Container image {image:?} is not a valid image reference"
+            ));
+        }
+        Ok(Some(image.to_string()))
+    }
+
+    /// Makes sure the image is available to the runtime and waits until
+    /// another action may be executed in it. The returned permit has to be
+    /// held while the action is executed.
+    pub async fn acquire(&self, image: &str) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        let container_image = self
+            .images
+            .lock()
+            .entry(image.to_string())
+            .or_insert_with(|| {
+                Arc::new(ContainerImage {
+                    pulled: OnceCell::new(),
+                    semaphore: (self.max_concurrent_actions_per_image != 0)
+                        .then(|| Arc::new(Semaphore::new(self.max_concurrent_actions_per_image))),
+                })
+            })
+            .clone();
+        // Actions that need the same image wait for the same pull.
+        container_image
+            .pulled
+            .get_or_try_init(|| self.pull(image))
+            .await
+            .err_tip(|| format!("Could not pull container image {image}"))?;
+        match &container_image.semaphore {
+            Some(semaphore) => {
+                Ok(Some(semaphore.clone().acquire_owned().await.map_err(|e| {
+                    make_err!(Code::Internal, "Container image semaphore closed : {e:?}")
+                })?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Pulls the image, unless the runtime already has it.
+    async fn pull(&self, image: &str) -> Result<(), Error> {
+        let image_exists = child_process::status(
+            self.runtime_command(["image", "inspect", "--", image])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )
+        .await
+        .err_tip(|| format!("Could not execute container runtime {:?}", self.runtime_cmd))?
+        .success();
+        if image_exists {
+            return Ok(());
+        }
+        log::info!("\x1b[0;31mPulling container image\x1b[0m: {image}");
+        let output = child_process::output(
+            self.runtime_command(["pull", "--", image])
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped()),
+        )
+        .await
+        .err_tip(|| format!("Could not execute container runtime {:?}", self.runtime_cmd))?;
+        if !output.status.success() {
+            return Err(make_input_err!(
+                "Container runtime failed to pull image with {} : {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Kills and removes the container `name`, eg: after the runtime was
+    /// killed, which does not stop the container itself.
+    pub async fn remove_container(&self, name: &str) -> Result<(), Error> {
+        let output = child_process::output(
+            self.runtime_command(["rm", "--force", name])
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped()),
+        )
+        .await
+        .err_tip(|| format!("Could not execute container runtime {:?}", self.runtime_cmd))?;
+        if !output.status.success() {
+            return Err(make_err!(
+                Code::Internal,
+                "Container runtime failed to remove container {name} with {} : {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    fn runtime_command<const N: usize>(&self, arguments: [&str; N]) -> process::Command {
+        let mut command = process::Command::new(&self.runtime_cmd[0]);
+        command
+            .args(&self.runtime_cmd[1..])
+            .args(arguments)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        command
+    }
+
+    /// Arguments that execute the arguments of an action in the container
+    /// `name` of the image, with the resource limits the action requested.
+    /// The work directory is mounted at the same path, so paths in the
+    /// action and its environment do not change. The action runs as
+    /// `execution_user` if set, otherwise as the user of the worker, so the
+    /// worker owns its outputs. The environment is read from `env_file`, see
+    /// `write_env_file`.
+    pub fn run_command(
+        &self,
+        name: &str,
+        image: &str,
+        action_info: &ActionInfo,
+        work_directory: &str,
+        working_directory: &str,
+        env_file: &str,
+    ) -> Result<Vec<OsString>, Error> {
+        let mut arguments: Vec<OsString> = self.runtime_cmd.iter().map(OsString::from).collect();
+        arguments.extend(
+            [
+                "run",
+                "--rm",
+                "--name",
+                name,
+                "--volume",
+                &format!("{work_directory}:{work_directory}"),
+                "--workdir",
+                working_directory,
+                "--env-file",
+                env_file,
+            ]
+            .into_iter()
+            .map(OsString::from),
+        );
+        #[cfg(target_family = "unix")]
+        {
+            let (uid, gid) = match self.execution_user {
+                Some(execution_user) => (execution_user.uid, execution_user.gid),
+                // SAFETY: getuid() and getgid() can not fail.
+                None => unsafe { (libc::getuid(), libc::getgid()) },
+            };
+            arguments.push(OsStr::new("--user").to_owned());
+            arguments.push(format!("{uid}:{gid}").into());
+        }
+        if let Some(memory_kb) = property_value(action_info, &self.memory_kb_property)? {
+            let memory_bytes = memory_kb
+                .checked_mul(1024)
+                .ok_or_else(|| make_input_err!("Platform property {} is too large", self.memory_kb_property))?;
+            arguments.push(OsStr::new("--memory").to_owned());
+            arguments.push(memory_bytes.to_string().into());
+        }
+        if let Some(cpu_count) = property_value(action_info, &self.cpu_count_property)? {
+            arguments.push(OsStr::new("--cpus").to_owned());
+            arguments.push(cpu_count.to_string().into());
+        }
+        arguments.extend(self.run_arguments.iter().map(OsString::from));
+        arguments.push(OsStr::new("--").to_owned());
+        arguments.push(image.into());
+        Ok(arguments)
+    }
+}
+
+/// Writes the environment of an action to `path` in the format of the
+/// `--env-file` of the runtime, so it is not visible in the arguments of
+/// the runtime process. The format has no escaping, so names and values
+/// with newlines are rejected.
+pub async fn write_env_file<'a>(
+    path: &str,
+    environment: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<(), Error> {
+    let mut contents = String::new();
+    for (name, value) in environment {
+        if name.is_empty() || name.contains(['=', '\n']) || value.contains('\n') {
+            return Err(make_input_err!(
+                "Environment variable {name:?} can not be passed to a container"
+            ));
+        }
+        contents.push_str(&format!("{name}={value}\n"));
+    }
+    let mut resumeable_file = fs::create_file(OsString::from(path))
+        .await
+        .err_tip(|| format!("Could not create container env file {path}"))?;
+    let file = resumeable_file
+        .as_writer()
+        .await
+        .err_tip(|| format!("Could not get writer for container env file {path}"))?;
+    file.write_all(contents.as_bytes())
+        .await
+        .err_tip(|| format!("Could not write container env file {path}"))?;
+    file.flush()
+        .await
+        .err_tip(|| format!("Could not flush container env file {path}"))
+}
@@ -0,0 +1,283 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use error::{make_err, Code, Error, ResultExt};
+use native_link_config::cas_server::{DirectoryCacheConfig, InputMaterialization};
+use native_link_config::stores::EvictionPolicy;
+use native_link_store::fast_slow_store::FastSlowStore;
+use native_link_store::filesystem_store::{FilesystemStore, FilesystemStoreLease};
+use native_link_util::common::{fs, log, DigestInfo};
+use native_link_util::evicting_map::{EvictingMap, LenEntry};
+use native_link_util::store_trait::Store;
+use tokio::task::spawn_blocking;
+use uuid::Uuid;
+
+use crate::running_actions_manager::{download_to_directory_impl, materialize_file_blocking};
+
+/// Maximum number of directories kept in the cache.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_ENTRIES: u64 = 10_000;
+
+/// Maximum total size of the files of the cached directories.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_BYTES: usize = 10 * 1024 * 1024 * 1024;
+
+/// A fully materialized directory in the cache.
+#[derive(Debug)]
+struct CachedDirectory {
+    path: String,
+    /// Paths relative to `path` and digests of the files in the directory
+    /// and its subdirectories.
+    files: Vec<(String, DigestInfo)>,
+    /// Size of the files directly in the directory. The files of its
+    /// subdirectories are charged to the cached subdirectories.
+    size: usize,
+    /// Set once the directory was removed from the cache, so it is deleted
+    /// when the last action copying it is done.
+    evicted: AtomicBool,
+}
+
+impl CachedDirectory {
+    fn new(path: String, files: Vec<(String, DigestInfo)>) -> Self {
+        let size = files
+            .iter()
+            .filter(|(path, _)| !path.contains('/'))
+            .map(|(_, digest)| digest.size_bytes as usize)
+            .sum();
+        Self {
+            path,
+            files,
+            size,
+            evicted: AtomicBool::new(false),
+        }
+    }
+
+    /// Copies the contents of the directory into the existing directory `to`
+    /// and returns the paths and digests of the files in the copy.
+    async fn copy_to(
+        &self,
+        to: &str,
+        materialization: InputMaterialization,
+    ) -> Result<Vec<(String, DigestInfo)>, Error> {
+        let (from, to_owned) = (self.path.clone(), to.to_string());
+        spawn_blocking(move || {
+            copy_tree(Path::new(&from), Path::new(&to_owned), materialization).map_err(|e| {
+                make_err!(
+                    Code::Internal,
+                    "Could not copy cached directory {from} to {to_owned} : {e:?}"
+                )
+            })
+        })
+        .await
+        .err_tip(|| "Failed to launch spawn_blocking in copy_to")??;
+        Ok(self
+            .files
+            .iter()
+            .map(|(path, digest)| (format!("{to}/{path}"), *digest))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LenEntry for CachedDirectory {
+    #[inline]
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    #[inline]
+    async fn unref(&self) {
+        self.evicted.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for CachedDirectory {
+    fn drop(&mut self) {
+        // `drop()` is also called during shutdown, where the directories are
+        // removed with the rest of the work directory on the next startup.
+        if !self.evicted.load(Ordering::Relaxed) {
+            return;
+        }
+        let path = std::mem::take(&mut self.path);
+        tokio::spawn(async move {
+            log::info!("\x1b[0;31mDirectory Cache\x1b[0m: Deleting: {path}");
+            if let Err(err) = fs::remove_dir_all(&path)
+                .await
+                .err_tip(|| format!("Failed to remove cached directory {path}"))
+            {
+                log::error!("{err:?}");
+            }
+        });
+    }
+}
+
+/// Keeps materialized subdirectories of input roots, keyed by the digest of
+/// their `Directory`, so they can be copied into the work directories of
+/// later actions with their hardlinks instead of being built file by file
+/// again. Input roots themselves are rarely shared between actions, so only
+/// their subdirectories are cached.
+pub struct DirectoryCache {
+    /// Directory the cached directories are created in.
+    root_directory: String,
+    evicting_map: EvictingMap<Arc<CachedDirectory>, SystemTime>,
+}
+
+impl DirectoryCache {
+    pub fn new(config: &DirectoryCacheConfig, root_directory: String) -> Self {
+        let eviction_policy = EvictionPolicy {
+            max_bytes: if config.max_bytes == 0 {
+                DEFAULT_MAX_BYTES
+            } else {
+                config.max_bytes
+            },
+            max_count: if config.max_entries == 0 {
+                DEFAULT_MAX_ENTRIES
+            } else {
+                config.max_entries
+            },
+            ..Default::default()
+        };
+        Self {
+            root_directory,
+            evicting_map: EvictingMap::new(&eviction_policy, SystemTime::now()),
+        }
+    }
+
+    /// Materializes the directory with the digest in `current_directory`,
+    /// which must be empty, from the cache if possible. Returns the paths
+    /// and digests of the files in it.
+    pub async fn download_to_directory<'a>(
+        &'a self,
+        cas_store: Pin<&'a FastSlowStore>,
+        filesystem_store: Pin<&'a FilesystemStore>,
+        lease: &'a FilesystemStoreLease<'a>,
+        materialization: InputMaterialization,
+        digest: &DigestInfo,
+        current_directory: &str,
+    ) -> Result<Vec<(String, DigestInfo)>, Error> {
+        if let Some(cached_directory) = self.evicting_map.get(digest).await {
+            // Checking the files also refreshes them in the store, so the
+            // store keeps the files of the directories that are used.
+            let file_digests: Vec<DigestInfo> = cached_directory.files.iter().map(|(_, digest)| *digest).collect();
+            let has_files = filesystem_store
+                .has_many(&file_digests)
+                .await
+                .err_tip(|| "Checking files of cached directory")?
+                .iter()
+                .all(Option::is_some);
+            if has_files {
+                return cached_directory.copy_to(current_directory, materialization).await;
+            }
+            // The files the store evicted would only be kept on disk by the
+            // cached directory.
+            self.evicting_map
+                .remove_if(digest, |entry| Arc::ptr_eq(entry, &cached_directory))
+                .await;
+        }
+
+        let path = format!("{}/{}", self.root_directory, Uuid::new_v4().simple());
+        fs::create_dir_all(&path)
+            .await
+            .err_tip(|| format!("Could not create cached directory {path}"))?;
+        // The cached directory itself always hardlinks the files of the
+        // store, only its copies are made according to `materialization`.
+        // Its subdirectories are copied from the cache as well.
+        let files = match download_to_directory_impl(
+            cas_store,
+            filesystem_store,
+            lease,
+            Some(self),
+            InputMaterialization::Hardlink,
+            digest,
+            &path,
+        )
+        .await
+        {
+            Ok(files) => files,
+            Err(err) => {
+                if let Err(remove_err) = fs::remove_dir_all(&path).await {
+                    log::error!("Could not remove cached directory {path} : {remove_err:?}");
+                }
+                return Err(err);
+            }
+        };
+        let files = files
+            .into_iter()
+            .map(|(file_path, digest)| {
+                let relative_path = file_path
+                    .strip_prefix(&format!("{path}/"))
+                    .err_tip(|| format!("Expected {file_path} to be in cached directory {path}"))?;
+                Ok((relative_path.to_string(), digest))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let cached_directory = Arc::new(CachedDirectory::new(path, files));
+        self.evicting_map.insert(*digest, cached_directory.clone()).await;
+        cached_directory.copy_to(current_directory, materialization).await
+    }
+
+    /// Removes the cached directories with any of the files, so they are not
+    /// copied for later actions. Used for files an action modified through a
+    /// hardlink, which modified the files of the cached directories as well.
+    pub async fn remove_directories_with_files(&self, digests: &[DigestInfo]) {
+        if digests.is_empty() {
+            return;
+        }
+        let removed = self
+            .evicting_map
+            .remove_all_if(|cached_directory| {
+                cached_directory
+                    .files
+                    .iter()
+                    .any(|(_, digest)| digests.contains(digest))
+            })
+            .await;
+        if removed > 0 {
+            log::warn!("\x1b[0;31mDirectory Cache\x1b[0m: Removed {removed} directories with modified files");
+        }
+    }
+}
+
+// The tree is copied with blocking calls, which is a lot faster than going
+// through the async file system wrappers for every entry.
+fn copy_tree(from: &Path, to: &Path, materialization: InputMaterialization) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let to = to.join(entry.file_name());
+        if file_type.is_dir() {
+            std::fs::create_dir(&to)?;
+            copy_tree(&entry.path(), &to, materialization)?;
+            continue;
+        }
+        #[cfg(target_family = "unix")]
+        if file_type.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &to)?;
+            continue;
+        }
+        materialize_file_blocking(&entry.path(), &to, materialization)?;
+    }
+    Ok(())
+}
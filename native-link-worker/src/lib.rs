@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(target_os = "linux")]
+pub mod cgroup;
+pub mod child_process;
+pub mod container_runtime;
+pub mod directory_cache;
+#[cfg(target_os = "linux")]
+pub mod linux_sandbox;
 pub mod local_worker;
+pub mod persistent_worker;
 pub mod running_actions_manager;
 pub mod worker_api_client_wrapper;
 pub mod worker_utils;
@@ -0,0 +1,446 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use error::{make_input_err, Error};
+use native_link_config::cas_server::{ExecutionUserConfig, LinuxSandboxConfig};
+use native_link_util::action_messages::ActionInfo;
+use tokio::process;
+
+/// Host paths that are bind mounted read-only into the sandbox if none are
+/// configured.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_READ_ONLY_PATHS: &[&str] = &["/bin", "/etc", "/lib", "/lib32", "/lib64", "/sbin", "/usr"];
+
+/// Platform property that controls network access of an action.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_NETWORK_PROPERTY: &str = "dockerNetwork";
+
+/// Value of the network platform property that isolates the action from the
+/// network.
+const NETWORK_OFF: &str = "off";
+
+/// Executes commands inside of user, mount, PID, IPC and (optionally) network
+/// namespaces. The root filesystem of the sandbox is a tmpfs that only holds
+/// the read-only host paths, the work directory of the action, `/dev`, a
+/// private `/tmp` and a `/proc` for the PID namespace.
+pub struct LinuxSandbox {
+    read_only_paths: Vec<PathBuf>,
+    network_property: String,
+}
+
+impl LinuxSandbox {
+    pub fn new(config: &LinuxSandboxConfig) -> Self {
+        let read_only_paths = if config.read_only_paths.is_empty() {
+            DEFAULT_READ_ONLY_PATHS.iter().map(PathBuf::from).collect()
+        } else {
+            config.read_only_paths.iter().map(PathBuf::from).collect()
+        };
+        let network_property = if config.network_property.is_empty() {
+            DEFAULT_NETWORK_PROPERTY.to_string()
+        } else {
+            config.network_property.clone()
+        };
+        Self {
+            read_only_paths,
+            network_property,
+        }
+    }
+
+    /// Whether the action asked to be isolated from the network.
+    pub fn isolates_network(&self, action_info: &ActionInfo) -> bool {
+        action_info
+            .platform_properties
+            .properties
+            .get(&self.network_property)
+            .is_some_and(|value| value.as_str() == NETWORK_OFF)
+    }
+
+    /// Configures `command` to be executed in the sandbox. `sandbox_root` must
+    /// be an empty directory that is used as mount point for the root
+    /// filesystem of the sandbox. `work_directory` is mounted writable at the
+    /// same location in the sandbox and the command is started in
+    /// `current_directory`. If `execution_user` is set, the command must
+    /// already have switched to it when it enters the sandbox.
+    pub fn apply(
+        &self,
+        command: &mut process::Command,
+        sandbox_root: &str,
+        work_directory: &str,
+        current_directory: &str,
+        isolate_network: bool,
+        execution_user: Option<ExecutionUserConfig>,
+    ) -> Result<(), Error> {
+        let setup = SandboxSetup::new(
+            self,
+            sandbox_root,
+            work_directory,
+            current_directory,
+            isolate_network,
+            execution_user,
+        )?;
+        // SAFETY: `SandboxSetup::enter` only performs system calls on data that
+        // was prepared before the fork.
+        unsafe {
+            command.pre_exec(move || setup.enter());
+        }
+        Ok(())
+    }
+}
+
+enum MountKind {
+    Bind { source: CString, read_only: bool },
+    Tmpfs,
+    Proc,
+}
+
+/// A mount to perform in the sandbox.
+struct Mount {
+    kind: MountKind,
+    /// Directories to create before mounting, parents first.
+    directories: Vec<CString>,
+    /// Whether the mount point is a file instead of a directory.
+    is_file: bool,
+    target: CString,
+}
+
+/// Everything needed to enter the sandbox. All allocations happen in
+/// `SandboxSetup::new` because `SandboxSetup::enter` runs between fork and
+/// exec.
+struct SandboxSetup {
+    unshare_flags: libc::c_int,
+    setgroups_path: CString,
+    setgroups: &'static [u8],
+    uid_map_path: CString,
+    uid_map: Vec<u8>,
+    gid_map_path: CString,
+    gid_map: Vec<u8>,
+    root: CString,
+    tmpfs: CString,
+    proc: CString,
+    mounts: Vec<Mount>,
+    loopback: Option<[libc::c_char; libc::IFNAMSIZ]>,
+    current_directory: CString,
+    slash: CString,
+    dot: CString,
+}
+
+fn to_cstring(path: &Path) -> Result<CString, Error> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| make_input_err!("Invalid path {path:?} for sandbox : {e:?}"))
+}
+
+impl SandboxSetup {
+    fn new(
+        sandbox: &LinuxSandbox,
+        sandbox_root: &str,
+        work_directory: &str,
+        current_directory: &str,
+        isolate_network: bool,
+        execution_user: Option<ExecutionUserConfig>,
+    ) -> Result<Self, Error> {
+        let root = Path::new(sandbox_root);
+        let work_directory = Path::new(work_directory);
+
+        let mut mounts = Vec::new();
+        for read_only_path in &sandbox.read_only_paths {
+            if work_directory.starts_with(read_only_path) {
+                return Err(make_input_err!(
+                    "Work directory {work_directory:?} may not be inside of sandbox read-only path {read_only_path:?}"
+                ));
+            }
+            let Ok(metadata) = std::fs::metadata(read_only_path) else {
+                continue;
+            };
+            mounts.push((read_only_path.clone(), !metadata.is_dir(), Some(true)));
+        }
+        mounts.push((PathBuf::from("/dev"), false, Some(false)));
+        mounts.push((work_directory.to_path_buf(), false, Some(false)));
+        mounts.push((PathBuf::from("/tmp"), false, None));
+        mounts.push((PathBuf::from("/proc"), false, None));
+        // Parents have to be mounted before their children, so the mount
+        // points of children are created on the mounted parent.
+        mounts.sort();
+        let mounts = mounts
+            .into_iter()
+            .map(|(path, is_file, bind_read_only)| {
+                let in_root = |path: &Path| to_cstring(&root.join(path.strip_prefix("/").unwrap_or(path)));
+                let kind = match bind_read_only {
+                    Some(read_only) => MountKind::Bind {
+                        source: to_cstring(&path)?,
+                        read_only,
+                    },
+                    None if path == Path::new("/proc") => MountKind::Proc,
+                    None => MountKind::Tmpfs,
+                };
+                let mut directories = path
+                    .ancestors()
+                    .skip(usize::from(is_file))
+                    .filter(|ancestor| *ancestor != Path::new("/"))
+                    .map(in_root)
+                    .collect::<Result<Vec<_>, Error>>()?;
+                directories.reverse();
+                Ok(Mount {
+                    kind,
+                    directories,
+                    is_file,
+                    target: in_root(&path)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut unshare_flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWIPC;
+        let mut loopback = None;
+        if isolate_network {
+            unshare_flags |= libc::CLONE_NEWNET;
+            let mut name = [0; libc::IFNAMSIZ];
+            for (dst, src) in name.iter_mut().zip(b"lo") {
+                *dst = *src as libc::c_char;
+            }
+            loopback = Some(name);
+        }
+        let (uid, gid) = match execution_user {
+            Some(execution_user) => (execution_user.uid, execution_user.gid),
+            // SAFETY: getuid() and getgid() are always successful.
+            None => unsafe { (libc::getuid(), libc::getgid()) },
+        };
+        Ok(Self {
+            unshare_flags,
+            setgroups_path: CString::new("/proc/self/setgroups").unwrap(),
+            setgroups: b"deny",
+            uid_map_path: CString::new("/proc/self/uid_map").unwrap(),
+            uid_map: format!("{uid} {uid} 1").into_bytes(),
+            gid_map_path: CString::new("/proc/self/gid_map").unwrap(),
+            gid_map: format!("{gid} {gid} 1").into_bytes(),
+            root: to_cstring(root)?,
+            tmpfs: CString::new("tmpfs").unwrap(),
+            proc: CString::new("proc").unwrap(),
+            mounts,
+            loopback,
+            current_directory: to_cstring(Path::new(current_directory))?,
+            slash: CString::new("/").unwrap(),
+            dot: CString::new(".").unwrap(),
+        })
+    }
+
+    /// Moves the calling process into the sandbox. Called between fork and
+    /// exec, so this may only perform async-signal-safe operations.
+    fn enter(&self) -> io::Result<()> {
+        // SAFETY: All pointers passed to the system calls below point to
+        // null-terminated strings or buffers owned by `self`.
+        unsafe {
+            check(libc::unshare(self.unshare_flags))?;
+            write_file(&self.setgroups_path, self.setgroups)?;
+            write_file(&self.uid_map_path, &self.uid_map)?;
+            write_file(&self.gid_map_path, &self.gid_map)?;
+
+            // The PID namespace only applies to children, so fork once more to
+            // make the action the init process of the namespace. The
+            // intermediate process forwards SIGTERM to the action and the exit
+            // status of the action to the worker. The handler is installed
+            // before the fork, so no SIGTERM is missed.
+            check(libc::signal(
+                libc::SIGTERM,
+                forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            ) as isize)?;
+            let pid = check(libc::fork())?;
+            if pid != 0 {
+                // The action gets its own process group, so the forwarded
+                // signal does not reach the intermediate process again. Both
+                // sides set it, so it is set before the first signal is
+                // forwarded.
+                libc::setpgid(pid, pid);
+                ACTION_PID.store(pid, Ordering::Relaxed);
+                wait_and_exit(pid);
+            }
+            check(libc::setpgid(0, 0))?;
+            // If the intermediate process is killed (eg: once the grace period
+            // is over), the action and with it every process in the namespace
+            // is killed.
+            check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+
+            check(libc::mount(
+                ptr::null(),
+                self.slash.as_ptr(),
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+            check(libc::mount(
+                self.tmpfs.as_ptr(),
+                self.root.as_ptr(),
+                self.tmpfs.as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                ptr::null(),
+            ))?;
+            for mount in &self.mounts {
+                for directory in &mount.directories {
+                    if libc::mkdir(directory.as_ptr(), 0o755) == -1 && *libc::__errno_location() != libc::EEXIST {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if mount.is_file {
+                    let fd = check(libc::open(
+                        mount.target.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    ))?;
+                    libc::close(fd);
+                }
+                match &mount.kind {
+                    MountKind::Bind { source, read_only } => {
+                        check(libc::mount(
+                            source.as_ptr(),
+                            mount.target.as_ptr(),
+                            ptr::null(),
+                            libc::MS_BIND | libc::MS_REC,
+                            ptr::null(),
+                        ))?;
+                        if *read_only {
+                            remount_read_only(&mount.target)?;
+                        }
+                    }
+                    MountKind::Tmpfs => {
+                        check(libc::mount(
+                            self.tmpfs.as_ptr(),
+                            mount.target.as_ptr(),
+                            self.tmpfs.as_ptr(),
+                            libc::MS_NOSUID | libc::MS_NODEV,
+                            ptr::null(),
+                        ))?;
+                    }
+                    MountKind::Proc => {
+                        check(libc::mount(
+                            self.proc.as_ptr(),
+                            mount.target.as_ptr(),
+                            self.proc.as_ptr(),
+                            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                            ptr::null(),
+                        ))?;
+                    }
+                }
+            }
+            if let Some(loopback) = &self.loopback {
+                bring_up_interface(loopback)?;
+            }
+
+            // Swap the root filesystem and detach the old one, so it can not be
+            // reached from inside the sandbox.
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(libc::syscall(libc::SYS_pivot_root, self.dot.as_ptr(), self.dot.as_ptr()) as libc::c_int)?;
+            check(libc::umount2(self.dot.as_ptr(), libc::MNT_DETACH))?;
+            check(libc::chdir(self.current_directory.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+fn check<T: Default + PartialOrd>(result: T) -> io::Result<T> {
+    if result < T::default() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result)
+}
+
+unsafe fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+    let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+    let result = check(libc::write(fd, contents.as_ptr().cast(), contents.len()));
+    libc::close(fd);
+    result.map(|_| ())
+}
+
+/// Bind mounts are created with the flags of the source mount, which may not
+/// be dropped inside of a user namespace. So they are carried over when
+/// making the mount read-only.
+unsafe fn remount_read_only(target: &CStr) -> io::Result<()> {
+    let mut stat: libc::statvfs = std::mem::zeroed();
+    check(libc::statvfs(target.as_ptr(), &mut stat))?;
+    let mut flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY;
+    for (st_flag, ms_flag) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat.f_flag & st_flag != 0 {
+            flags |= ms_flag;
+        }
+    }
+    check(libc::mount(
+        ptr::null(),
+        target.as_ptr(),
+        ptr::null(),
+        flags,
+        ptr::null(),
+    ))?;
+    Ok(())
+}
+
+unsafe fn bring_up_interface(name: &[libc::c_char; libc::IFNAMSIZ]) -> io::Result<()> {
+    let socket = check(libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0))?;
+    let mut request: libc::ifreq = std::mem::zeroed();
+    request.ifr_name = *name;
+    let mut result = check(libc::ioctl(socket, libc::SIOCGIFFLAGS, &mut request));
+    if result.is_ok() {
+        request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+        result = check(libc::ioctl(socket, libc::SIOCSIFFLAGS, &request));
+    }
+    libc::close(socket);
+    result.map(|_| ())
+}
+
+/// Pid of the action in the intermediate process, 0 in any other process.
+static ACTION_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Signal handler of the intermediate process, which sends the signal on to
+/// the process group of the action. Only async-signal-safe operations may be
+/// performed here.
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let pid = ACTION_PID.load(Ordering::Relaxed);
+    // The action inherits the handler until it executes, where it is reset.
+    if pid <= 0 {
+        return;
+    }
+    // SAFETY: kill() is async-signal-safe.
+    unsafe {
+        libc::kill(-pid, signal);
+    }
+}
+
+/// Waits for the action and exits the same way it did.
+unsafe fn wait_and_exit(pid: libc::pid_t) -> ! {
+    // Release the file descriptors inherited from the worker, in particular
+    // the pipe the worker uses to wait for the exec of the action.
+    libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0);
+    let mut status = 0;
+    while libc::waitpid(pid, &mut status, 0) == -1 {
+        if *libc::__errno_location() != libc::EINTR {
+            libc::_exit(1);
+        }
+    }
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        libc::signal(signal, libc::SIG_DFL);
+        libc::kill(libc::getpid(), signal);
+        libc::_exit(128 + signal);
+    }
+    libc::_exit(libc::WEXITSTATUS(status));
+}
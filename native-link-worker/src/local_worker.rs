@@ -25,8 +25,8 @@ use futures::stream::FuturesUnordered;
 use futures::{select, Future, FutureExt, StreamExt, TryFutureExt};
 use native_link_config::cas_server::LocalWorkerConfig;
 use native_link_store::fast_slow_store::FastSlowStore;
-use native_link_util::action_messages::{ActionResult, ActionStage};
-use native_link_util::common::{fs, log};
+use native_link_util::action_messages::{ActionInfoHashKey, ActionResult, ActionStage};
+use native_link_util::common::{fs, log, DigestInfo};
 use native_link_util::digest_hasher::DigestHasherFunc;
 use native_link_util::metrics_utils::{
     AsyncCounterWrapper, Collector, CollectorState, CounterWithTime, MetricsComponent, Registry,
@@ -44,6 +44,7 @@ use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::transport::Channel as TonicChannel;
 use tonic::Streaming;
 
+use crate::child_process;
 use crate::running_actions_manager::{
     ExecutionConfiguration, Metrics as RunningActionManagerMetrics, RunningAction, RunningActionsManager,
     RunningActionsManagerArgs, RunningActionsManagerImpl,
@@ -67,6 +68,11 @@ const DEFAULT_ENDPOINT_TIMEOUT_S: f32 = 5.;
 /// If this value gets modified the documentation in `cas_server.rs` must also be updated.
 const DEFAULT_MAX_ACTION_TIMEOUT: Duration = Duration::from_secs(1200); // 20 mins.
 
+/// Default amount of time the processes of an action get to exit after being
+/// asked to terminate.
+/// If this value gets modified the documentation in `cas_server.rs` must also be updated.
+const DEFAULT_TERMINATION_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 struct LocalWorkerImpl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> {
     config: &'a LocalWorkerConfig,
     // According to the tonic documentation it is a cheap operation to clone this.
@@ -92,14 +98,15 @@ async fn preconditions_met(precondition_script: Option<String>) -> Result<(), Er
     //       future to pass useful information through?  Or perhaps we'll
     //       have a pre-condition and a pre-execute script instead, although
     //       arguably entrypoint_cmd already gives us that.
-    let precondition_process = process::Command::new(precondition_script)
-        .kill_on_drop(true)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .env_clear()
-        .spawn()
-        .err_tip(|| format!("Could not execute precondition command {:?}", precondition_script))?;
+    let precondition_process = child_process::spawn(
+        process::Command::new(precondition_script)
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .env_clear(),
+    )
+    .err_tip(|| format!("Could not execute precondition command {:?}", precondition_script))?;
     let output = precondition_process.wait_with_output().await?;
     if output.status.code() == Some(0) {
         Ok(())
@@ -152,6 +159,12 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
             if let Err(e) = grpc_client
                 .keep_alive(KeepAliveRequest {
                     worker_id: self.worker_id.clone(),
+                    recent_input_root_digests: self
+                        .running_actions_manager
+                        .recent_input_root_digests()
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
                 })
                 .await
             {
@@ -206,6 +219,22 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                         Update::KeepAlive(()) => {
                             self.metrics.keep_alives_received.inc();
                         }
+                        Update::KillActionRequest(kill_action_request) => {
+                            self.metrics.kill_actions_received.inc();
+                            let action_digest: DigestInfo = kill_action_request
+                                .action_digest
+                                .err_tip(|| "Expected action_digest to exist in KillActionRequest")?
+                                .try_into()?;
+                            let action_id = ActionInfoHashKey {
+                                instance_name: kill_action_request.instance_name,
+                                digest: action_digest,
+                                salt: kill_action_request.salt,
+                            }
+                            .get_hash();
+                            if let Err(err) = self.running_actions_manager.kill_action(&action_id).await {
+                                log::warn!("Could not kill action requested by the scheduler : {:?}", err);
+                            }
+                        }
                         Update::StartAction(start_execute) => {
                             self.metrics.start_actions_received.inc();
                             let add_future_channel = add_future_channel.clone();
@@ -260,6 +289,7 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                                                 log::error!("\x1b[0;31mError saving action in store\x1b[0m: {} - {:?}", err, action_digest);
                                             }
                                         }
+                                        let flaky_failure_reason = action_result.flaky_failure_reason.take().unwrap_or_default();
                                         let action_stage = ActionStage::Completed(action_result);
                                         grpc_client.execution_response(
                                             ExecuteResult{
@@ -268,6 +298,7 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                                                 action_digest,
                                                 salt,
                                                 result: Some(execute_result::Result::ExecuteResponse(action_stage.into())),
+                                                flaky_failure_reason,
                                             }
                                         )
                                         .await
@@ -280,6 +311,7 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                                             action_digest,
                                             salt,
                                             result: Some(execute_result::Result::InternalError(e.into())),
+                                            flaky_failure_reason: String::new(),
                                         }).await.err_tip(|| "Error calling execution_response with error")?;
                                     },
                                 }
@@ -357,11 +389,27 @@ pub async fn new_local_worker(
     } else {
         Duration::from_secs(config.max_action_timeout as u64)
     };
+    let termination_grace_period = if config.termination_grace_period == 0 {
+        DEFAULT_TERMINATION_GRACE_PERIOD
+    } else {
+        Duration::from_secs(config.termination_grace_period as u64)
+    };
     let running_actions_manager = Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
         root_work_directory: config.work_directory.clone(),
         execution_configuration: ExecutionConfiguration {
             entrypoint_cmd,
             additional_environment: config.additional_environment.clone(),
+            flaky_failures: config.flaky_failures.clone(),
+            linux_sandbox: config.linux_sandbox.clone(),
+            cgroup: config.cgroup.clone(),
+            termination_grace_period,
+            max_stdout_size: config.max_stdout_size,
+            max_stderr_size: config.max_stderr_size,
+            persistent_workers: config.persistent_workers.clone(),
+            container: config.container.clone(),
+            directory_cache: config.directory_cache.clone(),
+            input_materialization: config.input_materialization,
+            execution_user: config.execution_user,
         },
         cas_store: fast_slow_store,
         ac_store,
@@ -531,6 +579,7 @@ struct Metrics {
     start_actions_received: CounterWithTime,
     disconnects_received: CounterWithTime,
     keep_alives_received: CounterWithTime,
+    kill_actions_received: CounterWithTime,
     preconditions: AsyncCounterWrapper,
     running_actions_manager_metrics: Weak<RunningActionManagerMetrics>,
 }
@@ -541,6 +590,7 @@ impl Metrics {
             start_actions_received: CounterWithTime::default(),
             disconnects_received: CounterWithTime::default(),
             keep_alives_received: CounterWithTime::default(),
+            kill_actions_received: CounterWithTime::default(),
             preconditions: AsyncCounterWrapper::default(),
             running_actions_manager_metrics,
         }
@@ -574,6 +624,11 @@ impl MetricsComponent for Metrics {
             &self.keep_alives_received,
             "Total number of keep-alives received from the scheduler.",
         );
+        c.publish(
+            "kill_actions_received",
+            &self.kill_actions_received,
+            "Total number of requests to kill a running action received from the scheduler.",
+        );
         c.publish(
             "preconditions",
             &self.preconditions,
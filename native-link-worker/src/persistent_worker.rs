@@ -0,0 +1,695 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use error::{make_err, make_input_err, Code, Error, ResultExt};
+use native_link_config::cas_server::PersistentWorkersConfig;
+use native_link_store::ac_utils::get_and_decode_digest;
+use native_link_util::action_messages::ActionInfo;
+use native_link_util::common::{fs, log, DigestInfo};
+use native_link_util::digest_hasher::{DigestHasher, DigestHasherFunc};
+use native_link_util::store_trait::Store;
+use parking_lot::Mutex;
+use prost::Message;
+use proto::blaze::worker::{Input, WorkRequest, WorkResponse};
+use proto::build::bazel::remote::execution::v2::{Command as ProtoCommand, Directory as ProtoDirectory};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::process;
+use uuid::Uuid;
+
+use crate::child_process;
+use crate::worker_utils::or_default;
+
+/// Platform property with the key of the persistent worker of an action.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_KEY_PROPERTY: &str = "persistentWorkerKey";
+
+/// Platform property with the protocol spoken with the persistent worker.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_PROTOCOL_PROPERTY: &str = "persistentWorkerProtocol";
+
+/// Number of idle workers kept running per key.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_IDLE_WORKERS_PER_KEY: usize = 1;
+
+/// Number of idle workers kept running across all keys.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_IDLE_WORKERS: usize = 16;
+
+/// Seconds after which an idle worker is stopped.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_IDLE_TIMEOUT_SECONDS: u64 = 600;
+
+/// Number of bytes at the end of the stderr log of a worker that are added
+/// to the error of a request the worker failed.
+const MAX_REPORTED_LOG_SIZE: u64 = 16 * 1024;
+
+/// Argument that tells a tool to run as a persistent worker.
+const PERSISTENT_WORKER_ARGUMENT: &str = "--persistent_worker";
+
+/// Node property Bazel marks the tool inputs of an action with.
+const TOOL_INPUT_NODE_PROPERTY: &str = "bazel_tool_input";
+
+/// Maximum size of a work response, so a misbehaving worker can not use up
+/// the memory of the worker.
+const MAX_WORK_RESPONSE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The framing of the messages exchanged with a persistent worker.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum WorkerProtocol {
+    /// Length delimited `WorkRequest` and `WorkResponse` protos.
+    Proto,
+    /// `WorkRequest` and `WorkResponse` in their JSON mapping.
+    Json,
+}
+
+/// Persistent workers are only reused for actions with the same key.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PersistentWorkerKey {
+    /// Value of the key platform property of the action.
+    key: String,
+    /// Digest of the paths and digests of the tool inputs of the action.
+    tool_inputs_digest: DigestInfo,
+    /// Arguments the worker process is started with.
+    startup_arguments: Vec<String>,
+    environment: Vec<(String, String)>,
+    protocol: WorkerProtocol,
+}
+
+/// An action that is executed as a request to a persistent worker.
+pub struct PersistentAction {
+    pub key: PersistentWorkerKey,
+    pub request: WorkRequest,
+}
+
+/// A worker waiting for the next action with its key.
+struct IdleWorker {
+    key: PersistentWorkerKey,
+    worker: PersistentWorker,
+    idle_since: Instant,
+}
+
+/// Keeps the idle persistent workers of the actions.
+pub struct PersistentWorkers {
+    /// Directory the directories of the workers are created in.
+    root_directory: String,
+    key_property: String,
+    protocol_property: String,
+    max_idle_workers_per_key: usize,
+    max_idle_workers: usize,
+    idle_timeout: Duration,
+    /// The idle workers of all keys, the longest idle first.
+    idle_workers: Mutex<VecDeque<IdleWorker>>,
+}
+
+impl PersistentWorkers {
+    pub fn new(config: &PersistentWorkersConfig, root_directory: String) -> Self {
+        Self {
+            root_directory,
+            key_property: or_default(&config.key_property, DEFAULT_KEY_PROPERTY),
+            protocol_property: or_default(&config.protocol_property, DEFAULT_PROTOCOL_PROPERTY),
+            max_idle_workers_per_key: if config.max_idle_workers_per_key == 0 {
+                DEFAULT_MAX_IDLE_WORKERS_PER_KEY
+            } else {
+                config.max_idle_workers_per_key
+            },
+            max_idle_workers: if config.max_idle_workers == 0 {
+                DEFAULT_MAX_IDLE_WORKERS
+            } else {
+                config.max_idle_workers
+            },
+            idle_timeout: Duration::from_secs(if config.idle_timeout == 0 {
+                DEFAULT_IDLE_TIMEOUT_SECONDS
+            } else {
+                config.idle_timeout as u64
+            }),
+            idle_workers: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Removes the workers that were idle for longer than the idle timeout.
+    /// The removed workers have to be dropped after the lock is released.
+    fn take_expired_workers(&self, idle_workers: &mut VecDeque<IdleWorker>) -> Vec<PersistentWorker> {
+        let mut expired_workers = Vec::new();
+        while let Some(idle_worker) = idle_workers.front() {
+            if idle_worker.idle_since.elapsed() < self.idle_timeout {
+                break;
+            }
+            expired_workers.extend(idle_workers.pop_front().map(|idle_worker| idle_worker.worker));
+        }
+        expired_workers
+    }
+
+    /// Returns the request to send to a persistent worker for the action, or
+    /// None if the action can not be executed in a persistent worker.
+    /// `work_directory` must contain the inputs of the action.
+    pub async fn persistent_action(
+        &self,
+        cas_store: Pin<&dyn Store>,
+        action_info: &ActionInfo,
+        command: &ProtoCommand,
+        work_directory: &str,
+    ) -> Result<Option<PersistentAction>, Error> {
+        let Some(key) = action_info.platform_properties.properties.get(&self.key_property) else {
+            return Ok(None);
+        };
+        let Some((flagfile_argument, arguments)) = command.arguments.split_last() else {
+            return Ok(None);
+        };
+        let Some(flagfile) = flagfile_argument
+            .strip_prefix('@')
+            .or_else(|| flagfile_argument.strip_prefix("--flagfile="))
+        else {
+            return Ok(None);
+        };
+        // The working directory of a worker can not change between requests,
+        // so it is always the input root.
+        if !command.working_directory.is_empty() && command.working_directory != "." {
+            return Ok(None);
+        }
+        let protocol = match action_info
+            .platform_properties
+            .properties
+            .get(&self.protocol_property)
+            .map(|value| value.as_str())
+            .as_deref()
+        {
+            None | Some("proto") => WorkerProtocol::Proto,
+            Some("json") => WorkerProtocol::Json,
+            Some(protocol) => {
+                return Err(make_input_err!("Unsupported persistent worker protocol '{protocol}'"));
+            }
+        };
+        let flagfile_contents = fs::read(format!("{work_directory}/{flagfile}"))
+            .await
+            .err_tip(|| format!("Could not read flagfile {flagfile} of persistent worker action"))?;
+        let request_arguments = String::from_utf8(flagfile_contents)
+            .map_err(|e| make_input_err!("Flagfile {flagfile} is not valid utf8 : {e:?}"))?
+            .lines()
+            .map(String::from)
+            .collect();
+        let (inputs, tool_inputs_digest) =
+            collect_inputs(cas_store, &action_info.input_root_digest, action_info.digest_function).await?;
+        let startup_arguments = arguments
+            .iter()
+            .cloned()
+            .chain(std::iter::once(PERSISTENT_WORKER_ARGUMENT.to_string()))
+            .collect();
+        let environment = command
+            .environment_variables
+            .iter()
+            .map(|environment_variable| (environment_variable.name.clone(), environment_variable.value.clone()))
+            .collect();
+        Ok(Some(PersistentAction {
+            key: PersistentWorkerKey {
+                key: key.as_str().into_owned(),
+                tool_inputs_digest,
+                startup_arguments,
+                environment,
+                protocol,
+            },
+            request: WorkRequest {
+                arguments: request_arguments,
+                inputs,
+                ..Default::default()
+            },
+        }))
+    }
+
+    /// Takes an idle worker for `key`, or creates a new one if there is
+    /// none. The process of a new worker is started by its first request,
+    /// so the tool inputs can be moved into its directory before.
+    pub async fn take(&self, key: &PersistentWorkerKey) -> Result<PersistentWorker, Error> {
+        let (maybe_worker, expired_workers) = {
+            let mut idle_workers = self.idle_workers.lock();
+            let expired_workers = self.take_expired_workers(&mut idle_workers);
+            let maybe_worker = idle_workers
+                .iter()
+                .rposition(|idle_worker| &idle_worker.key == key)
+                .and_then(|index| idle_workers.remove(index))
+                .map(|idle_worker| idle_worker.worker);
+            (maybe_worker, expired_workers)
+        };
+        drop(expired_workers);
+        if let Some(worker) = maybe_worker {
+            return Ok(worker);
+        }
+        let directory = format!("{}/{}", self.root_directory, Uuid::new_v4().simple());
+        fs::create_dir_all(&directory)
+            .await
+            .err_tip(|| format!("Could not create persistent worker directory {directory}"))?;
+        Ok(PersistentWorker {
+            directory,
+            process: None,
+        })
+    }
+
+    /// Makes a worker that answered its request available to other actions
+    /// with the same key. Workers beyond the idle limits are stopped, the
+    /// longest idle first.
+    pub fn put(&self, key: PersistentWorkerKey, worker: PersistentWorker) {
+        let stopped_workers = {
+            let mut idle_workers = self.idle_workers.lock();
+            let mut stopped_workers = self.take_expired_workers(&mut idle_workers);
+            let idle_workers_of_key = idle_workers.iter().filter(|idle_worker| idle_worker.key == key).count();
+            if idle_workers_of_key < self.max_idle_workers_per_key {
+                idle_workers.push_back(IdleWorker {
+                    key,
+                    worker,
+                    idle_since: Instant::now(),
+                });
+            } else {
+                stopped_workers.push(worker);
+            }
+            while idle_workers.len() > self.max_idle_workers {
+                stopped_workers.extend(idle_workers.pop_front().map(|idle_worker| idle_worker.worker));
+            }
+            stopped_workers
+        };
+        drop(stopped_workers);
+    }
+}
+
+struct WorkerProcess {
+    child: child_process::Child,
+    /// The worker is started in its own session, so the processes it
+    /// started can be killed together with it.
+    process_group: u32,
+    stdin: process::ChildStdin,
+    stdout: process::ChildStdout,
+    /// Data read from the worker that is not part of a response yet.
+    buffer: BytesMut,
+}
+
+impl WorkerProcess {
+    /// Kills every process in the process group of the worker.
+    fn kill_process_group(&self) {
+        #[cfg(target_family = "unix")]
+        // SAFETY: kill() has no memory safety requirements.
+        if unsafe { libc::kill(-(self.process_group as libc::pid_t), libc::SIGKILL) } == -1 {
+            let e = std::io::Error::last_os_error();
+            // ESRCH means there is no process left in the group.
+            if e.raw_os_error() != Some(libc::ESRCH) {
+                log::error!(
+                    "Could not kill process group {} of persistent worker : {e:?}",
+                    self.process_group
+                );
+            }
+        }
+    }
+}
+
+impl Drop for WorkerProcess {
+    fn drop(&mut self) {
+        // Dropping the child only kills the worker itself.
+        self.kill_process_group();
+    }
+}
+
+/// A long-lived worker process and the directory it runs in. The process
+/// is killed and the directory removed when dropped.
+pub struct PersistentWorker {
+    directory: String,
+    process: Option<WorkerProcess>,
+}
+
+impl PersistentWorker {
+    /// The working directory of the worker process, the inputs of a request
+    /// have to be placed in it.
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    fn log_path(&self) -> String {
+        format!("{}.log", self.directory)
+    }
+
+    /// Sends `request` to the worker and waits for its response. The worker
+    /// process is started if it is not running yet. Errors include the end
+    /// of the stderr log of the worker.
+    pub async fn execute(&mut self, key: &PersistentWorkerKey, request: &WorkRequest) -> Result<WorkResponse, Error> {
+        let result = self.send_request(key, request).await;
+        if result.is_ok() {
+            return result;
+        }
+        let log = self.log_tail().await.unwrap_or_else(|e| format!("{e:?}"));
+        result.err_tip(|| format!("Stderr of persistent worker : {log}"))
+    }
+
+    /// Returns the end of the stderr log of the worker.
+    async fn log_tail(&self) -> Result<String, Error> {
+        let log_path = self.log_path();
+        let mut log_file = tokio::fs::File::open(&log_path)
+            .await
+            .err_tip(|| format!("Could not open persistent worker log {log_path}"))?;
+        let log_size = log_file
+            .metadata()
+            .await
+            .err_tip(|| format!("Could not read metadata of persistent worker log {log_path}"))?
+            .len();
+        log_file
+            .seek(SeekFrom::Start(log_size.saturating_sub(MAX_REPORTED_LOG_SIZE)))
+            .await
+            .err_tip(|| format!("Could not seek in persistent worker log {log_path}"))?;
+        let mut log = Vec::new();
+        log_file
+            .read_to_end(&mut log)
+            .await
+            .err_tip(|| format!("Could not read persistent worker log {log_path}"))?;
+        Ok(String::from_utf8_lossy(&log).into_owned())
+    }
+
+    async fn send_request(&mut self, key: &PersistentWorkerKey, request: &WorkRequest) -> Result<WorkResponse, Error> {
+        if self.process.is_none() {
+            self.process = Some(self.start(key).await?);
+        }
+        let process = self
+            .process
+            .as_mut()
+            .err_tip(|| "Expected worker process to be started")?;
+        let message = match key.protocol {
+            WorkerProtocol::Proto => request.encode_length_delimited_to_vec(),
+            WorkerProtocol::Json => {
+                let mut message = serde_json::to_vec(&JsonWorkRequest::from(request))
+                    .map_err(|e| make_err!(Code::Internal, "Could not encode json work request : {e:?}"))?;
+                message.push(b'\n');
+                message
+            }
+        };
+        process
+            .stdin
+            .write_all(&message)
+            .await
+            .err_tip(|| "Could not send work request to persistent worker")?;
+        process
+            .stdin
+            .flush()
+            .await
+            .err_tip(|| "Could not send work request to persistent worker")?;
+        let response = loop {
+            let maybe_response = match key.protocol {
+                WorkerProtocol::Proto => decode_proto_response(&mut process.buffer)?,
+                WorkerProtocol::Json => decode_json_response(&mut process.buffer)?,
+            };
+            if let Some(response) = maybe_response {
+                break response;
+            }
+            if process.buffer.len() > MAX_WORK_RESPONSE_SIZE {
+                return Err(make_err!(
+                    Code::Internal,
+                    "Work response of persistent worker is larger than {MAX_WORK_RESPONSE_SIZE} bytes"
+                ));
+            }
+            let sz = process
+                .stdout
+                .read_buf(&mut process.buffer)
+                .await
+                .err_tip(|| "Error reading work response of persistent worker")?;
+            if sz == 0 {
+                return Err(make_err!(
+                    Code::Internal,
+                    "Persistent worker exited before sending a work response"
+                ));
+            }
+        };
+        if response.request_id != request.request_id {
+            return Err(make_err!(
+                Code::Internal,
+                "Persistent worker answered request {} instead of {}",
+                response.request_id,
+                request.request_id
+            ));
+        }
+        Ok(response)
+    }
+
+    /// Kills the worker process and the processes it started and waits for
+    /// the worker to exit, so it does not touch its directory anymore.
+    pub async fn stop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            process.kill_process_group();
+            if let Err(e) = process.child.kill().await {
+                log::error!("Could not kill persistent worker in {} : {e:?}", self.directory);
+            }
+        }
+    }
+
+    async fn start(&self, key: &PersistentWorkerKey) -> Result<WorkerProcess, Error> {
+        let (program, arguments) = key
+            .startup_arguments
+            .split_first()
+            .err_tip(|| "Expected persistent worker to have startup arguments")?;
+        // The worker writes its diagnostics to stderr, which is kept in a log
+        // file next to its directory.
+        let log_path = self.log_path();
+        let log_file = tokio::fs::File::create(&log_path)
+            .await
+            .err_tip(|| format!("Could not create persistent worker log {log_path}"))?
+            .into_std()
+            .await;
+        log::info!(
+            "\x1b[0;31mWorker Starting Persistent Worker\x1b[0m: {:?}",
+            key.startup_arguments
+        );
+        let mut command = process::Command::new(program);
+        command
+            .args(arguments)
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::from(log_file))
+            .current_dir(&self.directory)
+            .env_clear()
+            .envs(key.environment.iter().map(|(name, value)| (name, value)));
+        // Like actions, every worker gets its own session and with it its
+        // own process group.
+        #[cfg(target_family = "unix")]
+        // SAFETY: setsid() is async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let mut child = child_process::spawn(&mut command)
+            .err_tip(|| format!("Could not start persistent worker {:?}", key.startup_arguments))?;
+        let process_group = child
+            .id()
+            .err_tip(|| "Expected persistent worker to have an id, this should never happen")?;
+        let stdin = child
+            .stdin
+            .take()
+            .err_tip(|| "Expected stdin to exist on persistent worker this should never happen")?;
+        let stdout = child
+            .stdout
+            .take()
+            .err_tip(|| "Expected stdout to exist on persistent worker this should never happen")?;
+        Ok(WorkerProcess {
+            child,
+            process_group,
+            stdin,
+            stdout,
+            buffer: BytesMut::new(),
+        })
+    }
+}
+
+impl Drop for PersistentWorker {
+    fn drop(&mut self) {
+        // Dropping the process kills it and the processes it started.
+        drop(self.process.take());
+        let log_path = self.log_path();
+        let directory = std::mem::take(&mut self.directory);
+        let remove_directory = move || {
+            if let Err(e) = std::fs::remove_dir_all(&directory) {
+                log::error!("Could not remove persistent worker directory {directory} : {e:?}");
+            }
+            let _ = std::fs::remove_file(log_path);
+        };
+        // Removing the directory blocks, so it is not done on the runtime.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(remove_directory)),
+            Err(_) => remove_directory(),
+        }
+    }
+}
+
+/// Returns the files in the input root and the digest of the tool inputs
+/// among them.
+async fn collect_inputs(
+    cas_store: Pin<&dyn Store>,
+    input_root_digest: &DigestInfo,
+    hasher: DigestHasherFunc,
+) -> Result<(Vec<Input>, DigestInfo), Error> {
+    let mut inputs = Vec::new();
+    let mut tool_inputs = Vec::new();
+    let mut directories = vec![(String::new(), *input_root_digest)];
+    while let Some((path, digest)) = directories.pop() {
+        let directory = get_and_decode_digest::<ProtoDirectory>(cas_store, &digest)
+            .await
+            .err_tip(|| format!("Converting digest to Directory for persistent worker input {path:?}"))?;
+        let join = |name: &str| {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{path}/{name}")
+            }
+        };
+        for file in directory.files {
+            let file_path = join(&file.name);
+            let digest: DigestInfo = file
+                .digest
+                .err_tip(|| "Expected Digest to exist in Directory::file::digest")?
+                .try_into()
+                .err_tip(|| "In Directory::file::digest")?;
+            let is_tool_input = file.node_properties.is_some_and(|node_properties| {
+                node_properties
+                    .properties
+                    .iter()
+                    .any(|property| property.name == TOOL_INPUT_NODE_PROPERTY)
+            });
+            if is_tool_input {
+                tool_inputs.push((file_path.clone(), digest));
+            }
+            inputs.push(Input {
+                path: file_path,
+                digest: Bytes::copy_from_slice(&digest.packed_hash),
+            });
+        }
+        for directory_node in directory.directories {
+            let digest: DigestInfo = directory_node
+                .digest
+                .err_tip(|| "Expected Digest to exist in Directory::directories::digest")?
+                .try_into()
+                .err_tip(|| "In Directory::directories::digest")?;
+            directories.push((join(&directory_node.name), digest));
+        }
+    }
+    tool_inputs.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    let mut digest_hasher: DigestHasher = hasher.into();
+    for (path, digest) in &tool_inputs {
+        digest_hasher.update(path.as_bytes());
+        digest_hasher.update(&[0]);
+        digest_hasher.update(&digest.packed_hash);
+        digest_hasher.update(&digest.size_bytes.to_le_bytes());
+    }
+    Ok((inputs, digest_hasher.finalize_digest(0)))
+}
+
+/// Decodes a length delimited `WorkResponse` from the start of `buffer`,
+/// or returns None if it was not fully received yet.
+fn decode_proto_response(buffer: &mut BytesMut) -> Result<Option<WorkResponse>, Error> {
+    // The length prefix is a varint, which is complete once a byte without
+    // the continuation bit was received.
+    if !buffer.iter().take(10).any(|byte| byte & 0x80 == 0) {
+        return Ok(None);
+    }
+    let length = prost::decode_length_delimiter(&buffer[..])
+        .map_err(|e| make_err!(Code::Internal, "Invalid length of work response : {e:?}"))?;
+    if length > MAX_WORK_RESPONSE_SIZE {
+        return Err(make_err!(
+            Code::Internal,
+            "Work response of persistent worker is larger than {MAX_WORK_RESPONSE_SIZE} bytes"
+        ));
+    }
+    let prefix_length = prost::length_delimiter_len(length);
+    if buffer.len() < prefix_length + length {
+        return Ok(None);
+    }
+    let message = buffer.split_to(prefix_length + length);
+    WorkResponse::decode(&message[prefix_length..])
+        .map(Some)
+        .map_err(|e| make_err!(Code::Internal, "Invalid work response : {e:?}"))
+}
+
+/// Decodes a JSON `WorkResponse` from the start of `buffer`, or returns None
+/// if it was not fully received yet.
+fn decode_json_response(buffer: &mut BytesMut) -> Result<Option<WorkResponse>, Error> {
+    let mut responses = serde_json::Deserializer::from_slice(buffer).into_iter::<JsonWorkResponse>();
+    match responses.next() {
+        Some(Ok(response)) => {
+            let length = responses.byte_offset();
+            drop(buffer.split_to(length));
+            Ok(Some(response.into()))
+        }
+        Some(Err(e)) if e.is_eof() => Ok(None),
+        Some(Err(e)) => Err(make_err!(Code::Internal, "Invalid json work response : {e:?}")),
+        None => Ok(None),
+    }
+}
+
+/// `Input` in the JSON mapping of protos.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonInput<'a> {
+    path: &'a str,
+    digest: String,
+}
+
+/// `WorkRequest` in the JSON mapping of protos.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonWorkRequest<'a> {
+    arguments: &'a [String],
+    inputs: Vec<JsonInput<'a>>,
+    request_id: i32,
+}
+
+impl<'a> From<&'a WorkRequest> for JsonWorkRequest<'a> {
+    fn from(request: &'a WorkRequest) -> Self {
+        Self {
+            arguments: &request.arguments,
+            inputs: request
+                .inputs
+                .iter()
+                .map(|input| JsonInput {
+                    path: &input.path,
+                    digest: base64::engine::general_purpose::STANDARD.encode(&input.digest),
+                })
+                .collect(),
+            request_id: request.request_id,
+        }
+    }
+}
+
+/// `WorkResponse` in the JSON mapping of protos.
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct JsonWorkResponse {
+    exit_code: i32,
+    output: String,
+    request_id: i32,
+    was_cancelled: bool,
+}
+
+impl From<JsonWorkResponse> for WorkResponse {
+    fn from(response: JsonWorkResponse) -> Self {
+        Self {
+            exit_code: response.exit_code,
+            output: response.output,
+            request_id: response.request_id,
+            was_cancelled: response.was_cancelled,
+        }
+    }
+}
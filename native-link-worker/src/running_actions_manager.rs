@@ -19,6 +19,7 @@ use std::ffi::{OsStr, OsString};
 use std::fmt::Debug;
 #[cfg(target_family = "unix")]
 use std::fs::Permissions;
+use std::io::SeekFrom;
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::Path;
@@ -26,25 +27,30 @@ use std::pin::Pin;
 use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use error::{make_err, make_input_err, Code, Error, ResultExt};
 use filetime::{set_file_mtime, FileTime};
 use formatx::Template;
-use futures::future::{try_join, try_join3, try_join_all, BoxFuture, Future, FutureExt, TryFutureExt};
+use futures::future::{try_join, try_join3, try_join_all, BoxFuture, Fuse, Future, FutureExt, TryFutureExt};
 use futures::stream::{FuturesUnordered, StreamExt, TryStreamExt};
-use native_link_config::cas_server::{EnvironmentSource, UploadActionResultConfig, UploadCacheResultsStrategy};
+use native_link_config::cas_server::{
+    CgroupConfig, ContainerConfig, DirectoryCacheConfig, EnvironmentSource, ExecutionUserConfig, FlakyFailuresConfig,
+    InputMaterialization, LinuxSandboxConfig, PersistentWorkersConfig, UploadActionResultConfig,
+    UploadCacheResultsStrategy,
+};
 use native_link_store::ac_utils::{
     compute_buf_digest, compute_digest, get_and_decode_digest, serialize_and_upload_message, upload_buf_to_store,
     upload_file_to_store, ESTIMATED_DIGEST_SIZE,
 };
 use native_link_store::fast_slow_store::FastSlowStore;
-use native_link_store::filesystem_store::{FileEntry, FilesystemStore};
+use native_link_store::filesystem_store::{FileEntry, FilesystemStore, FilesystemStoreLease};
 use native_link_store::grpc_store::GrpcStore;
 use native_link_util::action_messages::{
-    to_execute_response, ActionInfo, ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath, SymlinkInfo,
+    to_execute_response, ActionInfo, ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath,
+    NodeProperties, OutputTruncation, SymlinkInfo,
 };
 use native_link_util::common::{fs, log, DigestInfo, JoinHandleDropGuard};
 use native_link_util::digest_hasher::DigestHasherFunc;
@@ -57,10 +63,11 @@ use proto::build::bazel::remote::execution::v2::{
     DirectoryNode, ExecuteResponse, FileNode, SymlinkNode, Tree as ProtoTree, UpdateActionResultRequest,
 };
 use proto::com::github::trace_machina::native_link::remote_execution::{HistoricalExecuteResponse, StartExecute};
+use regex::bytes::Regex;
 use relative_path::RelativePath;
 use scopeguard::{guard, ScopeGuard};
 use serde::Deserialize;
-use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::process;
 use tokio::sync::{oneshot, watch};
 use tokio::task::spawn_blocking;
@@ -69,6 +76,15 @@ use tokio_stream::wrappers::ReadDirStream;
 use tonic::Request;
 use uuid::Uuid;
 
+#[cfg(target_os = "linux")]
+use crate::cgroup::{ActionCgroup, Cgroups};
+use crate::child_process;
+use crate::container_runtime::{write_env_file, ContainerRuntime};
+use crate::directory_cache::DirectoryCache;
+#[cfg(target_os = "linux")]
+use crate::linux_sandbox::LinuxSandbox;
+use crate::persistent_worker::{PersistentAction, PersistentWorkers};
+
 pub type ActionId = [u8; 32];
 
 /// For simplicity we use a fixed exit code for cases when our program is terminated
@@ -80,6 +96,18 @@ const EXIT_CODE_FOR_SIGNAL: i32 = 9;
 /// should reflect it.
 const DEFAULT_HISTORICAL_RESULTS_STRATEGY: UploadCacheResultsStrategy = UploadCacheResultsStrategy::FailuresOnly;
 
+/// Maximum number of recently materialized input root digests remembered and
+/// reported to the scheduler.
+const MAX_RECENT_INPUT_ROOT_DIGESTS: usize = 256;
+
+/// Size of the buffer the stdout and stderr of an action are read into.
+const OUTPUT_STREAM_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Number of bytes at the end of stderr that are matched against the flaky
+/// stderr regexes.
+/// Note: If this changes, remember to change the documentation in the config.
+const MAX_FLAKY_STDERR_SIZE: u64 = 1024 * 1024;
+
 /// Valid string reasons for a failure.
 /// Note: If these change, the documentation should be updated.
 #[allow(non_camel_case_types)]
@@ -87,6 +115,8 @@ const DEFAULT_HISTORICAL_RESULTS_STRATEGY: UploadCacheResultsStrategy = UploadCa
 enum SideChannelFailureReason {
     /// Task should be considered timedout.
     timeout,
+    /// Task should be considered to have failed in a flaky way.
+    retry,
 }
 
 /// This represents the json data that can be passed from the running process
@@ -105,7 +135,9 @@ struct SideChannelInfo {
 /// We require the `FilesystemStore` to be the `fast` store of `FastSlowStore`. This is for
 /// efficiency reasons. We will request the `FastSlowStore` to populate the entry then we will
 /// assume the `FilesystemStore` has the file available immediately after and hardlink the file
-/// to a new location.
+/// to a new location. The files are pinned in the `FilesystemStore` until the whole directory is
+/// built, so they cannot be evicted in between. Files that need another mode or mtime than the
+/// read-only file of the store are copied instead.
 // Sadly we cannot use `async fn` here because the rust compiler cannot determine the auto traits
 // of the future. So we need to force this function to return a dynamic future instead.
 // see: https://github.com/rust-lang/rust/issues/78649
@@ -115,6 +147,37 @@ pub fn download_to_directory<'a>(
     digest: &'a DigestInfo,
     current_directory: &'a str,
 ) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        let lease = filesystem_store.get_ref().lease();
+        download_to_directory_impl(
+            cas_store,
+            filesystem_store,
+            &lease,
+            None,
+            InputMaterialization::Hardlink,
+            digest,
+            current_directory,
+        )
+        .await
+        .map(|_| ())
+    }
+    .boxed()
+}
+
+/// Same as `download_to_directory()`, but the files are placed according to
+/// `materialization` and the subdirectories are materialized through the
+/// `directory_cache` if it is set. The files are pinned with `lease`, which
+/// has to be held until the caller is done with them. Returns the paths and
+/// digests of all files in the directory.
+pub(crate) fn download_to_directory_impl<'a>(
+    cas_store: Pin<&'a FastSlowStore>,
+    filesystem_store: Pin<&'a FilesystemStore>,
+    lease: &'a FilesystemStoreLease<'a>,
+    directory_cache: Option<&'a DirectoryCache>,
+    materialization: InputMaterialization,
+    digest: &'a DigestInfo,
+    current_directory: &'a str,
+) -> BoxFuture<'a, Result<Vec<(String, DigestInfo)>, Error>> {
     async move {
         let directory = get_and_decode_digest::<ProtoDirectory>(cas_store, digest)
             .await
@@ -128,14 +191,14 @@ pub fn download_to_directory<'a>(
                 .try_into()
                 .err_tip(|| "In Directory::file::digest")?;
             let dest = format!("{}/{}", current_directory, file.name);
-            let (mtime, mut unix_mode) = match file.node_properties {
+            let (mtime, unix_mode) = match file.node_properties {
                 Some(properties) => (properties.mtime, properties.unix_mode),
                 None => (None, None),
             };
-            #[cfg_attr(target_family = "windows", allow(unused_assignments))]
-            if file.is_executable {
-                unix_mode = Some(unix_mode.unwrap_or(0o444) | 0o111);
-            }
+            let is_executable = file.is_executable;
+            // Pinned before it is populated, so the file cannot be evicted
+            // again before it is materialized.
+            lease.pin(digest);
             futures.push(
                 cas_store
                     .populate_fast_store(digest)
@@ -143,18 +206,49 @@ pub fn download_to_directory<'a>(
                         let file_entry = filesystem_store
                             .get_file_entry_for_digest(&digest)
                             .await
-                            .err_tip(|| "During hard link")?;
-                        file_entry
-                            .get_file_path_locked(|src| fs::hard_link(src, &dest))
+                            .err_tip(|| "During materialization")?;
+                        let has_node_properties = mtime.is_some() || unix_mode.is_some();
+                        let link_dest = dest.clone();
+                        let is_hardlinked = file_entry
+                            .get_file_path_locked(|src| async move {
+                                if materialization == InputMaterialization::Hardlink
+                                    && can_hardlink(&src, is_executable, has_node_properties).await?
+                                {
+                                    fs::hard_link(src, &link_dest).await?;
+                                    return Ok(true);
+                                }
+                                // Files that need their own mode or mtime
+                                // get a private copy instead of a hardlink.
+                                let materialization = match materialization {
+                                    InputMaterialization::Hardlink => InputMaterialization::Reflink,
+                                    materialization => materialization,
+                                };
+                                materialize_file(src, link_dest, materialization).await?;
+                                Ok(false)
+                            })
                             .await
-                            .map_err(|e| make_err!(Code::Internal, "Could not make hardlink, {e:?} : {dest}"))?;
+                            .map_err(|e| make_err!(Code::Internal, "Could not materialize file, {e:?} : {dest}"))?;
+                        if is_hardlinked {
+                            return Ok(vec![(dest, digest)]);
+                        }
                         #[cfg(target_family = "unix")]
-                        if let Some(unix_mode) = unix_mode {
+                        {
+                            // Copies of the read-only files of the store are
+                            // writable, except where they replace hardlinks,
+                            // because the cached directories hardlink them.
+                            let mut unix_mode = unix_mode.unwrap_or(0o644);
+                            if is_executable {
+                                unix_mode |= 0o111;
+                            }
+                            if materialization == InputMaterialization::Hardlink {
+                                unix_mode &= !0o222;
+                            }
                             fs::set_permissions(&dest, Permissions::from_mode(unix_mode))
                                 .await
                                 .err_tip(|| format!("Could not set unix mode in download_to_directory {dest}"))?;
                         }
                         if let Some(mtime) = mtime {
+                            let dest = dest.clone();
                             spawn_blocking(move || {
                                 set_file_mtime(&dest, FileTime::from_unix_time(mtime.seconds, mtime.nanos as u32))
                                     .err_tip(|| format!("Failed to set mtime in download_to_directory {dest}"))
@@ -162,7 +256,7 @@ pub fn download_to_directory<'a>(
                             .await
                             .err_tip(|| "Failed to launch spawn_blocking in download_to_directory")??;
                         }
-                        Ok(())
+                        Ok(vec![(dest, digest)])
                     })
                     .map_err(move |e| e.append(format!("for digest {digest:?}")))
                     .boxed(),
@@ -181,10 +275,33 @@ pub fn download_to_directory<'a>(
                     fs::create_dir(&new_directory_path)
                         .await
                         .err_tip(|| format!("Could not create directory {new_directory_path}"))?;
-                    download_to_directory(cas_store, filesystem_store, &digest, &new_directory_path)
-                        .await
-                        .err_tip(|| format!("in download_to_directory : {new_directory_path}"))?;
-                    Ok(())
+                    match directory_cache {
+                        Some(directory_cache) => {
+                            directory_cache
+                                .download_to_directory(
+                                    cas_store,
+                                    filesystem_store,
+                                    lease,
+                                    materialization,
+                                    &digest,
+                                    &new_directory_path,
+                                )
+                                .await
+                        }
+                        None => {
+                            download_to_directory_impl(
+                                cas_store,
+                                filesystem_store,
+                                lease,
+                                None,
+                                materialization,
+                                &digest,
+                                &new_directory_path,
+                            )
+                            .await
+                        }
+                    }
+                    .err_tip(|| format!("in download_to_directory : {new_directory_path}"))
                 }
                 .boxed(),
             );
@@ -198,18 +315,168 @@ pub fn download_to_directory<'a>(
                     fs::symlink(&symlink_node.target, &dest)
                         .await
                         .err_tip(|| format!("Could not create symlink {} -> {}", symlink_node.target, dest))?;
-                    Ok(())
+                    Ok(Vec::new())
                 }
                 .boxed(),
             );
         }
 
-        while futures.try_next().await?.is_some() {}
-        Ok(())
+        let mut files = Vec::new();
+        while let Some(directory_files) = futures.try_next().await? {
+            files.extend(directory_files);
+        }
+        Ok(files)
     }
     .boxed()
 }
 
+/// Returns whether the file `src` of the `FilesystemStore` can be hardlinked
+/// into the work directory as it is. Hardlinks share the file with the store
+/// and other actions, so its mode and mtime are never changed for an action.
+async fn can_hardlink(src: &OsString, is_executable: bool, has_node_properties: bool) -> Result<bool, Error> {
+    if has_node_properties {
+        return Ok(false);
+    }
+    let metadata = fs::metadata(src)
+        .await
+        .err_tip(|| format!("Could not read metadata of {src:?}"))?;
+    #[cfg(target_family = "unix")]
+    let has_mode = metadata.mode() & 0o111 == if is_executable { 0o111 } else { 0 };
+    #[cfg(target_family = "windows")]
+    let has_mode = {
+        let _ = is_executable;
+        true
+    };
+    Ok(metadata.permissions().readonly() && has_mode)
+}
+
+/// Places the file `src` of the `FilesystemStore` at `dest`.
+async fn materialize_file(src: OsString, dest: String, materialization: InputMaterialization) -> Result<(), Error> {
+    match materialization {
+        InputMaterialization::Hardlink => fs::hard_link(src, &dest).await,
+        InputMaterialization::Copy => fs::copy(src, &dest).await.map(|_| ()),
+        InputMaterialization::Reflink => {
+            let _permit = fs::OPEN_FILE_SEMAPHORE
+                .acquire()
+                .await
+                .map_err(|e| make_err!(Code::Internal, "Open file semaphore closed {:?}", e))?;
+            spawn_blocking(move || reflink_file(Path::new(&src), Path::new(&dest)).map_err(Into::into))
+                .await
+                .err_tip(|| "Failed to launch spawn_blocking in materialize_file")?
+        }
+    }
+}
+
+/// Blocking version of `materialize_file()`.
+pub(crate) fn materialize_file_blocking(
+    src: &Path,
+    dest: &Path,
+    materialization: InputMaterialization,
+) -> std::io::Result<()> {
+    match materialization {
+        InputMaterialization::Hardlink => std::fs::hard_link(src, dest),
+        InputMaterialization::Copy => std::fs::copy(src, dest).map(|_| ()),
+        InputMaterialization::Reflink => reflink_file(src, dest),
+    }
+}
+
+/// Clones `src` to `dest` with a copy-on-write reflink, or copies it if the
+/// filesystem does not support reflinks.
+fn reflink_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let src_file = std::fs::File::open(src)?;
+        let dest_file = std::fs::OpenOptions::new().write(true).create_new(true).open(dest)?;
+        // SAFETY: Both file descriptors stay open for the duration of the call.
+        if unsafe { libc::ioctl(dest_file.as_raw_fd(), libc::FICLONE as _, src_file.as_raw_fd()) } == 0 {
+            return dest_file.set_permissions(src_file.metadata()?.permissions());
+        }
+        drop(dest_file);
+        std::fs::remove_file(dest)?;
+    }
+    std::fs::copy(src, dest).map(|_| ())
+}
+
+/// An input file of an action that may share its file with the
+/// `FilesystemStore` and its attributes before the action was executed.
+struct LinkedInput {
+    path: String,
+    digest: DigestInfo,
+    inode: u64,
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+fn inode(metadata: &std::fs::Metadata) -> u64 {
+    #[cfg(target_family = "unix")]
+    return metadata.ino();
+    #[cfg(target_family = "windows")]
+    {
+        let _ = metadata;
+        0
+    }
+}
+
+/// Reads the attributes of the hardlinked input files of an action.
+fn stat_linked_inputs(files: Vec<(String, DigestInfo)>) -> Vec<LinkedInput> {
+    files
+        .into_iter()
+        .filter_map(|(path, digest)| {
+            let metadata = std::fs::symlink_metadata(&path).ok()?;
+            Some(LinkedInput {
+                inode: inode(&metadata),
+                size: metadata.len(),
+                mtime: metadata.modified().ok(),
+                path,
+                digest,
+            })
+        })
+        .collect()
+}
+
+/// Returns the hardlinked input files that were modified since
+/// `stat_linked_inputs()`. Inputs the action replaced or removed are not
+/// considered modified.
+fn modified_linked_inputs(linked_inputs: Vec<LinkedInput>) -> Vec<LinkedInput> {
+    linked_inputs
+        .into_iter()
+        .filter(|linked_input| {
+            let Ok(metadata) = std::fs::symlink_metadata(&linked_input.path) else {
+                return false;
+            };
+            inode(&metadata) == linked_input.inode
+                && (metadata.len() != linked_input.size
+                    || metadata.modified().ok() != linked_input.mtime
+                    || !metadata.permissions().readonly())
+        })
+        .collect()
+}
+
+/// Gives the directories under `path` and the files that are not hardlinked
+/// from the `FilesystemStore` to the user actions are executed as. Hardlinked
+/// files stay owned by the worker, so actions can not make them writable.
+#[cfg(target_family = "unix")]
+fn give_to_execution_user(
+    path: &Path,
+    execution_user: ExecutionUserConfig,
+    materialization: InputMaterialization,
+) -> std::io::Result<()> {
+    let chown = |path: &Path| std::os::unix::fs::lchown(path, Some(execution_user.uid), Some(execution_user.gid));
+    chown(path)?;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            give_to_execution_user(&entry.path(), execution_user, materialization)?;
+        } else if file_type.is_symlink() || materialization != InputMaterialization::Hardlink {
+            chown(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(target_family = "windows")]
 async fn is_executable(_file_handle: &fs::FileSlot, _full_path: &impl AsRef<Path>) -> Result<bool, Error> {
     static EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "bat", "com"];
@@ -228,13 +495,177 @@ async fn is_executable(file_handle: &fs::FileSlot, full_path: &impl AsRef<Path>)
     Ok((metadata.mode() & 0o001) != 0)
 }
 
+/// Writes everything read from `reader` to a new file at `path`. Once
+/// `max_size` bytes were written, the rest of the stream is read but
+/// discarded and a note that it was truncated is appended to the file.
+/// A `max_size` of zero means there is no limit. Returns how the stream was
+/// truncated, if it was.
+async fn write_output_stream(
+    mut reader: impl AsyncRead + Unpin,
+    path: String,
+    max_size: u64,
+    stream_name: &'static str,
+) -> Result<Option<OutputTruncation>, Error> {
+    let mut resumeable_file = fs::create_file(OsString::from(&path))
+        .await
+        .err_tip(|| format!("Could not create {stream_name} file {path}"))?;
+    let file = resumeable_file
+        .as_writer()
+        .await
+        .err_tip(|| format!("Could not get writer for {stream_name} file {path}"))?;
+    let mut buf = BytesMut::with_capacity(OUTPUT_STREAM_READ_BUFFER_SIZE);
+    let mut total_size: u64 = 0;
+    loop {
+        buf.clear();
+        let sz = reader
+            .read_buf(&mut buf)
+            .await
+            .err_tip(|| format!("Error reading {stream_name} stream"))?;
+        if sz == 0 {
+            break; // EOF.
+        }
+        let keep = if max_size == 0 {
+            sz
+        } else {
+            max_size.saturating_sub(total_size).min(sz as u64) as usize
+        };
+        total_size += sz as u64;
+        file.write_all(&buf[..keep])
+            .await
+            .err_tip(|| format!("Error writing {stream_name} file {path}"))?;
+    }
+    let maybe_output_truncation = if max_size != 0 && total_size > max_size {
+        let note = format!("\n[{stream_name} truncated by the worker: kept {max_size} of {total_size} bytes]\n");
+        file.write_all(note.as_bytes())
+            .await
+            .err_tip(|| format!("Error writing {stream_name} file {path}"))?;
+        Some(OutputTruncation {
+            stream_name: stream_name.to_string(),
+            kept_bytes: max_size,
+            total_bytes: total_size,
+        })
+    } else {
+        None
+    };
+    file.flush()
+        .await
+        .err_tip(|| format!("Error flushing {stream_name} file {path}"))?;
+    Ok(maybe_output_truncation)
+}
+
+/// Moves all entries of the directory `from` into the directory `to`.
+async fn move_directory_contents(from: &str, to: &str) -> Result<(), Error> {
+    // Note: The directory is closed before moving the entries, so it does not
+    // hold a file descriptor while waiting for another one.
+    let entries = {
+        let (_permit, dir_handle) = fs::read_dir(from)
+            .await
+            .err_tip(|| format!("Error reading dir {from}"))?
+            .into_inner();
+        ReadDirStream::new(dir_handle)
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .try_collect::<Vec<_>>()
+            .await
+            .err_tip(|| format!("Error while iterating directory {from}"))?
+    };
+    for name in entries {
+        let from_path = Path::new(from).join(&name);
+        let to_path = Path::new(to).join(&name);
+        fs::rename(&from_path, &to_path)
+            .await
+            .err_tip(|| format!("Could not move {from_path:?} to {to_path:?}"))?;
+    }
+    Ok(())
+}
+
+/// Uploads the stdout or stderr written to `maybe_file`. An action that was
+/// killed has no output, which is uploaded as an empty blob.
+async fn upload_output_stream(
+    cas_store: Pin<&dyn Store>,
+    maybe_file: Option<String>,
+    hasher: DigestHasherFunc,
+) -> Result<DigestInfo, Error> {
+    let Some(path) = maybe_file else {
+        let data = Bytes::new();
+        let digest = compute_buf_digest(&data, &mut hasher.into())
+            .await
+            .err_tip(|| "Computing empty output digest")?;
+        upload_buf_to_store(cas_store, digest, data).await?;
+        return Ok(digest);
+    };
+    let resumeable_file = fs::open_file(OsString::from(&path), u64::MAX)
+        .await
+        .err_tip(|| format!("Could not open output file {path}"))?;
+    Ok(upload_file(
+        resumeable_file,
+        cas_store,
+        &path,
+        hasher,
+        OutputNodeProperties::default(),
+    )
+    .await?
+    .digest)
+}
+
+/// The node properties of the outputs the command requested with
+/// `output_node_properties`.
+#[derive(Clone, Copy, Default)]
+struct OutputNodeProperties {
+    mtime: bool,
+    unix_mode: bool,
+}
+
+impl OutputNodeProperties {
+    fn new(output_node_properties: &[String]) -> Result<Self, Error> {
+        let mut requested = Self::default();
+        for property in output_node_properties {
+            match property.as_str() {
+                "mtime" => requested.mtime = true,
+                "unix_mode" => requested.unix_mode = true,
+                _ => return Err(make_input_err!("Output node property '{property}' is not supported")),
+            }
+        }
+        Ok(requested)
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.mtime && !self.unix_mode
+    }
+
+    /// Returns the requested properties of the node `metadata` belongs to,
+    /// or None if no properties were requested.
+    fn node_properties(
+        &self,
+        metadata: &std::fs::Metadata,
+        full_path: &impl AsRef<Path>,
+    ) -> Result<Option<NodeProperties>, Error> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let mut node_properties = NodeProperties::default();
+        if self.mtime {
+            node_properties.mtime = Some(
+                metadata
+                    .modified()
+                    .err_tip(|| format!("While reading mtime of {:?}", full_path.as_ref()))?,
+            );
+        }
+        #[cfg(target_family = "unix")]
+        if self.unix_mode {
+            node_properties.unix_mode = Some(metadata.mode() & 0o7777);
+        }
+        Ok(Some(node_properties))
+    }
+}
+
 async fn upload_file(
     mut resumeable_file: fs::ResumeableFileSlot<'static>,
     cas_store: Pin<&dyn Store>,
     full_path: impl AsRef<Path> + Debug,
     hasher: DigestHasherFunc,
+    output_node_properties: OutputNodeProperties,
 ) -> Result<FileInfo, Error> {
-    let (digest, is_executable, resumeable_file) = {
+    let (digest, is_executable, node_properties, resumeable_file) = {
         let (digest, mut resumeable_file) = JoinHandleDropGuard::new(tokio::spawn(async move {
             let file_handle = resumeable_file
                 .as_reader()
@@ -255,12 +686,23 @@ async fn upload_file(
             .await
             .err_tip(|| "Could not get reader from file slot in RunningActionsManager::upload_file()")?;
         let is_executable = is_executable(file_handle.get_ref(), &full_path).await?;
+        let node_properties = if output_node_properties.is_empty() {
+            None
+        } else {
+            let metadata = file_handle
+                .get_ref()
+                .as_ref()
+                .metadata()
+                .await
+                .err_tip(|| format!("While reading metadata for {:?}", full_path.as_ref()))?;
+            output_node_properties.node_properties(&metadata, &full_path)?
+        };
         file_handle
             .get_mut()
             .rewind()
             .await
             .err_tip(|| "Could not rewind file")?;
-        (digest, is_executable, resumeable_file)
+        (digest, is_executable, node_properties, resumeable_file)
     };
     upload_file_to_store(cas_store, digest, resumeable_file)
         .await
@@ -278,6 +720,7 @@ async fn upload_file(
         name_or_path: NameOrPath::Name(name),
         digest,
         is_executable,
+        node_properties,
     })
 }
 
@@ -325,6 +768,7 @@ fn upload_directory<'a, P: AsRef<Path> + Debug + Send + Sync + Clone + 'a>(
     full_dir_path: P,
     full_work_directory: &'a str,
     hasher: DigestHasherFunc,
+    output_node_properties: OutputNodeProperties,
 ) -> BoxFuture<'a, Result<(Directory, VecDeque<ProtoDirectory>), Error>> {
     Box::pin(async move {
         let file_futures = FuturesUnordered::new();
@@ -353,37 +797,43 @@ fn upload_directory<'a, P: AsRef<Path> + Debug + Send + Sync + Clone + 'a>(
                 if file_type.is_dir() {
                     let full_dir_path = full_dir_path.clone();
                     dir_futures.push(
-                        upload_directory(cas_store, full_path.clone(), full_work_directory, hasher)
-                            .and_then(|(dir, all_dirs)| async move {
-                                let directory_name = full_path
-                                    .file_name()
-                                    .err_tip(|| format!("Expected file_name to exist on {full_dir_path:?}"))?
-                                    .to_str()
-                                    .err_tip(|| {
-                                        make_err!(Code::Internal, "Could not convert {:?} to string", full_dir_path)
-                                    })?
-                                    .to_string();
-
-                                let digest = serialize_and_upload_message(&dir, cas_store, &mut hasher.into())
-                                    .await
-                                    .err_tip(|| format!("for {full_path:?}"))?;
-
-                                Result::<(DirectoryNode, VecDeque<Directory>), Error>::Ok((
-                                    DirectoryNode {
-                                        name: directory_name,
-                                        digest: Some(digest.into()),
-                                    },
-                                    all_dirs,
-                                ))
-                            })
-                            .boxed(),
+                        upload_directory(
+                            cas_store,
+                            full_path.clone(),
+                            full_work_directory,
+                            hasher,
+                            output_node_properties,
+                        )
+                        .and_then(|(dir, all_dirs)| async move {
+                            let directory_name = full_path
+                                .file_name()
+                                .err_tip(|| format!("Expected file_name to exist on {full_dir_path:?}"))?
+                                .to_str()
+                                .err_tip(|| {
+                                    make_err!(Code::Internal, "Could not convert {:?} to string", full_dir_path)
+                                })?
+                                .to_string();
+
+                            let digest = serialize_and_upload_message(&dir, cas_store, &mut hasher.into())
+                                .await
+                                .err_tip(|| format!("for {full_path:?}"))?;
+
+                            Result::<(DirectoryNode, VecDeque<Directory>), Error>::Ok((
+                                DirectoryNode {
+                                    name: directory_name,
+                                    digest: Some(digest.into()),
+                                },
+                                all_dirs,
+                            ))
+                        })
+                        .boxed(),
                     );
                 } else if file_type.is_file() {
                     file_futures.push(async move {
                         let file_handle = fs::open_file(full_path.as_os_str().to_os_string(), u64::MAX)
                             .await
                             .err_tip(|| format!("Could not open file {full_path:?}"))?;
-                        upload_file(file_handle, cas_store, &full_path, hasher)
+                        upload_file(file_handle, cas_store, &full_path, hasher, output_node_properties)
                             .map_ok(|v| v.into())
                             .await
                     });
@@ -416,11 +866,19 @@ fn upload_directory<'a, P: AsRef<Path> + Debug + Send + Sync + Clone + 'a>(
         directory_nodes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
         symlinks.sort_unstable_by(|a, b| a.name.cmp(&b.name));
 
+        let node_properties = if output_node_properties.is_empty() {
+            None
+        } else {
+            let metadata = fs::metadata(&full_dir_path)
+                .await
+                .err_tip(|| format!("While reading metadata for {full_dir_path:?}"))?;
+            output_node_properties.node_properties(&metadata, &full_dir_path)?
+        };
         let directory = Directory {
             files: file_nodes,
             directories: directory_nodes,
             symlinks,
-            node_properties: None, // We don't support file properties.
+            node_properties: node_properties.map(Into::into),
         };
         all_child_directories.push_back(directory.clone());
 
@@ -430,9 +888,7 @@ fn upload_directory<'a, P: AsRef<Path> + Debug + Send + Sync + Clone + 'a>(
 
 async fn process_side_channel_file(
     side_channel_file: Cow<'_, OsStr>,
-    args: &[&OsStr],
-    timeout: Duration,
-) -> Result<Option<Error>, Error> {
+) -> Result<Option<SideChannelFailureReason>, Error> {
     let mut json_contents = String::new();
     {
         // Note: Scoping `file_slot` allows the file_slot semaphore to be released faster.
@@ -459,16 +915,128 @@ async fn process_side_channel_file(
     let side_channel_info: SideChannelInfo = serde_json5::from_str(&json_contents).map_err(|e| {
         make_input_err!("Could not convert contents of side channel file (json) to SideChannelInfo : {e:?}")
     })?;
-    Ok(side_channel_info.failure.map(|failure| match failure {
-        SideChannelFailureReason::timeout => Error::new(
-            Code::DeadlineExceeded,
-            format!(
-                "Command '{}' timed out after {} seconds",
-                args.join(OsStr::new(" ")).to_string_lossy(),
-                timeout.as_secs_f32()
-            ),
-        ),
-    }))
+    Ok(side_channel_info.failure)
+}
+
+/// How often to check whether all processes of an action have exited.
+#[cfg(target_family = "unix")]
+const PROCESS_GROUP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to wait for killed processes of an action to exit.
+#[cfg(target_family = "unix")]
+const PROCESS_GROUP_KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends `signal` to every process in the process group of an action. Every
+/// action is started in its own process group, whose id is the pid of the
+/// action.
+#[cfg(target_family = "unix")]
+fn signal_process_group(process_group: u32, signal: libc::c_int) -> Result<(), Error> {
+    // SAFETY: kill() has no memory safety requirements.
+    if unsafe { libc::kill(-(process_group as libc::pid_t), signal) } == -1 {
+        let e = std::io::Error::last_os_error();
+        // ESRCH means there is no process left in the group.
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            return Err(e).err_tip(|| format!("Could not send signal {signal} to process group {process_group}"));
+        }
+    }
+    Ok(())
+}
+
+/// Asks every process of an action to terminate.
+#[cfg(target_family = "unix")]
+fn terminate_action_processes(_child_process: &mut process::Child, process_group: u32) -> Result<(), Error> {
+    signal_process_group(process_group, libc::SIGTERM)
+}
+
+#[cfg(target_family = "windows")]
+fn terminate_action_processes(child_process: &mut process::Child, _process_group: u32) -> Result<(), Error> {
+    child_process.start_kill().err_tip(|| "Could not kill action")
+}
+
+/// Kills every process of an action.
+#[cfg(target_family = "unix")]
+fn kill_action_processes(_child_process: &mut process::Child, process_group: u32) -> Result<(), Error> {
+    signal_process_group(process_group, libc::SIGKILL)
+}
+
+#[cfg(target_family = "windows")]
+fn kill_action_processes(child_process: &mut process::Child, _process_group: u32) -> Result<(), Error> {
+    child_process.start_kill().err_tip(|| "Could not kill action")
+}
+
+/// Sends `signal` to the processes in the cgroup of an action that started a
+/// session of their own, which the signals to the process group of the action
+/// do not reach. The action itself is started in a session whose id is the id
+/// of its process group.
+#[cfg(target_os = "linux")]
+fn signal_escaped_processes(cgroup: &ActionCgroup, process_group: u32, signal: libc::c_int) -> Result<(), Error> {
+    for pid in cgroup.pids()? {
+        // SAFETY: getsid() has no memory safety requirements.
+        if unsafe { libc::getsid(pid as libc::pid_t) } == process_group as libc::pid_t {
+            continue;
+        }
+        // SAFETY: kill() has no memory safety requirements.
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } == -1 {
+            let e = std::io::Error::last_os_error();
+            // ESRCH means the process already exited.
+            if e.raw_os_error() != Some(libc::ESRCH) {
+                return Err(e).err_tip(|| format!("Could not send signal {signal} to process {pid}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reaps the processes of the process group that exited and were reparented
+/// to the worker, which is a subreaper. Returns whether no process is left in
+/// the group.
+#[cfg(target_family = "unix")]
+fn reap_process_group(process_group: u32) -> bool {
+    loop {
+        // SAFETY: siginfo_t is a plain C struct.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        // SAFETY: `info` is valid for writes.
+        let result = unsafe {
+            libc::waitid(
+                libc::P_PGID,
+                process_group as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOHANG,
+            )
+        };
+        // Stop if no child of the worker is in the group or none exited.
+        // SAFETY: `info` was initialized above.
+        if result == -1 || unsafe { info.si_pid() } == 0 {
+            break;
+        }
+    }
+    // SAFETY: kill() has no memory safety requirements.
+    unsafe { libc::kill(-(process_group as libc::pid_t), 0) == -1 }
+}
+
+/// Makes sure no process of an action is left once the action itself exited.
+/// If the action is being terminated, its processes get until
+/// `termination_deadline` to exit. All remaining processes are killed.
+#[cfg(target_family = "unix")]
+async fn finish_processes(
+    termination_deadline: Option<Instant>,
+    mut all_exited: impl FnMut() -> Result<bool, Error>,
+    kill: impl FnOnce() -> Result<(), Error>,
+) -> Result<(), Error> {
+    if let Some(termination_deadline) = termination_deadline {
+        while !all_exited()? && Instant::now() < termination_deadline {
+            tokio::time::sleep(PROCESS_GROUP_POLL_INTERVAL).await;
+        }
+    }
+    kill()?;
+    let kill_deadline = Instant::now() + PROCESS_GROUP_KILL_TIMEOUT;
+    while !all_exited()? {
+        if Instant::now() >= kill_deadline {
+            return Err(make_err!(Code::Internal, "Processes did not exit after being killed"));
+        }
+        tokio::time::sleep(PROCESS_GROUP_POLL_INTERVAL).await;
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -495,8 +1063,10 @@ pub trait RunningAction: Sync + Send + Sized + Unpin + 'static {
 }
 
 struct RunningActionImplExecutionResult {
-    stdout: Bytes,
-    stderr: Bytes,
+    /// Files stdout and stderr of the action were written to, or None if
+    /// the output was discarded because the action was killed.
+    stdout_file: Option<String>,
+    stderr_file: Option<String>,
     exit_code: i32,
 }
 
@@ -515,6 +1085,12 @@ struct RunningActionImplState {
     // that prevented the action from running, upload failures, timeouts, exc...
     // but we have (or could have) the action results (like stderr/stdout).
     error: Option<Error>,
+    // Set if the execution failed in a way that is configured to be
+    // considered flaky.
+    flaky_failure_reason: Option<String>,
+    // The inputs that share their file with the `FilesystemStore`, to find
+    // the ones the action modified.
+    linked_inputs: Vec<LinkedInput>,
 }
 
 pub struct RunningActionImpl {
@@ -551,6 +1127,8 @@ impl RunningActionImpl {
                 action_result: None,
                 execution_metadata,
                 error: None,
+                flaky_failure_reason: None,
+                linked_inputs: Vec::new(),
             }),
             did_cleanup: AtomicBool::new(false),
         }
@@ -580,13 +1158,34 @@ impl RunningActionImpl {
             });
             let filesystem_store_pin = Pin::new(self.running_actions_manager.filesystem_store.as_ref());
             // Download the input files/folder and place them into the temp directory.
-            let download_to_directory_fut = self.metrics().download_to_directory.wrap(download_to_directory(
+            let materialization = self
+                .running_actions_manager
+                .execution_configuration
+                .input_materialization;
+            // Held until the inputs are materialized and checked.
+            let lease = self.running_actions_manager.filesystem_store.lease();
+            let download_to_directory_fut = self.metrics().download_to_directory.wrap(download_to_directory_impl(
                 cas_store_pin,
                 filesystem_store_pin,
+                &lease,
+                self.running_actions_manager.directory_cache.as_ref(),
+                materialization,
                 &self.action_info.input_root_digest,
                 &self.work_directory,
             ));
-            let (command, _) = try_join(command_fut, download_to_directory_fut).await?;
+            let (command, files) = try_join(command_fut, download_to_directory_fut).await?;
+            // Rejected before the action runs, instead of after its outputs
+            // were produced.
+            OutputNodeProperties::new(&command.output_node_properties)
+                .err_tip(|| "In Command::output_node_properties")?;
+            self.running_actions_manager
+                .add_recent_input_root_digest(self.action_info.input_root_digest);
+            if materialization == InputMaterialization::Hardlink {
+                let linked_inputs = spawn_blocking(move || stat_linked_inputs(files))
+                    .await
+                    .err_tip(|| "Failed to launch spawn_blocking in prepare_action")?;
+                self.state.lock().linked_inputs = linked_inputs;
+            }
             command
         };
         {
@@ -620,6 +1219,23 @@ impl RunningActionImpl {
                 ))
                 .await?;
         }
+        #[cfg(target_family = "unix")]
+        if let Some(execution_user) = self.running_actions_manager.execution_configuration.execution_user {
+            let work_directory = self.work_directory.clone();
+            let materialization = self
+                .running_actions_manager
+                .execution_configuration
+                .input_materialization;
+            spawn_blocking(move || give_to_execution_user(Path::new(&work_directory), execution_user, materialization))
+                .await
+                .err_tip(|| "Failed to launch spawn_blocking in prepare_action")?
+                .map_err(|e| {
+                    make_err!(
+                        Code::Internal,
+                        "Could not give work directory to execution user : {e:?}"
+                    )
+                })?;
+        }
         log::info!("\x1b[0;31mWorker Received Command\x1b[0m: {:?}", command);
         {
             let mut state = self.state.lock();
@@ -650,26 +1266,38 @@ impl RunningActionImpl {
         if command_proto.arguments.is_empty() {
             return Err(make_input_err!("No arguments provided in Command proto"));
         }
-        let args: Vec<&OsStr> =
-            if let Some(entrypoint_cmd) = &self.running_actions_manager.execution_configuration.entrypoint_cmd {
-                std::iter::once(entrypoint_cmd.as_ref())
-                    .chain(command_proto.arguments.iter().map(AsRef::as_ref))
-                    .collect()
-            } else {
-                command_proto.arguments.iter().map(AsRef::as_ref).collect()
-            };
-        log::info!("\x1b[0;31mWorker Executing\x1b[0m: {:?}", &args);
-        let mut command_builder = process::Command::new(args[0]);
-        command_builder
-            .args(&args[1..])
-            .kill_on_drop(true)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .current_dir(format!("{}/{}", self.work_directory, command_proto.working_directory))
-            .env_clear();
+        let maybe_container = match &self.running_actions_manager.container_runtime {
+            Some(container_runtime) => container_runtime
+                .image(&self.action_info)?
+                .map(|image| (container_runtime, image)),
+            None => None,
+        };
+        // Persistent workers run on the host, so actions that need a
+        // container are executed in their container like any other action.
+        let maybe_persistent_workers = match maybe_container {
+            Some(_) => None,
+            None => self.running_actions_manager.persistent_workers.as_ref(),
+        };
+        if let Some(persistent_workers) = maybe_persistent_workers {
+            let maybe_persistent_action = persistent_workers
+                .persistent_action(
+                    Pin::new(self.running_actions_manager.cas_store.as_ref()),
+                    &self.action_info,
+                    &command_proto,
+                    &self.work_directory,
+                )
+                .await
+                .err_tip(|| "Preparing request for persistent worker")?;
+            if let Some(persistent_action) = maybe_persistent_action {
+                return self
+                    .inner_execute_in_persistent_worker(persistent_action, command_proto, kill_channel_rx)
+                    .await;
+            }
+        }
+        let working_directory = format!("{}/{}", self.work_directory, command_proto.working_directory);
 
         let mut maybe_side_channel_file: Option<Cow<'_, OsStr>> = None;
+        let mut environment: Vec<(&str, Cow<'_, str>)> = Vec::new();
         if let Some(additional_environment) = &self
             .running_actions_manager
             .execution_configuration
@@ -686,17 +1314,12 @@ impl RunningActionImpl {
                     EnvironmentSource::Value(value) => Cow::Borrowed(value.as_str()),
                     EnvironmentSource::TimeoutMillis => Cow::Owned(self.timeout.as_millis().to_string()),
                     EnvironmentSource::SideChannelFile => {
-                        let file_cow = format!(
-                            "{}/{}/{}",
-                            self.work_directory,
-                            command_proto.working_directory,
-                            Uuid::new_v4().simple(),
-                        );
+                        let file_cow = format!("{working_directory}/{}", Uuid::new_v4().simple());
                         maybe_side_channel_file = Some(Cow::Owned(file_cow.clone().into()));
                         Cow::Owned(file_cow)
                     }
                 };
-                command_builder.env(name, value.as_ref());
+                environment.push((name, value));
             }
         }
 
@@ -726,66 +1349,240 @@ impl RunningActionImpl {
             envs
         };
         for environment_variable in envs {
-            command_builder.env(&environment_variable.name, &environment_variable.value);
+            environment.push((&environment_variable.name, Cow::Borrowed(&environment_variable.value)));
+        }
+
+        // Actions executed in a container get their environment from the
+        // runtime, the runtime itself uses the environment of the worker.
+        let container_command;
+        let container_name = format!("native-link-{}", hex::encode(self.action_id));
+        let args: Vec<&OsStr> = if let Some((container_runtime, image)) = &maybe_container {
+            let env_file = self.container_env_file();
+            write_env_file(
+                &env_file,
+                environment.iter().map(|(name, value)| (*name, value.as_ref())),
+            )
+            .await
+            .err_tip(|| "Preparing container of action")?;
+            container_command = container_runtime
+                .run_command(
+                    &container_name,
+                    image,
+                    &self.action_info,
+                    &self.work_directory,
+                    &working_directory,
+                    &env_file,
+                )
+                .err_tip(|| "Preparing container of action")?;
+            container_command
+                .iter()
+                .map(AsRef::as_ref)
+                .chain(command_proto.arguments.iter().map(AsRef::as_ref))
+                .collect()
+        } else if let Some(entrypoint_cmd) = &self.running_actions_manager.execution_configuration.entrypoint_cmd {
+            std::iter::once(entrypoint_cmd.as_ref())
+                .chain(command_proto.arguments.iter().map(AsRef::as_ref))
+                .collect()
+        } else {
+            command_proto.arguments.iter().map(AsRef::as_ref).collect()
+        };
+        log::info!("\x1b[0;31mWorker Executing\x1b[0m: {:?}", &args);
+        let mut command_builder = process::Command::new(args[0]);
+        command_builder
+            .args(&args[1..])
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .current_dir(&working_directory);
+        if maybe_container.is_none() {
+            command_builder.env_clear();
+            for (name, value) in &environment {
+                command_builder.env(name, value.as_ref());
+            }
+        }
+        // Every action gets its own session and with it its own process group,
+        // so all processes of the action can be terminated together. This has
+        // to happen before the action enters its cgroup or sandbox.
+        #[cfg(target_family = "unix")]
+        // SAFETY: setsid() is async-signal-safe.
+        unsafe {
+            command_builder.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        // The cgroup has to be entered before the sandbox, which loses the
+        // permissions of the worker on the cgroup.
+        #[cfg(target_os = "linux")]
+        let mut maybe_cgroup = match &self.running_actions_manager.execution_configuration.cgroup {
+            Some(cgroup_config) => {
+                let cgroup = Cgroups::new(cgroup_config)
+                    .create_action_cgroup(&hex::encode(self.action_id), &self.action_info)
+                    .err_tip(|| "Could not create cgroup for action")?;
+                cgroup.apply(&mut command_builder)?;
+                Some(cgroup)
+            }
+            None => None,
+        };
+        #[cfg(not(target_os = "linux"))]
+        if self.running_actions_manager.execution_configuration.cgroup.is_some() {
+            return Err(make_input_err!("cgroup is only supported on Linux"));
+        }
+
+        // The user is switched after the cgroup was entered with the
+        // permissions of the worker. Containers are started as the user by
+        // the runtime.
+        #[cfg_attr(target_family = "windows", allow(unused_variables))]
+        let maybe_execution_user = self
+            .running_actions_manager
+            .execution_configuration
+            .execution_user
+            .filter(|_| maybe_container.is_none());
+        #[cfg(target_family = "unix")]
+        if let Some(execution_user) = maybe_execution_user {
+            // SAFETY: setgroups(), setgid() and setuid() only perform system
+            // calls.
+            unsafe {
+                command_builder.pre_exec(move || {
+                    if libc::setgroups(0, std::ptr::null()) == -1
+                        || libc::setgid(execution_user.gid) == -1
+                        || libc::setuid(execution_user.uid) == -1
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        // Containers are already isolated from the host.
+        let maybe_sandbox_config = match maybe_container {
+            Some(_) => None,
+            None => self
+                .running_actions_manager
+                .execution_configuration
+                .linux_sandbox
+                .as_ref(),
+        };
+        if let Some(sandbox_config) = maybe_sandbox_config {
+            #[cfg(target_os = "linux")]
+            {
+                let sandbox = LinuxSandbox::new(sandbox_config);
+                let sandbox_root = self.sandbox_root();
+                fs::create_dir(&sandbox_root)
+                    .await
+                    .err_tip(|| format!("Could not create sandbox root {sandbox_root}"))?;
+                sandbox
+                    .apply(
+                        &mut command_builder,
+                        &sandbox_root,
+                        &self.work_directory,
+                        &working_directory,
+                        sandbox.isolates_network(&self.action_info),
+                        maybe_execution_user,
+                    )
+                    .err_tip(|| "Could not set up linux sandbox")?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = sandbox_config;
+                return Err(make_input_err!("linux_sandbox is only supported on Linux"));
+            }
         }
 
-        let mut child_process = command_builder
-            .spawn()
-            .err_tip(|| format!("Could not execute command {:?}", args))?;
-        let mut stdout_reader = child_process
+        // Held until the action finishes, so it counts against the limit of
+        // actions executed in its image.
+        let _container_permit = match &maybe_container {
+            Some((container_runtime, image)) => tokio::select! {
+                maybe_permit = container_runtime.acquire(image) => {
+                    maybe_permit.err_tip(|| "Preparing container of action")?
+                },
+                _ = &mut kill_channel_rx => {
+                    return Err(make_err!(
+                        Code::Aborted,
+                        "Command '{}' was killed by scheduler while waiting for container image {image}",
+                        args.join(OsStr::new(" ")).to_string_lossy()
+                    ));
+                },
+            },
+            None => None,
+        };
+
+        let mut child_process =
+            child_process::spawn(&mut command_builder).err_tip(|| format!("Could not execute command {:?}", args))?;
+        let stdout_reader = child_process
             .stdout
             .take()
             .err_tip(|| "Expected stdout to exist on command this should never happen")?;
-        let mut stderr_reader = child_process
+        let stderr_reader = child_process
             .stderr
             .take()
             .err_tip(|| "Expected stderr to exist on command this should never happen")?;
 
-        let mut child_process_guard = guard(child_process, |mut child_process| {
+        let process_group = child_process
+            .id()
+            .err_tip(|| "Expected child process to have an id, this should never happen")?;
+
+        let mut child_process_guard = guard(child_process, move |mut child_process| {
             log::error!(
                 "Child process was not cleaned up before dropping the call to execute(), killing in background spawn."
             );
+            if let Err(e) = kill_action_processes(&mut child_process, process_group) {
+                log::error!("{e:?}");
+            }
             tokio::spawn(async move { child_process.kill().await });
         });
 
-        let all_stdout_fut = JoinHandleDropGuard::new(tokio::spawn(async move {
-            let mut all_stdout = BytesMut::new();
-            loop {
-                let sz = stdout_reader
-                    .read_buf(&mut all_stdout)
-                    .await
-                    .err_tip(|| "Error reading stdout stream")?;
-                if sz == 0 {
-                    break; // EOF.
-                }
-            }
-            Result::<Bytes, Error>::Ok(all_stdout.freeze())
-        }));
-        let all_stderr_fut = JoinHandleDropGuard::new(tokio::spawn(async move {
-            let mut all_stderr = BytesMut::new();
-            loop {
-                let sz = stderr_reader
-                    .read_buf(&mut all_stderr)
-                    .await
-                    .err_tip(|| "Error reading stderr stream")?;
-                if sz == 0 {
-                    break; // EOF.
-                }
-            }
-            Result::<Bytes, Error>::Ok(all_stderr.freeze())
-        }));
+        // The output is streamed to disk, so actions with a lot of output
+        // do not use up the memory of the worker.
+        let execution_configuration = &self.running_actions_manager.execution_configuration;
+        let (stdout_file, stderr_file) = self.output_stream_files();
+        let all_stdout_fut = JoinHandleDropGuard::new(tokio::spawn(write_output_stream(
+            stdout_reader,
+            stdout_file.clone(),
+            execution_configuration.max_stdout_size,
+            "stdout",
+        )));
+        let all_stderr_fut = JoinHandleDropGuard::new(tokio::spawn(write_output_stream(
+            stderr_reader,
+            stderr_file.clone(),
+            execution_configuration.max_stderr_size,
+            "stderr",
+        )));
         let mut killed_action = false;
+        // When the processes of the action were asked to terminate, they are
+        // killed if they did not exit by this deadline.
+        let mut termination_deadline: Option<Instant> = None;
+        let mut force_kill_fut: Fuse<BoxFuture<'static, ()>> = Fuse::terminated();
+        let termination_grace_period = self
+            .running_actions_manager
+            .execution_configuration
+            .termination_grace_period;
 
         let timer = self.metrics().child_process.begin_timer();
         let mut sleep_fut = (self.running_actions_manager.callbacks.sleep_fn)(self.timeout).fuse();
         loop {
+            if killed_action && termination_deadline.is_none() {
+                if let Err(e) = terminate_action_processes(&mut child_process_guard, process_group) {
+                    log::error!("Could not terminate action {} : {e:?}", hex::encode(self.action_id));
+                }
+                #[cfg(target_os = "linux")]
+                if let Some(cgroup) = maybe_cgroup.as_ref() {
+                    if let Err(e) = signal_escaped_processes(cgroup, process_group, libc::SIGTERM) {
+                        log::error!("Could not terminate action {} : {e:?}", hex::encode(self.action_id));
+                    }
+                }
+                termination_deadline = Some(Instant::now() + termination_grace_period);
+                force_kill_fut = tokio::time::sleep(termination_grace_period).boxed().fuse();
+            }
             tokio::select! {
                 _ = &mut sleep_fut => {
                     self.running_actions_manager.metrics.task_timeouts.inc();
                     killed_action = true;
-                    if let Err(e) = child_process_guard.start_kill() {
-                        log::error!("Could not kill process in RunningActionsManager for timeout : {:?}", e);
-                    }
                     {
                         let mut state = self.state.lock();
                         state.error = Error::merge_option(state.error.take(), Some(Error::new(
@@ -798,24 +1595,60 @@ impl RunningActionImpl {
                         )));
                     }
                 },
+                _ = &mut force_kill_fut => {
+                    if let Err(e) = kill_action_processes(&mut child_process_guard, process_group) {
+                        log::error!("Could not kill action {} : {e:?}", hex::encode(self.action_id));
+                    }
+                    #[cfg(target_os = "linux")]
+                    if let Some(Err(e)) = maybe_cgroup.as_ref().map(ActionCgroup::kill) {
+                        log::error!("Could not kill action {} : {e:?}", hex::encode(self.action_id));
+                    }
+                },
                 maybe_exit_status = child_process_guard.wait() => {
                     // Defuse our guard so it does not try to cleanup and make nessless logs.
                     drop(ScopeGuard::<_, _>::into_inner(child_process_guard));
                     let exit_status = maybe_exit_status.err_tip(|| "Failed to collect exit code of process")?;
+                    // Processes the action left behind would keep stdout and stderr
+                    // open and must not outlive the action.
+                    #[cfg(target_family = "unix")]
+                    if let Err(e) = finish_processes(
+                        termination_deadline,
+                        || Ok(reap_process_group(process_group)),
+                        || signal_process_group(process_group, libc::SIGKILL),
+                    ).await {
+                        log::error!("Could not end all processes of action {} : {e:?}", hex::encode(self.action_id));
+                    }
+                    // Processes that left the process group are only known
+                    // through the cgroup. Their exit status is collected by the
+                    // worker, which is their subreaper.
+                    #[cfg(target_os = "linux")]
+                    if let Some(cgroup) = maybe_cgroup.as_ref() {
+                        if let Err(e) = finish_processes(
+                            termination_deadline,
+                            || Ok(cgroup.pids()?.is_empty()),
+                            || cgroup.kill(),
+                        ).await {
+                            log::error!("Could not end all processes of action {} : {e:?}", hex::encode(self.action_id));
+                        }
+                    }
+                    // Killing the runtime does not stop the container.
+                    if let Some((container_runtime, _)) = maybe_container.as_ref().filter(|_| killed_action) {
+                        if let Err(e) = container_runtime.remove_container(&container_name).await {
+                            log::error!("Could not remove container of action {} : {e:?}", hex::encode(self.action_id));
+                        }
+                    }
                     // TODO(allada) We should implement stderr/stdout streaming to client here.
                     // If we get killed before the stream is started, then these will lock up.
-                    // TODO(allada) There is a significant bug here. If we kill the action and the action creates
-                    // child processes, it can create zombies. See: https://github.com/tracemachina/native-link/issues/225
-                    let (stdout, stderr) = if killed_action {
+                    let mut output_truncations = Vec::new();
+                    let (stdout_file, stderr_file) = if killed_action {
                         drop(timer);
-                        (Bytes::new(), Bytes::new())
+                        (None, None)
                     } else {
                         timer.measure();
                         let (maybe_all_stdout, maybe_all_stderr) = tokio::join!(all_stdout_fut, all_stderr_fut);
-                        (
-                            maybe_all_stdout.err_tip(|| "Internal error reading from stdout of worker task")??,
-                            maybe_all_stderr.err_tip(|| "Internal error reading from stderr of worker task")??
-                        )
+                        output_truncations.extend(maybe_all_stdout.err_tip(|| "Internal error reading from stdout of worker task")??);
+                        output_truncations.extend(maybe_all_stderr.err_tip(|| "Internal error reading from stderr of worker task")??);
+                        (Some(stdout_file), Some(stderr_file))
                     };
                     let exit_code = if let Some(exit_code) = exit_status.code() {
                         if exit_code == 0 {
@@ -828,20 +1661,61 @@ impl RunningActionImpl {
                         EXIT_CODE_FOR_SIGNAL
                     };
 
-                    let maybe_error_override = if let Some(side_channel_file) = maybe_side_channel_file {
-                        process_side_channel_file(side_channel_file.clone(), &args, self.timeout).await
+                    let mut maybe_resource_usage = None;
+                    #[cfg(target_os = "linux")]
+                    if let Some(cgroup) = maybe_cgroup.take() {
+                        let resource_usage = cgroup.resource_usage().err_tip(|| "Could not get resource usage of action")?;
+                        // The action already completed, so a cgroup that can not be removed
+                        // only leaks an empty directory.
+                        if let Err(err) = cgroup.remove().await {
+                            log::error!("{err:?}");
+                        }
+                        maybe_resource_usage = Some(resource_usage);
+                    }
+                    let maybe_oom_error = maybe_resource_usage.as_ref().filter(|resource_usage| resource_usage.oom_kills > 0).map(|_| {
+                        Error::new(
+                            Code::ResourceExhausted,
+                            format!(
+                                "Command '{}' was killed because it ran out of memory",
+                                args.join(OsStr::new(" ")).to_string_lossy(),
+                            ),
+                        )
+                    });
+
+                    let maybe_side_channel_failure = if let Some(side_channel_file) = maybe_side_channel_file {
+                        process_side_channel_file(side_channel_file.clone()).await
                         .err_tip(|| format!("Error processing side channel file: {side_channel_file:?}"))?
                     } else {
                         None
                     };
+                    let maybe_error_override = match maybe_side_channel_failure {
+                        Some(SideChannelFailureReason::timeout) => Some(Error::new(
+                            Code::DeadlineExceeded,
+                            format!(
+                                "Command '{}' timed out after {} seconds",
+                                args.join(OsStr::new(" ")).to_string_lossy(),
+                                self.timeout.as_secs_f32()
+                            ),
+                        )),
+                        Some(SideChannelFailureReason::retry) | None => None,
+                    };
+                    let flaky_failure_reason = if matches!(maybe_side_channel_failure, Some(SideChannelFailureReason::retry)) {
+                        Some("Side channel file requested a retry".to_string())
+                    } else {
+                        self.running_actions_manager.flaky_failure_reason(exit_code, stderr_file.as_deref()).await?
+                    };
                     {
                         let mut state = self.state.lock();
                         state.error = Error::merge_option(state.error.take(), maybe_error_override);
+                        state.error = Error::merge_option(state.error.take(), maybe_oom_error);
+                        state.flaky_failure_reason = flaky_failure_reason;
+                        state.execution_metadata.resource_usage = maybe_resource_usage;
+                        state.execution_metadata.output_truncations = output_truncations;
 
                         state.command_proto = Some(command_proto);
                         state.execution_result = Some(RunningActionImplExecutionResult{
-                            stdout,
-                            stderr,
+                            stdout_file,
+                            stderr_file,
                             exit_code,
                         });
                         state.execution_metadata.execution_completed_timestamp = (self.running_actions_manager.callbacks.now_fn)();
@@ -850,17 +1724,6 @@ impl RunningActionImpl {
                 },
                 _ = &mut kill_channel_rx => {
                     killed_action = true;
-                    if let Err(e) = child_process_guard.start_kill() {
-                        log::error!(
-                            "Could not kill process in RunningActionsManager for action {} : {:?}",
-                            hex::encode(self.action_id),
-                            e);
-                    } else {
-                        log::error!(
-                            "Could not get child process id, maybe already dead? for action {}",
-                            hex::encode(self.action_id)
-                        );
-                    }
                     {
                         let mut state = self.state.lock();
                         state.error = Error::merge_option(state.error.take(), Some(Error::new(
@@ -877,6 +1740,133 @@ impl RunningActionImpl {
         // Unreachable.
     }
 
+    /// Executes the action as a request to a persistent worker. The worker
+    /// can not change its working directory between requests, so the inputs
+    /// are moved into the directory of the worker for the request and moved
+    /// back together with the outputs afterwards.
+    async fn inner_execute_in_persistent_worker(
+        self: Arc<Self>,
+        persistent_action: PersistentAction,
+        command_proto: ProtoCommand,
+        mut kill_channel_rx: Fuse<oneshot::Receiver<()>>,
+    ) -> Result<Arc<Self>, Error> {
+        let running_actions_manager = self.running_actions_manager.clone();
+        let persistent_workers = running_actions_manager
+            .persistent_workers
+            .as_ref()
+            .err_tip(|| "Expected persistent workers to be configured")?;
+        let PersistentAction { key, request } = persistent_action;
+        log::info!(
+            "\x1b[0;31mWorker Executing In Persistent Worker\x1b[0m: {:?}",
+            &request.arguments
+        );
+        let mut worker = persistent_workers.take(&key).await?;
+        move_directory_contents(&self.work_directory, worker.directory())
+            .await
+            .err_tip(|| "Moving inputs into persistent worker directory")?;
+
+        let timer = self.metrics().child_process.begin_timer();
+        let mut sleep_fut = (running_actions_manager.callbacks.sleep_fn)(self.timeout).fuse();
+        let maybe_response = tokio::select! {
+            maybe_response = worker.execute(&key, &request) => Some(maybe_response),
+            _ = &mut sleep_fut => {
+                running_actions_manager.metrics.task_timeouts.inc();
+                let mut state = self.state.lock();
+                state.error = Error::merge_option(state.error.take(), Some(Error::new(
+                    Code::DeadlineExceeded,
+                    format!(
+                        "Command '{}' timed out after {} seconds",
+                        command_proto.arguments.join(" "),
+                        self.action_info.timeout.as_secs_f32()
+                    )
+                )));
+                None
+            },
+            _ = &mut kill_channel_rx => {
+                let mut state = self.state.lock();
+                state.error = Error::merge_option(state.error.take(), Some(Error::new(
+                    Code::Aborted,
+                    format!(
+                        "Command '{}' was killed by scheduler",
+                        command_proto.arguments.join(" ")
+                    )
+                )));
+                None
+            },
+        };
+        // A worker that did not answer might still be working on the request
+        // and must not touch the outputs anymore.
+        if !matches!(maybe_response, Some(Ok(_))) {
+            worker.stop().await;
+        }
+        let move_back_result = move_directory_contents(worker.directory(), &self.work_directory)
+            .await
+            .err_tip(|| "Moving outputs out of persistent worker directory");
+        let maybe_response = match (maybe_response.transpose(), move_back_result) {
+            (Ok(maybe_response), Ok(())) => maybe_response,
+            (Err(e), Ok(())) | (Ok(_), Err(e)) => return Err(e).err_tip(|| "In persistent worker"),
+            (Err(e), Err(move_back_error)) => return Err(e.merge(move_back_error)),
+        };
+        let Some(response) = maybe_response else {
+            drop(timer);
+            // Like the output of killed processes, the output of requests that
+            // were not answered is discarded.
+            {
+                let mut state = self.state.lock();
+                state.command_proto = Some(command_proto);
+                state.execution_result = Some(RunningActionImplExecutionResult {
+                    stdout_file: None,
+                    stderr_file: None,
+                    exit_code: EXIT_CODE_FOR_SIGNAL,
+                });
+                state.execution_metadata.execution_completed_timestamp = (running_actions_manager.callbacks.now_fn)();
+            }
+            return Ok(self);
+        };
+        timer.measure();
+        persistent_workers.put(key, worker);
+
+        if response.exit_code == 0 {
+            self.metrics().child_process_success_error_code.inc();
+        } else {
+            self.metrics().child_process_failure_error_code.inc();
+        }
+        // Persistent workers report the diagnostics of a request in the
+        // output of its response, their stdout is used by the protocol.
+        let execution_configuration = &running_actions_manager.execution_configuration;
+        let (stdout_file, stderr_file) = self.output_stream_files();
+        write_output_stream(
+            &[][..],
+            stdout_file.clone(),
+            execution_configuration.max_stdout_size,
+            "stdout",
+        )
+        .await?;
+        let maybe_output_truncation = write_output_stream(
+            response.output.as_bytes(),
+            stderr_file.clone(),
+            execution_configuration.max_stderr_size,
+            "stderr",
+        )
+        .await?;
+        let flaky_failure_reason = running_actions_manager
+            .flaky_failure_reason(response.exit_code, Some(&stderr_file))
+            .await?;
+        {
+            let mut state = self.state.lock();
+            state.flaky_failure_reason = flaky_failure_reason;
+            state.execution_metadata.output_truncations = maybe_output_truncation.into_iter().collect();
+            state.command_proto = Some(command_proto);
+            state.execution_result = Some(RunningActionImplExecutionResult {
+                stdout_file: Some(stdout_file),
+                stderr_file: Some(stderr_file),
+                exit_code: response.exit_code,
+            });
+            state.execution_metadata.execution_completed_timestamp = (running_actions_manager.callbacks.now_fn)();
+        }
+        Ok(self)
+    }
+
     async fn inner_upload_results(self: Arc<Self>) -> Result<Arc<Self>, Error> {
         log::info!("\x1b[0;31mWorker Uploading Results\x1b[0m");
         let (mut command_proto, execution_result, mut execution_metadata) = {
@@ -896,6 +1886,8 @@ impl RunningActionImpl {
         };
         let cas_store = Pin::new(self.running_actions_manager.cas_store.as_ref());
         let hasher = self.action_info.digest_function;
+        let output_node_properties = OutputNodeProperties::new(&command_proto.output_node_properties)
+            .err_tip(|| "In Command::output_node_properties")?;
         enum OutputType {
             None,
             File(FileInfo),
@@ -951,7 +1943,7 @@ impl RunningActionImpl {
                     .err_tip(|| format!("While querying symlink metadata for {entry}"))?;
                     if metadata.is_file() {
                         return Ok(OutputType::File(
-                            upload_file(resumeable_file, cas_store, &full_path, hasher)
+                            upload_file(resumeable_file, cas_store, &full_path, hasher, output_node_properties)
                                 .await
                                 .map(|mut file_info| {
                                     file_info.name_or_path = NameOrPath::Path(entry);
@@ -964,7 +1956,7 @@ impl RunningActionImpl {
                 };
                 if metadata.is_dir() {
                     Ok(OutputType::Directory(
-                        upload_directory(cas_store, &full_path, work_directory, hasher)
+                        upload_directory(cas_store, &full_path, work_directory, hasher, output_node_properties)
                             .and_then(|(root_dir, children)| async move {
                                 let tree = ProtoTree {
                                     root: Some(root_dir),
@@ -1022,33 +2014,21 @@ impl RunningActionImpl {
 
         if execution_result.exit_code != 0 {
             log::info!(
-                "Command returned exit code {} : {} {}",
+                "Command returned exit code {} : stdout in {:?} stderr in {:?}",
                 execution_result.exit_code,
-                std::str::from_utf8(&execution_result.stdout).unwrap_or(""),
-                std::str::from_utf8(&execution_result.stderr).unwrap_or("")
+                execution_result.stdout_file,
+                execution_result.stderr_file
             );
         }
 
-        let stdout_digest_fut = self.metrics().upload_stdout.wrap(async {
-            let data = execution_result.stdout;
-            let digest = compute_buf_digest(&data, &mut hasher.into())
-                .await
-                .err_tip(|| "Computing stdout digest")?;
-            upload_buf_to_store(cas_store, digest, data)
-                .await
-                .err_tip(|| "Uploading stdout")?;
-            Result::<DigestInfo, Error>::Ok(digest)
-        });
-        let stderr_digest_fut = self.metrics().upload_stderr.wrap(async {
-            let data = execution_result.stderr;
-            let digest = compute_buf_digest(&data, &mut hasher.into())
-                .await
-                .err_tip(|| "Computing stderr digest")?;
-            upload_buf_to_store(cas_store, digest, data)
-                .await
-                .err_tip(|| "Uploading stderr")?;
-            Result::<DigestInfo, Error>::Ok(digest)
-        });
+        let stdout_digest_fut = self.metrics().upload_stdout.wrap(
+            upload_output_stream(cas_store, execution_result.stdout_file, hasher)
+                .map(|r| r.err_tip(|| "Uploading stdout")),
+        );
+        let stderr_digest_fut = self.metrics().upload_stderr.wrap(
+            upload_output_stream(cas_store, execution_result.stderr_file, hasher)
+                .map(|r| r.err_tip(|| "Uploading stderr")),
+        );
 
         let upload_result = futures::try_join!(stdout_digest_fut, stderr_digest_fut, async {
             while let Some(output_type) = output_path_futures.try_next().await? {
@@ -1088,6 +2068,7 @@ impl RunningActionImpl {
                 server_logs: HashMap::default(), // TODO(allada) Not implemented.
                 error: state.error.clone(),
                 message: String::new(), // Will be filled in on cache_action_result if needed.
+                flaky_failure_reason: state.flaky_failure_reason.clone(),
             });
         }
         Ok(self)
@@ -1095,10 +2076,32 @@ impl RunningActionImpl {
 
     async fn inner_cleanup(self: Arc<Self>) -> Result<Arc<Self>, Error> {
         log::info!("\x1b[0;31mWorker Cleanup\x1b[0m");
+        self.evict_modified_inputs().await;
         // Note: We need to be careful to keep trying to cleanup even if one of the steps fails.
-        let remove_dir_result = fs::remove_dir_all(&self.work_directory)
+        let mut remove_dir_result = fs::remove_dir_all(&self.work_directory)
             .await
             .err_tip(|| format!("Could not remove working directory {}", self.work_directory));
+        // The sandbox root only exists if the action was executed in a sandbox.
+        let sandbox_root = self.sandbox_root();
+        if fs::metadata(&sandbox_root).await.is_ok() {
+            remove_dir_result = remove_dir_result.and(
+                fs::remove_dir_all(&sandbox_root)
+                    .await
+                    .err_tip(|| format!("Could not remove sandbox root {sandbox_root}")),
+            );
+        }
+        // The output files do not exist if the action was never executed and
+        // the env file only if it was executed in a container.
+        let (stdout_file, stderr_file) = self.output_stream_files();
+        for file in [stdout_file, stderr_file, self.container_env_file()] {
+            if fs::metadata(&file).await.is_ok() {
+                remove_dir_result = remove_dir_result.and(
+                    fs::remove_file(&file)
+                        .await
+                        .err_tip(|| format!("Could not remove file {file}")),
+                );
+            }
+        }
         self.did_cleanup.store(true, Ordering::Relaxed);
         if let Err(e) = self.running_actions_manager.cleanup_action(&self.action_id) {
             log::error!("Error cleaning up action: {e:?}");
@@ -1114,6 +2117,73 @@ impl RunningActionImpl {
         Ok(self)
     }
 
+    /// Evicts the files of the `FilesystemStore` that the action modified
+    /// through its hardlinked inputs, so later actions do not get the
+    /// modified content. Cached directories with these files are removed as
+    /// well, their hardlinks share the modified files.
+    async fn evict_modified_inputs(&self) {
+        let linked_inputs = std::mem::take(&mut self.state.lock().linked_inputs);
+        if linked_inputs.is_empty() {
+            return;
+        }
+        let modified_inputs = match spawn_blocking(move || modified_linked_inputs(linked_inputs)).await {
+            Ok(modified_inputs) => modified_inputs,
+            Err(e) => {
+                log::error!("Failed to launch spawn_blocking in evict_modified_inputs : {e:?}");
+                return;
+            }
+        };
+        if let Some(directory_cache) = &self.running_actions_manager.directory_cache {
+            let modified_digests: Vec<DigestInfo> = modified_inputs.iter().map(|input| input.digest).collect();
+            directory_cache.remove_directories_with_files(&modified_digests).await;
+        }
+        let filesystem_store = &self.running_actions_manager.filesystem_store;
+        for modified_input in modified_inputs {
+            // Inputs that were copied instead of hardlinked, eg: because of
+            // their node properties, do not share the file of the store.
+            let Ok(file_entry) = filesystem_store.get_file_entry_for_digest(&modified_input.digest).await else {
+                continue;
+            };
+            let is_store_file = file_entry
+                .get_file_path_locked(
+                    |path| async move { Ok(inode(&fs::metadata(path).await?) == modified_input.inode) },
+                )
+                .await
+                .unwrap_or(false);
+            if !is_store_file {
+                continue;
+            }
+            log::warn!(
+                "Action {} modified its input {}, evicting it from the filesystem store",
+                hex::encode(self.action_id),
+                modified_input.digest.hash_str()
+            );
+            filesystem_store.remove_entry_for_digest(&modified_input.digest).await;
+        }
+    }
+
+    /// Empty directory the root filesystem of the sandbox is mounted on.
+    fn sandbox_root(&self) -> String {
+        format!("{}.sandbox", self.work_directory)
+    }
+
+    /// File the environment of an action executed in a container is passed
+    /// in. It is kept out of the work directory, which is mounted into the
+    /// container.
+    fn container_env_file(&self) -> String {
+        format!("{}.env", self.work_directory)
+    }
+
+    /// Files stdout and stderr of the action are written to. They are kept
+    /// out of the work directory, so they cannot collide with the inputs
+    /// and outputs of the action.
+    fn output_stream_files(&self) -> (String, String) {
+        (
+            format!("{}.stdout", self.work_directory),
+            format!("{}.stderr", self.work_directory),
+        )
+    }
+
     async fn inner_get_finished_result(self: Arc<Self>) -> Result<ActionResult, Error> {
         let mut state = self.state.lock();
         state
@@ -1190,6 +2260,13 @@ pub trait RunningActionsManager: Sync + Send + Sized + Unpin + 'static {
 
     async fn kill_all(&self);
 
+    /// Kills the action with the given id. The action still goes through the
+    /// rest of its lifecycle and reports its (likely failed) result.
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error>;
+
+    /// Input root digests of the most recently prepared actions, most recent first.
+    fn recent_input_root_digests(&self) -> Vec<DigestInfo>;
+
     fn metrics(&self) -> &Arc<Metrics>;
 }
 
@@ -1222,6 +2299,29 @@ pub struct ExecutionConfiguration {
     /// executes other than those in the ActionInfo.  On Windows, SystemRoot
     /// and PATH are also assigned (see inner_execute).
     pub additional_environment: Option<HashMap<String, EnvironmentSource>>,
+    /// Which failed executions are reported as flaky.
+    pub flaky_failures: FlakyFailuresConfig,
+    /// If set, the command is executed in a Linux sandbox.
+    pub linux_sandbox: Option<LinuxSandboxConfig>,
+    /// If set, the command is executed in its own cgroup.
+    pub cgroup: Option<CgroupConfig>,
+    /// How long the processes of an action get to exit after being asked to
+    /// terminate on timeout or kill, before they are killed.
+    pub termination_grace_period: Duration,
+    /// Bytes of stdout kept of an action, zero for no limit.
+    pub max_stdout_size: u64,
+    /// Bytes of stderr kept of an action, zero for no limit.
+    pub max_stderr_size: u64,
+    /// If set, actions that support it are executed in persistent workers.
+    pub persistent_workers: Option<PersistentWorkersConfig>,
+    /// If set, actions with a container image are executed in a container.
+    pub container: Option<ContainerConfig>,
+    /// If set, the subdirectories of input roots are cached.
+    pub directory_cache: Option<DirectoryCacheConfig>,
+    /// How input files are placed in the work directories.
+    pub input_materialization: InputMaterialization,
+    /// If set, actions are executed as this user.
+    pub execution_user: Option<ExecutionUserConfig>,
 }
 
 struct UploadActionResults {
@@ -1454,7 +2554,12 @@ pub struct RunningActionsManagerImpl {
     upload_action_results: UploadActionResults,
     max_action_timeout: Duration,
     timeout_handled_externally: bool,
+    flaky_stderr_regexes: Vec<Regex>,
+    persistent_workers: Option<PersistentWorkers>,
+    container_runtime: Option<ContainerRuntime>,
+    directory_cache: Option<DirectoryCache>,
     running_actions: Mutex<HashMap<ActionId, Weak<RunningActionImpl>>>,
+    recent_input_root_digests: Mutex<VecDeque<DigestInfo>>,
     // Note: We don't use Notify because we need to support a .wait_for()-like function, which
     // Notify does not support.
     action_done_tx: watch::Sender<()>,
@@ -1473,6 +2578,55 @@ impl RunningActionsManagerImpl {
             .downcast_ref::<Arc<FilesystemStore>>()
             .err_tip(|| "Expected FilesystemStore store for .fast_store() in RunningActionsManagerImpl")?
             .clone();
+        let flaky_stderr_regexes = args
+            .execution_configuration
+            .flaky_failures
+            .stderr_regexes
+            .iter()
+            .map(|stderr_regex| {
+                Regex::new(stderr_regex)
+                    .map_err(|e| make_input_err!("Could not compile flaky stderr regex '{stderr_regex}' : {e:?}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(target_os = "linux")]
+        child_process::become_subreaper()?;
+        if args.execution_configuration.execution_user.is_some() {
+            #[cfg(target_family = "unix")]
+            // SAFETY: geteuid() can not fail.
+            if unsafe { libc::geteuid() } != 0 {
+                return Err(make_input_err!("execution_user requires the worker to run as root"));
+            }
+            #[cfg(not(target_family = "unix"))]
+            return Err(make_input_err!("execution_user is only supported on unix"));
+        }
+        // Persistent workers outlive the actions, so they can not be isolated
+        // like the actions are.
+        if args.execution_configuration.persistent_workers.is_some() {
+            let execution_configuration = &args.execution_configuration;
+            if execution_configuration.linux_sandbox.is_some()
+                || execution_configuration.cgroup.is_some()
+                || execution_configuration.execution_user.is_some()
+            {
+                return Err(make_input_err!(
+                    "persistent_workers can not be combined with linux_sandbox, cgroup or execution_user"
+                ));
+            }
+        }
+        let persistent_workers =
+            args.execution_configuration.persistent_workers.as_ref().map(|config| {
+                PersistentWorkers::new(config, format!("{}/persistent_workers", args.root_work_directory))
+            });
+        let container_runtime = args
+            .execution_configuration
+            .container
+            .as_ref()
+            .map(|config| ContainerRuntime::new(config, args.execution_configuration.execution_user))
+            .transpose()?;
+        let directory_cache = args
+            .execution_configuration
+            .directory_cache
+            .as_ref()
+            .map(|config| DirectoryCache::new(config, format!("{}/directory_cache", args.root_work_directory)));
         let (action_done_tx, _) = watch::channel(());
         Ok(Self {
             root_work_directory: args.root_work_directory,
@@ -1487,7 +2641,12 @@ impl RunningActionsManagerImpl {
             .err_tip(|| "During RunningActionsManagerImpl construction")?,
             max_action_timeout: args.max_action_timeout,
             timeout_handled_externally: args.timeout_handled_externally,
+            flaky_stderr_regexes,
+            persistent_workers,
+            container_runtime,
+            directory_cache,
             running_actions: Mutex::new(HashMap::new()),
+            recent_input_root_digests: Mutex::new(VecDeque::new()),
             action_done_tx,
             callbacks,
             metrics: Arc::new(Metrics::default()),
@@ -1504,6 +2663,55 @@ impl RunningActionsManagerImpl {
         )
     }
 
+    /// Returns why a failed execution is considered flaky, or None if it is not.
+    async fn flaky_failure_reason(&self, exit_code: i32, stderr_file: Option<&str>) -> Result<Option<String>, Error> {
+        if exit_code == 0 {
+            return Ok(None);
+        }
+        if self
+            .execution_configuration
+            .flaky_failures
+            .exit_codes
+            .contains(&exit_code)
+        {
+            return Ok(Some(format!("Exit code {exit_code} is configured as flaky")));
+        }
+        let Some(stderr_file) = stderr_file.filter(|_| !self.flaky_stderr_regexes.is_empty()) else {
+            return Ok(None);
+        };
+        // Only the end of stderr is matched, so actions with a lot of output
+        // do not use up the memory of the worker.
+        let mut file = fs::open_file(OsString::from(stderr_file), MAX_FLAKY_STDERR_SIZE)
+            .await
+            .err_tip(|| format!("Could not open stderr file {stderr_file}"))?;
+        let reader = file
+            .as_reader()
+            .await
+            .err_tip(|| format!("Could not get reader for stderr file {stderr_file}"))?;
+        let file_size = reader
+            .get_ref()
+            .as_ref()
+            .metadata()
+            .await
+            .err_tip(|| format!("Could not read metadata of stderr file {stderr_file}"))?
+            .len();
+        reader
+            .get_mut()
+            .seek(SeekFrom::Start(file_size.saturating_sub(MAX_FLAKY_STDERR_SIZE)))
+            .await
+            .err_tip(|| format!("Could not seek in stderr file {stderr_file}"))?;
+        let mut stderr = Vec::with_capacity(file_size.min(MAX_FLAKY_STDERR_SIZE) as usize);
+        reader
+            .read_to_end(&mut stderr)
+            .await
+            .err_tip(|| format!("Could not read stderr file {stderr_file}"))?;
+        Ok(self
+            .flaky_stderr_regexes
+            .iter()
+            .find(|stderr_regex| stderr_regex.is_match(&stderr))
+            .map(|stderr_regex| format!("Stderr matched flaky regex '{stderr_regex}'")))
+    }
+
     fn make_work_directory<'a>(&'a self, action_id: &'a ActionId) -> impl Future<Output = Result<String, Error>> + 'a {
         self.metrics.make_work_directory.wrap(async move {
             let work_directory = format!("{}/{}", self.root_work_directory, hex::encode(action_id));
@@ -1544,6 +2752,15 @@ impl RunningActionsManagerImpl {
         })
     }
 
+    /// Records that the given input root was just materialized, moving it to the front
+    /// if it was already known and forgetting the oldest entries past the limit.
+    fn add_recent_input_root_digest(&self, input_root_digest: DigestInfo) {
+        let mut recent_input_root_digests = self.recent_input_root_digests.lock();
+        recent_input_root_digests.retain(|digest| digest != &input_root_digest);
+        recent_input_root_digests.push_front(input_root_digest);
+        recent_input_root_digests.truncate(MAX_RECENT_INPUT_ROOT_DIGESTS);
+    }
+
     fn cleanup_action(&self, action_id: &ActionId) -> Result<(), Error> {
         let mut running_actions = self.running_actions.lock();
         let result = running_actions
@@ -1556,7 +2773,7 @@ impl RunningActionsManagerImpl {
 
     // Note: We do not capture metrics on this call, only `.kill_all()`.
     // Important: When the future returns the process may still be running.
-    async fn kill_action(action: Arc<RunningActionImpl>) {
+    async fn kill_running_action(action: Arc<RunningActionImpl>) {
         let kill_channel_tx = {
             let mut action_state = action.state.lock();
             action_state.kill_channel_tx.take()
@@ -1601,6 +2818,9 @@ impl RunningActionsManager for RunningActionsManagerImpl {
                     execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                     output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                     output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                    previous_attempts: Vec::new(),
+                    resource_usage: None,
+                    output_truncations: Vec::new(),
                 };
                 let timeout = if action_info.timeout == Duration::ZERO || self.timeout_handled_externally {
                     self.max_action_timeout
@@ -1660,7 +2880,7 @@ impl RunningActionsManager for RunningActionsManagerImpl {
                         .collect()
                 };
                 for action in kill_actions {
-                    Self::kill_action(action).await;
+                    Self::kill_running_action(action).await;
                 }
             })
             .await;
@@ -1674,6 +2894,26 @@ impl RunningActionsManager for RunningActionsManagerImpl {
             .await;
     }
 
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error> {
+        let action = self
+            .running_actions
+            .lock()
+            .get(action_id)
+            .and_then(Weak::upgrade)
+            .err_tip_with_code(|_| {
+                (
+                    Code::NotFound,
+                    format!("Action {} is not running on this worker", hex::encode(action_id)),
+                )
+            })?;
+        Self::kill_running_action(action).await;
+        Ok(())
+    }
+
+    fn recent_input_root_digests(&self) -> Vec<DigestInfo> {
+        self.recent_input_root_digests.lock().iter().copied().collect()
+    }
+
     #[inline]
     fn metrics(&self) -> &Arc<Metrics> {
         &self.metrics
@@ -21,11 +21,45 @@ use std::str::from_utf8;
 use error::{make_err, make_input_err, Error, ResultExt};
 use futures::future::try_join_all;
 use native_link_config::cas_server::WorkerProperty;
+use native_link_util::action_messages::ActionInfo;
 use native_link_util::common::log;
 use proto::build::bazel::remote::execution::v2::platform::Property;
 use proto::com::github::trace_machina::native_link::remote_execution::SupportedProperties;
 use tokio::process;
 
+use crate::child_process;
+
+/// Platform property with the memory limit of an action in KiB.
+/// If this changes, remember to change the documentation in the config.
+pub const DEFAULT_MEMORY_KB_PROPERTY: &str = "memory_kb";
+
+/// Platform property with the number of CPUs an action may use.
+/// If this changes, remember to change the documentation in the config.
+pub const DEFAULT_CPU_COUNT_PROPERTY: &str = "cpu_count";
+
+/// Returns the configured name of a platform property, or `default` if it
+/// is not configured.
+pub fn or_default(value: &str, default: &str) -> String {
+    if value.is_empty() {
+        default.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the numeric value of the platform property of the action, or
+/// None if the action does not have the property.
+pub fn property_value(action_info: &ActionInfo, property: &str) -> Result<Option<u64>, Error> {
+    let Some(value) = action_info.platform_properties.properties.get(property) else {
+        return Ok(None);
+    };
+    value
+        .as_str()
+        .parse::<u64>()
+        .map(Some)
+        .map_err(|e| make_input_err!("Could not parse platform property {property} as number : {e:?}"))
+}
+
 pub async fn make_supported_properties<S: BuildHasher>(
     worker_properties: &HashMap<String, WorkerProperty, S>,
 ) -> Result<SupportedProperties, Error> {
@@ -59,9 +93,11 @@ pub async fn make_supported_properties<S: BuildHasher>(
                     process.env_clear();
                     process.args(args);
                     process.stdin(Stdio::null());
+                    process.stdout(Stdio::piped());
+                    process.stderr(Stdio::piped());
                     let err_fn = || format!("Error executing property_name {property_name} command");
                     log::info!("Spawning process for cmd: '{}' for property: '{}'", cmd, property_name);
-                    let process_output = process.output().await.err_tip(err_fn)?;
+                    let process_output = child_process::output(&mut process).await.err_tip(err_fn)?;
                     if !process_output.status.success() {
                         return Err(make_err!(process_output.status.code().unwrap().into(), "{}", err_fn()));
                     }
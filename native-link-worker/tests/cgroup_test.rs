@@ -0,0 +1,147 @@
+// Copyright 2023 The Native Link Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(target_os = "linux")]
+
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use error::{Code, Error};
+use native_link_config::cas_server::CgroupConfig;
+use native_link_util::action_messages::{ActionInfo, ActionInfoHashKey, ResourceUsage};
+use native_link_util::common::DigestInfo;
+use native_link_util::digest_hasher::DigestHasherFunc;
+use native_link_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
+use native_link_worker::cgroup::Cgroups;
+use rand::{thread_rng, Rng};
+
+fn make_action_info(properties: &[(&str, PlatformPropertyValue)]) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties: PlatformProperties {
+            properties: properties
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+        },
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: UNIX_EPOCH,
+        unique_qualifier: ActionInfoHashKey {
+            instance_name: String::new(),
+            digest: DigestInfo::new([1u8; 32], 1),
+            salt: 0,
+        },
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+        action_mnemonic: None,
+    }
+}
+
+/// Makes a directory that looks like a cgroup v2 with all controllers
+/// available. The files of its child cgroups are regular files.
+fn make_fake_cgroup() -> Result<PathBuf, Error> {
+    let path = PathBuf::from(env::var("TEST_TMPDIR").unwrap_or(env::temp_dir().to_str().unwrap().to_string()))
+        .join(thread_rng().gen::<u64>().to_string());
+    std::fs::create_dir_all(&path)?;
+    std::fs::write(path.join("cgroup.controllers"), "cpuset cpu io memory pids\n")?;
+    Ok(path)
+}
+
+fn make_cgroups(path: &Path) -> Cgroups {
+    Cgroups::new(&CgroupConfig {
+        path: path.to_str().unwrap().to_string(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod cgroup_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn limits_are_set_from_platform_properties() -> Result<(), Error> {
+        let path = make_fake_cgroup()?;
+        let _action_cgroup = make_cgroups(&path).create_action_cgroup(
+            "action",
+            &make_action_info(&[
+                ("memory_kb", PlatformPropertyValue::Minimum(1024)),
+                ("cpu_count", PlatformPropertyValue::Minimum(2)),
+                ("max_pids", PlatformPropertyValue::Exact("10".to_string())),
+            ]),
+        )?;
+
+        let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap();
+        assert_eq!(read("cgroup.subtree_control"), "+cpu +memory +pids");
+        assert_eq!(read("action/memory.max"), "1048576");
+        assert_eq!(read("action/memory.oom.group"), "1");
+        assert_eq!(read("action/cpu.max"), "200000 100000");
+        assert_eq!(read("action/pids.max"), "10");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_limits_are_rejected() -> Result<(), Error> {
+        let path = make_fake_cgroup()?;
+        let cgroups = make_cgroups(&path);
+        for (i, property) in [
+            ("memory_kb", PlatformPropertyValue::Exact("lots".to_string())),
+            ("memory_kb", PlatformPropertyValue::Minimum(u64::MAX)),
+            ("cpu_count", PlatformPropertyValue::Minimum(u64::MAX)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let result = cgroups.create_action_cgroup(&format!("action{i}"), &make_action_info(&[property]));
+            assert_eq!(
+                result.err().map(|err| err.code),
+                Some(Code::InvalidArgument),
+                "Expected action {i} to be rejected"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resource_usage_reports_oom_kills() -> Result<(), Error> {
+        let path = make_fake_cgroup()?;
+        let action_cgroup = make_cgroups(&path).create_action_cgroup("action", &make_action_info(&[]))?;
+        std::fs::write(path.join("action/memory.peak"), "4096\n")?;
+        std::fs::write(
+            path.join("action/cpu.stat"),
+            "usage_usec 30\nuser_usec 10\nsystem_usec 20\n",
+        )?;
+        std::fs::write(
+            path.join("action/memory.events"),
+            "low 0\nhigh 0\nmax 3\noom 1\noom_kill 1\n",
+        )?;
+
+        assert_eq!(
+            action_cgroup.resource_usage()?,
+            ResourceUsage {
+                peak_memory_bytes: 4096,
+                user_cpu_time: Duration::from_micros(10),
+                system_cpu_time: Duration::from_micros(20),
+                oom_kills: 1,
+            }
+        );
+        Ok(())
+    }
+}
@@ -43,7 +43,8 @@ use prost::Message;
 use proto::build::bazel::remote::execution::v2::platform::Property;
 use proto::com::github::trace_machina::native_link::remote_execution::update_for_worker::Update;
 use proto::com::github::trace_machina::native_link::remote_execution::{
-    execute_result, ConnectionResult, ExecuteResult, StartExecute, SupportedProperties, UpdateForWorker,
+    execute_result, ConnectionResult, ExecuteResult, KillActionRequest, StartExecute, SupportedProperties,
+    UpdateForWorker,
 };
 use rand::{thread_rng, Rng};
 use tokio::io::AsyncWriteExt;
@@ -183,6 +184,60 @@ mod local_worker_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn kill_action_request_kills_action_test() -> Result<(), Box<dyn std::error::Error>> {
+        const SALT: u64 = 1000;
+
+        let mut test_context = setup_local_worker(HashMap::new()).await;
+        let streaming_response = test_context.maybe_streaming_response.take().unwrap();
+
+        {
+            // Ensure our worker connects and properties were sent.
+            let props = test_context.client.expect_connect_worker(Ok(streaming_response)).await;
+            assert_eq!(props, SupportedProperties::default());
+        }
+
+        let mut tx_stream = test_context.maybe_tx_stream.take().unwrap();
+        {
+            tx_stream
+                .send_data(encode_stream_proto(&UpdateForWorker {
+                    update: Some(Update::ConnectionResult(ConnectionResult {
+                        worker_id: "foobar".to_string(),
+                    })),
+                })?)
+                .await
+                .map_err(|e| make_input_err!("Could not send : {:?}", e))?;
+        }
+
+        let action_digest = DigestInfo::new([3u8; 32], 10);
+        {
+            // Ask the worker to kill the action.
+            tx_stream
+                .send_data(encode_stream_proto(&UpdateForWorker {
+                    update: Some(Update::KillActionRequest(KillActionRequest {
+                        instance_name: INSTANCE_NAME.to_string(),
+                        action_digest: Some(action_digest.into()),
+                        salt: SALT,
+                    })),
+                })?)
+                .await
+                .map_err(|e| make_input_err!("Could not send : {:?}", e))?;
+        }
+
+        let expected_action_id = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: SALT,
+        }
+        .get_hash();
+        assert_eq!(
+            test_context.actions_manager.expect_kill_action().await,
+            expected_action_id
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn blake3_digest_function_registerd_properly() -> Result<(), Box<dyn std::error::Error>> {
         const SALT: u64 = 1000;
@@ -227,6 +282,7 @@ mod local_worker_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Blake3,
+            action_mnemonic: None,
         };
 
         {
@@ -308,6 +364,7 @@ mod local_worker_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         };
 
         {
@@ -342,10 +399,14 @@ mod local_worker_tests {
                 execution_completed_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_start_timestamp: SystemTime::UNIX_EPOCH,
                 output_upload_completed_timestamp: SystemTime::UNIX_EPOCH,
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             server_logs: HashMap::new(),
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         let running_action = Arc::new(MockRunningAction::new());
 
@@ -385,6 +446,7 @@ mod local_worker_tests {
                 result: Some(execute_result::Result::ExecuteResponse(
                     ActionStage::Completed(action_result).into()
                 )),
+                flaky_failure_reason: String::new(),
             }
         );
 
@@ -557,6 +619,7 @@ mod local_worker_tests {
             },
             skip_cache_lookup: true,
             digest_function: DigestHasherFunc::Sha256,
+            action_mnemonic: None,
         };
 
         {
@@ -595,6 +658,7 @@ mod local_worker_tests {
                 result: Some(execute_result::Result::InternalError(
                     make_err!(Code::ResourceExhausted, "{}", EXPECTED_MSG,).into()
                 )),
+                flaky_failure_reason: String::new(),
             }
         );
 
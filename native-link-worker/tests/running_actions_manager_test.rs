@@ -20,6 +20,7 @@ use std::fs::Permissions;
 use std::io::{Cursor, Write};
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
 use std::pin::Pin;
 use std::str::from_utf8;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
@@ -28,14 +29,17 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use error::{make_input_err, Code, Error, ResultExt};
 use futures::{FutureExt, TryFutureExt};
-use native_link_config::cas_server::EnvironmentSource;
+use native_link_config::cas_server::{
+    CgroupConfig, ContainerConfig, DirectoryCacheConfig, EnvironmentSource, ExecutionUserConfig, InputMaterialization,
+    LinuxSandboxConfig, PersistentWorkersConfig,
+};
 use native_link_store::ac_utils::{compute_digest, get_and_decode_digest, serialize_and_upload_message};
 use native_link_store::fast_slow_store::FastSlowStore;
-use native_link_store::filesystem_store::FilesystemStore;
+use native_link_store::filesystem_store::{FileEntry, FilesystemStore};
 use native_link_store::memory_store::MemoryStore;
 #[cfg_attr(target_family = "windows", allow(unused_imports))]
 use native_link_util::action_messages::{
-    ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath, SymlinkInfo,
+    ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath, OutputTruncation, SymlinkInfo,
 };
 use native_link_util::common::{fs, DigestInfo};
 use native_link_util::digest_hasher::DigestHasherFunc;
@@ -48,9 +52,9 @@ use once_cell::sync::Lazy;
 use prost::Message;
 #[cfg_attr(target_family = "windows", allow(unused_imports))]
 use proto::build::bazel::remote::execution::v2::{
-    digest_function::Value as ProtoDigestFunction, platform::Property, Action, ActionResult as ProtoActionResult,
-    Command, Directory, DirectoryNode, ExecuteRequest, ExecuteResponse, FileNode, NodeProperties, Platform,
-    SymlinkNode, Tree,
+    command::EnvironmentVariable, digest_function::Value as ProtoDigestFunction, platform::Property, Action,
+    ActionResult as ProtoActionResult, Command, Directory, DirectoryNode, ExecuteRequest, ExecuteResponse, FileNode,
+    NodeProperties, Platform, SymlinkNode, Tree,
 };
 use proto::com::github::trace_machina::native_link::remote_execution::{HistoricalExecuteResponse, StartExecute};
 use rand::{thread_rng, Rng};
@@ -119,6 +123,195 @@ async fn run_action(action: Arc<RunningActionImpl>) -> Result<ActionResult, Erro
         .await
 }
 
+const WORKER_ID: &str = "foo_worker_id";
+
+/// Makes a manager executing actions with the configuration in a new root work
+/// directory, which is returned as well. Results are not uploaded to the action cache.
+async fn setup_running_actions_manager(
+    execution_configuration: ExecutionConfiguration,
+    cas_store: &Pin<Arc<FastSlowStore>>,
+    ac_store: &Pin<Arc<MemoryStore>>,
+) -> Result<(String, Arc<RunningActionsManagerImpl>), Error> {
+    let root_work_directory = make_temp_path("root_work_directory");
+    fs::create_dir_all(&root_work_directory).await?;
+    let running_actions_manager = Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+        root_work_directory: root_work_directory.clone(),
+        execution_configuration,
+        cas_store: Pin::into_inner(cas_store.clone()),
+        ac_store: Some(Pin::into_inner(ac_store.clone())),
+        historical_store: Pin::into_inner(cas_store.clone()),
+        upload_action_result_config: &native_link_config::cas_server::UploadActionResultConfig {
+            upload_ac_results_strategy: native_link_config::cas_server::UploadCacheResultsStrategy::Never,
+            ..Default::default()
+        },
+        max_action_timeout: Duration::MAX,
+        timeout_handled_externally: false,
+    })?);
+    Ok((root_work_directory, running_actions_manager))
+}
+
+/// Uploads the command, the input root and an action running them with the
+/// platform properties. Returns the digest of the action.
+async fn upload_action(
+    cas_store: &Pin<Arc<FastSlowStore>>,
+    command: &Command,
+    input_root: &Directory,
+    platform_properties: &[(&str, &str)],
+) -> Result<DigestInfo, Error> {
+    let command_digest =
+        serialize_and_upload_message(command, cas_store.as_ref(), &mut DigestHasherFunc::Sha256.into()).await?;
+    let input_root_digest =
+        serialize_and_upload_message(input_root, cas_store.as_ref(), &mut DigestHasherFunc::Sha256.into()).await?;
+    let action = Action {
+        command_digest: Some(command_digest.into()),
+        input_root_digest: Some(input_root_digest.into()),
+        platform: (!platform_properties.is_empty()).then(|| Platform {
+            properties: platform_properties
+                .iter()
+                .map(|(name, value)| Property {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        }),
+        ..Default::default()
+    };
+    serialize_and_upload_message(&action, cas_store.as_ref(), &mut DigestHasherFunc::Sha256.into()).await
+}
+
+/// Starts an execution of the action with the salt and runs it to completion.
+async fn execute_action(
+    running_actions_manager: &Arc<RunningActionsManagerImpl>,
+    action_digest: DigestInfo,
+    salt: u64,
+) -> Result<ActionResult, Error> {
+    let running_action_impl = running_actions_manager
+        .clone()
+        .create_and_add_action(
+            WORKER_ID.to_string(),
+            StartExecute {
+                execute_request: Some(ExecuteRequest {
+                    action_digest: Some(action_digest.into()),
+                    ..Default::default()
+                }),
+                salt,
+                queued_timestamp: Some(make_system_time(1000).into()),
+            },
+        )
+        .await?;
+    run_action(running_action_impl).await
+}
+
+/// Persistent worker that answers every JSON work request and writes the
+/// number of the request to `out.txt` and the output of the action.
+#[cfg(target_family = "unix")]
+const COUNTING_WORKER_SCRIPT: &str = concat!(
+    "test \"$1\" = --persistent_worker || exit 1; ",
+    "count=0; ",
+    "while read -r request; do ",
+    "count=$((count + 1)); ",
+    "echo \"request $count\" > out.txt; ",
+    "printf '{\"exitCode\":0,\"output\":\"request %s of worker %s\"}\\n' \"$count\" \"$$\"; ",
+    "done",
+);
+
+/// Uploads an action that is executed in a persistent worker with the key,
+/// which runs `worker_script` and speaks the JSON protocol. Returns the
+/// digest of the action.
+#[cfg(target_family = "unix")]
+async fn upload_persistent_worker_action(
+    cas_store: &Pin<Arc<FastSlowStore>>,
+    slow_store: &Pin<Arc<MemoryStore>>,
+    worker_script: &str,
+    key: &str,
+) -> Result<DigestInfo, Error> {
+    let flagfile_digest = DigestInfo::new([4u8; 32], 6);
+    slow_store
+        .as_ref()
+        .update_oneshot(flagfile_digest, "--foo\n".into())
+        .await?;
+    let input_root = Directory {
+        files: vec![FileNode {
+            name: "flagfile".to_string(),
+            digest: Some(flagfile_digest.into()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let command = Command {
+        arguments: vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            worker_script.to_string(),
+            "worker".to_string(),
+            "@flagfile".to_string(),
+        ],
+        output_paths: vec!["out.txt".to_string()],
+        ..Default::default()
+    };
+    upload_action(
+        cas_store,
+        &command,
+        &input_root,
+        &[("persistentWorkerKey", key), ("persistentWorkerProtocol", "json")],
+    )
+    .await
+}
+
+/// Stands in for docker: no image exists locally, pulls only log the image,
+/// removals log their arguments and runs check the user and execute the
+/// arguments after the image on the host. The names of the containers are
+/// logged separately.
+#[cfg(target_family = "unix")]
+const FAKE_CONTAINER_RUNTIME_SCRIPT: &str = r#"
+log="$(dirname "$0")/runtime.log"
+case "$1" in
+  image) exit 1 ;;
+  pull) test "$2" = -- || exit 1; echo "pull $3" >> "$log" ;;
+  rm) echo "rm $2 $3" >> "$log" ;;
+  run)
+    shift
+    limits=""
+    while [ $# -gt 0 ]; do
+      case "$1" in
+        --rm) shift ;;
+        --name) echo "$2" >> "$(dirname "$0")/names.log" && shift 2 ;;
+        --user) test "$2" = "$(id -u):$(id -g)" || exit 1; shift 2 ;;
+        --memory|--cpus) limits="$limits $1 $2" && shift 2 ;;
+        --volume) shift 2 ;;
+        --workdir) cd "$2" && shift 2 ;;
+        --env-file)
+          while IFS= read -r variable; do export "$variable"; done < "$2"
+          shift 2
+          ;;
+        --) shift && break ;;
+        *) exit 1 ;;
+      esac
+    done
+    echo "run $1$limits" >> "$log"
+    shift
+    exec "$@"
+    ;;
+  *) exit 1 ;;
+esac
+"#;
+
+/// Writes the fake container runtime to a new directory, its logs are
+/// written next to it. Returns the path of the script.
+#[cfg(target_family = "unix")]
+async fn write_fake_container_runtime() -> Result<String, Error> {
+    let runtime_script = make_temp_path("runtime.sh");
+    fs::create_dir_all(Path::new(&runtime_script).parent().unwrap()).await?;
+    std::fs::write(&runtime_script, FAKE_CONTAINER_RUNTIME_SCRIPT)?;
+    Ok(runtime_script)
+}
+
+/// Returns the content of the blob in the CAS as a string.
+async fn get_blob_string(cas_store: &Pin<Arc<FastSlowStore>>, digest: DigestInfo) -> Result<String, Error> {
+    let data = cas_store.as_ref().get_part_unchunked(digest, 0, None, None).await?;
+    String::from_utf8(data.to_vec()).map_err(|e| make_input_err!("Blob {digest:?} is not utf8 : {e:?}"))
+}
+
 const NOW_TIME: u64 = 10000;
 
 fn make_system_time(add_time: u64) -> SystemTime {
@@ -227,10 +420,17 @@ mod running_actions_manager_tests {
             let file2_content = fs::read(&file2_path).await?;
             assert_eq!(std::str::from_utf8(&file2_content)?, FILE2_CONTENT);
 
+            // Files without node properties are hardlinked from the store.
+            #[cfg(target_family = "unix")]
+            assert_eq!(fs::metadata(format!("{download_dir}/{FILE1_NAME}")).await?.nlink(), 2);
+
             let file2_metadata = fs::metadata(&file2_path).await?;
-            // Note: We sent 0o710, but because is_executable was set it turns into 0o711.
+            // Note: We sent 0o710, but because is_executable was set it turns into 0o711
+            // and because the file is copied in place of a hardlink it is made read-only.
             #[cfg(target_family = "unix")]
-            assert_eq!(file2_metadata.mode() & 0o777, FILE2_MODE | 0o111);
+            assert_eq!(file2_metadata.mode() & 0o777, (FILE2_MODE | 0o111) & !0o222);
+            #[cfg(target_family = "unix")]
+            assert_eq!(file2_metadata.nlink(), 1);
             assert_eq!(
                 file2_metadata
                     .modified()?
@@ -740,6 +940,7 @@ mod running_actions_manager_tests {
                     name_or_path: NameOrPath::Path("test.txt".to_string()),
                     digest: DigestInfo::try_new("3f488ba478fc6716c756922c9f34ebd7e84b85c3e03e33e22e7a3736cafdc6d8", 4)?,
                     is_executable: false,
+                    node_properties: None,
                 }],
                 stdout_digest: DigestInfo::try_new(
                     "af1720193ae81515067a3ef39f0dfda3ad54a1a9d216e55d32fe5c1e178c6a7d",
@@ -765,9 +966,13 @@ mod running_actions_manager_tests {
                     output_upload_start_timestamp: increment_clock(&mut clock_time),
                     output_upload_completed_timestamp: increment_clock(&mut clock_time),
                     worker_completed_timestamp: increment_clock(&mut clock_time),
+                    previous_attempts: Vec::new(),
+                    resource_usage: None,
+                    output_truncations: Vec::new(),
                 },
                 error: None,
                 message: String::new(),
+                flaky_failure_reason: None,
             }
         );
         Ok(())
@@ -898,6 +1103,7 @@ mod running_actions_manager_tests {
                     name_or_path: NameOrPath::Path("test.txt".to_string()),
                     digest: DigestInfo::try_new("c69e10a5f54f4e28e33897fbd4f8701595443fa8c3004aeaa20dd4d9a463483b", 4)?,
                     is_executable: false,
+                    node_properties: None,
                 }],
                 stdout_digest: DigestInfo::try_new(
                     "15019a676f057d97d1ad3af86f3cc1e623cb33b18ff28422bbe3248d2471cc94",
@@ -923,9 +1129,13 @@ mod running_actions_manager_tests {
                     output_upload_start_timestamp: increment_clock(&mut clock_time),
                     output_upload_completed_timestamp: increment_clock(&mut clock_time),
                     worker_completed_timestamp: increment_clock(&mut clock_time),
+                    previous_attempts: Vec::new(),
+                    resource_usage: None,
+                    output_truncations: Vec::new(),
                 },
                 error: None,
                 message: String::new(),
+                flaky_failure_reason: None,
             }
         );
         Ok(())
@@ -1102,14 +1312,103 @@ mod running_actions_manager_tests {
                     output_upload_start_timestamp: increment_clock(&mut clock_time),
                     output_upload_completed_timestamp: increment_clock(&mut clock_time),
                     worker_completed_timestamp: increment_clock(&mut clock_time),
+                    previous_attempts: Vec::new(),
+                    resource_usage: None,
+                    output_truncations: Vec::new(),
                 },
                 error: None,
                 message: String::new(),
+                flaky_failure_reason: None,
             }
         );
         Ok(())
     }
 
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn output_node_properties_are_captured_test() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) =
+            setup_running_actions_manager(ExecutionConfiguration::default(), &cas_store, &ac_store).await?;
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                concat!(
+                    "mkdir dir && ",
+                    "printf foo > dir/file && ",
+                    "printf bar > file && ",
+                    "chmod 640 file dir/file && ",
+                    "chmod 750 dir && ",
+                    "TZ=UTC touch -t 197001121346.40 file dir/file dir",
+                )
+                .to_string(),
+            ],
+            // Whether an output path is a file or a directory is decided by
+            // what the action created.
+            output_paths: vec!["dir".to_string(), "file".to_string()],
+            output_node_properties: vec!["mtime".to_string(), "unix_mode".to_string()],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let action_result = execute_action(&running_actions_manager, action_digest, 56).await?;
+        assert_eq!(action_result.exit_code, 0);
+
+        let node_properties = |unix_mode| NodeProperties {
+            properties: vec![],
+            mtime: Some((UNIX_EPOCH + Duration::from_secs(1_000_000)).into()),
+            unix_mode: Some(unix_mode),
+        };
+        assert_eq!(action_result.output_files.len(), 1);
+        assert_eq!(
+            action_result.output_files[0].name_or_path,
+            NameOrPath::Path("file".to_string())
+        );
+        assert_eq!(
+            action_result.output_files[0]
+                .node_properties
+                .clone()
+                .map(NodeProperties::from),
+            Some(node_properties(0o640))
+        );
+
+        assert_eq!(action_result.output_folders.len(), 1);
+        let tree =
+            get_and_decode_digest::<Tree>(slow_store.as_ref(), &action_result.output_folders[0].tree_digest).await?;
+        let root_directory = tree.root.err_tip(|| "Expected root in tree")?;
+        assert_eq!(root_directory.node_properties, Some(node_properties(0o750)));
+        assert_eq!(root_directory.files.len(), 1);
+        assert_eq!(root_directory.files[0].node_properties, Some(node_properties(0o640)));
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn unsupported_output_node_properties_are_rejected_before_execution() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) =
+            setup_running_actions_manager(ExecutionConfiguration::default(), &cas_store, &ac_store).await?;
+        let marker_path = make_temp_path("unsupported_output_node_properties_marker");
+        let command = Command {
+            arguments: vec!["touch".to_string(), marker_path.clone()],
+            output_node_properties: vec!["unix_mode".to_string(), "owner".to_string()],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 79).await;
+        assert_eq!(result.err().map(|err| err.code), Some(Code::InvalidArgument));
+        assert!(
+            !Path::new(&marker_path).exists(),
+            "Expected the action to not be executed"
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn cleanup_happens_on_job_failure() -> Result<(), Box<dyn std::error::Error>> {
         const WORKER_ID: &str = "foo_worker_id";
@@ -1219,9 +1518,13 @@ mod running_actions_manager_tests {
                     output_upload_start_timestamp: increment_clock(&mut clock_time),
                     output_upload_completed_timestamp: increment_clock(&mut clock_time),
                     worker_completed_timestamp: increment_clock(&mut clock_time),
+                    previous_attempts: Vec::new(),
+                    resource_usage: None,
+                    output_truncations: Vec::new(),
                 },
                 error: None,
                 message: String::new(),
+                flaky_failure_reason: None,
             }
         );
         let mut dir_stream = fs::read_dir(&root_work_directory).await?;
@@ -1316,6 +1619,150 @@ mod running_actions_manager_tests {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn kill_ends_all_processes_of_action() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                termination_grace_period: Duration::from_millis(100),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let pid_file = make_temp_path("pid_file");
+        fs::create_dir_all(Path::new(&pid_file).parent().unwrap()).await?;
+
+        // The action and its child ignore SIGTERM, so they have to be killed
+        // once the grace period is over.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("trap '' TERM; sleep 1000 & echo $! > {pid_file}.tmp; mv {pid_file}.tmp {pid_file}; wait"),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        // Kill the action once its child process is running.
+        let (result, child_pid) = futures::join!(execute_action(&running_actions_manager, action_digest, 57), async {
+            loop {
+                if let Ok(child_pid) = std::fs::read_to_string(&pid_file) {
+                    running_actions_manager.kill_all().await;
+                    return child_pid.trim().to_string();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let result = result?;
+
+        // Check that the action was killed.
+        assert_eq!(9, result.exit_code);
+        // The child of the action was killed and reaped as well.
+        assert!(
+            !Path::new(&format!("/proc/{child_pid}")).exists(),
+            "Expected child process {child_pid} to be gone"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn orphaned_processes_of_action_are_reaped() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) =
+            setup_running_actions_manager(ExecutionConfiguration::default(), &cas_store, &ac_store).await?;
+        let pid_file = make_temp_path("pid_file");
+        fs::create_dir_all(Path::new(&pid_file).parent().unwrap()).await?;
+
+        // The child of the action leaves the session of the action before the
+        // action exits and outlives it, so it is reparented to the worker.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "setsid sh -c 'echo $$ > {pid_file}.tmp; mv {pid_file}.tmp {pid_file}; exec sleep 0.2' \
+                     </dev/null >/dev/null 2>&1 & while [ ! -e {pid_file} ]; do sleep 0.01; done"
+                ),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 92).await?;
+        assert_eq!(0, result.exit_code);
+
+        // The child is reaped once it exits instead of staying a zombie.
+        let child_pid = std::fs::read_to_string(&pid_file)?.trim().to_string();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while Path::new(&format!("/proc/{child_pid}")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Expected child process {child_pid} to be reaped"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "Needs unprivileged user namespaces, run with --ignored where they are available"]
+    async fn kill_lets_sandboxed_action_handle_termination() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                linux_sandbox: Some(LinuxSandboxConfig::default()),
+                termination_grace_period: Duration::from_secs(10),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+
+        // The action exits with its own exit code once it is asked to terminate.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "trap 'exit 3' TERM; sleep 1000 & touch ready; wait".to_string(),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        // Kill the action once it handles SIGTERM.
+        let (result, ()) = futures::join!(execute_action(&running_actions_manager, action_digest, 91), async {
+            loop {
+                let is_ready = std::fs::read_dir(&root_work_directory)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .any(|entry| entry.path().join("ready").exists());
+                if is_ready {
+                    running_actions_manager.kill_all().await;
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        // The trap handler ran, instead of the action being killed right away.
+        assert_eq!(3, result?.exit_code);
+
+        Ok(())
+    }
+
     // This script runs a command under a wrapper script set in a config.
     // The wrapper script will print a constant string to stderr, and the test itself will
     // print to stdout. We then check the results of both to make sure the shell script was
@@ -1378,7 +1825,7 @@ exit 0
             root_work_directory: root_work_directory.clone(),
             execution_configuration: ExecutionConfiguration {
                 entrypoint_cmd: Some(test_wrapper_script.into_string().unwrap()),
-                additional_environment: None,
+                ..Default::default()
             },
             cas_store: Pin::into_inner(cas_store.clone()),
             ac_store: Some(Pin::into_inner(ac_store.clone())),
@@ -1515,6 +1962,7 @@ exit 0
                     ("VALUE".to_string(), EnvironmentSource::Value("raw_value".to_string())),
                     ("INNER_TIMEOUT".to_string(), EnvironmentSource::TimeoutMillis),
                 ])),
+                ..Default::default()
             },
             cas_store: Pin::into_inner(cas_store.clone()),
             ac_store: Some(Pin::into_inner(ac_store.clone())),
@@ -1652,6 +2100,7 @@ exit 1
                     "SIDE_CHANNEL_FILE".to_string(),
                     EnvironmentSource::SideChannelFile,
                 )])),
+                ..Default::default()
             },
             cas_store: Pin::into_inner(cas_store.clone()),
             ac_store: Some(Pin::into_inner(ac_store.clone())),
@@ -1709,6 +2158,1114 @@ exit 1
         Ok(())
     }
 
+    #[tokio::test]
+    async fn entrypoint_requests_retry_via_side_channel() -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(target_family = "unix")]
+        const TEST_WRAPPER_SCRIPT_CONTENT: &str = "\
+#!/bin/bash
+echo '{\"failure\":\"retry\"}' > \"$SIDE_CHANNEL_FILE\"
+exit 1
+";
+        #[cfg(target_family = "windows")]
+        const TEST_WRAPPER_SCRIPT_CONTENT: &str = "\
+@echo off
+echo | set /p={\"failure\":\"retry\"} 1>&2 > %SIDE_CHANNEL_FILE%
+exit 1
+";
+        const WORKER_ID: &str = "foo_worker_id";
+        const SALT: u64 = 67;
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let root_work_directory = make_temp_path("root_work_directory");
+        fs::create_dir_all(&root_work_directory).await?;
+
+        let test_wrapper_script = {
+            let test_wrapper_dir = make_temp_path("wrapper_dir");
+            fs::create_dir_all(&test_wrapper_dir).await?;
+            #[cfg(target_family = "unix")]
+            let test_wrapper_script = OsString::from(test_wrapper_dir + "/test_wrapper_script.sh");
+            #[cfg(target_family = "windows")]
+            let test_wrapper_script = OsString::from(test_wrapper_dir + "\\test_wrapper_script.bat");
+
+            // We use std::fs::File here because we sometimes get strange bugs here
+            // that result in: "Text file busy (os error 26)" if it is an executeable.
+            // It is likley because somewhere the file descriotor does not get closed
+            // in tokio's async context.
+            let mut test_wrapper_script_handle = std::fs::File::create(&test_wrapper_script)?;
+            test_wrapper_script_handle.write_all(TEST_WRAPPER_SCRIPT_CONTENT.as_bytes())?;
+            #[cfg(target_family = "unix")]
+            test_wrapper_script_handle.set_permissions(Permissions::from_mode(0o777))?;
+            test_wrapper_script_handle.sync_all()?;
+            drop(test_wrapper_script_handle);
+
+            test_wrapper_script
+        };
+
+        let running_actions_manager = Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+            root_work_directory: root_work_directory.clone(),
+            execution_configuration: ExecutionConfiguration {
+                entrypoint_cmd: Some(test_wrapper_script.into_string().unwrap()),
+                additional_environment: Some(HashMap::from([(
+                    "SIDE_CHANNEL_FILE".to_string(),
+                    EnvironmentSource::SideChannelFile,
+                )])),
+                ..Default::default()
+            },
+            cas_store: Pin::into_inner(cas_store.clone()),
+            ac_store: Some(Pin::into_inner(ac_store.clone())),
+            historical_store: Pin::into_inner(cas_store.clone()),
+            upload_action_result_config: &native_link_config::cas_server::UploadActionResultConfig {
+                upload_ac_results_strategy: native_link_config::cas_server::UploadCacheResultsStrategy::Never,
+                ..Default::default()
+            },
+            max_action_timeout: Duration::MAX,
+            timeout_handled_externally: false,
+        })?);
+        let arguments = vec!["true".to_string()];
+        let command = Command {
+            arguments,
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let command_digest =
+            serialize_and_upload_message(&command, cas_store.as_ref(), &mut DigestHasherFunc::Sha256.into()).await?;
+        let input_root_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.into(),
+        )
+        .await?;
+        let action = Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        };
+        let action_digest =
+            serialize_and_upload_message(&action, cas_store.as_ref(), &mut DigestHasherFunc::Sha256.into()).await?;
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: SALT,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+
+        let result = run_action(running_action_impl).await?;
+        assert_eq!(result.exit_code, 1, "Exit code should be 1");
+        assert_eq!(result.error, None, "A retry request is not an error");
+        assert_eq!(
+            result.flaky_failure_reason,
+            Some("Side channel file requested a retry".to_string())
+        );
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn output_streams_are_truncated_to_max_size() -> Result<(), Box<dyn std::error::Error>> {
+        const EXPECTED_STDOUT: &str = "0123\n[stdout truncated by the worker: kept 4 of 100000 bytes]\n";
+        const EXPECTED_STDERR: &str = "abcdefghij";
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                max_stdout_size: 4,
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "printf 0123; head -c 99996 /dev/zero; printf abcdefghij >&2".to_string(),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 58).await?;
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(
+            get_blob_string(&cas_store, result.stdout_digest).await?,
+            EXPECTED_STDOUT
+        );
+        assert_eq!(
+            get_blob_string(&cas_store, result.stderr_digest).await?,
+            EXPECTED_STDERR
+        );
+        assert_eq!(
+            result.execution_metadata.output_truncations,
+            vec![OutputTruncation {
+                stream_name: "stdout".to_string(),
+                kept_bytes: 4,
+                total_bytes: 100000,
+            }]
+        );
+
+        // The files the output was written to are cleaned up with the work
+        // directory.
+        let mut root_work_directory_entries = std::fs::read_dir(&root_work_directory)?;
+        assert!(
+            root_work_directory_entries.next().is_none(),
+            "Expected root work directory to be empty"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn persistent_worker_is_reused_for_actions_with_same_key() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let action_digest =
+            upload_persistent_worker_action(&cas_store, &slow_store, COUNTING_WORKER_SCRIPT, "worker").await?;
+
+        let mut outputs = Vec::new();
+        for salt in [70, 71] {
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.exit_code, 0);
+            assert_eq!(result.output_files.len(), 1);
+            outputs.push((
+                get_blob_string(&cas_store, result.stderr_digest).await?,
+                get_blob_string(&cas_store, result.output_files[0].digest).await?,
+            ));
+        }
+
+        // Both requests were answered by the same worker process.
+        let worker_pid = outputs[0]
+            .0
+            .strip_prefix("request 1 of worker ")
+            .err_tip(|| format!("Unexpected work response output {:?}", outputs[0].0))?;
+        assert_eq!(
+            outputs,
+            vec![
+                (format!("request 1 of worker {worker_pid}"), "request 1\n".to_string()),
+                (format!("request 2 of worker {worker_pid}"), "request 2\n".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn idle_persistent_workers_are_limited_across_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig {
+                    max_idle_workers: 1,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let action_digest_a =
+            upload_persistent_worker_action(&cas_store, &slow_store, COUNTING_WORKER_SCRIPT, "a").await?;
+        let action_digest_b =
+            upload_persistent_worker_action(&cas_store, &slow_store, COUNTING_WORKER_SCRIPT, "b").await?;
+
+        let mut outputs = Vec::new();
+        for (action_digest, salt) in [(action_digest_a, 80), (action_digest_b, 81), (action_digest_a, 82)] {
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.output_files.len(), 1);
+            outputs.push(get_blob_string(&cas_store, result.output_files[0].digest).await?);
+        }
+
+        // The idle worker of "a" was stopped when the worker of "b" became idle.
+        assert_eq!(outputs, vec!["request 1\n", "request 1\n", "request 1\n"]);
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn idle_persistent_workers_expire() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig {
+                    idle_timeout: 1,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let action_digest =
+            upload_persistent_worker_action(&cas_store, &slow_store, COUNTING_WORKER_SCRIPT, "worker").await?;
+
+        let mut outputs = Vec::new();
+        for salt in [83, 84] {
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.output_files.len(), 1);
+            outputs.push(get_blob_string(&cas_store, result.output_files[0].digest).await?);
+            tokio::time::sleep(Duration::from_millis(1100)).await;
+        }
+
+        assert_eq!(outputs, vec!["request 1\n", "request 1\n"]);
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn persistent_worker_failure_includes_its_stderr() -> Result<(), Box<dyn std::error::Error>> {
+        const WORKER_SCRIPT: &str = "echo 'worker is broken' >&2; exit 1";
+
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let action_digest = upload_persistent_worker_action(&cas_store, &slow_store, WORKER_SCRIPT, "worker").await?;
+
+        let err = execute_action(&running_actions_manager, action_digest, 85)
+            .await
+            .err()
+            .err_tip(|| "Expected the action to fail")?;
+        assert!(
+            err.messages.iter().any(|message| message.contains("worker is broken")),
+            "Expected the error to include the stderr of the worker : {err:?}"
+        );
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn killed_persistent_worker_action_ends_processes_of_worker() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let pid_file = make_temp_path("pid_file");
+        fs::create_dir_all(Path::new(&pid_file).parent().unwrap()).await?;
+        // The worker starts a child and never answers.
+        let worker_script = format!(
+            "sleep 1000 & echo $! > {pid_file}.tmp; mv {pid_file}.tmp {pid_file}; \
+             while read -r request; do :; done"
+        );
+        let action_digest = upload_persistent_worker_action(&cas_store, &slow_store, &worker_script, "worker").await?;
+
+        // Kill the action once the child of the worker is running.
+        let (result, child_pid) = futures::join!(execute_action(&running_actions_manager, action_digest, 94), async {
+            loop {
+                if let Ok(child_pid) = std::fs::read_to_string(&pid_file) {
+                    running_actions_manager.kill_all().await;
+                    return child_pid.trim().to_string();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        assert_eq!(9, result?.exit_code);
+
+        // The child of the worker was killed with the worker.
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while Path::new(&format!("/proc/{child_pid}")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Expected child process {child_pid} to be killed"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persistent_workers_can_not_be_combined_with_cgroup() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let result = setup_running_actions_manager(
+            ExecutionConfiguration {
+                persistent_workers: Some(PersistentWorkersConfig::default()),
+                cgroup: Some(CgroupConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await;
+
+        assert_eq!(result.err().map(|err| err.code), Some(Code::InvalidArgument));
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn container_image_action_is_executed_by_container_runtime() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let runtime_script = write_fake_container_runtime().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                container: Some(ContainerConfig {
+                    runtime_cmd: format!("sh {runtime_script}"),
+                    max_concurrent_actions_per_image: 1,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let command = Command {
+            arguments: vec!["sh".to_string(), "-c".to_string(), "echo $FOO; pwd".to_string()],
+            environment_variables: vec![EnvironmentVariable {
+                name: "FOO".to_string(),
+                value: "bar baz".to_string(),
+            }],
+            working_directory: "some_cwd".to_string(),
+            ..Default::default()
+        };
+        let some_cwd_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.into(),
+        )
+        .await?;
+        let input_root = Directory {
+            directories: vec![DirectoryNode {
+                name: "some_cwd".to_string(),
+                digest: Some(some_cwd_digest.into()),
+            }],
+            ..Default::default()
+        };
+        let action_digest = upload_action(
+            &cas_store,
+            &command,
+            &input_root,
+            &[
+                ("container-image", "docker://example/image:1"),
+                ("memory_kb", "1024"),
+                ("cpu_count", "2"),
+            ],
+        )
+        .await?;
+
+        for salt in [72, 73] {
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.exit_code, 0);
+            let stdout = get_blob_string(&cas_store, result.stdout_digest).await?;
+            assert!(
+                stdout.starts_with(&format!("bar baz\n{root_work_directory}/")) && stdout.ends_with("/some_cwd\n"),
+                "Expected the action to run in its work directory : {stdout:?}"
+            );
+        }
+
+        // The image was only pulled for the first action.
+        let runtime_log = std::fs::read_to_string(Path::new(&runtime_script).with_file_name("runtime.log"))?;
+        assert_eq!(
+            runtime_log,
+            concat!(
+                "pull example/image:1\n",
+                "run example/image:1 --memory 1048576 --cpus 2\n",
+                "run example/image:1 --memory 1048576 --cpus 2\n",
+            )
+        );
+        // Every container is named after its action.
+        let container_names = std::fs::read_to_string(Path::new(&runtime_script).with_file_name("names.log"))?;
+        let container_names: Vec<&str> = container_names.lines().collect();
+        assert_eq!(container_names.len(), 2);
+        assert_ne!(container_names[0], container_names[1]);
+        assert!(container_names.iter().all(|name| name.starts_with("native-link-")));
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn killed_container_is_removed() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let runtime_script = write_fake_container_runtime().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                container: Some(ContainerConfig {
+                    runtime_cmd: format!("sh {runtime_script}"),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let command = Command {
+            arguments: vec!["sh".to_string(), "-c".to_string(), "sleep infinity".to_string()],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(
+            &cas_store,
+            &command,
+            &Directory::default(),
+            &[("container-image", "example/image:1")],
+        )
+        .await?;
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: 86,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+        let runtime_log_path = Path::new(&runtime_script).with_file_name("runtime.log");
+        let (result, _) = futures::join!(run_action(running_action_impl), async {
+            // Killed once the container runs.
+            while !std::fs::read_to_string(&runtime_log_path).is_ok_and(|runtime_log| runtime_log.contains("run ")) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            running_actions_manager.kill_all().await;
+        });
+        assert_eq!(result?.exit_code, 9);
+
+        let container_names = std::fs::read_to_string(Path::new(&runtime_script).with_file_name("names.log"))?;
+        let runtime_log = std::fs::read_to_string(&runtime_log_path)?;
+        assert_eq!(
+            runtime_log,
+            format!(
+                "pull example/image:1\nrun example/image:1\nrm --force {}\n",
+                container_names.trim()
+            )
+        );
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn container_image_that_is_not_image_reference_is_rejected() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let runtime_script = write_fake_container_runtime().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                container: Some(ContainerConfig {
+                    runtime_cmd: format!("sh {runtime_script}"),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let command = Command {
+            arguments: vec!["true".to_string()],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+
+        for (image, salt) in [("--privileged", 95), ("docker://-v/:/host", 96), ("Example/Image", 97)] {
+            let action_digest = upload_action(
+                &cas_store,
+                &command,
+                &Directory::default(),
+                &[("container-image", image)],
+            )
+            .await?;
+            let result = execute_action(&running_actions_manager, action_digest, salt).await;
+            assert_eq!(result.err().map(|err| err.code), Some(Code::InvalidArgument), "{image}");
+        }
+
+        // The runtime was never invoked.
+        assert!(!Path::new(&runtime_script).with_file_name("runtime.log").exists());
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn container_image_action_is_not_executed_in_persistent_worker() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let runtime_script = write_fake_container_runtime().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                container: Some(ContainerConfig {
+                    runtime_cmd: format!("sh {runtime_script}"),
+                    ..Default::default()
+                }),
+                persistent_workers: Some(PersistentWorkersConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let flagfile_digest = DigestInfo::new([4u8; 32], 6);
+        slow_store
+            .as_ref()
+            .update_oneshot(flagfile_digest, "--foo\n".into())
+            .await?;
+        let input_root = Directory {
+            files: vec![FileNode {
+                name: "flagfile".to_string(),
+                digest: Some(flagfile_digest.into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // Outside of a persistent worker, the tool reads its flags from the
+        // flagfile itself.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "cat \"${1#@}\"".to_string(),
+                "worker".to_string(),
+                "@flagfile".to_string(),
+            ],
+            ..Default::default()
+        };
+        let action_digest = upload_action(
+            &cas_store,
+            &command,
+            &input_root,
+            &[
+                ("container-image", "example/image:1"),
+                ("persistentWorkerKey", "worker"),
+            ],
+        )
+        .await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 87).await?;
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(get_blob_string(&cas_store, result.stdout_digest).await?, "--foo\n");
+        let runtime_log = std::fs::read_to_string(Path::new(&runtime_script).with_file_name("runtime.log"))?;
+        assert_eq!(runtime_log, "pull example/image:1\nrun example/image:1\n");
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn directory_cache_reuses_materialized_subdirectories() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                directory_cache: Some(DirectoryCacheConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+
+        let file_digest = DigestInfo::new([5u8; 32], 5);
+        slow_store.as_ref().update_oneshot(file_digest, "hello".into()).await?;
+        let file = FileNode {
+            name: "file".to_string(),
+            digest: Some(file_digest.into()),
+            ..Default::default()
+        };
+        let nested_digest = serialize_and_upload_message(
+            &Directory {
+                files: vec![file.clone()],
+                ..Default::default()
+            },
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.into(),
+        )
+        .await?;
+        let shared_digest = serialize_and_upload_message(
+            &Directory {
+                files: vec![file.clone()],
+                directories: vec![DirectoryNode {
+                    name: "nested".to_string(),
+                    digest: Some(nested_digest.into()),
+                }],
+                ..Default::default()
+            },
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.into(),
+        )
+        .await?;
+        // The input roots differ, but share a subdirectory.
+        let command = Command {
+            arguments: vec!["ls".to_string(), "shared".to_string()],
+            ..Default::default()
+        };
+        let mut action_digests = Vec::new();
+        for name in ["first", "second"] {
+            let input_root = Directory {
+                files: vec![FileNode {
+                    name: name.to_string(),
+                    ..file.clone()
+                }],
+                directories: vec![DirectoryNode {
+                    name: "shared".to_string(),
+                    digest: Some(shared_digest.into()),
+                }],
+                ..Default::default()
+            };
+            action_digests.push(upload_action(&cas_store, &command, &input_root, &[]).await?);
+        }
+
+        let result = execute_action(&running_actions_manager, action_digests[0], 74).await?;
+        assert_eq!(
+            get_blob_string(&cas_store, result.stdout_digest).await?,
+            "file\nnested\n"
+        );
+
+        // The subdirectory and its own subdirectory are cached, the input
+        // root is not.
+        let cached_directories = std::fs::read_dir(format!("{root_work_directory}/directory_cache"))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(cached_directories.len(), 2);
+        let shared_directory = cached_directories
+            .iter()
+            .find(|path| path.join("nested").exists())
+            .err_tip(|| "Expected the shared directory to be cached")?;
+        // The next action gets the subdirectory copied from the cache.
+        std::fs::write(shared_directory.join("marker"), "")?;
+        let result = execute_action(&running_actions_manager, action_digests[1], 75).await?;
+        assert_eq!(
+            get_blob_string(&cas_store, result.stdout_digest).await?,
+            "file\nmarker\nnested\n"
+        );
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn modified_inputs_do_not_corrupt_filesystem_store() -> Result<(), Box<dyn std::error::Error>> {
+        let (fast_store, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let input_digest = DigestInfo::new([6u8; 32], 5);
+        slow_store.as_ref().update_oneshot(input_digest, "hello".into()).await?;
+        let input_root = Directory {
+            files: vec![FileNode {
+                name: "input".to_string(),
+                digest: Some(input_digest.into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // Hardlinked inputs are read-only, but the action can make them
+        // writable again.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "test ! -w input || test $(id -u) = 0; chmod u+w input && echo changed >> input".to_string(),
+            ],
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &input_root, &[]).await?;
+
+        for (salt, input_materialization) in [
+            (76, InputMaterialization::Hardlink),
+            (77, InputMaterialization::Copy),
+            (78, InputMaterialization::Reflink),
+        ] {
+            let (_, running_actions_manager) = setup_running_actions_manager(
+                ExecutionConfiguration {
+                    input_materialization,
+                    ..Default::default()
+                },
+                &cas_store,
+                &ac_store,
+            )
+            .await?;
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.exit_code, 0);
+
+            if input_materialization == InputMaterialization::Hardlink {
+                // The modified file of the store was evicted.
+                assert_eq!(fast_store.as_ref().has(input_digest).await?, None);
+            } else {
+                // The action only modified its copy or reflink.
+                let content = fast_store
+                    .as_ref()
+                    .get_part_unchunked(input_digest, 0, None, None)
+                    .await?;
+                assert_eq!(from_utf8(&content)?, "hello");
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn inputs_with_different_mtimes_do_not_share_file() -> Result<(), Box<dyn std::error::Error>> {
+        let (fast_store, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) =
+            setup_running_actions_manager(ExecutionConfiguration::default(), &cas_store, &ac_store).await?;
+        let input_digest = DigestInfo::new([9u8; 32], 5);
+        fast_store.as_ref().update_oneshot(input_digest, "hello".into()).await?;
+        let store_file_mtime = || async {
+            fast_store
+                .get_file_entry_for_digest(&input_digest)
+                .await?
+                .get_file_path_locked(|path| async move { Ok(fs::metadata(path).await?.modified()?) })
+                .await
+        };
+        let original_mtime = store_file_mtime().await?;
+        let command = Command {
+            arguments: vec![
+                "stat".to_string(),
+                "-c".to_string(),
+                "%Y".to_string(),
+                "input".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        // Both actions request their own mtime for the same file.
+        for (salt, mtime) in [(98, 5), (99, 10)] {
+            let input_root = Directory {
+                files: vec![FileNode {
+                    name: "input".to_string(),
+                    digest: Some(input_digest.into()),
+                    is_executable: false,
+                    node_properties: Some(NodeProperties {
+                        mtime: Some(UNIX_EPOCH.checked_add(Duration::from_secs(mtime)).unwrap().into()),
+                        ..Default::default()
+                    }),
+                }],
+                ..Default::default()
+            };
+            let action_digest = upload_action(&cas_store, &command, &input_root, &[]).await?;
+            let result = execute_action(&running_actions_manager, action_digest, salt).await?;
+            assert_eq!(result.exit_code, 0);
+            assert_eq!(
+                get_blob_string(&cas_store, result.stdout_digest).await?,
+                format!("{mtime}\n")
+            );
+        }
+        // The file of the store was not touched.
+        assert_eq!(store_file_mtime().await?, original_mtime);
+        assert!(fast_store.as_ref().has(input_digest).await?.is_some());
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn modified_inputs_are_removed_from_directory_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let (_, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                directory_cache: Some(DirectoryCacheConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let input_digest = DigestInfo::new([7u8; 32], 5);
+        slow_store.as_ref().update_oneshot(input_digest, "hello".into()).await?;
+        let directory_digest = serialize_and_upload_message(
+            &Directory {
+                files: vec![FileNode {
+                    name: "input".to_string(),
+                    digest: Some(input_digest.into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.into(),
+        )
+        .await?;
+        let input_root = Directory {
+            directories: vec![DirectoryNode {
+                name: "directory".to_string(),
+                digest: Some(directory_digest.into()),
+            }],
+            ..Default::default()
+        };
+        let modify_command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "chmod u+w directory/input && echo changed >> directory/input".to_string(),
+            ],
+            ..Default::default()
+        };
+        let modify_action_digest = upload_action(&cas_store, &modify_command, &input_root, &[]).await?;
+        let read_command = Command {
+            arguments: vec!["cat".to_string(), "directory/input".to_string()],
+            ..Default::default()
+        };
+        let read_action_digest = upload_action(&cas_store, &read_command, &input_root, &[]).await?;
+
+        let result = execute_action(&running_actions_manager, modify_action_digest, 88).await?;
+        assert_eq!(result.exit_code, 0);
+
+        // The cached directory hardlinks the modified file, so it is removed.
+        let directory_cache = format!("{root_work_directory}/directory_cache");
+        let mut cached_directories = std::fs::read_dir(&directory_cache)?.count();
+        for _ in 0..500 {
+            if cached_directories == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cached_directories = std::fs::read_dir(&directory_cache)?.count();
+        }
+        assert_eq!(cached_directories, 0, "Expected cached directory to be removed");
+
+        let result = execute_action(&running_actions_manager, read_action_digest, 89).await?;
+        assert_eq!(get_blob_string(&cas_store, result.stdout_digest).await?, "hello");
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    #[ignore = "Needs to run as root, run with --ignored where it does"]
+    async fn execution_user_can_not_modify_hardlinked_inputs() -> Result<(), Box<dyn std::error::Error>> {
+        let (fast_store, slow_store, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                execution_user: Some(ExecutionUserConfig { uid: 65534, gid: 65534 }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let input_digest = DigestInfo::new([8u8; 32], 5);
+        slow_store.as_ref().update_oneshot(input_digest, "hello".into()).await?;
+        let input_root = Directory {
+            files: vec![FileNode {
+                name: "input".to_string(),
+                digest: Some(input_digest.into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // The action owns its work directory, but not its hardlinked inputs.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "chmod u+w input 2>/dev/null && echo changed >> input; echo out > out.txt; id -u".to_string(),
+            ],
+            output_paths: vec!["out.txt".to_string()],
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &input_root, &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 90).await?;
+        let stderr = get_blob_string(&cas_store, result.stderr_digest).await?;
+        assert_eq!(result.exit_code, 0, "Exit code should be 0, stderr : {stderr:?}");
+        assert_eq!(get_blob_string(&cas_store, result.stdout_digest).await?, "65534\n");
+        assert_eq!(result.output_files.len(), 1);
+        let content = fast_store
+            .as_ref()
+            .get_part_unchunked(input_digest, 0, None, None)
+            .await?;
+        assert_eq!(from_utf8(&content)?, "hello");
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "Needs unprivileged user namespaces, run with --ignored where they are available"]
+    async fn linux_sandbox_isolates_action() -> Result<(), Box<dyn std::error::Error>> {
+        // The action is the init process of its PID namespace, can not see host
+        // files outside of the sandbox, can write to its work directory and
+        // only has a loopback network interface.
+        const EXPECTED_STDOUT: &str = "1\nhidden\nwritable\n1\n";
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (root_work_directory, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                linux_sandbox: Some(LinuxSandboxConfig::default()),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let host_only_file = make_temp_path("host_only_file");
+        fs::create_dir_all(Path::new(&host_only_file).parent().unwrap()).await?;
+        std::fs::File::create(&host_only_file)?;
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "echo $$; test -e {host_only_file} || echo hidden; touch out.txt && echo writable; grep -c : /proc/net/dev"
+                ),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest =
+            upload_action(&cas_store, &command, &Directory::default(), &[("dockerNetwork", "off")]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 68).await?;
+        let stderr = get_blob_string(&cas_store, result.stderr_digest).await?;
+        assert_eq!(result.exit_code, 0, "Exit code should be 0, stderr : {stderr:?}");
+        assert_eq!(
+            get_blob_string(&cas_store, result.stdout_digest).await?,
+            EXPECTED_STDOUT
+        );
+
+        // The sandbox root is cleaned up with the work directory.
+        let mut root_work_directory_entries = std::fs::read_dir(&root_work_directory)?;
+        assert!(
+            root_work_directory_entries.next().is_none(),
+            "Expected root work directory to be empty"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "Needs a writable cgroup v2 hierarchy, run with --ignored where it is available"]
+    async fn cgroup_reports_resource_usage() -> Result<(), Box<dyn std::error::Error>> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let cgroup2_mount = mountinfo
+            .lines()
+            .find(|line| line.contains(" - cgroup2 "))
+            .and_then(|line| line.split(' ').nth(4))
+            .err_tip(|| "Expected cgroup v2 to be mounted")?;
+        let cgroup_path = format!("{cgroup2_mount}/{}", thread_rng().gen::<u64>());
+        std::fs::create_dir(&cgroup_path).err_tip(|| format!("Expected {cgroup2_mount} to be writable"))?;
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                cgroup: Some(CgroupConfig {
+                    path: cgroup_path.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done".to_string(),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 69).await?;
+        assert_eq!(result.exit_code, 0, "Exit code should be 0");
+        assert_eq!(result.error, None);
+        let resource_usage = result
+            .execution_metadata
+            .resource_usage
+            .expect("Expected resource usage to be reported");
+        assert!(
+            resource_usage.user_cpu_time + resource_usage.system_cpu_time > Duration::ZERO,
+            "Expected CPU time to be accounted : {resource_usage:?}"
+        );
+        assert_eq!(resource_usage.oom_kills, 0);
+
+        // The cgroup of the action is removed after the execution.
+        std::fs::remove_dir(&cgroup_path)?;
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[ignore = "Needs a writable cgroup v2 hierarchy, run with --ignored where it is available"]
+    async fn cgroup_ends_processes_that_left_session_of_action() -> Result<(), Box<dyn std::error::Error>> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+        let cgroup2_mount = mountinfo
+            .lines()
+            .find(|line| line.contains(" - cgroup2 "))
+            .and_then(|line| line.split(' ').nth(4))
+            .err_tip(|| "Expected cgroup v2 to be mounted")?;
+        let cgroup_path = format!("{cgroup2_mount}/{}", thread_rng().gen::<u64>());
+        std::fs::create_dir(&cgroup_path).err_tip(|| format!("Expected {cgroup2_mount} to be writable"))?;
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let (_, running_actions_manager) = setup_running_actions_manager(
+            ExecutionConfiguration {
+                cgroup: Some(CgroupConfig {
+                    path: cgroup_path.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            &cas_store,
+            &ac_store,
+        )
+        .await?;
+        let pid_file = make_temp_path("pid_file");
+        fs::create_dir_all(Path::new(&pid_file).parent().unwrap()).await?;
+
+        // The child of the action keeps stdout open, so the action only
+        // completes once the child was killed.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "setsid sh -c 'echo $$ > {pid_file}.tmp; mv {pid_file}.tmp {pid_file}; exec sleep 1000' & \
+                     while [ ! -e {pid_file} ]; do sleep 0.01; done"
+                ),
+            ],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let action_digest = upload_action(&cas_store, &command, &Directory::default(), &[]).await?;
+
+        let result = execute_action(&running_actions_manager, action_digest, 93).await?;
+        assert_eq!(0, result.exit_code);
+
+        // The child was killed and reaped.
+        let child_pid = std::fs::read_to_string(&pid_file)?.trim().to_string();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while Path::new(&format!("/proc/{child_pid}")).exists() {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "Expected child process {child_pid} to be reaped"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        std::fs::remove_dir(&cgroup_path)?;
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn caches_results_in_action_cache_store() -> Result<(), Box<dyn std::error::Error>> {
         let (_, _, cas_store, ac_store) = setup_stores().await?;
@@ -1733,6 +3290,7 @@ exit 1
                 name_or_path: NameOrPath::Path("test.txt".to_string()),
                 digest: DigestInfo::try_new("a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3", 3)?,
                 is_executable: false,
+                node_properties: None,
             }],
             stdout_digest: DigestInfo::try_new("426afaf613d8cfdd9fa8addcc030ae6c95a7950ae0301164af1d5851012081d5", 10)?,
             stderr_digest: DigestInfo::try_new("7b2e400d08b8e334e3172d105be308b506c6036c62a9bde5c509d7808b28b213", 10)?,
@@ -1752,9 +3310,13 @@ exit 1
                 output_upload_start_timestamp: make_system_time(5),
                 output_upload_completed_timestamp: make_system_time(6),
                 worker_completed_timestamp: make_system_time(7),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         running_actions_manager
             .cache_action_result(action_digest, &mut action_result, DigestHasherFunc::Sha256)
@@ -1792,6 +3354,7 @@ exit 1
                 name_or_path: NameOrPath::Path("test.txt".to_string()),
                 digest: DigestInfo::try_new("a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3", 3)?,
                 is_executable: false,
+                node_properties: None,
             }],
             stdout_digest: DigestInfo::try_new("426afaf613d8cfdd9fa8addcc030ae6c95a7950ae0301164af1d5851012081d5", 10)?,
             stderr_digest: DigestInfo::try_new("7b2e400d08b8e334e3172d105be308b506c6036c62a9bde5c509d7808b28b213", 10)?,
@@ -1811,9 +3374,13 @@ exit 1
                 output_upload_start_timestamp: make_system_time(5),
                 output_upload_completed_timestamp: make_system_time(6),
                 worker_completed_timestamp: make_system_time(7),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         running_actions_manager
             .cache_action_result(action_digest, &mut action_result, DigestHasherFunc::Sha256)
@@ -1854,6 +3421,7 @@ exit 1
                 name_or_path: NameOrPath::Path("test.txt".to_string()),
                 digest: DigestInfo::try_new("a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3", 3)?,
                 is_executable: false,
+                node_properties: None,
             }],
             stdout_digest: DigestInfo::try_new("426afaf613d8cfdd9fa8addcc030ae6c95a7950ae0301164af1d5851012081d5", 10)?,
             stderr_digest: DigestInfo::try_new("7b2e400d08b8e334e3172d105be308b506c6036c62a9bde5c509d7808b28b213", 10)?,
@@ -1873,9 +3441,13 @@ exit 1
                 output_upload_start_timestamp: make_system_time(5),
                 output_upload_completed_timestamp: make_system_time(6),
                 worker_completed_timestamp: make_system_time(7),
+                previous_attempts: Vec::new(),
+                resource_usage: None,
+                output_truncations: Vec::new(),
             },
             error: None,
             message: String::new(),
+            flaky_failure_reason: None,
         };
         running_actions_manager
             .cache_action_result(action_digest, &mut action_result, DigestHasherFunc::Sha256)
@@ -20,7 +20,7 @@ use error::{make_input_err, Error};
 use native_link_util::action_messages::ActionResult;
 use native_link_util::common::DigestInfo;
 use native_link_util::digest_hasher::DigestHasherFunc;
-use native_link_worker::running_actions_manager::{Metrics, RunningAction, RunningActionsManager};
+use native_link_worker::running_actions_manager::{ActionId, Metrics, RunningAction, RunningActionsManager};
 use proto::com::github::trace_machina::native_link::remote_execution::StartExecute;
 use tokio::sync::mpsc;
 
@@ -28,6 +28,7 @@ use tokio::sync::mpsc;
 enum RunningActionManagerCalls {
     CreateAndAddAction((String, StartExecute)),
     CacheActionResult(Box<(DigestInfo, ActionResult, DigestHasherFunc)>),
+    KillAction(ActionId),
 }
 
 enum RunningActionManagerReturns {
@@ -95,6 +96,14 @@ impl MockRunningActionsManager {
         }
     }
 
+    pub async fn expect_kill_action(&self) -> ActionId {
+        let mut rx_call_lock = self.rx_call.lock().await;
+        match rx_call_lock.recv().await.expect("Could not recieve msg in mpsc") {
+            RunningActionManagerCalls::KillAction(action_id) => action_id,
+            _ => panic!("Got incorrect call waiting for kill_action"),
+        }
+    }
+
     pub async fn expect_kill_all(&self) {
         let mut rx_kill_all_lock = self.rx_kill_all.lock().await;
         rx_kill_all_lock.recv().await.expect("Could not receive msg in mpsc");
@@ -142,6 +151,17 @@ impl RunningActionsManager for MockRunningActionsManager {
         self.tx_kill_all.send(()).expect("Could not send request to mpsc");
     }
 
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error> {
+        self.tx_call
+            .send(RunningActionManagerCalls::KillAction(*action_id))
+            .expect("Could not send request to mpsc");
+        Ok(())
+    }
+
+    fn recent_input_root_digests(&self) -> Vec<DigestInfo> {
+        Vec::new()
+    }
+
     fn metrics(&self) -> &Arc<Metrics> {
         &self.metrics
     }
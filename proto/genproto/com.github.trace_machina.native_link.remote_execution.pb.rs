@@ -12,178 +12,112 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-/// / Request object for keep alive requests.
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct KeepAliveRequest {
-    /// / ID of the worker making the request.
-    #[prost(string, tag = "1")]
-    pub worker_id: ::prost::alloc::string::String,
-}
-/// / Request object for going away requests.
+/// / Request to subscribe to the events of a scheduler.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct GoingAwayRequest {
-    /// / ID of the worker making the request.
+pub struct SubscribeSchedulerEventsRequest {
+    /// / If set, only events about actions with this instance name and events
+    /// / about workers are sent.
     #[prost(string, tag = "1")]
-    pub worker_id: ::prost::alloc::string::String,
-}
-/// / Represents the initial request sent to the scheduler informing the
-/// / scheduler about this worker's capabilities.
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct SupportedProperties {
-    /// / The list of properties this worker can support. The exact
-    /// / implementation is driven by the configuration matrix between the
-    /// / worker and scheduler.
-    /// /
-    /// / The scheduler may reject this worker if any property keys that
-    /// / the scheduler is not configured to support, or may simply ignore
-    /// / the unsupported properties.
-    /// /
-    /// / The details on how to use this property can be found here:
-    /// / <https://github.com/TraceMachina/native-link/blob/3147265047544572e3483c985e4aab0f9fdded38/native-link-config/src/cas_server.rs>
-    #[prost(message, repeated, tag = "1")]
-    pub properties: ::prost::alloc::vec::Vec<
-        super::super::super::super::super::build::bazel::remote::execution::v2::platform::Property,
-    >,
+    pub instance_name: ::prost::alloc::string::String,
 }
-/// / The result of an ExecutionRequest.
+/// / A structured event about an action or worker of a scheduler.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ExecuteResult {
-    /// / ID of the worker making the request.
-    #[prost(string, tag = "1")]
-    pub worker_id: ::prost::alloc::string::String,
-    /// / The `instance_name` this task was initially assigned to. This is set by the client
-    /// / that initially sent the job as part of the BRE protocol.
-    #[prost(string, tag = "6")]
+pub struct SchedulerEvent {
+    /// / When the event happened.
+    #[prost(message, optional, tag = "1")]
+    pub timestamp: ::core::option::Option<::prost_types::Timestamp>,
+    /// / What happened.
+    #[prost(enumeration = "SchedulerEventType", tag = "2")]
+    pub event_type: i32,
+    /// / The instance name of the action. Empty for worker events.
+    #[prost(string, tag = "3")]
     pub instance_name: ::prost::alloc::string::String,
-    /// / The original execution digest request for this response. The scheduler knows what it
-    /// / should be, but we do safety checks to ensure it really is the request we expected.
-    #[prost(message, optional, tag = "2")]
+    /// / The digest of the action. Not set for worker events.
+    #[prost(message, optional, tag = "4")]
     pub action_digest: ::core::option::Option<
         super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
     >,
-    /// / The salt originally sent along with the StartExecute request. This salt is used
-    /// / as a seed for cases where the execution digest should never be cached or merged
-    /// / with other jobs. This salt is added to the hash function used to compute jobs that
-    /// / are running or cached.
-    #[prost(uint64, tag = "3")]
-    pub salt: u64,
-    /// / The actual response data.
-    #[prost(oneof = "execute_result::Result", tags = "4, 5")]
-    pub result: ::core::option::Option<execute_result::Result>,
-}
-/// Nested message and enum types in `ExecuteResult`.
-pub mod execute_result {
-    /// / The actual response data.
-    #[allow(clippy::derive_partial_eq_without_eq)]
-    #[derive(Clone, PartialEq, ::prost::Oneof)]
-    pub enum Result {
-        /// / Result of the execution. See `build.bazel.remote.execution.v2.ExecuteResponse`
-        /// / for details.
-        #[prost(message, tag = "4")]
-        ExecuteResponse(
-            super::super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteResponse,
-        ),
-        /// / An internal error. This is only present when an internal error happened that
-        /// / was not recoverable. If the execution job failed but at no fault of the worker
-        /// / it should not use this field and should send the error via execute_response.
-        #[prost(message, tag = "5")]
-        InternalError(super::super::super::super::super::super::google::rpc::Status),
-    }
-}
-/// / Result sent back from the server when a node connects.
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ConnectionResult {
-    /// / The internal ID given to the newly connected node.
-    #[prost(string, tag = "1")]
+    /// / The worker involved in the event, if any.
+    #[prost(string, tag = "5")]
     pub worker_id: ::prost::alloc::string::String,
+    /// / The number of times the action was sent to a worker so far.
+    #[prost(uint64, tag = "6")]
+    pub attempts: u64,
+    /// / The exit code of the action. Only set for `ACTION_COMPLETED` events
+    /// / of actions that ran to completion.
+    #[prost(int32, optional, tag = "7")]
+    pub exit_code: ::core::option::Option<i32>,
+    /// / Human readable details of the event, eg: the error that caused an
+    /// / action to be retried or a worker to be evicted.
+    #[prost(string, tag = "8")]
+    pub message: ::prost::alloc::string::String,
 }
-/// / Communication from the scheduler to the worker.
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateForWorker {
-    #[prost(oneof = "update_for_worker::Update", tags = "1, 2, 3, 4")]
-    pub update: ::core::option::Option<update_for_worker::Update>,
+/// / The kind of a `SchedulerEvent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SchedulerEventType {
+    /// / Not a valid event type.
+    Unknown = 0,
+    /// / A new action was added to the queue.
+    ActionAdded = 1,
+    /// / An action was added while an identical action was already queued or
+    /// / running, so the client was subscribed to the existing action.
+    ActionJoinedExisting = 2,
+    /// / An action was sent to a worker to be executed.
+    ActionMatched = 3,
+    /// / An execution of an action failed and the action was put back in the
+    /// / queue to be retried.
+    ActionRetried = 4,
+    /// / An action finished, either successfully or with an error.
+    ActionCompleted = 5,
+    /// / A worker was removed from the pool of workers.
+    WorkerEvicted = 6,
 }
-/// Nested message and enum types in `UpdateForWorker`.
-pub mod update_for_worker {
-    #[allow(clippy::derive_partial_eq_without_eq)]
-    #[derive(Clone, PartialEq, ::prost::Oneof)]
-    pub enum Update {
-        /// / This will be sent only as the first item in the stream after the node
-        /// / has connected.
-        #[prost(message, tag = "1")]
-        ConnectionResult(super::ConnectionResult),
-        /// / Message used to let the worker know that it is still alive as well
-        /// / as check to see if the worker is still alive. The worker
-        /// / may close the connection if the scheduler has not sent any messages
-        /// / after some amount of time (configured in the scheduler's
-        /// / configuration).
-        #[prost(message, tag = "2")]
-        KeepAlive(()),
-        /// / Informs the worker about some work it should begin performing the
-        /// / requested action.
-        #[prost(message, tag = "3")]
-        StartAction(super::StartExecute),
-        /// / Informs the worker that it has been disconnected from the pool.
-        /// / The worker may discard any outstanding work that is being executed.
-        #[prost(message, tag = "4")]
-        Disconnect(()),
+impl SchedulerEventType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SchedulerEventType::Unknown => "UNKNOWN",
+            SchedulerEventType::ActionAdded => "ACTION_ADDED",
+            SchedulerEventType::ActionJoinedExisting => "ACTION_JOINED_EXISTING",
+            SchedulerEventType::ActionMatched => "ACTION_MATCHED",
+            SchedulerEventType::ActionRetried => "ACTION_RETRIED",
+            SchedulerEventType::ActionCompleted => "ACTION_COMPLETED",
+            SchedulerEventType::WorkerEvicted => "WORKER_EVICTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "UNKNOWN" => Some(Self::Unknown),
+            "ACTION_ADDED" => Some(Self::ActionAdded),
+            "ACTION_JOINED_EXISTING" => Some(Self::ActionJoinedExisting),
+            "ACTION_MATCHED" => Some(Self::ActionMatched),
+            "ACTION_RETRIED" => Some(Self::ActionRetried),
+            "ACTION_COMPLETED" => Some(Self::ActionCompleted),
+            "WORKER_EVICTED" => Some(Self::WorkerEvicted),
+            _ => None,
+        }
     }
-}
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct StartExecute {
-    /// / The action information used to execute job.
-    #[prost(message, optional, tag = "1")]
-    pub execute_request: ::core::option::Option<
-        super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteRequest,
-    >,
-    /// / See documentation in ExecuteResult::salt.
-    #[prost(uint64, tag = "2")]
-    pub salt: u64,
-    /// / The time at which the command was added to the queue to allow population
-    /// / of the ActionResult.
-    #[prost(message, optional, tag = "3")]
-    pub queued_timestamp: ::core::option::Option<::prost_types::Timestamp>,
-}
-/// / This is a special message used to save actions into the CAS that can be used
-/// / by programs like bb_browswer to inspect the history of a build.
-/// / Note: Ensure this is always compatible with:
-/// / <https://github.com/buildbarn/bb-remote-execution/blob/e95e066eb624dc9099682394ec18c12e218e8fc4/pkg/proto/cas/cas.proto#L23>
-#[allow(clippy::derive_partial_eq_without_eq)]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct HistoricalExecuteResponse {
-    #[prost(message, optional, tag = "1")]
-    pub action_digest: ::core::option::Option<
-        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
-    >,
-    #[prost(message, optional, tag = "3")]
-    pub execute_response: ::core::option::Option<
-        super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteResponse,
-    >,
 }
 /// Generated client implementations.
-pub mod worker_api_client {
+pub mod scheduler_events_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
     use tonic::codegen::http::Uri;
-    /// / This API describes how schedulers communicate with Worker nodes.
-    /// /
-    /// / When a worker node comes online it must be pre-configured with the
-    /// / endpoint of the scheduler it will register with. Once the worker
-    /// / connects to the scheduler it must send a `RegisterSupportedProperties`
-    /// / command to the scheduler. The scheduler will then use this information
-    /// / to determine which jobs the worker can process.
+    /// / This API lets operators follow what a scheduler does with actions and
+    /// / workers as it happens, eg: to build dashboards or to reconstruct what
+    /// / happened to an action after the fact.
     #[derive(Debug, Clone)]
-    pub struct WorkerApiClient<T> {
+    pub struct SchedulerEventsClient<T> {
         inner: tonic::client::Grpc<T>,
     }
-    impl WorkerApiClient<tonic::transport::Channel> {
+    impl SchedulerEventsClient<tonic::transport::Channel> {
         /// Attempt to create a new client by connecting to a given endpoint.
         pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
         where
@@ -194,7 +128,7 @@ pub mod worker_api_client {
             Ok(Self::new(conn))
         }
     }
-    impl<T> WorkerApiClient<T>
+    impl<T> SchedulerEventsClient<T>
     where
         T: tonic::client::GrpcService<tonic::body::BoxBody>,
         T::Error: Into<StdError>,
@@ -212,7 +146,7 @@ pub mod worker_api_client {
         pub fn with_interceptor<F>(
             inner: T,
             interceptor: F,
-        ) -> WorkerApiClient<InterceptedService<T, F>>
+        ) -> SchedulerEventsClient<InterceptedService<T, F>>
         where
             F: tonic::service::Interceptor,
             T::ResponseBody: Default,
@@ -226,7 +160,7 @@ pub mod worker_api_client {
                 http::Request<tonic::body::BoxBody>,
             >>::Error: Into<StdError> + Send + Sync,
         {
-            WorkerApiClient::new(InterceptedService::new(inner, interceptor))
+            SchedulerEventsClient::new(InterceptedService::new(inner, interceptor))
         }
         /// Compress requests with the given encoding.
         ///
@@ -259,15 +193,14 @@ pub mod worker_api_client {
             self.inner = self.inner.max_encoding_message_size(limit);
             self
         }
-        /// / Registers this worker and informs the scheduler what properties
-        /// / this worker supports. The response must be listened on the client
-        /// / side for updates from the server. The first item sent will always be
-        /// / a ConnectionResult, after that it is undefined.
-        pub async fn connect_worker(
+        /// / Streams the events of the scheduler from the time of the call on.
+        /// / Events that happened before the call are not sent. If the subscriber
+        /// / can not keep up, the oldest events that were not sent yet are dropped.
+        pub async fn subscribe(
             &mut self,
-            request: impl tonic::IntoRequest<super::SupportedProperties>,
+            request: impl tonic::IntoRequest<super::SubscribeSchedulerEventsRequest>,
         ) -> std::result::Result<
-            tonic::Response<tonic::codec::Streaming<super::UpdateForWorker>>,
+            tonic::Response<tonic::codec::Streaming<super::SchedulerEvent>>,
             tonic::Status,
         > {
             self.inner
@@ -281,30 +214,635 @@ pub mod worker_api_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ConnectWorker",
+                "/com.github.trace_machina.native_link.remote_execution.SchedulerEvents/Subscribe",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
-                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
-                        "ConnectWorker",
+                        "com.github.trace_machina.native_link.remote_execution.SchedulerEvents",
+                        "Subscribe",
                     ),
                 );
             self.inner.server_streaming(req, path, codec).await
         }
-        /// / Message used to let the scheduler know that it is still alive as
-        /// / well as check to see if the scheduler is still alive. The scheduler
-        /// / may close the connection if the worker has not sent any messages
-        /// / after some amount of time (configured in the scheduler's
-        /// / configuration).
-        pub async fn keep_alive(
+    }
+}
+/// Generated server implementations.
+pub mod scheduler_events_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerEventsServer.
+    #[async_trait]
+    pub trait SchedulerEvents: Send + Sync + 'static {
+        /// Server streaming response type for the Subscribe method.
+        type SubscribeStream: futures_core::Stream<
+                Item = std::result::Result<super::SchedulerEvent, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// / Streams the events of the scheduler from the time of the call on.
+        /// / Events that happened before the call are not sent. If the subscriber
+        /// / can not keep up, the oldest events that were not sent yet are dropped.
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::SubscribeSchedulerEventsRequest>,
+        ) -> std::result::Result<tonic::Response<Self::SubscribeStream>, tonic::Status>;
+    }
+    /// / This API lets operators follow what a scheduler does with actions and
+    /// / workers as it happens, eg: to build dashboards or to reconstruct what
+    /// / happened to an action after the fact.
+    #[derive(Debug)]
+    pub struct SchedulerEventsServer<T: SchedulerEvents> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: SchedulerEvents> SchedulerEventsServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerEventsServer<T>
+    where
+        T: SchedulerEvents,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
             &mut self,
-            request: impl tonic::IntoRequest<super::KeepAliveRequest>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
-            self.inner
-                .ready()
-                .await
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/com.github.trace_machina.native_link.remote_execution.SchedulerEvents/Subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSvc<T: SchedulerEvents>(pub Arc<T>);
+                    impl<
+                        T: SchedulerEvents,
+                    > tonic::server::ServerStreamingService<
+                        super::SubscribeSchedulerEventsRequest,
+                    > for SubscribeSvc<T> {
+                        type Response = super::SchedulerEvent;
+                        type ResponseStream = T::SubscribeStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::SubscribeSchedulerEventsRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).subscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: SchedulerEvents> Clone for SchedulerEventsServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    impl<T: SchedulerEvents> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: SchedulerEvents> tonic::server::NamedService for SchedulerEventsServer<T> {
+        const NAME: &'static str = "com.github.trace_machina.native_link.remote_execution.SchedulerEvents";
+    }
+}
+/// / Request object for keep alive requests.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeepAliveRequest {
+    /// / ID of the worker making the request.
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// / Input root digests of the actions most recently materialized on this
+    /// / worker, most recent first. The files referenced by these input roots
+    /// / are likely still in the worker's local cache, so the scheduler may use
+    /// / this list to prefer this worker for actions that share an input root.
+    /// /
+    /// / Each keep alive replaces the previously reported list.
+    #[prost(message, repeated, tag = "2")]
+    pub recent_input_root_digests: ::prost::alloc::vec::Vec<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+}
+/// / Request object for going away requests.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GoingAwayRequest {
+    /// / ID of the worker making the request.
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+}
+/// / Represents the initial request sent to the scheduler informing the
+/// / scheduler about this worker's capabilities.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SupportedProperties {
+    /// / The list of properties this worker can support. The exact
+    /// / implementation is driven by the configuration matrix between the
+    /// / worker and scheduler.
+    /// /
+    /// / The scheduler may reject this worker if any property keys that
+    /// / the scheduler is not configured to support, or may simply ignore
+    /// / the unsupported properties.
+    /// /
+    /// / The details on how to use this property can be found here:
+    /// / <https://github.com/TraceMachina/native-link/blob/3147265047544572e3483c985e4aab0f9fdded38/native-link-config/src/cas_server.rs>
+    #[prost(message, repeated, tag = "1")]
+    pub properties: ::prost::alloc::vec::Vec<
+        super::super::super::super::super::build::bazel::remote::execution::v2::platform::Property,
+    >,
+}
+/// / The result of an ExecutionRequest.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecuteResult {
+    /// / ID of the worker making the request.
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// / The `instance_name` this task was initially assigned to. This is set by the client
+    /// / that initially sent the job as part of the BRE protocol.
+    #[prost(string, tag = "6")]
+    pub instance_name: ::prost::alloc::string::String,
+    /// / The original execution digest request for this response. The scheduler knows what it
+    /// / should be, but we do safety checks to ensure it really is the request we expected.
+    #[prost(message, optional, tag = "2")]
+    pub action_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    /// / The salt originally sent along with the StartExecute request. This salt is used
+    /// / as a seed for cases where the execution digest should never be cached or merged
+    /// / with other jobs. This salt is added to the hash function used to compute jobs that
+    /// / are running or cached.
+    #[prost(uint64, tag = "3")]
+    pub salt: u64,
+    /// / If set, the execution failed in a way the worker is configured to
+    /// / consider flaky, eg: a known infrastructure failure. The scheduler may
+    /// / run the action again instead of completing it. Describes why the
+    /// / execution is considered flaky.
+    #[prost(string, tag = "7")]
+    pub flaky_failure_reason: ::prost::alloc::string::String,
+    /// / The actual response data.
+    #[prost(oneof = "execute_result::Result", tags = "4, 5")]
+    pub result: ::core::option::Option<execute_result::Result>,
+}
+/// Nested message and enum types in `ExecuteResult`.
+pub mod execute_result {
+    /// / The actual response data.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Result {
+        /// / Result of the execution. See `build.bazel.remote.execution.v2.ExecuteResponse`
+        /// / for details.
+        #[prost(message, tag = "4")]
+        ExecuteResponse(
+            super::super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteResponse,
+        ),
+        /// / An internal error. This is only present when an internal error happened that
+        /// / was not recoverable. If the execution job failed but at no fault of the worker
+        /// / it should not use this field and should send the error via execute_response.
+        #[prost(message, tag = "5")]
+        InternalError(super::super::super::super::super::super::google::rpc::Status),
+    }
+}
+/// / A previous execution of an action that was not used as its result,
+/// / because it failed in a way that was considered flaky and the action was
+/// / run again. Sent to clients in
+/// / `build.bazel.remote.execution.v2.ExecutedActionMetadata.auxiliary_metadata`
+/// / of the final result.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutionAttempt {
+    /// / The worker that ran the execution.
+    #[prost(string, tag = "1")]
+    pub worker: ::prost::alloc::string::String,
+    /// / When the worker started the execution.
+    #[prost(message, optional, tag = "2")]
+    pub worker_start_timestamp: ::core::option::Option<::prost_types::Timestamp>,
+    /// / When the worker completed the execution.
+    #[prost(message, optional, tag = "3")]
+    pub worker_completed_timestamp: ::core::option::Option<::prost_types::Timestamp>,
+    /// / The exit code of the execution.
+    #[prost(int32, tag = "4")]
+    pub exit_code: i32,
+    /// / The digest of the stdout of the execution.
+    #[prost(message, optional, tag = "5")]
+    pub stdout_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    /// / The digest of the stderr of the execution.
+    #[prost(message, optional, tag = "6")]
+    pub stderr_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    /// / Why the execution was considered flaky.
+    #[prost(string, tag = "7")]
+    pub flaky_failure_reason: ::prost::alloc::string::String,
+}
+/// / Where a queued action is in the queue of its scheduler. Sent to clients in
+/// / `build.bazel.remote.execution.v2.ExecutedActionMetadata.auxiliary_metadata`
+/// / of `ExecuteOperationMetadata.partial_execution_metadata` while the action
+/// / is queued.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueuePosition {
+    /// / The number of queued actions with the same platform properties that
+    /// / will be sent to a worker before this action. Zero if this action is
+    /// / next.
+    #[prost(uint64, tag = "1")]
+    pub position: u64,
+    /// / The number of queued actions with the same platform properties,
+    /// / including this action.
+    #[prost(uint64, tag = "2")]
+    pub queue_length: u64,
+    /// / When the action is expected to be sent to a worker. Not set if the
+    /// / scheduler can not estimate it, eg: if no worker can run the action or
+    /// / the durations of the actions before it are unknown.
+    #[prost(message, optional, tag = "3")]
+    pub estimated_start_timestamp: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// / Resources used by an execution, measured with the cgroup the worker ran
+/// / the execution in. Sent to clients in
+/// / `build.bazel.remote.execution.v2.ExecutedActionMetadata.auxiliary_metadata`
+/// / of the result.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResourceUsage {
+    /// / The peak memory usage of the execution in bytes.
+    #[prost(uint64, tag = "1")]
+    pub peak_memory_bytes: u64,
+    /// / The CPU time the execution spent in user mode.
+    #[prost(message, optional, tag = "2")]
+    pub user_cpu_time: ::core::option::Option<::prost_types::Duration>,
+    /// / The CPU time the execution spent in kernel mode.
+    #[prost(message, optional, tag = "3")]
+    pub system_cpu_time: ::core::option::Option<::prost_types::Duration>,
+    /// / The number of processes of the execution that were killed because
+    /// / the execution exceeded its memory limit.
+    #[prost(uint64, tag = "4")]
+    pub oom_kills: u64,
+}
+/// / An output stream of an execution that the worker truncated, because it
+/// / was larger than the worker is configured to keep. Sent to clients in
+/// / `build.bazel.remote.execution.v2.ExecutedActionMetadata.auxiliary_metadata`
+/// / of the result.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OutputTruncation {
+    /// / The name of the truncated stream, eg: "stdout" or "stderr".
+    #[prost(string, tag = "1")]
+    pub stream_name: ::prost::alloc::string::String,
+    /// / The number of bytes of the stream that were kept.
+    #[prost(uint64, tag = "2")]
+    pub kept_bytes: u64,
+    /// / The number of bytes the execution wrote to the stream.
+    #[prost(uint64, tag = "3")]
+    pub total_bytes: u64,
+}
+/// / Result sent back from the server when a node connects.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConnectionResult {
+    /// / The internal ID given to the newly connected node.
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+}
+/// / Communication from the scheduler to the worker.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateForWorker {
+    #[prost(oneof = "update_for_worker::Update", tags = "1, 2, 3, 4, 5")]
+    pub update: ::core::option::Option<update_for_worker::Update>,
+}
+/// Nested message and enum types in `UpdateForWorker`.
+pub mod update_for_worker {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Update {
+        /// / This will be sent only as the first item in the stream after the node
+        /// / has connected.
+        #[prost(message, tag = "1")]
+        ConnectionResult(super::ConnectionResult),
+        /// / Message used to let the worker know that it is still alive as well
+        /// / as check to see if the worker is still alive. The worker
+        /// / may close the connection if the scheduler has not sent any messages
+        /// / after some amount of time (configured in the scheduler's
+        /// / configuration).
+        #[prost(message, tag = "2")]
+        KeepAlive(()),
+        /// / Informs the worker about some work it should begin performing the
+        /// / requested action.
+        #[prost(message, tag = "3")]
+        StartAction(super::StartExecute),
+        /// / Informs the worker that it has been disconnected from the pool.
+        /// / The worker may discard any outstanding work that is being executed.
+        #[prost(message, tag = "4")]
+        Disconnect(()),
+        /// / Informs the worker that a running action is no longer needed and
+        /// / should be killed. The worker must still report the outcome of the
+        /// / action through `ExecutionResponse`.
+        #[prost(message, tag = "5")]
+        KillActionRequest(super::KillActionRequest),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KillActionRequest {
+    /// / The `instance_name` of the action to kill. See `ExecuteResult::instance_name`.
+    #[prost(string, tag = "1")]
+    pub instance_name: ::prost::alloc::string::String,
+    /// / The digest of the action to kill.
+    #[prost(message, optional, tag = "2")]
+    pub action_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    /// / The salt of the action to kill. See `ExecuteResult::salt`.
+    #[prost(uint64, tag = "3")]
+    pub salt: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StartExecute {
+    /// / The action information used to execute job.
+    #[prost(message, optional, tag = "1")]
+    pub execute_request: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteRequest,
+    >,
+    /// / See documentation in ExecuteResult::salt.
+    #[prost(uint64, tag = "2")]
+    pub salt: u64,
+    /// / The time at which the command was added to the queue to allow population
+    /// / of the ActionResult.
+    #[prost(message, optional, tag = "3")]
+    pub queued_timestamp: ::core::option::Option<::prost_types::Timestamp>,
+}
+/// / This is a special message used to save actions into the CAS that can be used
+/// / by programs like bb_browswer to inspect the history of a build.
+/// / Note: Ensure this is always compatible with:
+/// / <https://github.com/buildbarn/bb-remote-execution/blob/e95e066eb624dc9099682394ec18c12e218e8fc4/pkg/proto/cas/cas.proto#L23>
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoricalExecuteResponse {
+    #[prost(message, optional, tag = "1")]
+    pub action_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    #[prost(message, optional, tag = "3")]
+    pub execute_response: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::ExecuteResponse,
+    >,
+}
+/// Generated client implementations.
+pub mod worker_api_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// / This API describes how schedulers communicate with Worker nodes.
+    /// /
+    /// / When a worker node comes online it must be pre-configured with the
+    /// / endpoint of the scheduler it will register with. Once the worker
+    /// / connects to the scheduler it must send a `RegisterSupportedProperties`
+    /// / command to the scheduler. The scheduler will then use this information
+    /// / to determine which jobs the worker can process.
+    #[derive(Debug, Clone)]
+    pub struct WorkerApiClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl WorkerApiClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> WorkerApiClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> WorkerApiClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            WorkerApiClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// / Registers this worker and informs the scheduler what properties
+        /// / this worker supports. The response must be listened on the client
+        /// / side for updates from the server. The first item sent will always be
+        /// / a ConnectionResult, after that it is undefined.
+        pub async fn connect_worker(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SupportedProperties>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::UpdateForWorker>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ConnectWorker",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
+                        "ConnectWorker",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// / Message used to let the scheduler know that it is still alive as
+        /// / well as check to see if the scheduler is still alive. The scheduler
+        /// / may close the connection if the worker has not sent any messages
+        /// / after some amount of time (configured in the scheduler's
+        /// / configuration).
+        pub async fn keep_alive(
+            &mut self,
+            request: impl tonic::IntoRequest<super::KeepAliveRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
                 .map_err(|e| {
                     tonic::Status::new(
                         tonic::Code::Unknown,
@@ -325,49 +863,570 @@ pub mod worker_api_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// / Informs the scheduler that the service is going offline and
-        /// / should stop issuing any new actions on this worker.
-        /// /
-        /// / The worker may stay connected even after sending this command
-        /// / and may even send an `ExecuteResult` after sending this command.
-        /// / It is up to the scheduler implementation to decide how to handle
-        /// / this case.
-        /// /
-        /// / Any job that was running on this instance likely needs to be
-        /// / executed again, but up to the scheduler on how or when to handle
-        /// / this case.
-        pub async fn going_away(
-            &mut self,
-            request: impl tonic::IntoRequest<super::GoingAwayRequest>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::new(
-                        tonic::Code::Unknown,
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
-            let codec = tonic::codec::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/GoingAway",
-            );
-            let mut req = request.into_request();
-            req.extensions_mut()
-                .insert(
-                    GrpcMethod::new(
-                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
-                        "GoingAway",
-                    ),
-                );
-            self.inner.unary(req, path, codec).await
+        /// / Informs the scheduler that the service is going offline and
+        /// / should stop issuing any new actions on this worker.
+        /// /
+        /// / The worker may stay connected even after sending this command
+        /// / and may even send an `ExecuteResult` after sending this command.
+        /// / It is up to the scheduler implementation to decide how to handle
+        /// / this case.
+        /// /
+        /// / Any job that was running on this instance likely needs to be
+        /// / executed again, but up to the scheduler on how or when to handle
+        /// / this case.
+        pub async fn going_away(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GoingAwayRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/GoingAway",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
+                        "GoingAway",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// / Informs the scheduler about the result of an execution request.
+        pub async fn execution_response(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecuteResult>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ExecutionResponse",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
+                        "ExecutionResponse",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod worker_api_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with WorkerApiServer.
+    #[async_trait]
+    pub trait WorkerApi: Send + Sync + 'static {
+        /// Server streaming response type for the ConnectWorker method.
+        type ConnectWorkerStream: futures_core::Stream<
+                Item = std::result::Result<super::UpdateForWorker, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// / Registers this worker and informs the scheduler what properties
+        /// / this worker supports. The response must be listened on the client
+        /// / side for updates from the server. The first item sent will always be
+        /// / a ConnectionResult, after that it is undefined.
+        async fn connect_worker(
+            &self,
+            request: tonic::Request<super::SupportedProperties>,
+        ) -> std::result::Result<
+            tonic::Response<Self::ConnectWorkerStream>,
+            tonic::Status,
+        >;
+        /// / Message used to let the scheduler know that it is still alive as
+        /// / well as check to see if the scheduler is still alive. The scheduler
+        /// / may close the connection if the worker has not sent any messages
+        /// / after some amount of time (configured in the scheduler's
+        /// / configuration).
+        async fn keep_alive(
+            &self,
+            request: tonic::Request<super::KeepAliveRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// / Informs the scheduler that the service is going offline and
+        /// / should stop issuing any new actions on this worker.
+        /// /
+        /// / The worker may stay connected even after sending this command
+        /// / and may even send an `ExecuteResult` after sending this command.
+        /// / It is up to the scheduler implementation to decide how to handle
+        /// / this case.
+        /// /
+        /// / Any job that was running on this instance likely needs to be
+        /// / executed again, but up to the scheduler on how or when to handle
+        /// / this case.
+        async fn going_away(
+            &self,
+            request: tonic::Request<super::GoingAwayRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// / Informs the scheduler about the result of an execution request.
+        async fn execution_response(
+            &self,
+            request: tonic::Request<super::ExecuteResult>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+    }
+    /// / This API describes how schedulers communicate with Worker nodes.
+    /// /
+    /// / When a worker node comes online it must be pre-configured with the
+    /// / endpoint of the scheduler it will register with. Once the worker
+    /// / connects to the scheduler it must send a `RegisterSupportedProperties`
+    /// / command to the scheduler. The scheduler will then use this information
+    /// / to determine which jobs the worker can process.
+    #[derive(Debug)]
+    pub struct WorkerApiServer<T: WorkerApi> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: WorkerApi> WorkerApiServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for WorkerApiServer<T>
+    where
+        T: WorkerApi,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ConnectWorker" => {
+                    #[allow(non_camel_case_types)]
+                    struct ConnectWorkerSvc<T: WorkerApi>(pub Arc<T>);
+                    impl<
+                        T: WorkerApi,
+                    > tonic::server::ServerStreamingService<super::SupportedProperties>
+                    for ConnectWorkerSvc<T> {
+                        type Response = super::UpdateForWorker;
+                        type ResponseStream = T::ConnectWorkerStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SupportedProperties>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).connect_worker(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ConnectWorkerSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/KeepAlive" => {
+                    #[allow(non_camel_case_types)]
+                    struct KeepAliveSvc<T: WorkerApi>(pub Arc<T>);
+                    impl<
+                        T: WorkerApi,
+                    > tonic::server::UnaryService<super::KeepAliveRequest>
+                    for KeepAliveSvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::KeepAliveRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).keep_alive(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = KeepAliveSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/GoingAway" => {
+                    #[allow(non_camel_case_types)]
+                    struct GoingAwaySvc<T: WorkerApi>(pub Arc<T>);
+                    impl<
+                        T: WorkerApi,
+                    > tonic::server::UnaryService<super::GoingAwayRequest>
+                    for GoingAwaySvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GoingAwayRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).going_away(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GoingAwaySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ExecutionResponse" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecutionResponseSvc<T: WorkerApi>(pub Arc<T>);
+                    impl<T: WorkerApi> tonic::server::UnaryService<super::ExecuteResult>
+                    for ExecutionResponseSvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecuteResult>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).execution_response(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExecutionResponseSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        Ok(
+                            http::Response::builder()
+                                .status(200)
+                                .header("grpc-status", "12")
+                                .header("content-type", "application/grpc")
+                                .body(empty_body())
+                                .unwrap(),
+                        )
+                    })
+                }
+            }
+        }
+    }
+    impl<T: WorkerApi> Clone for WorkerApiServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    impl<T: WorkerApi> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: WorkerApi> tonic::server::NamedService for WorkerApiServer<T> {
+        const NAME: &'static str = "com.github.trace_machina.native_link.remote_execution.WorkerApi";
+    }
+}
+/// / Request for the current demand of a scheduler.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWorkerPoolDemandRequest {}
+/// / The demand of the actions that requested the same platform properties.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PlatformPropertiesDemand {
+    /// / The platform properties requested by the actions.
+    #[prost(map = "string, string", tag = "1")]
+    pub platform_properties: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// / The number of actions waiting for a worker.
+    #[prost(uint64, tag = "2")]
+    pub queued_actions: u64,
+    /// / The number of actions running on a worker.
+    #[prost(uint64, tag = "3")]
+    pub executing_actions: u64,
+    /// / The number of workers that satisfy the platform properties and are
+    /// / not running any action. A worker that satisfies multiple sets of
+    /// / platform properties is counted for each of them.
+    #[prost(uint64, tag = "4")]
+    pub idle_workers: u64,
+    /// / The number of workers that should be added to run the queued actions,
+    /// / which is the number of queued actions that can not be sent to an idle
+    /// / worker.
+    #[prost(uint64, tag = "5")]
+    pub recommended_additional_workers: u64,
+}
+/// / The current demand of a scheduler.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetWorkerPoolDemandResponse {
+    /// / The demand per distinct set of platform properties of queued or
+    /// / executing actions, ordered by the platform properties.
+    #[prost(message, repeated, tag = "1")]
+    pub demands: ::prost::alloc::vec::Vec<PlatformPropertiesDemand>,
+}
+/// Generated client implementations.
+pub mod worker_pool_demand_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// / This API reports how much work a scheduler has for each kind of worker,
+    /// / so pools of workers can be scaled by an external autoscaler.
+    #[derive(Debug, Clone)]
+    pub struct WorkerPoolDemandClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl WorkerPoolDemandClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> WorkerPoolDemandClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> WorkerPoolDemandClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            WorkerPoolDemandClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
         }
-        /// / Informs the scheduler about the result of an execution request.
-        pub async fn execution_response(
+        /// / Returns the current demand of the scheduler per distinct set of
+        /// / platform properties requested by actions.
+        pub async fn get_worker_pool_demand(
             &mut self,
-            request: impl tonic::IntoRequest<super::ExecuteResult>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            request: impl tonic::IntoRequest<super::GetWorkerPoolDemandRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetWorkerPoolDemandResponse>,
+            tonic::Status,
+        > {
             self.inner
                 .ready()
                 .await
@@ -379,14 +1438,14 @@ pub mod worker_api_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ExecutionResponse",
+                "/com.github.trace_machina.native_link.remote_execution.WorkerPoolDemand/GetWorkerPoolDemand",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
-                        "com.github.trace_machina.native_link.remote_execution.WorkerApi",
-                        "ExecutionResponse",
+                        "com.github.trace_machina.native_link.remote_execution.WorkerPoolDemand",
+                        "GetWorkerPoolDemand",
                     ),
                 );
             self.inner.unary(req, path, codec).await
@@ -394,68 +1453,26 @@ pub mod worker_api_client {
     }
 }
 /// Generated server implementations.
-pub mod worker_api_server {
+pub mod worker_pool_demand_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with WorkerApiServer.
+    /// Generated trait containing gRPC methods that should be implemented for use with WorkerPoolDemandServer.
     #[async_trait]
-    pub trait WorkerApi: Send + Sync + 'static {
-        /// Server streaming response type for the ConnectWorker method.
-        type ConnectWorkerStream: futures_core::Stream<
-                Item = std::result::Result<super::UpdateForWorker, tonic::Status>,
-            >
-            + Send
-            + 'static;
-        /// / Registers this worker and informs the scheduler what properties
-        /// / this worker supports. The response must be listened on the client
-        /// / side for updates from the server. The first item sent will always be
-        /// / a ConnectionResult, after that it is undefined.
-        async fn connect_worker(
+    pub trait WorkerPoolDemand: Send + Sync + 'static {
+        /// / Returns the current demand of the scheduler per distinct set of
+        /// / platform properties requested by actions.
+        async fn get_worker_pool_demand(
             &self,
-            request: tonic::Request<super::SupportedProperties>,
+            request: tonic::Request<super::GetWorkerPoolDemandRequest>,
         ) -> std::result::Result<
-            tonic::Response<Self::ConnectWorkerStream>,
+            tonic::Response<super::GetWorkerPoolDemandResponse>,
             tonic::Status,
         >;
-        /// / Message used to let the scheduler know that it is still alive as
-        /// / well as check to see if the scheduler is still alive. The scheduler
-        /// / may close the connection if the worker has not sent any messages
-        /// / after some amount of time (configured in the scheduler's
-        /// / configuration).
-        async fn keep_alive(
-            &self,
-            request: tonic::Request<super::KeepAliveRequest>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
-        /// / Informs the scheduler that the service is going offline and
-        /// / should stop issuing any new actions on this worker.
-        /// /
-        /// / The worker may stay connected even after sending this command
-        /// / and may even send an `ExecuteResult` after sending this command.
-        /// / It is up to the scheduler implementation to decide how to handle
-        /// / this case.
-        /// /
-        /// / Any job that was running on this instance likely needs to be
-        /// / executed again, but up to the scheduler on how or when to handle
-        /// / this case.
-        async fn going_away(
-            &self,
-            request: tonic::Request<super::GoingAwayRequest>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
-        /// / Informs the scheduler about the result of an execution request.
-        async fn execution_response(
-            &self,
-            request: tonic::Request<super::ExecuteResult>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
     }
-    /// / This API describes how schedulers communicate with Worker nodes.
-    /// /
-    /// / When a worker node comes online it must be pre-configured with the
-    /// / endpoint of the scheduler it will register with. Once the worker
-    /// / connects to the scheduler it must send a `RegisterSupportedProperties`
-    /// / command to the scheduler. The scheduler will then use this information
-    /// / to determine which jobs the worker can process.
+    /// / This API reports how much work a scheduler has for each kind of worker,
+    /// / so pools of workers can be scaled by an external autoscaler.
     #[derive(Debug)]
-    pub struct WorkerApiServer<T: WorkerApi> {
+    pub struct WorkerPoolDemandServer<T: WorkerPoolDemand> {
         inner: _Inner<T>,
         accept_compression_encodings: EnabledCompressionEncodings,
         send_compression_encodings: EnabledCompressionEncodings,
@@ -463,7 +1480,7 @@ pub mod worker_api_server {
         max_encoding_message_size: Option<usize>,
     }
     struct _Inner<T>(Arc<T>);
-    impl<T: WorkerApi> WorkerApiServer<T> {
+    impl<T: WorkerPoolDemand> WorkerPoolDemandServer<T> {
         pub fn new(inner: T) -> Self {
             Self::from_arc(Arc::new(inner))
         }
@@ -515,9 +1532,9 @@ pub mod worker_api_server {
             self
         }
     }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for WorkerApiServer<T>
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for WorkerPoolDemandServer<T>
     where
-        T: WorkerApi,
+        T: WorkerPoolDemand,
         B: Body + Send + 'static,
         B::Error: Into<StdError> + Send + 'static,
     {
@@ -533,158 +1550,25 @@ pub mod worker_api_server {
         fn call(&mut self, req: http::Request<B>) -> Self::Future {
             let inner = self.inner.clone();
             match req.uri().path() {
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ConnectWorker" => {
-                    #[allow(non_camel_case_types)]
-                    struct ConnectWorkerSvc<T: WorkerApi>(pub Arc<T>);
-                    impl<
-                        T: WorkerApi,
-                    > tonic::server::ServerStreamingService<super::SupportedProperties>
-                    for ConnectWorkerSvc<T> {
-                        type Response = super::UpdateForWorker;
-                        type ResponseStream = T::ConnectWorkerStream;
-                        type Future = BoxFuture<
-                            tonic::Response<Self::ResponseStream>,
-                            tonic::Status,
-                        >;
-                        fn call(
-                            &mut self,
-                            request: tonic::Request<super::SupportedProperties>,
-                        ) -> Self::Future {
-                            let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                (*inner).connect_worker(request).await
-                            };
-                            Box::pin(fut)
-                        }
-                    }
-                    let accept_compression_encodings = self.accept_compression_encodings;
-                    let send_compression_encodings = self.send_compression_encodings;
-                    let max_decoding_message_size = self.max_decoding_message_size;
-                    let max_encoding_message_size = self.max_encoding_message_size;
-                    let inner = self.inner.clone();
-                    let fut = async move {
-                        let inner = inner.0;
-                        let method = ConnectWorkerSvc(inner);
-                        let codec = tonic::codec::ProstCodec::default();
-                        let mut grpc = tonic::server::Grpc::new(codec)
-                            .apply_compression_config(
-                                accept_compression_encodings,
-                                send_compression_encodings,
-                            )
-                            .apply_max_message_size_config(
-                                max_decoding_message_size,
-                                max_encoding_message_size,
-                            );
-                        let res = grpc.server_streaming(method, req).await;
-                        Ok(res)
-                    };
-                    Box::pin(fut)
-                }
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/KeepAlive" => {
-                    #[allow(non_camel_case_types)]
-                    struct KeepAliveSvc<T: WorkerApi>(pub Arc<T>);
-                    impl<
-                        T: WorkerApi,
-                    > tonic::server::UnaryService<super::KeepAliveRequest>
-                    for KeepAliveSvc<T> {
-                        type Response = ();
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
-                        fn call(
-                            &mut self,
-                            request: tonic::Request<super::KeepAliveRequest>,
-                        ) -> Self::Future {
-                            let inner = Arc::clone(&self.0);
-                            let fut = async move { (*inner).keep_alive(request).await };
-                            Box::pin(fut)
-                        }
-                    }
-                    let accept_compression_encodings = self.accept_compression_encodings;
-                    let send_compression_encodings = self.send_compression_encodings;
-                    let max_decoding_message_size = self.max_decoding_message_size;
-                    let max_encoding_message_size = self.max_encoding_message_size;
-                    let inner = self.inner.clone();
-                    let fut = async move {
-                        let inner = inner.0;
-                        let method = KeepAliveSvc(inner);
-                        let codec = tonic::codec::ProstCodec::default();
-                        let mut grpc = tonic::server::Grpc::new(codec)
-                            .apply_compression_config(
-                                accept_compression_encodings,
-                                send_compression_encodings,
-                            )
-                            .apply_max_message_size_config(
-                                max_decoding_message_size,
-                                max_encoding_message_size,
-                            );
-                        let res = grpc.unary(method, req).await;
-                        Ok(res)
-                    };
-                    Box::pin(fut)
-                }
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/GoingAway" => {
+                "/com.github.trace_machina.native_link.remote_execution.WorkerPoolDemand/GetWorkerPoolDemand" => {
                     #[allow(non_camel_case_types)]
-                    struct GoingAwaySvc<T: WorkerApi>(pub Arc<T>);
+                    struct GetWorkerPoolDemandSvc<T: WorkerPoolDemand>(pub Arc<T>);
                     impl<
-                        T: WorkerApi,
-                    > tonic::server::UnaryService<super::GoingAwayRequest>
-                    for GoingAwaySvc<T> {
-                        type Response = ();
-                        type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
-                            tonic::Status,
-                        >;
-                        fn call(
-                            &mut self,
-                            request: tonic::Request<super::GoingAwayRequest>,
-                        ) -> Self::Future {
-                            let inner = Arc::clone(&self.0);
-                            let fut = async move { (*inner).going_away(request).await };
-                            Box::pin(fut)
-                        }
-                    }
-                    let accept_compression_encodings = self.accept_compression_encodings;
-                    let send_compression_encodings = self.send_compression_encodings;
-                    let max_decoding_message_size = self.max_decoding_message_size;
-                    let max_encoding_message_size = self.max_encoding_message_size;
-                    let inner = self.inner.clone();
-                    let fut = async move {
-                        let inner = inner.0;
-                        let method = GoingAwaySvc(inner);
-                        let codec = tonic::codec::ProstCodec::default();
-                        let mut grpc = tonic::server::Grpc::new(codec)
-                            .apply_compression_config(
-                                accept_compression_encodings,
-                                send_compression_encodings,
-                            )
-                            .apply_max_message_size_config(
-                                max_decoding_message_size,
-                                max_encoding_message_size,
-                            );
-                        let res = grpc.unary(method, req).await;
-                        Ok(res)
-                    };
-                    Box::pin(fut)
-                }
-                "/com.github.trace_machina.native_link.remote_execution.WorkerApi/ExecutionResponse" => {
-                    #[allow(non_camel_case_types)]
-                    struct ExecutionResponseSvc<T: WorkerApi>(pub Arc<T>);
-                    impl<T: WorkerApi> tonic::server::UnaryService<super::ExecuteResult>
-                    for ExecutionResponseSvc<T> {
-                        type Response = ();
+                        T: WorkerPoolDemand,
+                    > tonic::server::UnaryService<super::GetWorkerPoolDemandRequest>
+                    for GetWorkerPoolDemandSvc<T> {
+                        type Response = super::GetWorkerPoolDemandResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecuteResult>,
+                            request: tonic::Request<super::GetWorkerPoolDemandRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).execution_response(request).await
+                                (*inner).get_worker_pool_demand(request).await
                             };
                             Box::pin(fut)
                         }
@@ -696,7 +1580,7 @@ pub mod worker_api_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ExecutionResponseSvc(inner);
+                        let method = GetWorkerPoolDemandSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -727,7 +1611,7 @@ pub mod worker_api_server {
             }
         }
     }
-    impl<T: WorkerApi> Clone for WorkerApiServer<T> {
+    impl<T: WorkerPoolDemand> Clone for WorkerPoolDemandServer<T> {
         fn clone(&self) -> Self {
             let inner = self.inner.clone();
             Self {
@@ -739,7 +1623,7 @@ pub mod worker_api_server {
             }
         }
     }
-    impl<T: WorkerApi> Clone for _Inner<T> {
+    impl<T: WorkerPoolDemand> Clone for _Inner<T> {
         fn clone(&self) -> Self {
             Self(Arc::clone(&self.0))
         }
@@ -749,7 +1633,7 @@ pub mod worker_api_server {
             write!(f, "{:?}", self.0)
         }
     }
-    impl<T: WorkerApi> tonic::server::NamedService for WorkerApiServer<T> {
-        const NAME: &'static str = "com.github.trace_machina.native_link.remote_execution.WorkerApi";
+    impl<T: WorkerPoolDemand> tonic::server::NamedService for WorkerPoolDemandServer<T> {
+        const NAME: &'static str = "com.github.trace_machina.native_link.remote_execution.WorkerPoolDemand";
     }
 }
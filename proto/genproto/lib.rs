@@ -17,6 +17,11 @@
 // `bazel run proto:update_protos`
 
 
+pub mod blaze {
+  pub mod worker {
+    include!("blaze.worker.pb.rs");
+  }
+}
 pub mod build {
   pub mod bazel {
     pub mod remote {
@@ -28,13 +28,16 @@ use hyper::{Body, Response};
 use native_link_config::cas_server::{
     CasConfig, CompressionAlgorithm, ConfigDigestHashFunction, GlobalConfig, ServerConfig, WorkerConfig,
 };
-use native_link_scheduler::default_scheduler_factory::scheduler_factory;
+use native_link_scheduler::default_scheduler_factory::{scheduler_creation_order, scheduler_factory};
 use native_link_service::ac_server::AcServer;
 use native_link_service::bytestream_server::ByteStreamServer;
 use native_link_service::capabilities_server::CapabilitiesServer;
 use native_link_service::cas_server::CasServer;
 use native_link_service::execution_server::ExecutionServer;
+use native_link_service::operations_server::OperationsServer;
+use native_link_service::scheduler_events_server::SchedulerEventsServer;
 use native_link_service::worker_api_server::WorkerApiServer;
+use native_link_service::worker_pool_demand_server::WorkerPoolDemandServer;
 use native_link_store::default_store_factory::store_factory;
 use native_link_store::store_manager::StoreManager;
 use native_link_util::common::fs::{set_idle_file_descriptor_timeout, set_open_file_limit};
@@ -58,6 +61,9 @@ use tower::util::ServiceExt;
 /// Note: This must be kept in sync with the documentation in `PrometheusConfig::path`.
 const DEFAULT_PROMETHEUS_METRICS_PATH: &str = "/metrics";
 
+/// Note: This must be kept in sync with the documentation in `WorkerPoolDemandConfig::path`.
+const DEFAULT_WORKER_POOL_DEMAND_PATH: &str = "/worker_pool_demand";
+
 /// Name of environment variable to disable metrics.
 const METRICS_DISABLE_ENV: &str = "NATIVE_LINK_DISABLE_METRICS";
 
@@ -94,12 +100,16 @@ async fn inner_main(cfg: CasConfig, server_start_timestamp: u64) -> Result<(), B
 
     let mut action_schedulers = HashMap::new();
     let mut worker_schedulers = HashMap::new();
-    if let Some(schedulers_cfg) = cfg.schedulers {
+    if let Some(mut schedulers_cfg) = cfg.schedulers {
         let root_scheduler_metrics = root_metrics_registry.sub_registry_with_prefix("schedulers");
-        for (name, scheduler_cfg) in schedulers_cfg {
+        // Routers forward actions to other schedulers, so those need to be created first.
+        for name in scheduler_creation_order(&schedulers_cfg).err_tip(|| "Invalid schedulers config")? {
+            let scheduler_cfg = schedulers_cfg
+                .remove(&name)
+                .err_tip(|| format!("Scheduler '{name}' is not in the config"))?;
             let scheduler_metrics = root_scheduler_metrics.sub_registry_with_prefix(&name);
             let (maybe_action_scheduler, maybe_worker_scheduler) =
-                scheduler_factory(&scheduler_cfg, &store_manager, scheduler_metrics)
+                scheduler_factory(&scheduler_cfg, &store_manager, &action_schedulers, scheduler_metrics)
                     .err_tip(|| format!("Failed to create scheduler '{name}'"))?;
             if let Some(action_scheduler) = maybe_action_scheduler {
                 action_schedulers.insert(name.clone(), action_scheduler);
@@ -182,6 +192,22 @@ async fn inner_main(cfg: CasConfig, server_start_timestamp: u64) -> Result<(), B
     for (server_cfg, connected_clients_mux) in servers_and_clients {
         let services = server_cfg.services.ok_or("'services' must be configured")?;
 
+        // The demand is served over gRPC and HTTP, so the server is shared by both.
+        let worker_pool_demand = services
+            .worker_pool_demand
+            .map(|cfg| {
+                WorkerPoolDemandServer::new(&cfg, &worker_schedulers).map(|worker_pool_demand_server| {
+                    let path = if cfg.path.is_empty() {
+                        DEFAULT_WORKER_POOL_DEMAND_PATH.to_string()
+                    } else {
+                        cfg.path
+                    };
+                    (path, worker_pool_demand_server)
+                })
+            })
+            .transpose()
+            .err_tip(|| "Could not create WorkerPoolDemand service")?;
+
         let tonic_services = TonicServer::builder()
             .add_optional_service(
                 services
@@ -255,6 +281,30 @@ async fn inner_main(cfg: CasConfig, server_start_timestamp: u64) -> Result<(), B
                     })
                     .err_tip(|| "Could not create Execution service")?,
             )
+            .add_optional_service(
+                services
+                    .operations
+                    .map_or(Ok(None), |cfg| {
+                        OperationsServer::new(&cfg, &action_schedulers).map(|v| {
+                            let mut service = v.into_service();
+                            let send_algo = &server_cfg.compression.send_compression_algorithm;
+                            if let Some(encoding) = into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::None)) {
+                                service = service.send_compressed(encoding);
+                            }
+                            for encoding in server_cfg
+                                .compression
+                                .accepted_compression_algorithms
+                                .iter()
+                                // Filter None values.
+                                .filter_map(into_encoding)
+                            {
+                                service = service.accept_compressed(encoding);
+                            }
+                            Some(service)
+                        })
+                    })
+                    .err_tip(|| "Could not create Operations service")?,
+            )
             .add_optional_service(
                 services
                     .bytestream
@@ -333,7 +383,48 @@ async fn inner_main(cfg: CasConfig, server_start_timestamp: u64) -> Result<(), B
                         })
                     })
                     .err_tip(|| "Could not create WorkerApi service")?,
-            );
+            )
+            .add_optional_service(
+                services
+                    .scheduler_events
+                    .map_or(Ok(None), |cfg| {
+                        SchedulerEventsServer::new(&cfg, &worker_schedulers).map(|v| {
+                            let mut service = v.into_service();
+                            let send_algo = &server_cfg.compression.send_compression_algorithm;
+                            if let Some(encoding) = into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::None)) {
+                                service = service.send_compressed(encoding);
+                            }
+                            for encoding in server_cfg
+                                .compression
+                                .accepted_compression_algorithms
+                                .iter()
+                                // Filter None values.
+                                .filter_map(into_encoding)
+                            {
+                                service = service.accept_compressed(encoding);
+                            }
+                            Some(service)
+                        })
+                    })
+                    .err_tip(|| "Could not create SchedulerEvents service")?,
+            )
+            .add_optional_service(worker_pool_demand.as_ref().map(|(_, worker_pool_demand_server)| {
+                let mut service = worker_pool_demand_server.clone().into_service();
+                let send_algo = &server_cfg.compression.send_compression_algorithm;
+                if let Some(encoding) = into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::None)) {
+                    service = service.send_compressed(encoding);
+                }
+                for encoding in server_cfg
+                    .compression
+                    .accepted_compression_algorithms
+                    .iter()
+                    // Filter None values.
+                    .filter_map(into_encoding)
+                {
+                    service = service.accept_compressed(encoding);
+                }
+                service
+            }));
 
         let root_metrics_registry = root_metrics_registry.clone();
 
@@ -390,6 +481,24 @@ async fn inner_main(cfg: CasConfig, server_start_timestamp: u64) -> Result<(), B
             )
         }
 
+        if let Some((path, worker_pool_demand_server)) = worker_pool_demand {
+            svc = svc.route_service(
+                &path,
+                axum::routing::get(move || async move {
+                    match worker_pool_demand_server.demands_json() {
+                        Ok(json) => Response::builder()
+                            .header(hyper::header::CONTENT_TYPE, "application/json")
+                            .body(Body::from(json))
+                            .unwrap(),
+                        Err(e) => Response::builder()
+                            .status(500)
+                            .body(format!("Error: {e:?}").into())
+                            .unwrap(),
+                    }
+                }),
+            )
+        }
+
         // Configure our TLS acceptor if we have TLS configured.
         let maybe_tls_acceptor = server_cfg.tls.map_or(Ok(None), |tls_config| {
             let mut cert_reader = std::io::BufReader::new(